@@ -43,8 +43,8 @@ fn fletcher16(data: &[u8]) -> u16 {
     let mut sum1: u16 = 0;
     let mut sum2: u16 = 0;
 
-    for index in 0..data.len() {
-        sum1 = (sum1 + data[index] as u16) % 255;
+    for byte in data {
+        sum1 = (sum1 + *byte as u16) % 255;
         sum2 = (sum2 + sum1) % 255;
     }
 
@@ -97,7 +97,7 @@ impl Game {
         self.game_state = cell.load().expect("No data found.");
     }
 
-    fn advance_frame(&mut self, inputs: Vec<(Input, InputStatus)>) {
+    fn advance_frame(&mut self, inputs: Vec<(Input, InputStatus, u8)>) {
         // advance the game state
         self.game_state.advance(inputs);
 
@@ -219,13 +219,13 @@ impl State {
         let mut velocities = Vec::new();
         let mut rotations = Vec::new();
 
-        let r = WINDOW_WIDTH as f32 / 4.0;
+        let r = WINDOW_WIDTH / 4.0;
 
         for i in 0..num_players as i32 {
             let rot = i as f32 / num_players as f32 * 2.0 * std::f32::consts::PI;
-            let x = WINDOW_WIDTH as f32 / 2.0 + r * rot.cos();
-            let y = WINDOW_HEIGHT as f32 / 2.0 + r * rot.sin();
-            positions.push((x as f32, y as f32));
+            let x = WINDOW_WIDTH / 2.0 + r * rot.cos();
+            let y = WINDOW_HEIGHT / 2.0 + r * rot.sin();
+            positions.push((x, y));
             velocities.push((0.0, 0.0));
             rotations.push((rot + std::f32::consts::PI) % (2.0 * std::f32::consts::PI));
         }
@@ -239,14 +239,15 @@ impl State {
         }
     }
 
-    pub fn advance(&mut self, inputs: Vec<(Input, InputStatus)>) {
+    pub fn advance(&mut self, inputs: Vec<(Input, InputStatus, u8)>) {
         // increase the frame counter
         self.frame += 1;
 
+        #[allow(clippy::needless_range_loop)]
         for i in 0..self.num_players {
             // get input of that player
             let input = match inputs[i].1 {
-                InputStatus::Confirmed => inputs[i].0.inp,
+                InputStatus::ConfirmedLocal | InputStatus::ConfirmedRemote => inputs[i].0.inp,
                 InputStatus::Predicted => inputs[i].0.inp,
                 InputStatus::Disconnected => 4, // disconnected players spin
             };
@@ -0,0 +1,72 @@
+/// The algorithms GGRS can use to compute a checksum over the bytes of a saved state.
+/// See [`SessionBuilder::with_desync_checksum()`] and [`GameStateCell::save_with_bytes()`].
+///
+/// [`SessionBuilder::with_desync_checksum()`]: crate::SessionBuilder#method.with_desync_checksum
+/// [`GameStateCell::save_with_bytes()`]: crate::GameStateCell#method.save_with_bytes
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ChecksumKind {
+    /// The 128-bit variant of the Fowler–Noll–Vo hash function (FNV-1a).
+    Fnv1a128,
+    /// The 64-bit cyclic redundancy check as used by XZ (CRC-64/XZ).
+    Crc64,
+}
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+const CRC64_POLY: u64 = 0xc96c5795d7870f42;
+
+impl ChecksumKind {
+    /// Computes the checksum of the given bytes.
+    pub fn checksum(&self, bytes: &[u8]) -> u128 {
+        match self {
+            ChecksumKind::Fnv1a128 => {
+                let mut hash = FNV_OFFSET_BASIS;
+                for byte in bytes {
+                    hash ^= *byte as u128;
+                    hash = hash.wrapping_mul(FNV_PRIME);
+                }
+                hash
+            }
+            ChecksumKind::Crc64 => {
+                let mut crc = u64::MAX;
+                for byte in bytes {
+                    crc ^= *byte as u64;
+                    for _ in 0..8 {
+                        crc = if crc & 1 == 1 {
+                            (crc >> 1) ^ CRC64_POLY
+                        } else {
+                            crc >> 1
+                        };
+                    }
+                }
+                (crc ^ u64::MAX) as u128
+            }
+        }
+    }
+}
+
+// #########
+// # TESTS #
+// #########
+
+#[cfg(test)]
+mod checksum_tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a128() {
+        assert_eq!(ChecksumKind::Fnv1a128.checksum(b""), FNV_OFFSET_BASIS);
+        assert_eq!(
+            ChecksumKind::Fnv1a128.checksum(b"a"),
+            0xd228cb696f1a8caf78912b704e4a8964
+        );
+    }
+
+    #[test]
+    fn test_crc64() {
+        assert_eq!(
+            ChecksumKind::Crc64.checksum(b"123456789"),
+            0x995dc9bbdf1939fa
+        );
+    }
+}
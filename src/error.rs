@@ -1,31 +1,121 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use core::fmt::Display;
+use core::ops::Range;
+#[cfg(feature = "std")]
 use std::error::Error;
-use std::fmt;
-use std::fmt::Display;
 
-use crate::Frame;
+use crate::{Frame, PlayerHandle};
 
 /// This enum contains all error messages this library can return. Most API functions will generally return a [`Result<(),GGRSError>`].
 ///
-/// [`Result<(),GGRSError>`]: std::result::Result
+/// Errors are either retryable, i.e. calling the same function again later (while polling the session) can succeed,
+/// fatal, i.e. the session cannot continue, or neither, i.e. the call was wrong but the session is unaffected.
+/// See [`is_retryable()`] and [`is_fatal()`].
+///
+/// [`Result<(),GGRSError>`]: core::result::Result
+/// [`is_retryable()`]: Self#method.is_retryable
+/// [`is_fatal()`]: Self#method.is_fatal
 #[derive(Debug, Clone, PartialEq, Hash)]
 pub enum GGRSError {
-    /// When the prediction threshold has been reached, we cannot accept more inputs from the local player.
+    /// When the prediction threshold has been reached, we cannot accept more inputs from the local player. Retryable.
     PredictionThreshold,
-    /// You made an invalid request, usually by using wrong parameters for function calls.
+    /// You made an invalid request, usually by using wrong parameters for function calls. Neither retryable nor fatal.
     InvalidRequest {
         /// Further specifies why the request was invalid.
         info: String,
     },
-    /// In a [`SyncTestSession`], this error is returned if checksums of resimulated frames do not match up with the original checksum.
+    /// In a [`SyncTestSession`], this error is returned if checksums of resimulated frames do not match up with the original checksum,
+    /// or if a checksum does not match the recorded checksum of that frame. Fatal.
     ///
     /// [`SyncTestSession`]: crate::SyncTestSession
     MismatchedChecksum {
         /// The frame at which the mismatch occurred.
         frame: Frame,
+        /// The checksum that was originally recorded for this frame.
+        expected: Option<u128>,
+        /// The checksum that was found instead.
+        got: Option<u128>,
+    },
+    /// In a [`SyncTestSession`] with [`SessionBuilder::with_byte_diff()`], this error is returned if the serialized bytes of a resimulated state
+    /// differ from the originally saved state, even if their checksums match. Fatal.
+    ///
+    /// [`SyncTestSession`]: crate::SyncTestSession
+    /// [`SessionBuilder::with_byte_diff()`]: crate::SessionBuilder#method.with_byte_diff
+    MismatchedState {
+        /// The frame at which the mismatch occurred.
+        frame: Frame,
+        /// The checksum that was originally recorded for this frame.
+        expected: Option<u128>,
+        /// The checksum that was found instead.
+        got: Option<u128>,
+        /// The ranges of bytes that differ between the serialized states. If the lengths differ, the last range covers the extra bytes.
+        differing_bytes: Vec<Range<usize>>,
+    },
+    /// The state of a frame GGRS needs to roll back to has never been saved, because [`GameStateCell::save()`] has not been called
+    /// while handling the [`SaveGameState`] request for that frame. Fatal.
+    ///
+    /// [`GameStateCell::save()`]: crate::GameStateCell#method.save
+    /// [`SaveGameState`]: crate::GGRSRequest::SaveGameState
+    EmptySaveState {
+        /// The frame of the state that has not been saved.
+        frame: Frame,
+    },
+    /// The state of a frame GGRS needs to roll back to is not kept anymore, because fewer states are kept than needed for this rollback,
+    /// see [`SessionBuilder::with_max_saved_states()`]. Fatal.
+    ///
+    /// [`SessionBuilder::with_max_saved_states()`]: crate::SessionBuilder#method.with_max_saved_states
+    StateNotAvailable {
+        /// The frame of the state that was needed.
+        requested_frame: Frame,
+        /// The oldest frame of which a state is still kept.
+        oldest_frame: Frame,
+    },
+    /// The local frame ran more frames ahead of the confirmed frame than allowed by [`SessionBuilder::with_max_frame_lag()`],
+    /// usually because inputs of a remote player have not arrived for a long time. Retryable.
+    ///
+    /// [`SessionBuilder::with_max_frame_lag()`]: crate::SessionBuilder#method.with_max_frame_lag
+    FrameLagExceeded {
+        /// The current frame of the session.
+        frame: Frame,
+        /// The last frame for which all inputs have been received.
+        confirmed_frame: Frame,
+    },
+    /// With [`SessionBuilder::with_strict_input_ordering()`], a local input has been added for a frame that already has an input,
+    /// or for a frame after the next one without an input. Neither retryable nor fatal.
+    ///
+    /// [`SessionBuilder::with_strict_input_ordering()`]: crate::SessionBuilder#method.with_strict_input_ordering
+    NonMonotonicInput {
+        /// The next frame without a local input.
+        expected: Frame,
+        /// The frame the input has been added for.
+        got: Frame,
+    },
+    /// The size of a local input does not match the input size of the player, see [`SessionBuilder::add_player_with_input_size()`].
+    /// Neither retryable nor fatal.
+    ///
+    /// [`SessionBuilder::add_player_with_input_size()`]: crate::SessionBuilder#method.add_player_with_input_size
+    MismatchedInputSize {
+        /// The input size of the player, in bytes.
+        expected: usize,
+        /// The size of the given input, in bytes.
+        got: usize,
     },
-    /// The Session is not synchronized yet. Please start the session and wait a few ms to let the clients synchronize.
+    /// In a lockstep session, the inputs of the remote players for the current frame have not been received yet,
+    /// so the session cannot advance without predicting them. Keep polling and try again later. Retryable.
+    WaitingForInputs,
+    /// The session reached the frame of a coordinated pause, see [`P2PSession::pause()`], so it does not advance until the pause is resumed.
+    /// Keep polling, so the connections to the remote clients stay alive. Retryable.
+    ///
+    /// [`P2PSession::pause()`]: crate::P2PSession#method.pause
+    Paused,
+    /// The session has been ended with [`P2PSession::end_session()`], so it cannot advance anymore. Fatal.
+    ///
+    /// [`P2PSession::end_session()`]: crate::P2PSession#method.end_session
+    SessionEnded,
+    /// The Session is not synchronized yet. Please start the session and wait a few ms to let the clients synchronize. Retryable.
     NotSynchronized,
-    /// The spectator got so far behind the host that catching up is impossible.
+    /// The spectator got so far behind the host that catching up is impossible. Fatal.
     SpectatorTooFarBehind,
     /// Deprecated, will be removed in next major release
     SocketCreationFailed,
@@ -35,6 +125,33 @@ pub enum GGRSError {
     DecodingError,
 }
 
+impl GGRSError {
+    /// Returns true if the session cannot continue after this error, e.g. because the clients desynced or the session has ended.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            GGRSError::MismatchedChecksum { .. }
+                | GGRSError::MismatchedState { .. }
+                | GGRSError::EmptySaveState { .. }
+                | GGRSError::StateNotAvailable { .. }
+                | GGRSError::SessionEnded
+                | GGRSError::SpectatorTooFarBehind
+        )
+    }
+
+    /// Returns true if the same call can succeed later, e.g. once the remote inputs have arrived. Keep polling the session meanwhile.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            GGRSError::PredictionThreshold
+                | GGRSError::NotSynchronized
+                | GGRSError::FrameLagExceeded { .. }
+                | GGRSError::WaitingForInputs
+                | GGRSError::Paused
+        )
+    }
+}
+
 impl Display for GGRSError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -53,13 +170,81 @@ impl Display for GGRSError {
                     "The session is not yet synchronized with all remote sessions."
                 )
             }
-            GGRSError::MismatchedChecksum { frame } => {
+            GGRSError::MismatchedChecksum {
+                frame,
+                expected,
+                got,
+            } => {
                 write!(
                     f,
-                    "Detected checksum mismatch during rollback on frame {}.",
+                    "Detected checksum mismatch on frame {} (expected {:?}, got {:?}).",
+                    frame, expected, got
+                )
+            }
+            GGRSError::MismatchedState {
+                frame,
+                differing_bytes,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Detected state mismatch on frame {} (differing bytes: {:?}).",
+                    frame, differing_bytes
+                )
+            }
+            GGRSError::EmptySaveState { frame } => {
+                write!(
+                    f,
+                    "The state of frame {} has not been saved, so it cannot be loaded.",
                     frame
                 )
             }
+            GGRSError::StateNotAvailable {
+                requested_frame,
+                oldest_frame,
+            } => {
+                write!(
+                    f,
+                    "The state of frame {} is not kept anymore, the oldest kept state is from frame {}.",
+                    requested_frame, oldest_frame
+                )
+            }
+            GGRSError::FrameLagExceeded {
+                frame,
+                confirmed_frame,
+            } => {
+                write!(
+                    f,
+                    "The current frame {} is too far ahead of the confirmed frame {}.",
+                    frame, confirmed_frame
+                )
+            }
+            GGRSError::NonMonotonicInput { expected, got } => {
+                write!(
+                    f,
+                    "The local input has been added for frame {}, but the next frame without an input is {}.",
+                    got, expected
+                )
+            }
+            GGRSError::MismatchedInputSize { expected, got } => {
+                write!(
+                    f,
+                    "The input has {} bytes, but the inputs of the player have {} bytes.",
+                    got, expected
+                )
+            }
+            GGRSError::WaitingForInputs => {
+                write!(
+                    f,
+                    "The inputs of the remote players for the current frame have not been received yet."
+                )
+            }
+            GGRSError::Paused => {
+                write!(f, "The session is paused.")
+            }
+            GGRSError::SessionEnded => {
+                write!(f, "The session has been ended.")
+            }
             GGRSError::SpectatorTooFarBehind => {
                 write!(
                     f,
@@ -79,4 +264,65 @@ impl Display for GGRSError {
     }
 }
 
+#[cfg(feature = "std")]
 impl Error for GGRSError {}
+
+/// This enum describes a single problem with the players registered in a [`SessionBuilder`], as returned by [`SessionBuilder::validate()`].
+///
+/// [`SessionBuilder`]: crate::SessionBuilder
+/// [`SessionBuilder::validate()`]: crate::SessionBuilder#method.validate
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BuilderError {
+    /// A handle has been used for more than one player, spectator or reserved slot.
+    DuplicateHandle {
+        /// The offending handle.
+        handle: PlayerHandle,
+    },
+    /// A player handle is not below the number of players, or a spectator handle is below it.
+    HandleOutOfRange {
+        /// The offending handle.
+        handle: PlayerHandle,
+    },
+    /// No local player has been registered.
+    NoLocalPlayer,
+    /// The number of registered local and remote players does not match the number of players of the session.
+    PlayerCountMismatch {
+        /// The number of players of the session.
+        expected: usize,
+        /// The number of local and remote players registered.
+        registered: usize,
+    },
+}
+
+impl Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuilderError::DuplicateHandle { handle } => {
+                write!(f, "The handle {} has been added more than once.", handle)
+            }
+            BuilderError::HandleOutOfRange { handle } => {
+                write!(
+                    f,
+                    "The handle {} is out of range for its player type.",
+                    handle
+                )
+            }
+            BuilderError::NoLocalPlayer => {
+                write!(f, "No local player has been registered.")
+            }
+            BuilderError::PlayerCountMismatch {
+                expected,
+                registered,
+            } => {
+                write!(
+                    f,
+                    "Expected {} players, but {} have been registered.",
+                    expected, registered
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for BuilderError {}
@@ -1,8 +1,10 @@
-use crate::{Frame, NULL_FRAME};
+use crate::sync_layer::{SharedDeltaBases, SharedStateStore};
+use crate::{ChecksumKind, Frame, NULL_FRAME};
+use alloc::vec::Vec;
 
 /// Represents the game state of your game for a single frame. The `data` holds the game state, `frame` indicates the associated frame number
 /// and `checksum` can additionally be provided for use during a `SyncTestSession`.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub(crate) struct GameState<S: Clone> {
     /// The frame to which this info belongs to.
     pub frame: Frame,
@@ -10,6 +12,24 @@ pub(crate) struct GameState<S: Clone> {
     pub data: Option<S>,
     /// The checksum of the gamestate.
     pub checksum: Option<u128>,
+    /// Arbitrary bytes the user attached to the gamestate. They are never sent over the network.
+    pub metadata: Option<Vec<u8>>,
+    /// The serialized gamestate, if the user provided it and it needs to be kept.
+    pub bytes: Option<Vec<u8>>,
+    /// If true, the serialized gamestate is kept, so it can be sent to spectators.
+    pub keep_bytes: bool,
+    /// The algorithm used to compute a checksum if the user does not provide one.
+    pub checksum_kind: Option<ChecksumKind>,
+    /// If set, the game state is moved into this store instead of being kept in `data`.
+    pub store: Option<SharedStateStore<S>>,
+    /// If true, the game state of `frame` has been moved into the `store`.
+    pub in_store: bool,
+    /// If set, full game states are moved into these base states, so delta states can be saved against them.
+    pub delta_bases: Option<SharedDeltaBases<S>>,
+    /// If true, the game state of `frame` has been moved into the `delta_bases`.
+    pub is_base: bool,
+    /// The frame of the base state and the bytes to apply to it, if the game state has been saved as a delta.
+    pub delta: Option<(Frame, Vec<u8>)>,
 }
 
 impl<S: Clone> Default for GameState<S> {
@@ -18,6 +38,15 @@ impl<S: Clone> Default for GameState<S> {
             frame: NULL_FRAME,
             data: None,
             checksum: None,
+            metadata: None,
+            bytes: None,
+            keep_bytes: false,
+            checksum_kind: None,
+            store: None,
+            in_store: false,
+            delta_bases: None,
+            is_base: false,
+            delta: None,
         }
     }
 }
@@ -33,22 +62,36 @@ where
     pub frame: Frame,
     /// The input struct given by the user
     pub input: I,
+    /// The flags the user attached to the input. They are sent along with the input, but are not part of the input struct.
+    pub flags: u8,
 }
 
 impl<I: Copy + Clone + PartialEq + bytemuck::Pod + bytemuck::Zeroable> PlayerInput<I> {
     pub(crate) fn new(frame: Frame, input: I) -> Self {
-        Self { frame, input }
+        Self {
+            frame,
+            input,
+            flags: 0,
+        }
+    }
+
+    pub(crate) fn with_flags(mut self, flags: u8) -> Self {
+        self.flags = flags;
+        self
     }
 
     pub(crate) fn blank_input(frame: Frame) -> Self {
         Self {
             frame,
             input: I::zeroed(),
+            flags: 0,
         }
     }
 
     pub(crate) fn equal(&self, other: &Self, input_only: bool) -> bool {
-        (input_only || self.frame == other.frame) && self.input == other.input
+        (input_only || self.frame == other.frame)
+            && self.input == other.input
+            && self.flags == other.flags
     }
 }
 
@@ -87,4 +130,11 @@ mod game_input_tests {
         let input2 = PlayerInput::new(0, TestInput { inp: 7 });
         assert!(!input1.equal(&input2, false)); // different bits
     }
+
+    #[test]
+    fn test_input_equality_flags() {
+        let input1 = PlayerInput::new(0, TestInput { inp: 5 });
+        let input2 = PlayerInput::new(0, TestInput { inp: 5 }).with_flags(1);
+        assert!(!input1.equal(&input2, true)); // different flags
+    }
 }
@@ -1,12 +1,35 @@
 use crate::frame_info::PlayerInput;
-use crate::{Config, Frame, InputStatus, NULL_FRAME};
-use std::cmp;
+use crate::{Config, Frame, GGRSError, InputStatus, PredictionStrategy, NULL_FRAME};
+use alloc::sync::Arc;
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::cmp;
+use serde::{Deserialize, Serialize};
 
 /// The length of the input queue. This describes the number of inputs GGRS can hold at the same time per player.
-const INPUT_QUEUE_LENGTH: usize = 128;
+pub(crate) const INPUT_QUEUE_LENGTH: usize = 128;
+
+/// The default [`PredictionStrategy`]. Predicts that the player will keep doing the same thing they did in the last confirmed frame.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RepeatLast;
+
+impl<I: Copy> PredictionStrategy<I> for RepeatLast {
+    fn predict(&self, last_confirmed: I, _frames_ahead: u32) -> I {
+        last_confirmed
+    }
+}
+
+/// The inputs of a queue that survive exporting and restoring a session, see [`InputQueue::export_runtime()`].
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct QueueRuntime {
+    start_frame: Frame,
+    last_user_frame: Frame,
+    frame_delay: usize,
+    /// The frame, input bytes and flags of every exported input, in order.
+    inputs: Vec<(Frame, Vec<u8>, u8)>,
+}
 
 /// `InputQueue` handles inputs for a single player and saves them in a circular array. Valid Inputs are between `head` and `tail`.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub(crate) struct InputQueue<T>
 where
     T: Config,
@@ -19,9 +42,13 @@ where
     length: usize,
     /// Denotes if we still are in the first frame, an edge case to be considered by some methods.
     first_frame: bool,
+    /// The frame of the first input in the queue. Players joining a running session start later than frame 0.
+    start_frame: Frame,
 
-    /// The last frame added by the user
+    /// The last frame added to the queue, including the frame delay
     last_added_frame: Frame,
+    /// The frame of the last input added by the user, without the frame delay
+    last_user_frame: Frame,
     /// The first frame in the queue that is known to be an incorrect prediction
     first_incorrect_frame: Frame,
     /// The last frame that has been requested. We make sure to never delete anything after this, as we would throw away important data.
@@ -29,37 +56,130 @@ where
 
     /// The delay in frames by which inputs are sent back to the user. This can be set during initialization.
     frame_delay: usize,
+    /// If true, the inputs are added by the local user and returned as [`InputStatus::ConfirmedLocal`].
+    local: bool,
 
     /// Our cyclic input queue
     inputs: Vec<PlayerInput<T::Input>>,
-    /// A pre-allocated prediction we are going to use to return predictions from.
+    /// A pre-allocated prediction we are going to use to return predictions from. The frame denotes the next frame we expect an input for.
     prediction: PlayerInput<T::Input>,
+    /// The input the current prediction is based on, together with the frame it belongs to.
+    prediction_base: PlayerInput<T::Input>,
+    /// Produces the predicted inputs based on the `prediction_base`.
+    prediction_strategy: Arc<dyn PredictionStrategy<T::Input>>,
 }
 
 impl<T: Config> InputQueue<T> {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(prediction_strategy: Arc<dyn PredictionStrategy<T::Input>>) -> Self {
         Self {
             head: 0,
             tail: 0,
             length: 0,
             frame_delay: 0,
+            local: false,
             first_frame: true,
+            start_frame: 0,
             last_added_frame: NULL_FRAME,
+            last_user_frame: NULL_FRAME,
             first_incorrect_frame: NULL_FRAME,
             last_requested_frame: NULL_FRAME,
             prediction: PlayerInput::blank_input(NULL_FRAME),
+            prediction_base: PlayerInput::blank_input(NULL_FRAME),
+            prediction_strategy,
             inputs: vec![PlayerInput::blank_input(NULL_FRAME); INPUT_QUEUE_LENGTH],
         }
     }
 
+    /// Sets the frame of the first input in the queue. This can only be changed before any input has been added.
+    pub(crate) fn set_start_frame(&mut self, frame: Frame) {
+        assert!(self.first_frame);
+        self.start_frame = frame;
+        // inputs are kept at the position of their frame
+        self.head = frame as usize % INPUT_QUEUE_LENGTH;
+        self.tail = self.head;
+    }
+
+    /// Drops all inputs and lets the queue start over on the given frame, keeping its prediction strategy.
+    pub(crate) fn restart(&mut self, frame: Frame) {
+        *self = Self::new(self.prediction_strategy.clone());
+        self.set_start_frame(frame);
+    }
+
+    /// Returns all inputs in the queue from the given frame on, so they can be added to another queue with [`restore_runtime()`].
+    ///
+    /// [`restore_runtime()`]: Self#method.restore_runtime
+    pub(crate) fn export_runtime(&self, from: Frame) -> QueueRuntime {
+        let inputs: Vec<_> = self
+            .queued_inputs()
+            .filter(|(frame, _, _)| *frame >= from)
+            .collect();
+        // without inputs, the next input the queue expects is the first one of the restored queue
+        let start_frame = match inputs.first() {
+            Some((frame, _, _)) => *frame,
+            None if self.last_added_frame == NULL_FRAME => self.start_frame,
+            None => self.last_added_frame + 1,
+        };
+        QueueRuntime {
+            start_frame,
+            last_user_frame: self.last_user_frame,
+            frame_delay: self.frame_delay,
+            inputs,
+        }
+    }
+
+    /// Returns the frames, bytes and flags of all inputs in the queue, oldest first.
+    pub(crate) fn queued_inputs(&self) -> impl Iterator<Item = (Frame, Vec<u8>, u8)> + '_ {
+        (0..self.length)
+            .map(|i| self.inputs[(self.tail + i) % INPUT_QUEUE_LENGTH])
+            .map(|input| {
+                let bytes = bytemuck::bytes_of(&input.input).to_vec();
+                (input.frame, bytes, input.flags)
+            })
+    }
+
+    /// Fills an empty queue with exported inputs.
+    pub(crate) fn restore_runtime(&mut self, runtime: &QueueRuntime) -> Result<(), GGRSError> {
+        assert!(self.first_frame);
+        if runtime
+            .inputs
+            .iter()
+            .any(|(_, bytes, _)| bytes.len() != core::mem::size_of::<T::Input>())
+        {
+            return Err(GGRSError::InvalidRequest {
+                info: "The exported inputs do not match the input type of the session.".to_owned(),
+            });
+        }
+
+        self.set_start_frame(runtime.start_frame);
+        for (frame, bytes, flags) in &runtime.inputs {
+            let input =
+                PlayerInput::new(*frame, bytemuck::pod_read_unaligned(bytes)).with_flags(*flags);
+            self.add_input_by_frame(input, *frame);
+        }
+        self.last_user_frame = runtime.last_user_frame;
+        self.frame_delay = runtime.frame_delay;
+        Ok(())
+    }
+
     pub(crate) fn first_incorrect_frame(&self) -> Frame {
         self.first_incorrect_frame
     }
 
+    /// Sets the frame delay. This can be changed at any time: If the delay grows, the next added input fills the frames in between by
+    /// repeating the last input in the queue. If the delay shrinks, added inputs are dropped until they are no longer scheduled for frames already in the queue.
     pub(crate) fn set_frame_delay(&mut self, delay: usize) {
         self.frame_delay = delay;
     }
 
+    pub(crate) fn frame_delay(&self) -> usize {
+        self.frame_delay
+    }
+
+    /// Marks the queue as holding the inputs of a local player.
+    pub(crate) fn set_local(&mut self) {
+        self.local = true;
+    }
+
     pub(crate) fn reset_prediction(&mut self) {
         self.prediction.frame = NULL_FRAME;
         self.first_incorrect_frame = NULL_FRAME;
@@ -81,6 +201,11 @@ impl<T: Config> InputQueue<T> {
 
     /// Discards confirmed frames up to given `frame` from the queue. All confirmed frames are guaranteed to be synchronized between players, so there is no need to save the inputs anymore.
     pub(crate) fn discard_confirmed_frames(&mut self, mut frame: Frame) {
+        // there is nothing to discard before the first input
+        if self.last_added_frame == NULL_FRAME {
+            return;
+        }
+
         // we only drop frames until the last frame that was requested, otherwise we might delete data still needed
         if self.last_requested_frame != NULL_FRAME {
             frame = cmp::min(frame, self.last_requested_frame);
@@ -100,8 +225,9 @@ impl<T: Config> InputQueue<T> {
         }
     }
 
-    /// Returns the game input of a single player for a given frame, if that input does not exist, we return a prediction instead.
-    pub(crate) fn input(&mut self, requested_frame: Frame) -> (T::Input, InputStatus) {
+    /// Returns the game input and its flags of a single player for a given frame, if that input does not exist, we return a prediction instead.
+    /// Predicted inputs keep the flags of the input the prediction is based on.
+    pub(crate) fn input(&mut self, requested_frame: Frame) -> (T::Input, InputStatus, u8) {
         // No one should ever try to grab any input when we have a prediction error.
         // Doing so means that we're just going further down the wrong path. Assert this to verify that it's true.
         assert!(self.first_incorrect_frame == NULL_FRAME);
@@ -120,13 +246,18 @@ impl<T: Config> InputQueue<T> {
             if offset < self.length {
                 offset = (offset + self.tail) % INPUT_QUEUE_LENGTH;
                 assert!(self.inputs[offset].frame == requested_frame);
-                return (self.inputs[offset].input, InputStatus::Confirmed);
+                let input = self.inputs[offset];
+                let status = match self.local {
+                    true => InputStatus::ConfirmedLocal,
+                    false => InputStatus::ConfirmedRemote,
+                };
+                return (input.input, status, input.flags);
             }
 
-            // The requested frame isn't in the queue. This means we need to return a prediction frame. Predict that the user will do the same thing they did last time.
+            // The requested frame isn't in the queue. This means we need to return a prediction frame. The prediction strategy decides what the user will do based on what they did last time.
             if requested_frame == 0 || self.last_added_frame == NULL_FRAME {
                 // basing new prediction frame from nothing, since we are on frame 0 or we have no frames yet
-                self.prediction = PlayerInput::blank_input(self.prediction.frame);
+                self.prediction = PlayerInput::blank_input(self.start_frame - 1);
             } else {
                 // basing new prediction frame from previously added frame
                 let previous_position = match self.head {
@@ -135,23 +266,45 @@ impl<T: Config> InputQueue<T> {
                 };
                 self.prediction = self.inputs[previous_position];
             }
+            self.prediction_base = self.prediction;
             // update the prediction's frame
             self.prediction.frame += 1;
         }
 
-        // We must be predicting, so we return the prediction frame contents. We are adjusting the prediction to have the requested frame.
+        // We must be predicting, so we return the prediction for the requested frame.
         assert!(self.prediction.frame != NULL_FRAME);
-        let prediction_to_return = self.prediction; // GameInput has copy semantics
-        (prediction_to_return.input, InputStatus::Predicted)
+        (
+            self.predicted_input(requested_frame),
+            InputStatus::Predicted,
+            self.prediction_base.flags,
+        )
+    }
+
+    /// Returns the input the prediction strategy predicts for the given frame, based on the last known input before the prediction started.
+    fn predicted_input(&self, frame: Frame) -> T::Input {
+        let frames_ahead = (frame - self.prediction_base.frame) as u32;
+        self.prediction_strategy
+            .predict(self.prediction_base.input, frames_ahead)
+    }
+
+    /// Returns true if a prediction has been handed out for the frame of the given input and the input differs from that prediction.
+    pub(crate) fn is_prediction_miss(&self, input: &PlayerInput<T::Input>) -> bool {
+        // inputs delayed beyond the limit of the frame counter are never used
+        let frame = match input.frame.checked_add(self.frame_delay as i32) {
+            Some(frame) => frame,
+            None => return false,
+        };
+        self.prediction.frame == frame
+            && frame <= self.last_requested_frame
+            && (self.predicted_input(frame) != input.input
+                || self.prediction_base.flags != input.flags)
     }
 
     /// Adds an input frame to the queue. Will consider the set frame delay.
     pub(crate) fn add_input(&mut self, input: PlayerInput<T::Input>) -> Frame {
         // Verify that inputs are passed in sequentially by the user, regardless of frame delay.
-        assert!(
-            self.last_added_frame == NULL_FRAME
-                || input.frame + self.frame_delay as i32 == self.last_added_frame + 1
-        );
+        assert!(self.last_user_frame == NULL_FRAME || input.frame == self.last_user_frame + 1);
+        self.last_user_frame = input.frame;
 
         // Move the queue head to the correct point in preparation to input the frame into the queue.
         let new_frame = self.advance_queue_head(input.frame);
@@ -171,7 +324,10 @@ impl<T: Config> InputQueue<T> {
         };
 
         assert!(self.last_added_frame == NULL_FRAME || frame_number == self.last_added_frame + 1);
-        assert!(frame_number == 0 || self.inputs[previous_position].frame == frame_number - 1);
+        assert!(
+            (self.first_frame && frame_number == self.start_frame)
+                || self.inputs[previous_position].frame == frame_number - 1
+        );
 
         // Add the frame to the back of the queue
         self.inputs[self.head] = input;
@@ -187,7 +343,9 @@ impl<T: Config> InputQueue<T> {
             assert!(frame_number == self.prediction.frame);
 
             // Remember the first input which was incorrect so we can report it
-            if self.first_incorrect_frame == NULL_FRAME && !self.prediction.equal(&input, true) {
+            let predicted = PlayerInput::new(frame_number, self.predicted_input(frame_number))
+                .with_flags(self.prediction_base.flags);
+            if self.first_incorrect_frame == NULL_FRAME && !predicted.equal(&input, true) {
                 self.first_incorrect_frame = frame_number;
             }
 
@@ -204,19 +362,26 @@ impl<T: Config> InputQueue<T> {
     }
 
     /// Advances the queue head to the next frame and either drops inputs or fills the queue if the input delay has changed since the last frame.
-    fn advance_queue_head(&mut self, mut input_frame: Frame) -> Frame {
+    fn advance_queue_head(&mut self, input_frame: Frame) -> Frame {
         let previous_position = match self.head {
             0 => INPUT_QUEUE_LENGTH - 1,
             _ => self.head - 1,
         };
 
-        let mut expected_frame = if self.first_frame {
-            0
+        let expected_frame = if self.first_frame {
+            Some(self.start_frame)
         } else {
-            self.inputs[previous_position].frame + 1
+            self.inputs[previous_position].frame.checked_add(1)
         };
 
-        input_frame += self.frame_delay as i32;
+        // inputs delayed beyond the limit of the frame counter can never be used. Toss them.
+        let (mut expected_frame, input_frame) = match (
+            expected_frame,
+            input_frame.checked_add(self.frame_delay as i32),
+        ) {
+            (Some(expected_frame), Some(input_frame)) => (expected_frame, input_frame),
+            _ => return NULL_FRAME,
+        };
         //  This can occur when the frame delay has dropped since the last time we shoved a frame into the system. In this case, there's no room on the queue. Toss it.
         if expected_frame > input_frame {
             return NULL_FRAME;
@@ -234,7 +399,10 @@ impl<T: Config> InputQueue<T> {
             0 => INPUT_QUEUE_LENGTH - 1,
             _ => self.head - 1,
         };
-        assert!(input_frame == 0 || input_frame == self.inputs[previous_position].frame + 1);
+        assert!(
+            (self.first_frame && input_frame == self.start_frame)
+                || input_frame == self.inputs[previous_position].frame + 1
+        );
         input_frame
     }
 }
@@ -269,7 +437,7 @@ mod input_queue_tests {
     #[test]
     #[should_panic]
     fn test_add_input_wrong_frame() {
-        let mut queue = InputQueue::<TestConfig>::new();
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
         let input = PlayerInput::new(0, TestInput { inp: 0 });
         queue.add_input(input); // fine
         let input_wrong_frame = PlayerInput::new(3, TestInput { inp: 0 });
@@ -279,7 +447,7 @@ mod input_queue_tests {
     #[test]
     #[should_panic]
     fn test_add_input_twice() {
-        let mut queue = InputQueue::<TestConfig>::new();
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
         let input = PlayerInput::new(0, TestInput { inp: 0 });
         queue.add_input(input); // fine
         queue.add_input(input); // not fine
@@ -287,7 +455,7 @@ mod input_queue_tests {
 
     #[test]
     fn test_add_input_sequentially() {
-        let mut queue = InputQueue::<TestConfig>::new();
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
         for i in 0..10 {
             let input = PlayerInput::new(i, TestInput { inp: 0 });
             queue.add_input(input);
@@ -298,20 +466,20 @@ mod input_queue_tests {
 
     #[test]
     fn test_input_sequentially() {
-        let mut queue = InputQueue::<TestConfig>::new();
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
         for i in 0..10 {
             let input = PlayerInput::new(i, TestInput { inp: i as u8 });
             queue.add_input(input);
             assert_eq!(queue.last_added_frame, i);
             assert_eq!(queue.length, (i + 1) as usize);
-            let (input_in_queue, _status) = queue.input(i);
+            let (input_in_queue, _status, _flags) = queue.input(i);
             assert_eq!(input_in_queue.inp, i as u8);
         }
     }
 
     #[test]
     fn test_delayed_inputs() {
-        let mut queue = InputQueue::<TestConfig>::new();
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
         let delay: i32 = 2;
         queue.set_frame_delay(delay as usize);
         for i in 0..10 {
@@ -319,9 +487,145 @@ mod input_queue_tests {
             queue.add_input(input);
             assert_eq!(queue.last_added_frame, i + delay);
             assert_eq!(queue.length, (i + delay + 1) as usize);
-            let (input_in_queue, _status) = queue.input(i);
-            let correct_input = std::cmp::max(0, i - delay) as u8;
+            let (input_in_queue, _status, _flags) = queue.input(i);
+            let correct_input = core::cmp::max(0, i - delay) as u8;
             assert_eq!(input_in_queue.inp, correct_input);
         }
     }
+
+    #[test]
+    fn test_change_frame_delay() {
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
+        queue.set_frame_delay(1);
+        for i in 0..3 {
+            assert_eq!(
+                queue.add_input(PlayerInput::new(i, TestInput { inp: i as u8 })),
+                i + 1
+            );
+        }
+
+        // the frames in between are filled with the last input
+        queue.set_frame_delay(3);
+        assert_eq!(
+            queue.add_input(PlayerInput::new(3, TestInput { inp: 3 })),
+            6
+        );
+        assert_eq!(queue.confirmed_input(4).input.inp, 2);
+        assert_eq!(queue.confirmed_input(5).input.inp, 2);
+
+        // inputs scheduled for frames that are already in the queue are dropped
+        queue.set_frame_delay(1);
+        assert_eq!(
+            queue.add_input(PlayerInput::new(4, TestInput { inp: 4 })),
+            NULL_FRAME
+        );
+        assert_eq!(
+            queue.add_input(PlayerInput::new(5, TestInput { inp: 5 })),
+            NULL_FRAME
+        );
+        assert_eq!(
+            queue.add_input(PlayerInput::new(6, TestInput { inp: 6 })),
+            7
+        );
+        assert_eq!(queue.confirmed_input(6).input.inp, 3);
+        assert_eq!(queue.confirmed_input(7).input.inp, 6);
+    }
+
+    #[test]
+    fn test_start_frame() {
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
+        queue.set_start_frame(10);
+        queue.set_frame_delay(2);
+        // nothing is discarded before the first input
+        queue.discard_confirmed_frames(9);
+
+        // the frames up to the input delay are filled with blank inputs
+        assert_eq!(
+            queue.add_input(PlayerInput::new(10, TestInput { inp: 5 })),
+            12
+        );
+        assert_eq!(queue.confirmed_input(10).input.inp, 0);
+        assert_eq!(queue.confirmed_input(11).input.inp, 0);
+        assert_eq!(queue.input(12).0.inp, 5);
+        let (input, status, _flags) = queue.input(13);
+        assert_eq!((input.inp, status), (5, InputStatus::Predicted));
+    }
+
+    #[test]
+    fn test_start_frame_prediction() {
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
+        queue.set_start_frame(10);
+        let (input, status, _flags) = queue.input(10);
+        assert_eq!((input.inp, status), (0, InputStatus::Predicted));
+
+        // the first input arrives for the start frame and corrects the prediction
+        queue.add_input(PlayerInput::new(10, TestInput { inp: 1 }));
+        assert_eq!(queue.first_incorrect_frame(), 10);
+    }
+
+    struct CountUp;
+
+    impl PredictionStrategy<TestInput> for CountUp {
+        fn predict(&self, last_confirmed: TestInput, frames_ahead: u32) -> TestInput {
+            TestInput {
+                inp: last_confirmed.inp + frames_ahead as u8,
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_prediction_strategy() {
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(CountUp));
+        queue.add_input(PlayerInput::new(0, TestInput { inp: 10 }));
+        let (input, status, _flags) = queue.input(0);
+        assert_eq!((input.inp, status), (10, InputStatus::ConfirmedRemote));
+
+        for i in 1..5 {
+            let (input, status, _flags) = queue.input(i);
+            assert_eq!((input.inp, status), (10 + i as u8, InputStatus::Predicted));
+        }
+
+        // the arriving inputs match the predictions, so there is nothing incorrect
+        queue.add_input(PlayerInput::new(1, TestInput { inp: 11 }));
+        queue.add_input(PlayerInput::new(2, TestInput { inp: 12 }));
+        assert_eq!(queue.first_incorrect_frame(), NULL_FRAME);
+
+        // this one doesn't match
+        queue.add_input(PlayerInput::new(3, TestInput { inp: 0 }));
+        assert_eq!(queue.first_incorrect_frame(), 3);
+    }
+
+    #[test]
+    fn test_prediction_miss() {
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
+        queue.add_input(PlayerInput::new(0, TestInput { inp: 3 }));
+        // predict frames 1 and 2
+        queue.input(1);
+        queue.input(2);
+
+        let correct = PlayerInput::new(1, TestInput { inp: 3 });
+        assert!(!queue.is_prediction_miss(&correct));
+        queue.add_input(correct);
+
+        let incorrect = PlayerInput::new(2, TestInput { inp: 4 });
+        assert!(queue.is_prediction_miss(&incorrect));
+        queue.add_input(incorrect);
+
+        // no prediction was handed out for this frame
+        let unrequested = PlayerInput::new(3, TestInput { inp: 5 });
+        assert!(!queue.is_prediction_miss(&unrequested));
+    }
+
+    #[test]
+    fn test_prediction_keeps_flags() {
+        let mut queue = InputQueue::<TestConfig>::new(Arc::new(RepeatLast));
+        queue.add_input(PlayerInput::new(0, TestInput { inp: 3 }).with_flags(1));
+        assert_eq!(queue.input(1).2, 1);
+
+        // same input, but different flags
+        let incorrect = PlayerInput::new(1, TestInput { inp: 3 });
+        assert!(queue.is_prediction_miss(&incorrect));
+        queue.add_input(incorrect);
+        assert_eq!(queue.first_incorrect_frame(), 1);
+    }
 }
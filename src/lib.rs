@@ -3,48 +3,105 @@
 //! The callback-style API from the original library has been replaced with a much saner, simpler control flow.
 //! Instead of registering callback functions, GGRS returns a list of requests for the user to fulfill.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![forbid(unsafe_code)] // let us try
 #![deny(missing_docs)]
 #![deny(rustdoc::broken_intra_doc_links)]
 //#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
-use std::{fmt::Debug, hash::Hash};
+#[macro_use]
+extern crate alloc;
 
-pub use error::GGRSError;
+use alloc::vec::Vec;
+use core::{fmt::Debug, hash::Hash};
+
+use core::time::Duration;
+
+pub use checksum::ChecksumKind;
+pub use error::{BuilderError, GGRSError};
+pub use input_queue::RepeatLast;
+pub use network::compression::RleInputCodec;
+#[cfg(feature = "std")]
+pub use network::in_memory_socket::{InMemoryNetwork, InMemorySocket};
 pub use network::messages::Message;
 pub use network::network_stats::NetworkStats;
+pub use network::protocol::{MAX_PAYLOAD, MAX_QUALITY_REPORT_PAYLOAD};
+pub use network::shared_socket::{SharedSocket, SharedSocketSession};
+#[cfg(feature = "netsim")]
+pub use network::udp_socket::SimConfig;
+#[cfg(feature = "std")]
 pub use network::udp_socket::UdpNonBlockingSocket;
+#[cfg(all(feature = "wasm", target_arch = "wasm32", not(feature = "sync-send")))]
+pub use network::webrtc_socket::{PeerId, WebRtcNonBlockingSocket, MAX_DATA_CHANNEL_MESSAGE_SIZE};
+#[cfg(feature = "std")]
+pub use replay::ReplayReader;
+pub use rollback_stats::RollbackStats;
+#[cfg(feature = "async")]
+pub use sessions::async_p2p_session::AsyncP2PSession;
 pub use sessions::builder::SessionBuilder;
-pub use sessions::p2p_session::P2PSession;
+pub use sessions::p2p_session::{FastForward, P2PSession};
 pub use sessions::p2p_spectator_session::SpectatorSession;
+#[cfg(feature = "std")]
+pub use sessions::replay_session::ReplaySession;
+pub use sessions::runtime_state::RuntimeState;
 pub use sessions::sync_test_session::SyncTestSession;
 pub use sync_layer::GameStateCell;
+#[cfg(feature = "std")]
+pub use time_source::StdTimeSource;
 
+pub(crate) mod checksum;
 pub(crate) mod error;
 pub(crate) mod frame_info;
 pub(crate) mod input_queue;
+#[cfg(feature = "std")]
+pub(crate) mod replay;
+pub(crate) mod rollback_stats;
 pub(crate) mod sync_layer;
+#[cfg(feature = "std")]
+pub(crate) mod time_source;
 pub(crate) mod time_sync;
 pub(crate) mod sessions {
+    #[cfg(feature = "async")]
+    pub(crate) mod async_p2p_session;
     pub(crate) mod builder;
     pub(crate) mod p2p_session;
     pub(crate) mod p2p_spectator_session;
+    #[cfg(feature = "std")]
+    pub(crate) mod replay_session;
+    pub(crate) mod runtime_state;
     pub(crate) mod sync_test_session;
 }
 pub(crate) mod network {
     pub(crate) mod compression;
+    #[cfg(feature = "std")]
+    pub(crate) mod crypto_socket;
+    #[cfg(feature = "std")]
+    pub(crate) mod in_memory_socket;
     pub(crate) mod messages;
     pub(crate) mod network_stats;
     pub(crate) mod protocol;
+    pub(crate) mod serialized_size;
+    pub(crate) mod shared_socket;
+    #[cfg(feature = "std")]
     pub(crate) mod udp_socket;
+    #[cfg(all(feature = "wasm", target_arch = "wasm32", not(feature = "sync-send")))]
+    pub(crate) mod webrtc_socket;
 }
 
+// a blocking lock with std, a spin lock without it
+#[cfg(feature = "std")]
+pub(crate) use parking_lot::Mutex;
+#[cfg(not(feature = "std"))]
+pub(crate) use spin::Mutex;
+
 // #############
 // # CONSTANTS #
 // #############
 
 /// Internally, -1 represents no frame / invalid frame.
 pub const NULL_FRAME: i32 = -1;
-/// A frame is a single step of execution.
+/// A frame is a single step of execution. Frames are counted with an `i32`, which lasts for more than a year at 60 fps,
+/// but only for about 24 days at 1000 fps. A [`P2PSession`] sends [`GGRSEvent::FrameCounterNearLimit`] before the counter runs out,
+/// so long-running sessions can be restarted in time.
 pub type Frame = i32;
 /// Each player is identified by a player handle.
 pub type PlayerHandle = usize;
@@ -57,13 +114,16 @@ pub type PlayerHandle = usize;
 /// - local players, who play on the local device,
 /// - remote players, who play on other devices and
 /// - spectators, who are remote players that do not contribute to the game input.
+///
 /// Both [`PlayerType::Remote`] and [`PlayerType::Spectator`] have a socket address associated with them.
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PlayerType<A>
 where
     A: Clone + PartialEq + Eq + Hash,
 {
     /// This player plays on the local device.
+    #[default]
     Local,
     /// This player plays on a remote device identified by the socket address.
     Remote(A),
@@ -71,12 +131,6 @@ where
     Spectator(A),
 }
 
-impl<A: Clone + PartialEq + Eq + Hash> Default for PlayerType<A> {
-    fn default() -> Self {
-        Self::Local
-    }
-}
-
 /// A session is always in one of these states. You can query the current state of a session via [`current_state`].
 ///
 /// [`current_state`]: P2PSession#method.current_state
@@ -86,21 +140,119 @@ pub enum SessionState {
     Synchronizing,
     /// When running, the session has synchronized and is ready to take and transmit player input.
     Running,
+    /// The session has been ended with [`P2PSession::end_session()`] and no longer takes player input.
+    ///
+    /// [`P2PSession::end_session()`]: P2PSession#method.end_session
+    Ended,
+}
+
+/// Like [`SessionState`], but with details about the current phase of a session, e.g. to show them in your UI.
+/// You can query it via [`detailed_state`], and get the matching [`SessionState`] back with [`coarse()`].
+///
+/// [`detailed_state`]: P2PSession#method.detailed_state
+/// [`coarse()`]: Self#method.coarse
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DetailedSessionState {
+    /// The session attempts to establish a connection to the remote clients.
+    Synchronizing {
+        /// The number of remote clients the session still has to synchronize with before it starts running.
+        remaining_peers: usize,
+    },
+    /// The session has synchronized and is ready to take and transmit player input.
+    Running,
+    /// The session has been ended and no longer takes player input.
+    Ended,
+}
+
+impl DetailedSessionState {
+    /// Returns the [`SessionState`] without details.
+    pub fn coarse(&self) -> SessionState {
+        match self {
+            DetailedSessionState::Synchronizing { .. } => SessionState::Synchronizing,
+            DetailedSessionState::Running => SessionState::Running,
+            DetailedSessionState::Ended => SessionState::Ended,
+        }
+    }
+}
+
+/// The settings a session has been configured with, as returned by [`SessionBuilder::config_summary()`] and [`P2PSession::config_summary()`].
+/// Peers with different settings will likely desync, so comparing or logging the summaries of all peers helps to track down such problems.
+///
+/// [`SessionBuilder::config_summary()`]: SessionBuilder#method.config_summary
+/// [`P2PSession::config_summary()`]: P2PSession#method.config_summary
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionConfigSummary {
+    /// The number of players of the session, not counting spectators.
+    pub num_players: usize,
+    /// The input delay of the local players.
+    pub input_delay: usize,
+    /// The maximum number of frames the session may predict ahead of the confirmed frame.
+    pub max_prediction: usize,
+    /// Whether sparse saving is enabled.
+    pub sparse_saving: bool,
+    /// The expected update frequency of the session.
+    pub fps: usize,
+    /// The time after which a remote client is disconnected if no packets have been received from it.
+    pub disconnect_timeout: Duration,
+    /// The time after which the session notifies you that no packets have been received from a remote client.
+    pub disconnect_notify_start: Duration,
+}
+
+/// The connection state of a single player or spectator, as returned by [`P2PSession::player_connection_states()`].
+///
+/// [`P2PSession::player_connection_states()`]: P2PSession#method.player_connection_states
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlayerConnectionState {
+    /// The player is local to this session.
+    Local,
+    /// The remote player has not synchronized with this session yet.
+    Synchronizing,
+    /// The remote player has synchronized with this session and is exchanging inputs.
+    Synchronized,
+    /// The remote player or spectator has been disconnected.
+    Disconnected,
+    /// The handle belongs to a spectator that is still connected.
+    Spectator {
+        /// Whether the spectator has synchronized with this session.
+        synchronized: bool,
+    },
 }
 
 /// [`InputStatus`] will always be given together with player inputs when requested to advance the frame.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InputStatus {
-    /// The input of this player for this frame is an actual received input.
-    Confirmed,
+    /// The input of this player for this frame has been added to this session as a local input. It is final, even while resimulating
+    /// during a rollback, so you can use it to skip effects of local players you have already triggered.
+    ConfirmedLocal,
+    /// The input of this player for this frame is an actual input received from a remote client, or read from a replay.
+    ConfirmedRemote,
     /// The input of this player for this frame is predicted.
     Predicted,
     /// The player has disconnected at or prior to this frame, so this input is a dummy.
     Disconnected,
 }
 
+impl InputStatus {
+    /// Returns true for [`InputStatus::ConfirmedLocal`] and [`InputStatus::ConfirmedRemote`], i.e. if the input will not change anymore.
+    /// Checking this replaces matching the former `InputStatus::Confirmed`, if you do not care where a confirmed input came from.
+    pub fn is_confirmed(&self) -> bool {
+        matches!(self, Self::ConfirmedLocal | Self::ConfirmedRemote)
+    }
+}
+
 /// Notifications that you can receive from the session. Handling them is up to the user.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T::Address: serde::Serialize",
+        deserialize = "T::Address: serde::Deserialize<'de>"
+    ))
+)]
 pub enum GGRSEvent<T>
 where
     T: Config,
@@ -119,7 +271,7 @@ where
         /// The address of the endpoint.
         addr: T::Address,
     },
-    /// The remote client has disconnected.
+    /// The remote client has disconnected. When several remote clients disconnect at once, the events are sent in ascending order of their player handles.
     Disconnected {
         /// The address of the endpoint.
         addr: T::Address,
@@ -141,6 +293,167 @@ where
         /// Amount of frames recommended to be skipped in order to let other clients catch up.
         skip_frames: u32,
     },
+    /// Sent out when a received input of a remote player differs from the prediction that was used to advance that frame.
+    /// This will lead to a rollback.
+    PredictionMiss {
+        /// The frame of the mispredicted input.
+        frame: Frame,
+        /// The player whose input was mispredicted.
+        player: PlayerHandle,
+    },
+    /// Sent out when a received input of a remote player did not pass the validator set with [`SessionBuilder::with_input_validator()`].
+    /// The session uses the last valid input of that player instead.
+    ///
+    /// [`SessionBuilder::with_input_validator()`]: crate::SessionBuilder#method.with_input_validator
+    InvalidInput {
+        /// The player who sent the invalid input.
+        handle: PlayerHandle,
+        /// The frame of the invalid input.
+        frame: Frame,
+    },
+    /// Sent out when the session rolls back, i.e. loads the state of `to_frame` while it was at `from_frame`. The requests returned by the same
+    /// advance then re-simulate the frames from `to_frame` up to `from_frame - 1`, before advancing `from_frame` for the first time.
+    /// Effects that should only happen once, like sounds or particles, can be skipped for the re-simulated frames.
+    Rollback {
+        /// The current frame before rolling back.
+        from_frame: Frame,
+        /// The frame of the loaded state.
+        to_frame: Frame,
+    },
+    /// Sent out every time the session requests to save a state, if enabled with [`SessionBuilder::with_state_saved_events()`].
+    ///
+    /// [`SessionBuilder::with_state_saved_events()`]: crate::SessionBuilder#method.with_state_saved_events
+    StateSaved {
+        /// The frame of the saved state.
+        frame: Frame,
+    },
+    /// Sent out if a remote client confirmed different inputs up to `frame` than this session, if enabled with [`SessionBuilder::with_input_desync_detection()`].
+    /// The game states of both clients have diverged from this frame on at the latest. The `kind` is then [`DesyncKind::Inputs`].
+    ///
+    /// A [`SpectatorSession`] sends it if its state of `frame` has a different checksum than the state of the host, if enabled with
+    /// [`SessionBuilder::with_spectator_checksums()`]. The `kind` is then [`DesyncKind::State`].
+    ///
+    /// [`SessionBuilder::with_input_desync_detection()`]: crate::SessionBuilder#method.with_input_desync_detection
+    /// [`SessionBuilder::with_spectator_checksums()`]: crate::SessionBuilder#method.with_spectator_checksums
+    /// [`SpectatorSession`]: crate::SpectatorSession
+    DesyncDetected {
+        /// The frame up to which the inputs have been compared, or the frame of the compared states.
+        frame: Frame,
+        /// What the checksums have been computed over.
+        kind: DesyncKind,
+        /// The checksum of this session, see `kind`.
+        local_checksum: u128,
+        /// The checksum of the remote client, see `kind`.
+        remote_checksum: u128,
+        /// The address of the remote client.
+        addr: T::Address,
+    },
+    /// Sent out when all remote players have acknowledged the local inputs up to and including `frame`, so every peer has received them.
+    /// Disconnected players are not waited for. The frame already accounts for the input delay.
+    InputAcked {
+        /// The last frame for which the local inputs have been acknowledged.
+        frame: Frame,
+    },
+    /// Sent out after the session recovered from a [`GGRSEvent::DesyncDetected`] by loading the state of the authoritative peer,
+    /// if enabled with [`SessionBuilder::with_desync_recovery()`]. The session resimulated all frames from `frame` on.
+    ///
+    /// [`SessionBuilder::with_desync_recovery()`]: crate::SessionBuilder#method.with_desync_recovery
+    DesyncRecovered {
+        /// The frame of the loaded state.
+        frame: Frame,
+    },
+    /// Sent out if the session gave up recovering from a [`GGRSEvent::DesyncDetected`], because the authoritative peer could not send the state,
+    /// did not answer in time, sent a state that could not be decoded or disconnected. The session keeps running with the desynced state.
+    /// A later desync starts a new recovery.
+    DesyncRecoveryFailed {
+        /// The frame of the requested state.
+        frame: Frame,
+    },
+    /// Sent out once when the frame counter of the session is less than an hour of frames away from [`Frame::MAX`], at the fps of the session.
+    /// The session cannot advance past [`Frame::MAX`], so you should end it and start a new one from the current game state before that.
+    FrameCounterNearLimit {
+        /// The current frame of the session.
+        frame: Frame,
+        /// The number of frames that can still be advanced.
+        frames_left: Frame,
+    },
+    /// Sent out when a message sent with [`P2PSession::send_reliable()`] arrives. Messages from the same remote client arrive exactly once and in order.
+    ///
+    /// [`P2PSession::send_reliable()`]: crate::P2PSession#method.send_reliable
+    Message {
+        /// The address of the remote client that sent the message.
+        from: T::Address,
+        /// The bytes of the message.
+        bytes: Vec<u8>,
+    },
+    /// Sent out when a quality report of a remote client arrives with the payload set by [`SessionBuilder::with_quality_report_payload()`].
+    /// Quality reports are sent regularly, but are not resent if they are lost.
+    ///
+    /// [`SessionBuilder::with_quality_report_payload()`]: crate::SessionBuilder#method.with_quality_report_payload
+    QualityReportPayload {
+        /// The address of the remote client that attached the payload.
+        addr: T::Address,
+        /// The bytes of the payload.
+        bytes: Vec<u8>,
+    },
+    /// Sent out when all peers agreed on the frame of a coordinated pause, see [`P2PSession::pause()`]. The session keeps advancing until it reaches
+    /// that frame, then advancing returns [`GGRSError::Paused`].
+    ///
+    /// [`P2PSession::pause()`]: crate::P2PSession#method.pause
+    Paused {
+        /// The frame at which all peers stop advancing.
+        frame: Frame,
+    },
+    /// Sent out when a remote client resumed the coordinated pause, see [`P2PSession::resume()`].
+    ///
+    /// [`P2PSession::resume()`]: crate::P2PSession#method.resume
+    Resumed {
+        /// The address of the remote client that resumed.
+        addr: T::Address,
+    },
+    /// Sent out when a remote client tries to synchronize with a different app id, see [`SessionBuilder::with_app_id()`], a different
+    /// shared seed, see [`SessionBuilder::with_shared_seed()`], or a version of GGRS with a different message format. The session never synchronizes
+    /// with that client, so you should end the session and tell the user to update or to start the match again.
+    ///
+    /// [`SessionBuilder::with_app_id()`]: crate::SessionBuilder#method.with_app_id
+    /// [`SessionBuilder::with_shared_seed()`]: crate::SessionBuilder#method.with_shared_seed
+    IncompatiblePeer {
+        /// The address of the incompatible remote client.
+        addr: T::Address,
+        /// The lowest handle of the incompatible remote client. In a [`SpectatorSession`], this is always 0.
+        ///
+        /// [`SpectatorSession`]: crate::SpectatorSession
+        handle: PlayerHandle,
+    },
+    /// Sent out once when the estimated offset between the local clock and the clock of a remote client exceeds 100ms, i.e. the clocks drifted apart
+    /// that far since the connection was established, see [`P2PSession::estimated_clock_offset()`]. Sent out again if the offset falls back below and
+    /// then exceeds the threshold once more.
+    ///
+    /// [`P2PSession::estimated_clock_offset()`]: crate::P2PSession#method.estimated_clock_offset
+    ClockDriftWarning {
+        /// The lowest handle of the remote client. In a [`SpectatorSession`], this is always 0.
+        ///
+        /// [`SpectatorSession`]: crate::SpectatorSession
+        handle: PlayerHandle,
+        /// The estimated offset between the clocks, in either direction, as returned by [`P2PSession::estimated_clock_offset()`].
+        ///
+        /// [`P2PSession::estimated_clock_offset()`]: crate::P2PSession#method.estimated_clock_offset
+        offset: Duration,
+    },
+    /// Sent out when a remote client lets a player join on a different frame than this session did with [`P2PSession::add_player_at_frame()`]
+    /// or [`P2PSession::reconnect_player()`], or on a frame this session has already advanced without letting the player join.
+    /// The clients will not agree on the inputs of that player, so you should end the session.
+    ///
+    /// [`P2PSession::add_player_at_frame()`]: crate::P2PSession#method.add_player_at_frame
+    /// [`P2PSession::reconnect_player()`]: crate::P2PSession#method.reconnect_player
+    PlayerJoinConflict {
+        /// The address of the remote client.
+        addr: T::Address,
+        /// The handle of the joining player.
+        handle: PlayerHandle,
+        /// The frame the remote client lets the player join on.
+        frame: Frame,
+    },
 }
 
 /// Requests that you can receive from the session. Handling them is mandatory.
@@ -165,11 +478,56 @@ where
     /// You should advance the gamestate with the `inputs` provided to you.
     /// Disconnected players are indicated by having [`NULL_FRAME`] instead of the correct current frame in their input.
     AdvanceFrame {
-        /// Contains inputs and input status for each player.
-        inputs: Vec<(T::Input, InputStatus)>,
+        /// Contains inputs, input status and input flags for each player. The flags are 0 unless the input has been added with
+        /// [`P2PSession::add_local_input_with_flags()`]. Predicted inputs keep the flags of the input they are predicted from.
+        ///
+        /// [`P2PSession::add_local_input_with_flags()`]: crate::P2PSession#method.add_local_input_with_flags
+        inputs: Vec<(T::Input, InputStatus, u8)>,
     },
 }
 
+impl<T: Config> GGRSRequest<T> {
+    /// Returns the kind of this request, which can be used to inspect a request without matching on it, e.g. for logging.
+    pub fn kind(&self) -> RequestKind {
+        match self {
+            GGRSRequest::SaveGameState { .. } => RequestKind::Save,
+            GGRSRequest::LoadGameState { .. } => RequestKind::Load,
+            GGRSRequest::AdvanceFrame { .. } => RequestKind::Advance,
+        }
+    }
+
+    /// Returns the frame of the gamestate to save or load. Returns `None` for [`GGRSRequest::AdvanceFrame`], since it carries no frame.
+    pub fn frame(&self) -> Option<Frame> {
+        match self {
+            GGRSRequest::SaveGameState { frame, .. } | GGRSRequest::LoadGameState { frame, .. } => {
+                Some(*frame)
+            }
+            GGRSRequest::AdvanceFrame { .. } => None,
+        }
+    }
+}
+
+/// The kind of a [`GGRSRequest`], as returned by [`GGRSRequest::kind()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    /// A [`GGRSRequest::SaveGameState`] request.
+    Save,
+    /// A [`GGRSRequest::LoadGameState`] request.
+    Load,
+    /// A [`GGRSRequest::AdvanceFrame`] request.
+    Advance,
+}
+
+/// What the checksums of a [`GGRSEvent::DesyncDetected`] have been computed over.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DesyncKind {
+    /// The checksums over all confirmed inputs up to the frame.
+    Inputs,
+    /// The checksums of the saved states of the frame.
+    State,
+}
+
 // #############
 // #  TRAITS   #
 // #############
@@ -186,7 +544,11 @@ pub trait Config: 'static + Send + Sync {
     /// on platforms with different endianness. GGRS assumes that all players are
     /// running with the same endianness when encoding and decoding inputs.
     ///
+    /// All players of a session share this input type. If players need differently sized inputs, use a type that fits the largest one
+    /// and add the other players with [`SessionBuilder::add_player_with_input_size()`].
+    ///
     /// [Pod]: bytemuck::Pod
+    /// [`SessionBuilder::add_player_with_input_size()`]: crate::SessionBuilder#method.add_player_with_input_size
     type Input: Copy + Clone + PartialEq + bytemuck::Pod + bytemuck::Zeroable + Send + Sync;
 
     /// The save state type for the session.
@@ -211,6 +573,111 @@ where
     /// This method should return all messages received since the last time this method was called.
     /// The pairs `(A, Message)` indicate from which address each packet was received.
     fn receive_all_messages(&mut self) -> Vec<(A, Message)>;
+
+    /// Seeds the random numbers the socket uses, e.g. to simulate packet loss. Sessions call this with the seed given to
+    /// [`SessionBuilder::with_rng_seed()`] when they start. Does nothing by default.
+    ///
+    /// [`SessionBuilder::with_rng_seed()`]: crate::SessionBuilder#method.with_rng_seed
+    fn set_rng_seed(&mut self, _seed: u64) {}
+}
+
+/// An [`InputCodec`] compresses inputs before they are sent over the network and decompresses them on arrival.
+/// Before handing inputs to the codec, GGRS XOR-encodes them against the last input acknowledged by the remote client,
+/// so inputs that did not change since then consist of zero bytes only. The default codec is [`RleInputCodec`].
+/// The same codec is used for the connections to remote players and spectators, so all clients should use the same codec.
+#[cfg(feature = "sync-send")]
+pub trait InputCodec: Send + Sync {
+    /// Takes a buffer of one or more consecutive delta-encoded inputs and returns the compressed bytes.
+    fn encode(&self, inputs: &[u8]) -> Vec<u8>;
+
+    /// Takes bytes produced by [`InputCodec::encode`] and returns the decompressed delta-encoded inputs.
+    /// Each input is `input_size` bytes long. If the bytes cannot be decoded, an empty buffer should be returned, in which case the packet is dropped.
+    fn decode(&self, bytes: &[u8], input_size: usize) -> Vec<u8>;
+}
+
+/// A [`PredictionStrategy`] decides which input GGRS assumes for a remote player when their actual input has not arrived yet.
+/// Given the last confirmed input of the player and how many frames after that input the prediction is for, it returns the predicted input.
+/// The default strategy is [`RepeatLast`], which assumes that the player keeps doing what they did last. Predictions only affect
+/// how often rollbacks happen, never the correctness of the simulation.
+#[cfg(feature = "sync-send")]
+pub trait PredictionStrategy<I>: Send + Sync {
+    /// Returns the predicted input `frames_ahead` frames after the frame of `last_confirmed`. `frames_ahead` is always at least 1.
+    fn predict(&self, last_confirmed: I, frames_ahead: u32) -> I;
+}
+
+/// A [`TimeSource`] provides the current time for all timers of a session, like the disconnect timeout and the resending of packets.
+/// The returned time is measured from an arbitrary but fixed point in time and must never decrease.
+/// With the `std` feature, the default time source is [`StdTimeSource`]. Providing your own time source lets you use GGRS without a monotonic clock or drive the timers manually in tests.
+#[cfg(feature = "sync-send")]
+pub trait TimeSource: Send + Sync {
+    /// Returns the time passed since the fixed starting point of this time source.
+    fn now(&self) -> Duration;
+}
+
+/// A [`MessageCrypto`] seals every message before it is sent and opens every received message, for example with an AEAD cipher.
+/// Messages are serialized before being sealed and deserialized after being opened, so the socket only ever transports ciphertext.
+/// Messages that cannot be opened are dropped like corrupted packets, and so are unsealed messages. All clients of a session must use the same crypto.
+#[cfg(feature = "sync-send")]
+pub trait MessageCrypto: Send + Sync {
+    /// Takes a serialized message and returns the sealed bytes to send.
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8>;
+
+    /// Takes bytes produced by [`MessageCrypto::seal`] and returns the serialized message, or `None` if the bytes cannot be opened.
+    fn open(&self, ciphertext: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// A [`StateStore`] keeps the states you save through a [`GameStateCell`] outside of the session, e.g. in a memory pool or on disk.
+/// When a session has been configured with [`SessionBuilder::with_state_store()`], saved states are moved into the store and loading a cell retrieves them again.
+/// The session still decides which frames are saved and loaded, so a store only needs to keep the states of the most recent frames:
+/// GGRS never loads a frame that lies more than the prediction window behind the current frame.
+///
+/// [`SessionBuilder::with_state_store()`]: crate::SessionBuilder#method.with_state_store
+#[cfg(feature = "sync-send")]
+pub trait StateStore<S>: Send {
+    /// Stores the state of the given frame together with its checksum. A frame can be stored again after a rollback, replacing the previous state.
+    fn store(&mut self, frame: Frame, state: S, checksum: Option<u128>);
+
+    /// Returns the state and checksum most recently stored for the given frame, or `None` if there is none.
+    /// A frame can be retrieved multiple times, so the state should not be removed from the store.
+    /// GGRS keeps using the checksum given to [`store()`], so the returned checksum is only informative.
+    ///
+    /// [`store()`]: Self#tymethod.store
+    fn retrieve(&mut self, frame: Frame) -> Option<(S, Option<u128>)>;
+}
+
+/// A [`DisconnectHook`] is called synchronously by a [`P2PSession`] for every remote player it disconnects, with the first frame the player is disconnected on.
+/// The session then resimulates from that frame with the player marked as [`InputStatus::Disconnected`], so you can freeze the player or let an AI take over
+/// at exactly that frame. When several players are disconnected at once, the hook is called in ascending order of their handles.
+/// Any closure taking the player handle and the frame is a [`DisconnectHook`].
+#[cfg(feature = "sync-send")]
+pub trait DisconnectHook: Send + Sync {
+    /// Called when the given player is disconnected, with the first frame on which the player is treated as disconnected.
+    fn on_disconnect(&mut self, player_handle: PlayerHandle, frame: Frame);
+}
+
+#[cfg(feature = "sync-send")]
+impl<F: FnMut(PlayerHandle, Frame) + Send + Sync> DisconnectHook for F {
+    fn on_disconnect(&mut self, player_handle: PlayerHandle, frame: Frame) {
+        self(player_handle, frame)
+    }
+}
+
+/// The [`AsyncNonBlockingSocket`] trait is the awaitable counterpart of [`NonBlockingSocket`], used by an [`AsyncP2PSession`]
+/// to integrate GGRS into an async runtime. Messages should be sent in an UDP-like fashion, unordered and unreliable.
+#[cfg(all(feature = "async", feature = "sync-send"))]
+pub trait AsyncNonBlockingSocket<A>: Send + Sync
+where
+    A: Clone + PartialEq + Eq + Hash + Send + Sync,
+{
+    /// Takes a [`Message`] and sends it to the given address.
+    fn send_to(&mut self, msg: &Message, addr: &A)
+        -> impl core::future::Future<Output = ()> + Send;
+
+    /// This method should return all messages received since the last time this method was called, without waiting for new messages to arrive.
+    /// The pairs `(A, Message)` indicate from which address each packet was received.
+    fn receive_all_messages(
+        &mut self,
+    ) -> impl core::future::Future<Output = Vec<(A, Message)>> + Send;
 }
 
 /// Compile time parameterization for sessions.
@@ -223,7 +690,11 @@ pub trait Config: 'static {
     /// on platforms with different endianness. GGRS assumes that all players are
     /// running with the same endianness when encoding and decoding inputs.
     ///
+    /// All players of a session share this input type. If players need differently sized inputs, use a type that fits the largest one
+    /// and add the other players with [`SessionBuilder::add_player_with_input_size()`].
+    ///
     /// [Pod]: bytemuck::Pod
+    /// [`SessionBuilder::add_player_with_input_size()`]: crate::SessionBuilder#method.add_player_with_input_size
     type Input: Copy + Clone + PartialEq + bytemuck::Pod + bytemuck::Zeroable;
 
     /// The save state type for the session.
@@ -248,4 +719,106 @@ where
     /// This method should return all messages received since the last time this method was called.
     /// The pairs `(A, Message)` indicate from which address each packet was received.
     fn receive_all_messages(&mut self) -> Vec<(A, Message)>;
+
+    /// Seeds the random numbers the socket uses, e.g. to simulate packet loss. Sessions call this with the seed given to
+    /// [`SessionBuilder::with_rng_seed()`] when they start. Does nothing by default.
+    ///
+    /// [`SessionBuilder::with_rng_seed()`]: crate::SessionBuilder#method.with_rng_seed
+    fn set_rng_seed(&mut self, _seed: u64) {}
+}
+
+/// An [`InputCodec`] compresses inputs before they are sent over the network and decompresses them on arrival.
+/// Before handing inputs to the codec, GGRS XOR-encodes them against the last input acknowledged by the remote client,
+/// so inputs that did not change since then consist of zero bytes only. The default codec is [`RleInputCodec`].
+/// The same codec is used for the connections to remote players and spectators, so all clients should use the same codec.
+#[cfg(not(feature = "sync-send"))]
+pub trait InputCodec {
+    /// Takes a buffer of one or more consecutive delta-encoded inputs and returns the compressed bytes.
+    fn encode(&self, inputs: &[u8]) -> Vec<u8>;
+
+    /// Takes bytes produced by [`InputCodec::encode`] and returns the decompressed delta-encoded inputs.
+    /// Each input is `input_size` bytes long. If the bytes cannot be decoded, an empty buffer should be returned, in which case the packet is dropped.
+    fn decode(&self, bytes: &[u8], input_size: usize) -> Vec<u8>;
+}
+
+/// A [`PredictionStrategy`] decides which input GGRS assumes for a remote player when their actual input has not arrived yet.
+/// Given the last confirmed input of the player and how many frames after that input the prediction is for, it returns the predicted input.
+/// The default strategy is [`RepeatLast`], which assumes that the player keeps doing what they did last. Predictions only affect
+/// how often rollbacks happen, never the correctness of the simulation.
+#[cfg(not(feature = "sync-send"))]
+pub trait PredictionStrategy<I> {
+    /// Returns the predicted input `frames_ahead` frames after the frame of `last_confirmed`. `frames_ahead` is always at least 1.
+    fn predict(&self, last_confirmed: I, frames_ahead: u32) -> I;
+}
+
+/// A [`TimeSource`] provides the current time for all timers of a session, like the disconnect timeout and the resending of packets.
+/// The returned time is measured from an arbitrary but fixed point in time and must never decrease.
+/// With the `std` feature, the default time source is [`StdTimeSource`]. Providing your own time source lets you use GGRS without a monotonic clock or drive the timers manually in tests.
+#[cfg(not(feature = "sync-send"))]
+pub trait TimeSource {
+    /// Returns the time passed since the fixed starting point of this time source.
+    fn now(&self) -> Duration;
+}
+
+/// A [`MessageCrypto`] seals every message before it is sent and opens every received message, for example with an AEAD cipher.
+/// Messages are serialized before being sealed and deserialized after being opened, so the socket only ever transports ciphertext.
+/// Messages that cannot be opened are dropped like corrupted packets, and so are unsealed messages. All clients of a session must use the same crypto.
+#[cfg(not(feature = "sync-send"))]
+pub trait MessageCrypto {
+    /// Takes a serialized message and returns the sealed bytes to send.
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8>;
+
+    /// Takes bytes produced by [`MessageCrypto::seal`] and returns the serialized message, or `None` if the bytes cannot be opened.
+    fn open(&self, ciphertext: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// A [`StateStore`] keeps the states you save through a [`GameStateCell`] outside of the session, e.g. in a memory pool or on disk.
+/// When a session has been configured with [`SessionBuilder::with_state_store()`], saved states are moved into the store and loading a cell retrieves them again.
+/// The session still decides which frames are saved and loaded, so a store only needs to keep the states of the most recent frames:
+/// GGRS never loads a frame that lies more than the prediction window behind the current frame.
+///
+/// [`SessionBuilder::with_state_store()`]: crate::SessionBuilder#method.with_state_store
+#[cfg(not(feature = "sync-send"))]
+pub trait StateStore<S> {
+    /// Stores the state of the given frame together with its checksum. A frame can be stored again after a rollback, replacing the previous state.
+    fn store(&mut self, frame: Frame, state: S, checksum: Option<u128>);
+
+    /// Returns the state and checksum most recently stored for the given frame, or `None` if there is none.
+    /// A frame can be retrieved multiple times, so the state should not be removed from the store.
+    /// GGRS keeps using the checksum given to [`store()`], so the returned checksum is only informative.
+    ///
+    /// [`store()`]: Self#tymethod.store
+    fn retrieve(&mut self, frame: Frame) -> Option<(S, Option<u128>)>;
+}
+
+/// A [`DisconnectHook`] is called synchronously by a [`P2PSession`] for every remote player it disconnects, with the first frame the player is disconnected on.
+/// The session then resimulates from that frame with the player marked as [`InputStatus::Disconnected`], so you can freeze the player or let an AI take over
+/// at exactly that frame. When several players are disconnected at once, the hook is called in ascending order of their handles.
+/// Any closure taking the player handle and the frame is a [`DisconnectHook`].
+#[cfg(not(feature = "sync-send"))]
+pub trait DisconnectHook {
+    /// Called when the given player is disconnected, with the first frame on which the player is treated as disconnected.
+    fn on_disconnect(&mut self, player_handle: PlayerHandle, frame: Frame);
+}
+
+#[cfg(not(feature = "sync-send"))]
+impl<F: FnMut(PlayerHandle, Frame)> DisconnectHook for F {
+    fn on_disconnect(&mut self, player_handle: PlayerHandle, frame: Frame) {
+        self(player_handle, frame)
+    }
+}
+
+/// The [`AsyncNonBlockingSocket`] trait is the awaitable counterpart of [`NonBlockingSocket`], used by an [`AsyncP2PSession`]
+/// to integrate GGRS into an async runtime. Messages should be sent in an UDP-like fashion, unordered and unreliable.
+#[cfg(all(feature = "async", not(feature = "sync-send")))]
+pub trait AsyncNonBlockingSocket<A>
+where
+    A: Clone + PartialEq + Eq + Hash,
+{
+    /// Takes a [`Message`] and sends it to the given address.
+    fn send_to(&mut self, msg: &Message, addr: &A) -> impl core::future::Future<Output = ()>;
+
+    /// This method should return all messages received since the last time this method was called, without waiting for new messages to arrive.
+    /// The pairs `(A, Message)` indicate from which address each packet was received.
+    fn receive_all_messages(&mut self) -> impl core::future::Future<Output = Vec<(A, Message)>>;
 }
@@ -1,13 +1,98 @@
 // special thanks to james7132
 
+use alloc::vec::Vec;
+
+use crate::InputCodec;
+
+/// The default [`InputCodec`] used by GGRS. Since inputs are XOR-encoded against the last acknowledged input before being handed to the codec,
+/// unchanged inputs result in long sequences of zero bits, which are then compressed with a run-length encoding.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RleInputCodec;
+
+impl InputCodec for RleInputCodec {
+    fn encode(&self, inputs: &[u8]) -> Vec<u8> {
+        rle_encode(inputs)
+    }
+
+    fn decode(&self, bytes: &[u8], _input_size: usize) -> Vec<u8> {
+        rle_decode(bytes).unwrap_or_default()
+    }
+}
+
+/// Run-length encodes the bytes in the format of the `bitfield-rle` crate: a run of zero or 0xFF bytes is written as the varint
+/// `len << 2 | 1`, plus 2 for 0xFF bytes. All other bytes are written as the varint `len << 1`, followed by the bytes themselves.
+fn rle_encode(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut rest = bytes;
+    while let Some(&byte) = rest.first() {
+        if byte == 0 || byte == 0xFF {
+            let len = rest.iter().take_while(|&&b| b == byte).count();
+            let fill = if byte == 0xFF { 2 } else { 0 };
+            write_varint(&mut encoded, (len as u64) << 2 | 1 | fill);
+            rest = &rest[len..];
+        } else {
+            let len = rest.iter().take_while(|&&b| b != 0 && b != 0xFF).count();
+            write_varint(&mut encoded, (len as u64) << 1);
+            encoded.extend_from_slice(&rest[..len]);
+            rest = &rest[len..];
+        }
+    }
+    // like `bitfield-rle`, an empty buffer is encoded as an empty sequence of bytes
+    if bytes.is_empty() {
+        write_varint(&mut encoded, 0);
+    }
+    encoded
+}
+
+/// Decodes bytes encoded with [`rle_encode()`]. Returns `None` if the bytes are malformed.
+fn rle_decode(mut bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    while !bytes.is_empty() {
+        let (value, rest) = read_varint(bytes)?;
+        bytes = rest;
+        if value & 1 == 1 {
+            let len = usize::try_from(value >> 2).ok()?;
+            let fill = if value & 2 == 2 { 0xFF } else { 0 };
+            decoded.resize(decoded.len() + len, fill);
+        } else {
+            let len = usize::try_from(value >> 1).ok()?;
+            let (literal, rest) = (bytes.get(..len)?, &bytes[len..]);
+            decoded.extend_from_slice(literal);
+            bytes = rest;
+        }
+    }
+    Some(decoded)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value > 0x7F {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Reads a varint from the start of the bytes and returns it with the remaining bytes.
+fn read_varint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7F).checked_shl(7 * i as u32)?;
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
 pub(crate) fn encode<'a>(
+    codec: &dyn InputCodec,
     reference: &[u8],
     pending_input: impl Iterator<Item = &'a Vec<u8>>,
 ) -> Vec<u8> {
     // first, do a XOR encoding to the reference input (will probably lead to a lot of same bits in sequence)
     let buf = delta_encode(reference, pending_input);
-    // then, RLE encode the buffer (making use of the property mentioned above)
-    bitfield_rle::encode(buf)
+    // then, let the codec compress the buffer (making use of the property mentioned above)
+    codec.encode(&buf)
 }
 
 pub(crate) fn delta_encode<'a>(
@@ -30,18 +115,24 @@ pub(crate) fn delta_encode<'a>(
 }
 
 pub(crate) fn decode(
+    codec: &dyn InputCodec,
     reference: &[u8],
     data: &[u8],
-) -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error>> {
-    // decode the RLE encoding first
-    let buf = bitfield_rle::decode(data)?;
+) -> Option<Vec<Vec<u8>>> {
+    // let the codec decompress the data first
+    let buf = codec.decode(data, reference.len());
+
+    // if the decoded data does not consist of whole inputs, the data could not be decoded
+    if !buf.chunks_exact(reference.len()).remainder().is_empty() {
+        return None;
+    }
 
     // decode the delta-encoding
-    Ok(delta_decode(reference, &buf))
+    Some(delta_decode(reference, &buf))
 }
 
 pub(crate) fn delta_decode(ref_bytes: &[u8], data: &[u8]) -> Vec<Vec<u8>> {
-    assert!(data.len() % ref_bytes.len() == 0);
+    assert_eq!(data.len() % ref_bytes.len(), 0);
     let out_size = data.len() / ref_bytes.len();
     let mut output = Vec::with_capacity(out_size);
 
@@ -83,9 +174,69 @@ mod compression_tests {
 
         let pend_inp = vec![inp0, inp1, inp2, inp3, inp4];
 
-        let encoded = encode(&ref_input, pend_inp.iter());
-        let decoded = decode(&ref_input, &encoded).unwrap();
+        let encoded = encode(&RleInputCodec, &ref_input, pend_inp.iter());
+        let decoded = decode(&RleInputCodec, &ref_input, &encoded).unwrap();
 
         assert!(pend_inp == decoded);
     }
+
+    struct IdentityCodec;
+
+    impl InputCodec for IdentityCodec {
+        fn encode(&self, inputs: &[u8]) -> Vec<u8> {
+            inputs.to_vec()
+        }
+
+        fn decode(&self, bytes: &[u8], _input_size: usize) -> Vec<u8> {
+            bytes.to_vec()
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_custom_codec() {
+        let ref_input = vec![0, 0, 0, 1];
+        let inp0: Vec<u8> = vec![0, 0, 1, 0];
+        let inp1: Vec<u8> = vec![0, 0, 1, 1];
+
+        let pend_inp = vec![inp0, inp1];
+
+        let encoded = encode(&IdentityCodec, &ref_input, pend_inp.iter());
+        assert_eq!(encoded, vec![0, 0, 1, 1, 0, 0, 1, 0]); // only XOR encoded
+        let decoded = decode(&IdentityCodec, &ref_input, &encoded).unwrap();
+
+        assert!(pend_inp == decoded);
+    }
+
+    #[test]
+    fn test_rle_matches_bitfield_rle() {
+        let buffers: [&[u8]; 7] = [
+            &[],
+            &[0; 300],
+            &[0xFF, 0xFF, 0, 0, 0, 7],
+            &[1, 2, 3, 0, 0xFF, 4],
+            &[5; 200],
+            &[0, 9, 0, 9, 0xFF],
+            &[0x80, 0x7F, 0, 0, 0, 0, 0x81],
+        ];
+        for bytes in buffers {
+            let encoded = rle_encode(bytes);
+            assert_eq!(encoded, bitfield_rle::encode(bytes));
+            assert_eq!(rle_decode(&encoded).unwrap(), bytes);
+            assert_eq!(bitfield_rle::decode(&encoded).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_rle_decode_malformed() {
+        // a truncated varint and a literal run longer than the remaining bytes
+        assert!(rle_decode(&[0x80]).is_none());
+        assert!(rle_decode(&[6, 1]).is_none());
+        assert!(RleInputCodec.decode(&[6, 1], 1).is_empty());
+    }
+
+    #[test]
+    fn test_decode_partial_input() {
+        let ref_input = vec![0, 0, 0, 1];
+        assert!(decode(&IdentityCodec, &ref_input, &[0, 1, 0]).is_none());
+    }
 }
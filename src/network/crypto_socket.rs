@@ -0,0 +1,158 @@
+use alloc::sync::Arc;
+
+use crate::network::messages::{Message, MessageBody, MessageHeader};
+use crate::{Config, MessageCrypto, NonBlockingSocket};
+
+/// Wraps the socket of a session to seal all outgoing and open all incoming messages with a [`MessageCrypto`].
+/// Received messages that are not sealed or cannot be opened are dropped.
+pub(crate) struct CryptoSocket<T>
+where
+    T: Config,
+{
+    socket: Box<dyn NonBlockingSocket<T::Address>>,
+    crypto: Arc<dyn MessageCrypto>,
+}
+
+impl<T: Config> CryptoSocket<T> {
+    pub(crate) fn new(
+        socket: Box<dyn NonBlockingSocket<T::Address>>,
+        crypto: Arc<dyn MessageCrypto>,
+    ) -> Self {
+        Self { socket, crypto }
+    }
+
+    fn open(&self, msg: &Message) -> Option<Message> {
+        match &msg.body {
+            MessageBody::Sealed(ciphertext) => {
+                let plaintext = self.crypto.open(ciphertext)?;
+                match bincode::deserialize::<Message>(&plaintext).ok()? {
+                    // a sealed message should never contain another sealed message
+                    Message {
+                        body: MessageBody::Sealed(_),
+                        ..
+                    } => None,
+                    msg => Some(msg),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T: Config> NonBlockingSocket<T::Address> for CryptoSocket<T> {
+    fn send_to(&mut self, msg: &Message, addr: &T::Address) {
+        let plaintext = bincode::serialize(msg).expect("Messages should always be serializable");
+        // the header is sealed together with the message, so the outer header carries no information
+        let sealed = Message {
+            header: MessageHeader::default(),
+            body: MessageBody::Sealed(self.crypto.seal(&plaintext)),
+        };
+        self.socket.send_to(&sealed, addr);
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(T::Address, Message)> {
+        self.socket
+            .receive_all_messages()
+            .into_iter()
+            .filter_map(|(from, msg)| self.open(&msg).map(|msg| (from, msg)))
+            .collect()
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.socket.set_rng_seed(seed);
+    }
+}
+
+// #########
+// # TESTS #
+// #########
+
+#[cfg(all(test, feature = "std"))]
+mod crypto_socket_tests {
+    use super::*;
+    use crate::InMemoryNetwork;
+    use bytemuck::{Pod, Zeroable};
+    use std::net::SocketAddr;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, PartialEq, Pod, Zeroable)]
+    struct TestInput {
+        inp: u8,
+    }
+
+    struct TestConfig;
+
+    impl Config for TestConfig {
+        type Input = TestInput;
+        type State = u8;
+        type Address = SocketAddr;
+    }
+
+    /// Not secure at all, but enough to check that messages are sealed and opened.
+    struct XorCrypto(u8);
+
+    impl MessageCrypto for XorCrypto {
+        fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+            let mut ciphertext: Vec<u8> = plaintext.iter().map(|b| b ^ self.0).collect();
+            ciphertext.push(self.0);
+            ciphertext
+        }
+
+        fn open(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+            let (&tag, bytes) = ciphertext.split_last()?;
+            if tag != self.0 {
+                return None;
+            }
+            Some(bytes.iter().map(|b| b ^ self.0).collect())
+        }
+    }
+
+    fn keep_alive() -> Message {
+        Message {
+            header: MessageHeader {
+                magic: 1,
+                sequence: 0,
+            },
+            body: MessageBody::KeepAlive,
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let network = InMemoryNetwork::new(0);
+        let addr1: SocketAddr = "127.0.0.1:7000".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:7001".parse().unwrap();
+        let mut socket1 = CryptoSocket::<TestConfig>::new(
+            Box::new(network.socket(addr1)),
+            Arc::new(XorCrypto(3)),
+        );
+        let mut socket2 = CryptoSocket::<TestConfig>::new(
+            Box::new(network.socket(addr2)),
+            Arc::new(XorCrypto(3)),
+        );
+
+        socket1.send_to(&keep_alive(), &addr2);
+        assert_eq!(socket2.receive_all_messages(), vec![(addr1, keep_alive())]);
+    }
+
+    #[test]
+    fn test_drop_unopenable() {
+        let network = InMemoryNetwork::new(0);
+        let addr1: SocketAddr = "127.0.0.1:7000".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:7001".parse().unwrap();
+        let addr3: SocketAddr = "127.0.0.1:7002".parse().unwrap();
+        let mut plain_socket = network.socket(addr1);
+        let mut wrong_key_socket = CryptoSocket::<TestConfig>::new(
+            Box::new(network.socket(addr3)),
+            Arc::new(XorCrypto(5)),
+        );
+        let mut socket = CryptoSocket::<TestConfig>::new(
+            Box::new(network.socket(addr2)),
+            Arc::new(XorCrypto(3)),
+        );
+
+        plain_socket.send_to(&keep_alive(), &addr2);
+        wrong_key_socket.send_to(&keep_alive(), &addr2);
+        assert!(socket.receive_all_messages().is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use instant::Duration;
+use parking_lot::Mutex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{network::messages::Message, NonBlockingSocket, StdTimeSource, TimeSource};
+
+struct InFlightMessage {
+    deliver_at: Duration,
+    from: SocketAddr,
+    msg: Message,
+}
+
+struct NetworkState {
+    rng: StdRng,
+    latency: Duration,
+    packet_loss: f64,
+    mailboxes: HashMap<SocketAddr, VecDeque<InFlightMessage>>,
+}
+
+/// An [`InMemoryNetwork`] connects [`InMemorySocket`]s without using any real sockets, which is useful for testing sessions in a single thread.
+/// The network can delay messages by a fixed latency and drop messages with a given probability. Which messages are dropped is determined by
+/// an RNG seeded with the given seed, so the same sequence of sent messages will always lead to the same messages being dropped.
+/// The latency is measured with a [`TimeSource`], which should be the one given to the sessions for fully deterministic tests.
+///
+/// ```
+/// # use std::net::SocketAddr;
+/// # use ggrs::InMemoryNetwork;
+/// let network = InMemoryNetwork::new(42).with_packet_loss(0.1);
+/// let socket1 = network.socket("127.0.0.1:7000".parse::<SocketAddr>().unwrap());
+/// let socket2 = network.socket("127.0.0.1:7001".parse::<SocketAddr>().unwrap());
+/// ```
+#[derive(Clone)]
+pub struct InMemoryNetwork {
+    state: Arc<Mutex<NetworkState>>,
+    time_source: Arc<dyn TimeSource>,
+}
+
+impl InMemoryNetwork {
+    /// Creates a new network without latency and packet loss. The seed determines which messages will be dropped.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(NetworkState {
+                rng: StdRng::seed_from_u64(seed),
+                latency: Duration::ZERO,
+                packet_loss: 0.0,
+                mailboxes: HashMap::new(),
+            })),
+            time_source: Arc::new(StdTimeSource::new()),
+        }
+    }
+
+    /// Sets the time it takes for a message to arrive at the receiving socket. Default is zero.
+    pub fn with_latency(self, latency: Duration) -> Self {
+        self.state.lock().latency = latency;
+        self
+    }
+
+    /// Sets the [`TimeSource`] the sockets created afterwards use to delay messages by the latency. Default is [`StdTimeSource`].
+    /// Pass the same time source as to [`SessionBuilder::with_time_source()`], so messages arrive in step with the manually driven session timers.
+    ///
+    /// [`SessionBuilder::with_time_source()`]: crate::SessionBuilder#method.with_time_source
+    pub fn with_time_source(mut self, time_source: impl TimeSource + 'static) -> Self {
+        self.time_source = Arc::new(time_source);
+        self
+    }
+
+    /// Sets the probability for each message to be dropped. The probability is clamped between 0 and 1. Default is 0.
+    pub fn with_packet_loss(self, probability: f64) -> Self {
+        self.state.lock().packet_loss = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Creates a socket connected to this network, reachable by the given address.
+    pub fn socket(&self, addr: SocketAddr) -> InMemorySocket {
+        self.state.lock().mailboxes.entry(addr).or_default();
+        InMemorySocket {
+            addr,
+            state: self.state.clone(),
+            time_source: self.time_source.clone(),
+        }
+    }
+}
+
+/// A socket that sends and receives messages through an [`InMemoryNetwork`]. Messages to addresses without a socket in the same network are lost.
+pub struct InMemorySocket {
+    addr: SocketAddr,
+    state: Arc<Mutex<NetworkState>>,
+    time_source: Arc<dyn TimeSource>,
+}
+
+impl InMemorySocket {
+    /// Returns the address of this socket.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl NonBlockingSocket<SocketAddr> for InMemorySocket {
+    fn send_to(&mut self, msg: &Message, addr: &SocketAddr) {
+        let mut state = self.state.lock();
+        let packet_loss = state.packet_loss;
+        if packet_loss > 0.0 && state.rng.gen_bool(packet_loss) {
+            return;
+        }
+        let deliver_at = self.time_source.now() + state.latency;
+        if let Some(mailbox) = state.mailboxes.get_mut(addr) {
+            mailbox.push_back(InFlightMessage {
+                deliver_at,
+                from: self.addr,
+                msg: msg.clone(),
+            });
+        }
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(SocketAddr, Message)> {
+        let mut state = self.state.lock();
+        let now = self.time_source.now();
+        let mut received_messages = Vec::new();
+        if let Some(mailbox) = state.mailboxes.get_mut(&self.addr) {
+            // all messages have the same latency, so they arrive in the order they were sent
+            while matches!(mailbox.front(), Some(m) if m.deliver_at <= now) {
+                let in_flight = mailbox.pop_front().expect("Expected message to exist");
+                received_messages.push((in_flight.from, in_flight.msg));
+            }
+        }
+        received_messages
+    }
+}
+
+// #########
+// # TESTS #
+// #########
+
+#[cfg(test)]
+mod in_memory_socket_tests {
+    use super::*;
+    use crate::network::messages::{MessageBody, MessageHeader};
+
+    fn keep_alive() -> Message {
+        Message {
+            header: MessageHeader {
+                magic: 1,
+                sequence: 0,
+            },
+            body: MessageBody::KeepAlive,
+        }
+    }
+
+    fn received_after_sending(network: &InMemoryNetwork, count: usize) -> usize {
+        let addr1 = "127.0.0.1:7000".parse().unwrap();
+        let addr2 = "127.0.0.1:7001".parse().unwrap();
+        let mut socket1 = network.socket(addr1);
+        let mut socket2 = network.socket(addr2);
+        for _ in 0..count {
+            socket1.send_to(&keep_alive(), &addr2);
+        }
+        let received = socket2.receive_all_messages();
+        assert!(received.iter().all(|(from, _)| *from == addr1));
+        received.len()
+    }
+
+    #[test]
+    fn test_send_receive() {
+        let network = InMemoryNetwork::new(0);
+        assert_eq!(received_after_sending(&network, 10), 10);
+    }
+
+    #[test]
+    fn test_latency() {
+        let network = InMemoryNetwork::new(0).with_latency(Duration::from_secs(60));
+        assert_eq!(received_after_sending(&network, 10), 0);
+    }
+
+    #[derive(Clone)]
+    struct ManualClock(Arc<Mutex<Duration>>);
+
+    impl TimeSource for ManualClock {
+        fn now(&self) -> Duration {
+            *self.0.lock()
+        }
+    }
+
+    #[test]
+    fn test_latency_with_time_source() {
+        let clock = ManualClock(Arc::new(Mutex::new(Duration::ZERO)));
+        let network = InMemoryNetwork::new(0)
+            .with_latency(Duration::from_millis(100))
+            .with_time_source(clock.clone());
+        let addr1 = "127.0.0.1:7000".parse().unwrap();
+        let addr2 = "127.0.0.1:7001".parse().unwrap();
+        let mut socket1 = network.socket(addr1);
+        let mut socket2 = network.socket(addr2);
+        socket1.send_to(&keep_alive(), &addr2);
+
+        // the message only arrives once the time source has advanced by the latency
+        *clock.0.lock() = Duration::from_millis(99);
+        assert!(socket2.receive_all_messages().is_empty());
+        *clock.0.lock() = Duration::from_millis(100);
+        assert_eq!(socket2.receive_all_messages().len(), 1);
+    }
+
+    #[test]
+    fn test_deterministic_packet_loss() {
+        let received1 = received_after_sending(&InMemoryNetwork::new(7).with_packet_loss(0.5), 100);
+        let received2 = received_after_sending(&InMemoryNetwork::new(7).with_packet_loss(0.5), 100);
+        assert_eq!(received1, received2);
+        assert!(received1 > 0 && received1 < 100);
+    }
+}
@@ -1,6 +1,10 @@
+use alloc::{boxed::Box, vec::Vec};
 use serde::{Deserialize, Serialize};
 
-use crate::{Frame, NULL_FRAME};
+use crate::{Frame, PlayerHandle, NULL_FRAME};
+
+/// The version of the message format. It is bumped with every change to the messages, so clients with different formats refuse to synchronize.
+pub(crate) const PROTOCOL_VERSION: u16 = 1;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) struct ConnectionStatus {
@@ -19,12 +23,18 @@ impl Default for ConnectionStatus {
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub(crate) struct SyncRequest {
-    pub random_request: u32, // please reply back with this random data
+    pub protocol_version: u16, // only clients with the same message format synchronize
+    pub random_request: u32,   // please reply back with this random data
+    pub app_id: u64,           // only clients with the same app id synchronize
+    pub shared_seed: Option<u64>, // only clients with the same shared seed synchronize
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub(crate) struct SyncReply {
+    pub protocol_version: u16,
     pub random_reply: u32, // here's your random data back
+    pub app_id: u64,
+    pub shared_seed: Option<u64>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -34,6 +44,7 @@ pub(crate) struct Input {
     pub start_frame: Frame,
     pub ack_frame: Frame,
     pub bytes: Vec<u8>,
+    pub resend: bool, // the inputs are resent, because they have not been acknowledged in time
 }
 
 impl Default for Input {
@@ -44,6 +55,7 @@ impl Default for Input {
             start_frame: NULL_FRAME,
             ack_frame: NULL_FRAME,
             bytes: Vec::new(),
+            resend: false,
         }
     }
 }
@@ -61,20 +73,81 @@ impl Default for InputAck {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub(crate) struct QualityReport {
     pub frame_advantage: i8, // frame advantage of other player
     pub ping: u128,
+    pub input_checksum: Option<(Frame, u128)>, // latest checksum over all confirmed inputs, if input desync detection is enabled
+    pub state_checksum: Option<(Frame, u128)>, // latest checksum of a confirmed state, if spectator checksums are enabled
+    pub payload: Vec<u8>, // user payload, at most MAX_QUALITY_REPORT_PAYLOAD bytes
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub(crate) struct QualityReply {
     pub pong: u128,
+    /// The time of the replying client when it sent the reply, to estimate the offset between the clocks.
+    pub time: u128,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub(crate) struct SnapshotChunk {
+    pub frame: Frame,   // frame of the saved state
+    pub total_len: u32, // length of the whole serialized state
+    pub offset: u32,    // position of this chunk in the serialized state
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub(crate) struct SnapshotAck {
+    pub frame: Frame,
+    pub received: u32, // number of bytes received without gaps from the start of the state
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub(crate) struct StateRequest {
+    pub frame: Frame, // frame of the saved state requested to recover from a desync
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub(crate) struct StateUnavailable {
+    pub frame: Frame, // frame of the requested state that cannot be sent
+}
+
+/// Coordinates a pause between the peers. Sent over the reliable stream, so it is ordered with the messages of the user.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum PauseControl {
+    /// The sender pauses the `round`th pause at `frame` or later.
+    Pause { round: u32, frame: Frame },
+    /// The sender resumed from the `round`th pause.
+    Resume { round: u32 },
+}
+
+/// Tells the peers on which frame a player joins or rejoins the session. Sent over the reliable stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PlayerJoin {
+    pub handle: PlayerHandle,
+    pub round: u32, // how often the sender let this player join, including this time
+    pub frame: Frame,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub(crate) struct ReliableMessage {
+    pub sequence: u32, // position of this message in the reliable stream
+    pub bytes: Vec<u8>,
+    pub pause: Option<PauseControl>, // internal messages to coordinate a pause carry no bytes
+    pub join: Option<PlayerJoin>,    // internal messages to coordinate a join carry no bytes
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub(crate) struct ReliableAck {
+    pub received: u32, // number of reliable messages received in order
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub(crate) struct MessageHeader {
     pub magic: u16,
+    /// Counts up with every message sent to the same peer, so the peer can detect lost and reordered messages.
+    pub sequence: u32,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -85,7 +158,17 @@ pub(crate) enum MessageBody {
     InputAck(InputAck),
     QualityReport(QualityReport),
     QualityReply(QualityReply),
+    SnapshotChunk(SnapshotChunk),
+    SnapshotAck(SnapshotAck),
+    StateRequest(StateRequest),
+    StateUnavailable(StateUnavailable),
+    Reliable(ReliableMessage),
+    ReliableAck(ReliableAck),
     KeepAlive,
+    /// A serialized message sealed by a `MessageCrypto`.
+    Sealed(Vec<u8>),
+    /// A message of the session with the given id, sent through a `SharedSocket`.
+    Routed(u64, Box<Message>),
 }
 
 /// A messages that [`NonBlockingSocket`] sends and receives. When implementing [`NonBlockingSocket`],
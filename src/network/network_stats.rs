@@ -1,5 +1,6 @@
 /// The `NetworkStats` struct contains statistics about the current session.
 #[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NetworkStats {
     /// The length of the queue containing UDP packets which have not yet been acknowledged by the end client.
     /// The length of the send queue is a rough indication of the quality of the connection. The longer the send queue, the higher the round-trip time between the
@@ -7,8 +8,24 @@ pub struct NetworkStats {
     pub send_queue_len: usize,
     /// The roundtrip packet transmission time as calculated by GGRS.
     pub ping: u128,
+    /// The standard deviation of the most recent roundtrip times in milliseconds. A high jitter means that packets arrive irregularly,
+    /// even if the ping is low.
+    pub jitter: f32,
     /// The estimated bandwidth used between the two clients, in kilobits per second.
     pub kbps_sent: usize,
+    /// The estimated bandwidth received from the remote client, in kilobits per second.
+    pub kbps_received: usize,
+    /// The bytes per second sent to the remote client in input messages and their acknowledgements, without UDP headers.
+    pub input_bytes_per_sec: usize,
+    /// The bytes per second sent to the remote client in quality reports and their replies, without UDP headers.
+    pub quality_report_bytes_per_sec: usize,
+    /// The bytes per second sent to the remote client to synchronize the connection, without UDP headers.
+    pub sync_bytes_per_sec: usize,
+    /// The bytes per second sent to the remote client on the reliable channel, including acknowledgements, without UDP headers.
+    /// This covers the messages sent with [`P2PSession::send_reliable()`] and coordinated pauses.
+    ///
+    /// [`P2PSession::send_reliable()`]: crate::P2PSession#method.send_reliable
+    pub reliable_bytes_per_sec: usize,
 
     /// The number of frames GGRS calculates that the local client is behind the remote client at this instant in time.
     /// For example, if at this instant the current game client is running frame 1002 and the remote game client is running frame 1009,
@@ -18,6 +35,22 @@ pub struct NetworkStats {
     ///
     /// [`local_frames_behind`]: #structfield.local_frames_behind
     pub remote_frames_behind: i32,
+
+    /// The number of packets received from the remote client.
+    pub packets_received: usize,
+    /// The number of packets the remote client sent that have not arrived (yet). Packets that arrive late are no longer counted as lost.
+    pub packets_lost: usize,
+    /// The number of packets that arrived after a packet the remote client sent later.
+    pub out_of_order_count: usize,
+
+    /// The number of past frames the last input packet sent to the remote client re-included next to the newest frame.
+    /// This is limited by [`SessionBuilder::with_input_redundancy()`] and by the number of frames the remote client has not acknowledged yet.
+    ///
+    /// [`SessionBuilder::with_input_redundancy()`]: crate::SessionBuilder#method.with_input_redundancy
+    pub input_redundancy: usize,
+    /// The number of inputs of the remote client that got lost, but arrived re-included in a later packet, so they did not need to be resent.
+    /// Inputs the remote client batches on purpose, like the inputs sent to spectators with compression, are counted as well.
+    pub inputs_recovered: usize,
 }
 
 impl NetworkStats {
@@ -1,46 +1,62 @@
 use crate::frame_info::PlayerInput;
 use crate::network::compression::{decode, encode};
 use crate::network::messages::{
-    ConnectionStatus, Input, InputAck, Message, MessageBody, MessageHeader, QualityReply,
-    QualityReport, SyncReply, SyncRequest,
+    ConnectionStatus, Input, InputAck, Message, MessageBody, MessageHeader, PauseControl,
+    PlayerJoin, QualityReply, QualityReport, ReliableAck, ReliableMessage, SnapshotAck,
+    SnapshotChunk, StateRequest, StateUnavailable, SyncReply, SyncRequest, PROTOCOL_VERSION,
 };
+use crate::network::serialized_size::serialized_size;
 use crate::time_sync::TimeSync;
-use crate::{Config, Frame, GGRSError, NonBlockingSocket, PlayerHandle, NULL_FRAME};
-
-use instant::{Duration, Instant};
-use std::collections::vec_deque::Drain;
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::convert::TryFrom;
-use std::ops::Add;
+use crate::{
+    Config, Frame, GGRSError, InputCodec, NonBlockingSocket, PlayerHandle, TimeSource, NULL_FRAME,
+};
+use alloc::{borrow::ToOwned, boxed::Box, vec::Vec};
+
+use alloc::collections::vec_deque::Drain;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::convert::TryFrom;
+use core::time::Duration;
+use hashbrown::{HashMap, HashSet};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 use super::network_stats::NetworkStats;
 
 const UDP_HEADER_SIZE: usize = 28; // Size of IP + UDP headers
-const NUM_SYNC_PACKETS: u32 = 5;
+pub(crate) const NUM_SYNC_PACKETS: u32 = 5;
 const UDP_SHUTDOWN_TIMER: u64 = 5000;
 const PENDING_OUTPUT_SIZE: usize = 128;
-const SYNC_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+pub(crate) const SYNC_RETRY_INTERVAL: Duration = Duration::from_millis(200);
 const RUNNING_RETRY_INTERVAL: Duration = Duration::from_millis(200);
 const KEEP_ALIVE_INTERVAL: Duration = Duration::from_millis(200);
 const QUALITY_REPORT_INTERVAL: Duration = Duration::from_millis(200);
-const MAX_PAYLOAD: usize = 467; // 512 is max safe UDP payload, minus 45 bytes for the rest of the packet
-
-fn millis_since_epoch() -> u128 {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_millis()
-    }
-    #[cfg(target_arch = "wasm32")]
-    {
-        js_sys::Date::new_0().get_time() as u128
-    }
+const MAX_INPUT_CHECKSUMS: usize = 32;
+const SNAPSHOT_WINDOW: usize = 16; // number of snapshot chunks in flight
+pub(crate) const MAX_RELIABLE_MESSAGES: usize = 64; // number of reliable messages waiting for an acknowledgement
+const RELIABLE_WINDOW: usize = 8; // number of unacknowledged reliable messages resent at once
+const STATE_REQUEST_RETRIES: u32 = 50; // number of times a state request is resent before giving up
+const RTT_SAMPLES: usize = 16; // number of round-trip times the jitter is computed over
+/// The clock offset to a remote client may drift this far from its first estimate before the endpoint warns about it.
+const CLOCK_DRIFT_WARNING_THRESHOLD: Duration = Duration::from_millis(100);
+/// The maximum number of bytes of a message sent with [`P2PSession::send_reliable()`]. 512 is the max safe UDP payload, minus 45 bytes for the rest of the packet.
+///
+/// [`P2PSession::send_reliable()`]: crate::P2PSession#method.send_reliable
+pub const MAX_PAYLOAD: usize = 467;
+/// The maximum number of bytes of a payload attached to quality reports, see [`SessionBuilder::with_quality_report_payload()`].
+///
+/// [`SessionBuilder::with_quality_report_payload()`]: crate::SessionBuilder#method.with_quality_report_payload
+pub const MAX_QUALITY_REPORT_PAYLOAD: usize = 64;
+
+/// The size of a message as serialized by the sockets, without the IP and UDP headers.
+fn message_size(msg: &Message) -> usize {
+    serialized_size(msg)
 }
 
-// byte-encoded data representing the inputs of a client, possibly for multiple players at the same time
-#[derive(Clone)]
+// byte-encoded data representing the inputs of a client, possibly for multiple players at the same time.
+// The input bytes of each player are followed by a single byte holding the flags of that input
+#[derive(Clone, Serialize, Deserialize)]
 struct InputBytes {
     /// The frame to which this info belongs to. -1/[`NULL_FRAME`] represents an invalid frame
     pub frame: Frame,
@@ -51,7 +67,7 @@ struct InputBytes {
 impl InputBytes {
     fn zeroed<T: Config>(num_players: usize) -> Self {
         let zeroed = PlayerInput::<T::Input>::blank_input(NULL_FRAME);
-        let size = bytemuck::bytes_of(&zeroed.input).len() * num_players;
+        let size = (bytemuck::bytes_of(&zeroed.input).len() + 1) * num_players;
 
         Self {
             frame: NULL_FRAME,
@@ -74,23 +90,30 @@ impl InputBytes {
                 }
                 let byte_vec = bytemuck::bytes_of(&input.input);
                 bytes.extend_from_slice(byte_vec);
+                bytes.push(input.flags);
             }
         }
         Self { frame, bytes }
     }
 
-    fn to_player_inputs<T: Config>(&self, num_players: usize) -> Vec<PlayerInput<T::Input>> {
+    /// Splits the bytes into the inputs of the players. Returns `None` if the bytes do not hold an input and flags for every player.
+    fn to_player_inputs<T: Config>(
+        &self,
+        num_players: usize,
+    ) -> Option<Vec<PlayerInput<T::Input>>> {
+        let size = core::mem::size_of::<T::Input>() + 1;
+        if self.bytes.len() != num_players * size {
+            return None;
+        }
         let mut player_inputs = Vec::new();
-        assert!(self.bytes.len() % num_players == 0);
-        let size = self.bytes.len() / num_players;
         for p in 0..num_players {
             let start = p * size;
-            let end = start + size;
-            let input = *bytemuck::try_from_bytes::<T::Input>(&self.bytes[start..end])
-                .expect("Expected received data to be valid.");
-            player_inputs.push(PlayerInput::new(self.frame, input));
+            let end = start + size - 1;
+            // the input bytes are not aligned, since they follow the flags of the previous player
+            let input = bytemuck::pod_read_unaligned::<T::Input>(&self.bytes[start..end]);
+            player_inputs.push(PlayerInput::new(self.frame, input).with_flags(self.bytes[end]));
         }
-        player_inputs
+        Some(player_inputs)
     }
 }
 
@@ -114,6 +137,24 @@ where
     NetworkInterrupted { disconnect_timeout: u128 },
     /// Sent only after a `NetworkInterrupted` event, if communication has resumed.
     NetworkResumed,
+    /// The session has received the complete serialized state of the remote client for the given frame. This event will not be forwarded to the user.
+    Snapshot { frame: Frame, bytes: Vec<u8> },
+    /// The remote client requested the saved state of the given frame to recover from a desync. This event will not be forwarded to the user.
+    StateRequested { frame: Frame },
+    /// The remote client cannot send the requested state of the given frame, or did not answer the request in time. This event will not be forwarded to the user.
+    StateUnavailable { frame: Frame },
+    /// The session has received the next message of the reliable stream of the remote client.
+    Message { bytes: Vec<u8> },
+    /// The session has received a quality report with a payload attached by the remote client.
+    QualityReportPayload { bytes: Vec<u8> },
+    /// The remote client tried to synchronize with a different app id, so the session refuses to synchronize with it.
+    IncompatiblePeer,
+    /// The session has received the next message to coordinate a pause from the reliable stream of the remote client.
+    PauseControl(PauseControl),
+    /// The session has received the next message to coordinate a join from the reliable stream of the remote client.
+    PlayerJoin(PlayerJoin),
+    /// The estimated offset between the clocks of both clients drifted past the warning threshold.
+    ClockDriftWarning { offset: Duration },
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -125,6 +166,160 @@ enum ProtocolState {
     Shutdown,
 }
 
+/// The part of an endpoint that survives exporting and restoring a session, see [`UdpProtocol::export_runtime()`].
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct EndpointRuntime {
+    handles: Vec<PlayerHandle>,
+    disconnected: bool,
+    magic: u16,
+    remote_magic: u16,
+    peer_connect_status: Vec<ConnectionStatus>,
+    pending_output: VecDeque<InputBytes>,
+    last_acked_input: InputBytes,
+    recv_inputs: HashMap<Frame, InputBytes>,
+    reliable_output: VecDeque<ReliableMessage>,
+    next_reliable_sequence: u32,
+    next_recv_reliable_sequence: u32,
+    next_send_sequence: u32,
+    next_recv_sequence: u32,
+    remote_frame_advantage: i32,
+    round_trip_time: u128,
+}
+
+impl EndpointRuntime {
+    pub(crate) fn handles(&self) -> &[PlayerHandle] {
+        &self.handles
+    }
+}
+
+/// A serialized state that is sent to the remote client in chunks.
+struct OutgoingSnapshot {
+    frame: Frame,
+    bytes: Vec<u8>,
+    /// Bytes acknowledged by the remote client.
+    acked: usize,
+    /// Bytes sent since the last retry.
+    sent: usize,
+}
+
+/// A serialized state that is received from the remote client in chunks.
+struct IncomingSnapshot {
+    frame: Frame,
+    total_len: usize,
+    /// All bytes received without gaps from the start of the state.
+    bytes: Vec<u8>,
+    /// Chunks received ahead of the gapless bytes, by offset.
+    out_of_order: HashMap<usize, Vec<u8>>,
+    complete: bool,
+}
+
+/// The settings shared by all endpoints of a session, so endpoints can also be created after the session has started.
+#[derive(Clone)]
+pub(crate) struct EndpointSettings {
+    pub(crate) disconnect_timeout: Duration,
+    pub(crate) disconnect_notify_start: Duration,
+    pub(crate) input_codec: Arc<dyn InputCodec>,
+    pub(crate) time_source: Arc<dyn TimeSource>,
+    /// If set, the random numbers of every endpoint are generated from this seed, offset by the lowest handle of the endpoint.
+    pub(crate) rng_seed: Option<u64>,
+    /// If set, the codec and number of batched frames used for the inputs sent to spectators.
+    pub(crate) spectator_compression: Option<(Arc<dyn InputCodec>, usize)>,
+    /// If set, creates the payload attached to every quality report.
+    pub(crate) quality_report_payload: Option<fn() -> Vec<u8>>,
+    /// If set, the number of past frames regular input packets re-include.
+    pub(crate) input_redundancy: Option<usize>,
+    /// Endpoints only synchronize with remote clients that have the same app id.
+    pub(crate) app_id: u64,
+    /// Endpoints only synchronize with remote clients that have the same shared seed.
+    pub(crate) shared_seed: Option<u64>,
+    /// The number of sync roundtrips before an endpoint is synchronized.
+    pub(crate) sync_packets: u32,
+    /// The time after which unanswered sync requests are resent.
+    pub(crate) sync_interval: Duration,
+    /// The maximum number of bytes of encoded inputs, snapshot chunks and reliable messages, less than [`MAX_PAYLOAD`] if the socket adds bytes to every message.
+    pub(crate) max_payload: usize,
+}
+
+impl EndpointSettings {
+    /// Creates an endpoint for the given handles and starts its synchronization.
+    pub(crate) fn create_endpoint<T: Config>(
+        &self,
+        handles: Vec<PlayerHandle>,
+        peer_addr: T::Address,
+        num_players: usize,
+        local_players: usize,
+        max_prediction: usize,
+        fps: usize,
+    ) -> UdpProtocol<T> {
+        // every endpoint gets its own random numbers, even with a fixed seed
+        let rng = self.rng(handles.iter().copied().min().unwrap_or_default());
+        let mut endpoint = UdpProtocol::new(
+            handles,
+            peer_addr,
+            num_players,
+            local_players,
+            max_prediction,
+            self.disconnect_timeout,
+            self.disconnect_notify_start,
+            fps,
+            self.input_codec.clone(),
+            self.time_source.clone(),
+            rng,
+        );
+        self.configure(&mut endpoint);
+        endpoint.synchronize();
+        endpoint
+    }
+
+    /// Creates an endpoint for the spectators with the given handles and starts its synchronization. The host sends the inputs of all players to them.
+    pub(crate) fn create_spectator_endpoint<T: Config>(
+        &self,
+        handles: Vec<PlayerHandle>,
+        peer_addr: T::Address,
+        num_players: usize,
+        max_prediction: usize,
+        fps: usize,
+    ) -> UdpProtocol<T> {
+        let mut endpoint = self.create_endpoint(
+            handles,
+            peer_addr,
+            num_players,
+            num_players,
+            max_prediction,
+            fps,
+        );
+        if let Some((codec, batch_frames)) = &self.spectator_compression {
+            endpoint.set_input_compression(codec.clone(), *batch_frames);
+        }
+        endpoint
+    }
+
+    /// Applies the settings that are not passed to [`UdpProtocol::new()`] to the given endpoint.
+    pub(crate) fn configure<T: Config>(&self, endpoint: &mut UdpProtocol<T>) {
+        endpoint.quality_report_payload = self.quality_report_payload;
+        endpoint.app_id = self.app_id;
+        endpoint.shared_seed = self.shared_seed;
+        endpoint.input_redundancy = self.input_redundancy;
+        endpoint.sync_packets = self.sync_packets;
+        endpoint.sync_interval = self.sync_interval;
+        endpoint.max_payload = self.max_payload;
+    }
+
+    /// Creates the random number generator of the endpoint of the given player handle. Without a seed, it is seeded from the system,
+    /// or from the time source without the `std` feature.
+    pub(crate) fn rng(&self, handle: PlayerHandle) -> StdRng {
+        match self.rng_seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(handle as u64)),
+            #[cfg(feature = "std")]
+            None => StdRng::from_entropy(),
+            #[cfg(not(feature = "std"))]
+            None => StdRng::seed_from_u64(
+                (self.time_source.now().as_nanos() as u64).wrapping_add(handle as u64),
+            ),
+        }
+    }
+}
+
 pub(crate) struct UdpProtocol<T>
 where
     T: Config,
@@ -136,17 +331,27 @@ where
 
     // state
     state: ProtocolState,
+    sync_packets: u32,
+    sync_interval: Duration,
+    max_payload: usize,
     sync_remaining_roundtrips: u32,
     sync_random_requests: HashSet<u32>,
-    running_last_quality_report: Instant,
-    running_last_input_recv: Instant,
+    app_id: u64,
+    shared_seed: Option<u64>,
+    /// Set once the remote client turned out to have a different app id or shared seed. We never synchronize with it then.
+    incompatible: bool,
+    sync_last_request: Duration,
+    running_last_quality_report: Duration,
+    running_last_input_recv: Duration,
     disconnect_notify_sent: bool,
     disconnect_event_sent: bool,
+    /// Set once we said goodbye. Until the shutdown, the inputs that have not been acknowledged are still resent with the disconnect request.
+    goodbye_sent: bool,
 
     // constants
     disconnect_timeout: Duration,
     disconnect_notify_start: Duration,
-    shutdown_timeout: Instant,
+    shutdown_timeout: Duration,
     fps: usize,
     magic: u16,
 
@@ -156,7 +361,14 @@ where
     peer_connect_status: Vec<ConnectionStatus>,
 
     // input compression
+    input_codec: Arc<dyn InputCodec>,
     pending_output: VecDeque<InputBytes>,
+    input_batch: usize,
+    frames_since_output: usize,
+    /// If set, regular input packets re-include at most this many frames before the newest one. Resends always include all pending frames.
+    input_redundancy: Option<usize>,
+    /// The number of frames before the newest one that the last input packet re-included.
+    last_input_redundancy: usize,
     last_acked_input: InputBytes,
     max_prediction: usize,
     recv_inputs: HashMap<Frame, InputBytes>,
@@ -166,13 +378,60 @@ where
     local_frame_advantage: i32,
     remote_frame_advantage: i32,
 
+    // payload attached to quality reports
+    quality_report_payload: Option<fn() -> Vec<u8>>,
+
+    // input desync detection
+    local_input_checksum: Option<(Frame, u128)>,
+    remote_input_checksums: VecDeque<(Frame, u128)>,
+    last_remote_input_checksum_frame: Frame,
+
+    // state checksums for spectators
+    local_state_checksum: Option<(Frame, u128)>,
+    remote_state_checksums: VecDeque<(Frame, u128)>,
+    last_remote_state_checksum_frame: Frame,
+
+    // snapshots for spectators joining late
+    outgoing_snapshot: Option<OutgoingSnapshot>,
+    incoming_snapshot: Option<IncomingSnapshot>,
+    // the frame of the state requested from the remote client, until it starts to arrive
+    requested_state: Option<Frame>,
+    state_request_retries: u32,
+    last_state_request: Duration,
+
+    // reliable messages, resent until acknowledged
+    reliable_output: VecDeque<ReliableMessage>,
+    next_reliable_sequence: u32,
+    next_recv_reliable_sequence: u32,
+
     // network
-    stats_start_time: u128,
+    time_source: Arc<dyn TimeSource>,
+    rng: StdRng,
+    stats_start_time: Duration,
     packets_sent: usize,
     bytes_sent: usize,
+    input_bytes_sent: usize,
+    quality_report_bytes_sent: usize,
+    sync_bytes_sent: usize,
+    reliable_bytes_sent: usize,
+    bytes_received: usize,
+    packets_received: usize,
+    packets_lost: usize,
+    out_of_order_count: usize,
+    inputs_recovered: usize,
+    next_send_sequence: u32,
+    next_recv_sequence: u32,
+    /// The messages received among the 64 before `next_recv_sequence`, the lowest bit being the latest one.
+    recv_sequence_window: u64,
     round_trip_time: u128,
-    last_send_time: Instant,
-    last_recv_time: Instant,
+    round_trip_samples: VecDeque<u128>,
+    /// The most recent clock offsets to the remote client in milliseconds, together with the round-trip time they were measured with.
+    clock_offset_samples: VecDeque<(u128, i128)>,
+    clock_offset_baseline: Option<i128>,
+    clock_offset: Option<i128>,
+    clock_offset_warned: bool,
+    last_send_time: Duration,
+    last_recv_time: Duration,
 }
 
 impl<T: Config> PartialEq for UdpProtocol<T> {
@@ -182,6 +441,7 @@ impl<T: Config> PartialEq for UdpProtocol<T> {
 }
 
 impl<T: Config> UdpProtocol<T> {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         mut handles: Vec<PlayerHandle>,
         peer_addr: T::Address,
@@ -191,10 +451,14 @@ impl<T: Config> UdpProtocol<T> {
         disconnect_timeout: Duration,
         disconnect_notify_start: Duration,
         fps: usize,
+        input_codec: Arc<dyn InputCodec>,
+        time_source: Arc<dyn TimeSource>,
+        mut rng: StdRng,
     ) -> Self {
-        let mut magic = rand::random::<u16>();
+        let now = time_source.now();
+        let mut magic = rng.gen::<u16>();
         while magic == 0 {
-            magic = rand::random::<u16>();
+            magic = rng.gen::<u16>();
         }
 
         handles.sort_unstable();
@@ -218,17 +482,25 @@ impl<T: Config> UdpProtocol<T> {
 
             // state
             state: ProtocolState::Initializing,
+            sync_packets: NUM_SYNC_PACKETS,
+            sync_interval: SYNC_RETRY_INTERVAL,
+            max_payload: MAX_PAYLOAD,
             sync_remaining_roundtrips: NUM_SYNC_PACKETS,
             sync_random_requests: HashSet::new(),
-            running_last_quality_report: Instant::now(),
-            running_last_input_recv: Instant::now(),
+            app_id: 0,
+            shared_seed: None,
+            incompatible: false,
+            sync_last_request: now,
+            running_last_quality_report: now,
+            running_last_input_recv: now,
             disconnect_notify_sent: false,
             disconnect_event_sent: false,
+            goodbye_sent: false,
 
             // constants
             disconnect_timeout,
             disconnect_notify_start,
-            shutdown_timeout: Instant::now(),
+            shutdown_timeout: now,
             fps,
             magic,
 
@@ -238,7 +510,12 @@ impl<T: Config> UdpProtocol<T> {
             peer_connect_status,
 
             // input compression
+            input_codec,
             pending_output: VecDeque::with_capacity(PENDING_OUTPUT_SIZE),
+            input_batch: 1,
+            frames_since_output: 0,
+            input_redundancy: None,
+            last_input_redundancy: 0,
             last_acked_input: InputBytes::zeroed::<T>(local_players),
             max_prediction,
             recv_inputs,
@@ -249,12 +526,46 @@ impl<T: Config> UdpProtocol<T> {
             remote_frame_advantage: 0,
 
             // network
-            stats_start_time: 0,
+            time_source,
+            rng,
+            stats_start_time: Duration::ZERO,
             packets_sent: 0,
             bytes_sent: 0,
+            input_bytes_sent: 0,
+            quality_report_bytes_sent: 0,
+            sync_bytes_sent: 0,
+            reliable_bytes_sent: 0,
+            bytes_received: 0,
+            packets_received: 0,
+            packets_lost: 0,
+            out_of_order_count: 0,
+            inputs_recovered: 0,
+            next_send_sequence: 0,
+            next_recv_sequence: 0,
+            recv_sequence_window: 0,
+            quality_report_payload: None,
+            local_input_checksum: None,
+            remote_input_checksums: VecDeque::new(),
+            last_remote_input_checksum_frame: NULL_FRAME,
+            local_state_checksum: None,
+            remote_state_checksums: VecDeque::new(),
+            last_remote_state_checksum_frame: NULL_FRAME,
+            outgoing_snapshot: None,
+            incoming_snapshot: None,
+            requested_state: None,
+            state_request_retries: 0,
+            last_state_request: now,
+            reliable_output: VecDeque::new(),
+            next_reliable_sequence: 0,
+            next_recv_reliable_sequence: 0,
             round_trip_time: 0,
-            last_send_time: Instant::now(),
-            last_recv_time: Instant::now(),
+            round_trip_samples: VecDeque::new(),
+            clock_offset_samples: VecDeque::new(),
+            clock_offset_baseline: None,
+            clock_offset: None,
+            clock_offset_warned: false,
+            last_send_time: now,
+            last_recv_time: now,
         }
     }
 
@@ -274,8 +585,7 @@ impl<T: Config> UdpProtocol<T> {
             return Err(GGRSError::NotSynchronized);
         }
 
-        let now = millis_since_epoch();
-        let seconds = (now - self.stats_start_time) / 1000;
+        let seconds = (self.time_source.now() - self.stats_start_time).as_secs();
         if seconds == 0 {
             return Err(GGRSError::NotSynchronized);
         }
@@ -283,16 +593,196 @@ impl<T: Config> UdpProtocol<T> {
         let total_bytes_sent = self.bytes_sent + (self.packets_sent * UDP_HEADER_SIZE);
         let bps = total_bytes_sent / seconds as usize;
         //let upd_overhead = (self.packets_sent * UDP_HEADER_SIZE) / self.bytes_sent;
+        let total_bytes_received = self.bytes_received + (self.packets_received * UDP_HEADER_SIZE);
+        let bps_received = total_bytes_received / seconds as usize;
 
         Ok(NetworkStats {
             ping: self.round_trip_time,
+            jitter: self.jitter(),
             send_queue_len: self.pending_output.len(),
             kbps_sent: bps / 1024,
+            kbps_received: bps_received / 1024,
+            input_bytes_per_sec: self.input_bytes_sent / seconds as usize,
+            quality_report_bytes_per_sec: self.quality_report_bytes_sent / seconds as usize,
+            sync_bytes_per_sec: self.sync_bytes_sent / seconds as usize,
+            reliable_bytes_per_sec: self.reliable_bytes_sent / seconds as usize,
             local_frames_behind: self.local_frame_advantage,
             remote_frames_behind: self.remote_frame_advantage,
+            packets_received: self.packets_received,
+            packets_lost: self.packets_lost,
+            out_of_order_count: self.out_of_order_count,
+            input_redundancy: self.last_input_redundancy,
+            inputs_recovered: self.inputs_recovered,
         })
     }
 
+    /// Returns the most recent round-trip time in milliseconds.
+    pub(crate) fn round_trip_time(&self) -> u128 {
+        self.round_trip_time
+    }
+
+    /// Returns the offset between the clocks of both clients, relative to the offset first estimated, in either direction.
+    /// Returns `None` until enough quality reports have been answered to estimate the offset.
+    pub(crate) fn clock_offset(&self) -> Option<Duration> {
+        self.clock_offset
+            .map(|offset| Duration::from_millis(offset.unsigned_abs() as u64))
+    }
+
+    /// Returns the standard deviation of the most recent round-trip times in milliseconds.
+    pub(crate) fn jitter(&self) -> f32 {
+        if self.round_trip_samples.is_empty() {
+            return 0.0;
+        }
+        let count = self.round_trip_samples.len() as f32;
+        let mean = self.round_trip_samples.iter().sum::<u128>() as f32 / count;
+        let variance = self
+            .round_trip_samples
+            .iter()
+            .map(|&rtt| (rtt as f32 - mean) * (rtt as f32 - mean))
+            .sum::<f32>()
+            / count;
+        // `f32::sqrt` requires std
+        libm::sqrtf(variance)
+    }
+
+    /// Returns the last frame of inputs the remote client has acknowledged.
+    pub(crate) fn last_acked_frame(&self) -> Frame {
+        self.last_acked_input.frame
+    }
+
+    /// Returns the size of all queued messages plus the size of all inputs and reliable messages that have not been acknowledged yet.
+    pub(crate) fn pending_send_bytes(&self) -> usize {
+        let queued: usize = self.send_queue.iter().map(message_size).sum();
+        let pending: usize = self
+            .pending_output
+            .iter()
+            .map(|input| input.bytes.len())
+            .sum();
+        let reliable: usize = self.reliable_output.iter().map(|msg| msg.bytes.len()).sum();
+        queued + pending + reliable
+    }
+
+    /// Resets all counters of the network stats, so they only cover the time from now on.
+    pub(crate) fn reset_network_stats(&mut self) {
+        self.stats_start_time = self.time_source.now();
+        self.packets_sent = 0;
+        self.bytes_sent = 0;
+        self.input_bytes_sent = 0;
+        self.quality_report_bytes_sent = 0;
+        self.sync_bytes_sent = 0;
+        self.reliable_bytes_sent = 0;
+        self.bytes_received = 0;
+        self.packets_received = 0;
+        self.packets_lost = 0;
+        self.out_of_order_count = 0;
+        self.inputs_recovered = 0;
+    }
+
+    /// Sets the codec used to compress inputs and the number of frames whose inputs are batched into a single message.
+    /// Inputs that do not fill a batch are still sent when inputs are resent.
+    pub(crate) fn set_input_compression(
+        &mut self,
+        codec: Arc<dyn InputCodec>,
+        batch_frames: usize,
+    ) {
+        self.input_codec = codec;
+        self.input_batch = batch_frames;
+    }
+
+    /// Sets the input checksum that is sent along with all following quality reports.
+    pub(crate) fn set_input_checksum(&mut self, frame: Frame, checksum: u128) {
+        self.local_input_checksum = Some((frame, checksum));
+    }
+
+    /// Removes and returns all input checksums received from the remote client up to the given frame.
+    pub(crate) fn take_remote_input_checksums(&mut self, up_to: Frame) -> Vec<(Frame, u128)> {
+        let mut checksums = Vec::new();
+        while let Some(&(frame, checksum)) = self.remote_input_checksums.front() {
+            if frame > up_to {
+                break;
+            }
+            checksums.push((frame, checksum));
+            self.remote_input_checksums.pop_front();
+        }
+        checksums
+    }
+
+    /// Sets the checksum of a confirmed state that is sent along with all following quality reports.
+    pub(crate) fn set_state_checksum(&mut self, frame: Frame, checksum: u128) {
+        self.local_state_checksum = Some((frame, checksum));
+    }
+
+    /// Removes and returns all state checksums received from the remote client.
+    pub(crate) fn take_remote_state_checksums(&mut self) -> Vec<(Frame, u128)> {
+        self.remote_state_checksums.drain(..).collect()
+    }
+
+    /// Sends the serialized state of the given frame to the remote client, replacing any snapshot that is still being sent.
+    pub(crate) fn send_snapshot(&mut self, frame: Frame, bytes: Vec<u8>) {
+        self.outgoing_snapshot = Some(OutgoingSnapshot {
+            frame,
+            bytes,
+            acked: 0,
+            sent: 0,
+        });
+        self.send_snapshot_chunks();
+    }
+
+    /// Requests the saved state of the given frame from the remote client. The request is repeated until the state starts to arrive,
+    /// but at most [`STATE_REQUEST_RETRIES`] times. Then, an [`Event::StateUnavailable`] is sent.
+    pub(crate) fn request_state(&mut self, frame: Frame) {
+        self.requested_state = Some(frame);
+        self.state_request_retries = 0;
+        self.last_state_request = self.time_source.now();
+        self.queue_message(MessageBody::StateRequest(StateRequest { frame }));
+    }
+
+    /// Tells the remote client that the requested state of the given frame cannot be sent.
+    pub(crate) fn send_state_unavailable(&mut self, frame: Frame) {
+        self.queue_message(MessageBody::StateUnavailable(StateUnavailable { frame }));
+    }
+
+    /// Sends the given bytes as the next message of the reliable stream. The message is resent until the remote client acknowledges it.
+    pub(crate) fn send_reliable(&mut self, bytes: Vec<u8>) {
+        self.push_reliable(ReliableMessage {
+            bytes,
+            ..Default::default()
+        });
+    }
+
+    /// Returns the maximum number of bytes of a reliable message.
+    pub(crate) fn max_payload(&self) -> usize {
+        self.max_payload
+    }
+
+    /// Returns the number of reliable messages that have not been acknowledged yet.
+    pub(crate) fn unacked_reliable_messages(&self) -> usize {
+        self.reliable_output.len()
+    }
+
+    /// Sends a message to coordinate a pause over the reliable stream.
+    pub(crate) fn send_pause_control(&mut self, control: PauseControl) {
+        self.push_reliable(ReliableMessage {
+            pause: Some(control),
+            ..Default::default()
+        });
+    }
+
+    /// Tells the remote client on which frame a player joins over the reliable stream.
+    pub(crate) fn send_player_join(&mut self, join: PlayerJoin) {
+        self.push_reliable(ReliableMessage {
+            join: Some(join),
+            ..Default::default()
+        });
+    }
+
+    fn push_reliable(&mut self, mut msg: ReliableMessage) {
+        msg.sequence = self.next_reliable_sequence;
+        self.next_reliable_sequence += 1;
+        self.queue_message(MessageBody::Reliable(msg.clone()));
+        self.reliable_output.push_back(msg);
+    }
+
     pub(crate) fn handles(&self) -> &Vec<PlayerHandle> {
         &self.handles
     }
@@ -303,10 +793,22 @@ impl<T: Config> UdpProtocol<T> {
             || self.state == ProtocolState::Shutdown
     }
 
+    /// Returns the fraction of sync roundtrips that have been completed, which is 1 once the endpoint is synchronized.
+    pub(crate) fn sync_progress(&self) -> f32 {
+        if self.is_synchronized() {
+            return 1.0;
+        }
+        (self.sync_packets - self.sync_remaining_roundtrips) as f32 / self.sync_packets as f32
+    }
+
     pub(crate) fn is_running(&self) -> bool {
         self.state == ProtocolState::Running
     }
 
+    pub(crate) fn is_disconnected(&self) -> bool {
+        self.state == ProtocolState::Disconnected || self.state == ProtocolState::Shutdown
+    }
+
     pub(crate) fn is_handling_message(&self, addr: &T::Address) -> bool {
         self.peer_addr == *addr
     }
@@ -322,14 +824,87 @@ impl<T: Config> UdpProtocol<T> {
 
         self.state = ProtocolState::Disconnected;
         // schedule the timeout which will lead to shutdown
-        self.shutdown_timeout = Instant::now().add(Duration::from_millis(UDP_SHUTDOWN_TIMER))
+        self.shutdown_timeout = self.time_source.now() + Duration::from_millis(UDP_SHUTDOWN_TIMER)
+    }
+
+    /// Frees everything queued for sending, since nothing is sent anymore after a disconnect.
+    pub(crate) fn release_buffers(&mut self) {
+        self.send_queue = VecDeque::new();
+        self.pending_output = VecDeque::new();
+        self.outgoing_snapshot = None;
+        self.reliable_output = VecDeque::new();
+    }
+
+    /// Returns everything needed to continue the connection from another endpoint, see [`restore_runtime()`].
+    /// Only the endpoints of running sessions should be exported.
+    ///
+    /// [`restore_runtime()`]: Self#method.restore_runtime
+    pub(crate) fn export_runtime(&self) -> EndpointRuntime {
+        EndpointRuntime {
+            handles: self.handles.clone(),
+            disconnected: self.is_disconnected(),
+            magic: self.magic,
+            remote_magic: self.remote_magic,
+            peer_connect_status: self.peer_connect_status.clone(),
+            pending_output: self.pending_output.clone(),
+            last_acked_input: self.last_acked_input.clone(),
+            recv_inputs: self.recv_inputs.clone(),
+            reliable_output: self.reliable_output.clone(),
+            next_reliable_sequence: self.next_reliable_sequence,
+            next_recv_reliable_sequence: self.next_recv_reliable_sequence,
+            next_send_sequence: self.next_send_sequence,
+            next_recv_sequence: self.next_recv_sequence,
+            remote_frame_advantage: self.remote_frame_advantage,
+            round_trip_time: self.round_trip_time,
+        }
+    }
+
+    /// Continues the connection of an exported endpoint with the same remote client, without synchronizing again.
+    /// All timers start over, so the remote client is not considered to be interrupted right away.
+    pub(crate) fn restore_runtime(&mut self, runtime: EndpointRuntime) {
+        let now = self.time_source.now();
+        self.state = ProtocolState::Running;
+        self.sync_remaining_roundtrips = 0;
+        self.sync_random_requests.clear();
+        self.send_queue.clear();
+        self.magic = runtime.magic;
+        self.remote_magic = runtime.remote_magic;
+        self.peer_connect_status = runtime.peer_connect_status;
+        self.pending_output = runtime.pending_output;
+        self.last_acked_input = runtime.last_acked_input;
+        self.recv_inputs = runtime.recv_inputs;
+        self.reliable_output = runtime.reliable_output;
+        self.next_reliable_sequence = runtime.next_reliable_sequence;
+        self.next_recv_reliable_sequence = runtime.next_recv_reliable_sequence;
+        self.next_send_sequence = runtime.next_send_sequence;
+        self.next_recv_sequence = runtime.next_recv_sequence;
+        self.remote_frame_advantage = runtime.remote_frame_advantage;
+        self.round_trip_time = runtime.round_trip_time;
+        self.running_last_input_recv = now;
+        self.running_last_quality_report = now;
+        self.last_recv_time = now;
+        self.last_send_time = now;
+        self.stats_start_time = now;
+        if runtime.disconnected {
+            self.state = ProtocolState::Shutdown;
+            self.disconnect_event_sent = true;
+            self.release_buffers();
+        }
+    }
+
+    pub(crate) fn set_disconnect_timeout(&mut self, timeout: Duration) {
+        self.disconnect_timeout = timeout;
+    }
+
+    pub(crate) fn set_disconnect_notify_start(&mut self, notify_start: Duration) {
+        self.disconnect_notify_start = notify_start;
     }
 
     pub(crate) fn synchronize(&mut self) {
         assert_eq!(self.state, ProtocolState::Initializing);
         self.state = ProtocolState::Synchronizing;
-        self.sync_remaining_roundtrips = NUM_SYNC_PACKETS;
-        self.stats_start_time = millis_since_epoch();
+        self.sync_remaining_roundtrips = self.sync_packets;
+        self.stats_start_time = self.time_source.now();
         self.send_sync_request();
     }
 
@@ -341,12 +916,13 @@ impl<T: Config> UdpProtocol<T> {
         self.peer_addr.clone()
     }
 
-    pub(crate) fn poll(&mut self, connect_status: &[ConnectionStatus]) -> Drain<Event<T>> {
-        let now = Instant::now();
+    pub(crate) fn poll(&mut self, connect_status: &[ConnectionStatus]) -> Drain<'_, Event<T>> {
+        let now = self.time_source.now();
         match self.state {
             ProtocolState::Synchronizing => {
-                // some time has passed, let us send another sync request
-                if self.last_send_time + SYNC_RETRY_INTERVAL < now {
+                // some time has passed, let us send another sync request. Other messages, like replies to a remote client
+                // that is already running, do not delay this
+                if self.sync_last_request + self.sync_interval < now && !self.incompatible {
                     self.send_sync_request();
                 }
             }
@@ -354,7 +930,37 @@ impl<T: Config> UdpProtocol<T> {
                 // resend pending inputs, if some time has passed without sending or receiving inputs
                 if self.running_last_input_recv + RUNNING_RETRY_INTERVAL < now {
                     self.send_pending_output(connect_status);
-                    self.running_last_input_recv = Instant::now();
+                    // also resend all snapshot chunks that have not been acknowledged
+                    if let Some(snapshot) = self.outgoing_snapshot.as_mut() {
+                        snapshot.sent = snapshot.acked;
+                        self.send_snapshot_chunks();
+                    }
+                    // and the oldest reliable messages that have not been acknowledged. Later ones are dropped by the remote client until these arrive
+                    let resend: Vec<_> = self
+                        .reliable_output
+                        .iter()
+                        .take(RELIABLE_WINDOW)
+                        .cloned()
+                        .collect();
+                    for msg in resend {
+                        self.queue_message(MessageBody::Reliable(msg));
+                    }
+                    self.running_last_input_recv = now;
+                }
+
+                // resend the request for a state that did not arrive yet, independent of the inputs, and give up after a while
+                if let Some(frame) = self.requested_state {
+                    if self.last_state_request + RUNNING_RETRY_INTERVAL < now {
+                        if self.state_request_retries >= STATE_REQUEST_RETRIES {
+                            self.requested_state = None;
+                            self.event_queue
+                                .push_back(Event::StateUnavailable { frame });
+                        } else {
+                            self.state_request_retries += 1;
+                            self.last_state_request = now;
+                            self.queue_message(MessageBody::StateRequest(StateRequest { frame }));
+                        }
+                    }
                 }
 
                 // periodically send a quality report
@@ -371,7 +977,11 @@ impl<T: Config> UdpProtocol<T> {
                 if !self.disconnect_notify_sent
                     && self.last_recv_time + self.disconnect_notify_start < now
                 {
-                    let duration: Duration = self.disconnect_timeout - self.disconnect_notify_start;
+                    let duration: Duration = self
+                        .disconnect_timeout
+                        .saturating_sub(self.disconnect_notify_start);
+                    #[cfg(feature = "tracing")]
+                    tracing::info!(handles = ?self.handles, "network interrupted");
                     self.event_queue.push_back(Event::NetworkInterrupted {
                         disconnect_timeout: Duration::as_millis(&duration),
                     });
@@ -382,13 +992,24 @@ impl<T: Config> UdpProtocol<T> {
                 if !self.disconnect_event_sent
                     && self.last_recv_time + self.disconnect_timeout < now
                 {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(handles = ?self.handles, "disconnect timeout");
                     self.event_queue.push_back(Event::Disconnected);
                     self.disconnect_event_sent = true;
                 }
             }
             ProtocolState::Disconnected => {
-                if self.shutdown_timeout < Instant::now() {
+                // after saying goodbye, resend the inputs that have not been acknowledged yet
+                if self.goodbye_sent
+                    && !self.pending_output.is_empty()
+                    && self.running_last_input_recv + RUNNING_RETRY_INTERVAL < now
+                {
+                    self.send_pending_output(connect_status);
+                    self.running_last_input_recv = now;
+                }
+                if self.shutdown_timeout < now {
                     self.state = ProtocolState::Shutdown;
+                    self.release_buffers();
                 }
             }
             ProtocolState::Initializing | ProtocolState::Shutdown => (),
@@ -455,27 +1076,110 @@ impl<T: Config> UdpProtocol<T> {
             self.event_queue.push_back(Event::Disconnected);
         }
 
-        self.send_pending_output(connect_status);
+        self.frames_since_output += 1;
+        if self.frames_since_output >= self.input_batch {
+            self.frames_since_output = 0;
+            // until the remote client received an input, it can only decode packets starting at the first frame
+            let max_frames = match self.input_redundancy {
+                // the remote client only keeps the inputs of the last frames to decode packets with
+                Some(redundancy) if self.last_acked_input.frame != NULL_FRAME => core::cmp::max(
+                    core::cmp::min(redundancy, self.max_prediction) + 1,
+                    self.input_batch,
+                ),
+                _ => usize::MAX,
+            };
+            self.send_pending_frames(connect_status, max_frames, false);
+        }
+    }
+
+    /// Sends all pending inputs together with a disconnect request, so the remote client does not wait for a timeout, then disconnects.
+    /// Inputs that are not acknowledged are resent until the endpoint shuts down.
+    pub(crate) fn send_goodbye(&mut self, connect_status: &[ConnectionStatus]) {
+        if self.state != ProtocolState::Running {
+            return;
+        }
+        self.disconnect();
+        self.goodbye_sent = true;
+        self.running_last_input_recv = self.time_source.now();
+
+        if self.pending_output.is_empty() {
+            // without pending inputs, the disconnect request is sent without any inputs
+            let body = Input {
+                peer_connect_status: connect_status.to_owned(),
+                disconnect_requested: true,
+                start_frame: self.last_acked_input.frame + 1,
+                ack_frame: self.last_recv_frame(),
+                bytes: Vec::new(),
+                resend: false,
+            };
+            self.queue_message(MessageBody::Input(body));
+        } else {
+            self.send_pending_output(connect_status);
+        }
     }
 
+    /// Resends all pending inputs.
     fn send_pending_output(&mut self, connect_status: &[ConnectionStatus]) {
-        let mut body = Input::default();
+        self.send_pending_frames(connect_status, usize::MAX, true);
+    }
+
+    /// Sends the newest `max_frames` pending inputs, encoded relative to the input before them.
+    fn send_pending_frames(
+        &mut self,
+        connect_status: &[ConnectionStatus],
+        max_frames: usize,
+        resend: bool,
+    ) {
+        let mut body = Input {
+            resend,
+            ..Input::default()
+        };
 
         if let Some(input) = self.pending_output.front() {
             assert!(
                 self.last_acked_input.frame == NULL_FRAME
                     || self.last_acked_input.frame + 1 == input.frame
             );
-            body.start_frame = input.frame;
-
-            // encode all pending inputs to a byte buffer
-            body.bytes = encode(
-                &self.last_acked_input.bytes,
-                self.pending_output.iter().map(|gi| &gi.bytes),
-            );
-
-            // the byte buffer should not exceed a certain size to guarantee a maximum UDP packet size
-            assert!(body.bytes.len() <= MAX_PAYLOAD);
+            let skip = self.pending_output.len().saturating_sub(max_frames);
+            let reference = match skip {
+                0 => &self.last_acked_input,
+                _ => &self.pending_output[skip - 1],
+            };
+            body.start_frame = self.pending_output[skip].frame;
+
+            // encode the pending inputs to a byte buffer. The byte buffer should not exceed a certain size to guarantee
+            // a maximum UDP packet size, so only the oldest inputs that fit are sent, the rest follows with the next packets.
+            let mut end = self.pending_output.len();
+            loop {
+                body.bytes = encode(
+                    self.input_codec.as_ref(),
+                    &reference.bytes,
+                    self.pending_output.range(skip..end).map(|gi| &gi.bytes),
+                );
+                if body.bytes.len() <= self.max_payload || end == skip + 1 {
+                    break;
+                }
+                end = skip + (end - skip) / 2;
+            }
+            self.last_input_redundancy = end - skip - 1;
+
+            // a single encoded input does not fit into a packet, so the inputs can never reach the remote client
+            if body.bytes.len() > self.max_payload {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    "An encoded input of {} bytes exceeds the maximum payload of {} bytes, disconnecting.",
+                    body.bytes.len(),
+                    self.max_payload
+                );
+                if !self.disconnect_event_sent {
+                    self.event_queue.push_back(Event::Disconnected);
+                    self.disconnect_event_sent = true;
+                }
+                if self.state != ProtocolState::Disconnected {
+                    self.disconnect();
+                }
+                return;
+            }
 
             body.ack_frame = self.last_recv_frame();
             body.disconnect_requested = self.state == ProtocolState::Disconnected;
@@ -498,33 +1202,87 @@ impl<T: Config> UdpProtocol<T> {
     }
 
     fn send_sync_request(&mut self) {
-        let random_number = rand::random::<u32>();
+        self.sync_last_request = self.time_source.now();
+        let random_number = self.rng.gen::<u32>();
         self.sync_random_requests.insert(random_number);
         let body = SyncRequest {
+            protocol_version: PROTOCOL_VERSION,
             random_request: random_number,
+            app_id: self.app_id,
+            shared_seed: self.shared_seed,
         };
         self.queue_message(MessageBody::SyncRequest(body));
     }
 
     fn send_quality_report(&mut self) {
-        self.running_last_quality_report = Instant::now();
+        self.running_last_quality_report = self.time_source.now();
         let body = QualityReport {
             frame_advantage: i8::try_from(self.local_frame_advantage)
                 .expect("local_frame_advantage bigger than i8::MAX"),
-            ping: millis_since_epoch(),
+            ping: self.time_source.now().as_millis(),
+            input_checksum: self.local_input_checksum,
+            state_checksum: self.local_state_checksum,
+            // payloads that are too big are not sent at all, so they never bloat the reports
+            payload: self
+                .quality_report_payload
+                .map(|payload| payload())
+                .filter(|payload| payload.len() <= MAX_QUALITY_REPORT_PAYLOAD)
+                .unwrap_or_default(),
         };
 
         self.queue_message(MessageBody::QualityReport(body));
     }
 
+    /// Sends the chunks of the outgoing snapshot that have not been sent yet, as long as they fit into the window after the acknowledged bytes.
+    fn send_snapshot_chunks(&mut self) {
+        let snapshot = match self.outgoing_snapshot.as_mut() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        let window_end = core::cmp::min(
+            snapshot.acked + SNAPSHOT_WINDOW * self.max_payload,
+            snapshot.bytes.len(),
+        );
+        let mut chunks = Vec::new();
+        while snapshot.sent < window_end {
+            let end = core::cmp::min(snapshot.sent + self.max_payload, snapshot.bytes.len());
+            chunks.push(SnapshotChunk {
+                frame: snapshot.frame,
+                total_len: snapshot.bytes.len() as u32,
+                offset: snapshot.sent as u32,
+                bytes: snapshot.bytes[snapshot.sent..end].to_vec(),
+            });
+            snapshot.sent = end;
+        }
+        for chunk in chunks {
+            self.queue_message(MessageBody::SnapshotChunk(chunk));
+        }
+    }
+
     fn queue_message(&mut self, body: MessageBody) {
         // set the header
-        let header = MessageHeader { magic: self.magic };
+        let header = MessageHeader {
+            magic: self.magic,
+            sequence: self.next_send_sequence,
+        };
         let msg = Message { header, body };
 
+        self.next_send_sequence = self.next_send_sequence.wrapping_add(1);
         self.packets_sent += 1;
-        self.last_send_time = Instant::now();
-        self.bytes_sent += std::mem::size_of_val(&msg);
+        self.last_send_time = self.time_source.now();
+        let size = message_size(&msg);
+        self.bytes_sent += size;
+        match msg.body {
+            MessageBody::Input(_) | MessageBody::InputAck(_) => self.input_bytes_sent += size,
+            MessageBody::QualityReport(_) | MessageBody::QualityReply(_) => {
+                self.quality_report_bytes_sent += size
+            }
+            MessageBody::SyncRequest(_) | MessageBody::SyncReply(_) => self.sync_bytes_sent += size,
+            MessageBody::Reliable(_) | MessageBody::ReliableAck(_) => {
+                self.reliable_bytes_sent += size
+            }
+            _ => (),
+        }
 
         // add the packet to the back of the send queue
         self.send_queue.push_back(msg);
@@ -534,6 +1292,37 @@ impl<T: Config> UdpProtocol<T> {
      *  RECEIVING MESSAGES
      */
 
+    /// Counts skipped sequence numbers as lost. If a message arrives after a later one, it has been counted as lost before, but arrived out of order instead.
+    /// Sequence numbers wrap around, so a message counts as later if it is less than half the range ahead. Duplicates of recent messages are not counted at all.
+    /// Only gaps within the window of the last 64 messages are tracked, a larger jump in either direction is treated as the remote client resetting its sequence.
+    fn update_sequence_stats(&mut self, sequence: u32) {
+        let ahead = sequence.wrapping_sub(self.next_recv_sequence) as i32;
+        // bit n of the window tells if the message n before the latest one has been received
+        let behind = (-(ahead + 1)) as u32;
+        if (0..u64::BITS as i32).contains(&ahead) {
+            self.packets_received += 1;
+            self.packets_lost += ahead as usize;
+            self.next_recv_sequence = sequence.wrapping_add(1);
+            self.recv_sequence_window = self
+                .recv_sequence_window
+                .checked_shl(ahead as u32 + 1)
+                .unwrap_or(0)
+                | 1;
+        } else if ahead < 0 && behind < u64::BITS {
+            if self.recv_sequence_window & (1 << behind) != 0 {
+                return;
+            }
+            self.recv_sequence_window |= 1 << behind;
+            self.packets_received += 1;
+            self.out_of_order_count += 1;
+            self.packets_lost = self.packets_lost.saturating_sub(1);
+        } else {
+            self.packets_received += 1;
+            self.next_recv_sequence = sequence.wrapping_add(1);
+            self.recv_sequence_window = 1;
+        }
+    }
+
     pub(crate) fn handle_message(&mut self, msg: &Message) {
         // don't handle messages if shutdown
         if self.state == ProtocolState::Shutdown {
@@ -546,7 +1335,9 @@ impl<T: Config> UdpProtocol<T> {
         }
 
         // update time when we last received packages
-        self.last_recv_time = Instant::now();
+        self.last_recv_time = self.time_source.now();
+        self.update_sequence_stats(msg.header.sequence);
+        self.bytes_received += message_size(msg);
 
         // if the connection has been marked as interrupted, send an event to signal we are receiving again
         if self.disconnect_notify_sent && self.state == ProtocolState::Running {
@@ -562,18 +1353,54 @@ impl<T: Config> UdpProtocol<T> {
             MessageBody::InputAck(body) => self.on_input_ack(*body),
             MessageBody::QualityReport(body) => self.on_quality_report(body),
             MessageBody::QualityReply(body) => self.on_quality_reply(body),
+            MessageBody::SnapshotChunk(body) => self.on_snapshot_chunk(body),
+            MessageBody::SnapshotAck(body) => self.on_snapshot_ack(body),
+            MessageBody::StateRequest(body) => self.on_state_request(*body),
+            MessageBody::StateUnavailable(body) => self.on_state_unavailable(*body),
+            MessageBody::Reliable(body) => self.on_reliable(body),
+            MessageBody::ReliableAck(body) => self.on_reliable_ack(*body),
             MessageBody::KeepAlive => (),
+            // sealed messages are opened by the socket before they arrive here, if the session has a crypto
+            MessageBody::Sealed(_) => (),
+            // routed messages are unpacked by the shared socket before they arrive here
+            MessageBody::Routed(..) => (),
         }
     }
 
     /// Upon receiving a `SyncRequest`, answer with a `SyncReply` with the proper data
     fn on_sync_request(&mut self, body: SyncRequest) {
+        if !self.check_compatible(body.protocol_version, body.app_id, body.shared_seed) {
+            return;
+        }
         let reply_body = SyncReply {
+            protocol_version: PROTOCOL_VERSION,
             random_reply: body.random_request,
+            app_id: self.app_id,
+            shared_seed: self.shared_seed,
         };
         self.queue_message(MessageBody::SyncReply(reply_body));
     }
 
+    /// Returns true if the remote client has the same protocol version, app id and shared seed. Otherwise, stops synchronizing and notifies the session once.
+    fn check_compatible(
+        &mut self,
+        remote_version: u16,
+        remote_app_id: u64,
+        remote_shared_seed: Option<u64>,
+    ) -> bool {
+        if remote_version == PROTOCOL_VERSION
+            && remote_app_id == self.app_id
+            && remote_shared_seed == self.shared_seed
+        {
+            return true;
+        }
+        if !self.incompatible {
+            self.incompatible = true;
+            self.event_queue.push_back(Event::IncompatiblePeer);
+        }
+        false
+    }
+
     /// Upon receiving a `SyncReply`, check validity and either continue the synchronization process or conclude synchronization.
     fn on_sync_reply(&mut self, header: MessageHeader, body: SyncReply) {
         // ignore sync replies when not syncing
@@ -581,7 +1408,9 @@ impl<T: Config> UdpProtocol<T> {
             return;
         }
         // this is not the correct reply
-        if !self.sync_random_requests.remove(&body.random_reply) {
+        if !self.sync_random_requests.remove(&body.random_reply)
+            || !self.check_compatible(body.protocol_version, body.app_id, body.shared_seed)
+        {
             return;
         }
         // the sync reply is good, so we send a sync request again until we have finished the required roundtrips. Then, we can conclude the syncing process.
@@ -589,8 +1418,8 @@ impl<T: Config> UdpProtocol<T> {
         if self.sync_remaining_roundtrips > 0 {
             // register an event
             let evt = Event::Synchronizing {
-                total: NUM_SYNC_PACKETS,
-                count: NUM_SYNC_PACKETS - self.sync_remaining_roundtrips,
+                total: self.sync_packets,
+                count: self.sync_packets - self.sync_remaining_roundtrips,
             };
             self.event_queue.push_back(evt);
             // send another sync request
@@ -621,45 +1450,73 @@ impl<T: Config> UdpProtocol<T> {
             for i in 0..self.peer_connect_status.len() {
                 self.peer_connect_status[i].disconnected = body.peer_connect_status[i].disconnected
                     || self.peer_connect_status[i].disconnected;
-                self.peer_connect_status[i].last_frame = std::cmp::max(
+                self.peer_connect_status[i].last_frame = core::cmp::max(
                     self.peer_connect_status[i].last_frame,
                     body.peer_connect_status[i].last_frame,
                 );
             }
         }
 
-        // if the encoded packet is decoded with an input we did not receive yet, we cannot recover
-        assert!(
-            self.last_recv_frame() == NULL_FRAME || self.last_recv_frame() + 1 >= body.start_frame
-        );
+        // if the encoded packet is decoded with an input we did not receive yet, we cannot decode it.
+        // This happens if the packet re-includes only a few frames and earlier packets got lost, so we wait for the inputs to be resent
+        if self.last_recv_frame() != NULL_FRAME
+            && self.last_recv_frame().saturating_add(1) < body.start_frame
+        {
+            return;
+        }
 
         // if we did not receive any input yet, we decode with the blank input,
         // otherwise we use the input previous to the start of the encoded inputs
         let decode_frame = if self.last_recv_frame() == NULL_FRAME {
             NULL_FRAME
         } else {
-            body.start_frame - 1
+            body.start_frame.saturating_sub(1)
         };
 
         // if we have the necessary input saved, we decode
         if let Some(decode_inp) = self.recv_inputs.get(&decode_frame) {
-            self.running_last_input_recv = Instant::now();
+            self.running_last_input_recv = self.time_source.now();
 
-            let recv_inputs = decode(&decode_inp.bytes, &body.bytes).expect("decoding failed");
+            let recv_inputs =
+                match decode(self.input_codec.as_ref(), &decode_inp.bytes, &body.bytes) {
+                    Some(recv_inputs) => recv_inputs,
+                    // the packet could not be decoded, so we drop it
+                    None => return,
+                };
+
+            let num_players = self.handles.len();
+            let recv_inputs: Option<Vec<_>> = recv_inputs
+                .into_iter()
+                .enumerate()
+                .map(|(i, bytes)| {
+                    // inputs beyond the limit of the frame counter cannot be valid
+                    let input_data = InputBytes {
+                        frame: body.start_frame.checked_add(i as i32)?,
+                        bytes,
+                    };
+                    let player_inputs = input_data.to_player_inputs::<T>(num_players)?;
+                    Some((input_data, player_inputs))
+                })
+                .collect();
+            let recv_inputs = match recv_inputs {
+                Some(recv_inputs) => recv_inputs,
+                // the inputs do not have the size we expect, e.g. because the remote client uses a different input type, so we drop the packet
+                None => return,
+            };
 
-            for (i, inp) in recv_inputs.into_iter().enumerate() {
-                let inp_frame = body.start_frame + i as i32;
+            let newest_frame = body.start_frame + (recv_inputs.len() as i32 - 1);
+            for (input_data, player_inputs) in recv_inputs {
+                let inp_frame = input_data.frame;
                 // skip inputs that we don't need
                 if inp_frame <= self.last_recv_frame() {
                     continue;
                 }
+                // a new input that is not the newest of the packet has been lost before, but arrived without waiting for a resend
+                if inp_frame < newest_frame && !body.resend {
+                    self.inputs_recovered += 1;
+                }
 
-                let input_data = InputBytes {
-                    frame: inp_frame,
-                    bytes: inp,
-                };
                 // send the input to the session
-                let player_inputs = input_data.to_player_inputs::<T>(self.handles.len());
                 self.recv_inputs.insert(input_data.frame, input_data);
 
                 for (i, player_input) in player_inputs.into_iter().enumerate() {
@@ -688,15 +1545,206 @@ impl<T: Config> UdpProtocol<T> {
     /// Upon receiving a `QualityReport`, update network stats and reply with a `QualityReply`.
     fn on_quality_report(&mut self, body: &QualityReport) {
         self.remote_frame_advantage = body.frame_advantage as i32;
-        let reply_body = QualityReply { pong: body.ping };
+        // the same checksum is sent with every report until there is a newer one, so only keep new ones
+        if let Some((frame, checksum)) = body.input_checksum {
+            if frame > self.last_remote_input_checksum_frame {
+                self.last_remote_input_checksum_frame = frame;
+                self.remote_input_checksums.push_back((frame, checksum));
+                while self.remote_input_checksums.len() > MAX_INPUT_CHECKSUMS {
+                    self.remote_input_checksums.pop_front();
+                }
+            }
+        }
+        if let Some((frame, checksum)) = body.state_checksum {
+            if frame > self.last_remote_state_checksum_frame {
+                self.last_remote_state_checksum_frame = frame;
+                self.remote_state_checksums.push_back((frame, checksum));
+                while self.remote_state_checksums.len() > MAX_INPUT_CHECKSUMS {
+                    self.remote_state_checksums.pop_front();
+                }
+            }
+        }
+        if !body.payload.is_empty() && body.payload.len() <= MAX_QUALITY_REPORT_PAYLOAD {
+            self.event_queue.push_back(Event::QualityReportPayload {
+                bytes: body.payload.clone(),
+            });
+        }
+        let reply_body = QualityReply {
+            pong: body.ping,
+            time: self.time_source.now().as_millis(),
+        };
         self.queue_message(MessageBody::QualityReply(reply_body));
     }
 
     /// Upon receiving a `QualityReply`, update network stats.
     fn on_quality_reply(&mut self, body: &QualityReply) {
-        let millis = millis_since_epoch();
-        assert!(millis >= body.pong);
+        let millis = self.time_source.now().as_millis();
+        // a reply to a ping from the future is malformed or belongs to an earlier session
+        if millis < body.pong {
+            return;
+        }
         self.round_trip_time = millis - body.pong;
+        self.round_trip_samples.push_back(self.round_trip_time);
+        if self.round_trip_samples.len() > RTT_SAMPLES {
+            self.round_trip_samples.pop_front();
+        }
+        self.update_clock_offset(body.time as i128 - ((body.pong + millis) / 2) as i128);
+    }
+
+    /// Adds the clock offset measured with the latest quality reply and warns once the offset drifted too far from its first estimate.
+    /// Both clocks start at arbitrary points in time, so only the change of the offset is meaningful.
+    fn update_clock_offset(&mut self, offset: i128) {
+        self.clock_offset_samples
+            .push_back((self.round_trip_time, offset));
+        if self.clock_offset_samples.len() > RTT_SAMPLES {
+            self.clock_offset_samples.pop_front();
+        }
+        // the offset measured with the lowest round-trip time is the least distorted by queueing delays, prefer the newest of those
+        let offset = self
+            .clock_offset_samples
+            .iter()
+            .rev()
+            .min_by_key(|(rtt, _)| *rtt)
+            .map(|(_, offset)| *offset)
+            .expect("There is at least one clock offset sample");
+        let baseline = match self.clock_offset_baseline {
+            Some(baseline) => baseline,
+            None => {
+                if self.clock_offset_samples.len() < RTT_SAMPLES {
+                    return;
+                }
+                self.clock_offset_baseline = Some(offset);
+                offset
+            }
+        };
+        self.clock_offset = Some(offset - baseline);
+
+        let offset = self
+            .clock_offset()
+            .expect("The clock offset has just been set");
+        if offset > CLOCK_DRIFT_WARNING_THRESHOLD {
+            if !self.clock_offset_warned {
+                self.clock_offset_warned = true;
+                self.event_queue
+                    .push_back(Event::ClockDriftWarning { offset });
+            }
+        } else {
+            self.clock_offset_warned = false;
+        }
+    }
+
+    /// Upon receiving a `SnapshotChunk`, add it to the incoming snapshot and acknowledge all bytes received so far.
+    /// Once the snapshot is complete, it is handed to the session.
+    fn on_snapshot_chunk(&mut self, body: &SnapshotChunk) {
+        // the requested state is arriving, the remote client resends its chunks from now on
+        if self.requested_state == Some(body.frame) {
+            self.requested_state = None;
+        }
+        // a snapshot of a different frame replaces the current one
+        if !matches!(&self.incoming_snapshot, Some(snapshot) if snapshot.frame == body.frame) {
+            self.incoming_snapshot = Some(IncomingSnapshot {
+                frame: body.frame,
+                total_len: body.total_len as usize,
+                bytes: Vec::new(),
+                out_of_order: HashMap::new(),
+                complete: false,
+            });
+        }
+        let snapshot = self
+            .incoming_snapshot
+            .as_mut()
+            .expect("the incoming snapshot has just been set");
+
+        let offset = body.offset as usize;
+        let end = offset + body.bytes.len();
+        // drop chunks that do not fit the announced state or are too far ahead of the window
+        let fits = !snapshot.complete
+            && end <= snapshot.total_len
+            && offset <= snapshot.bytes.len() + SNAPSHOT_WINDOW * MAX_PAYLOAD;
+        if fits && offset == snapshot.bytes.len() {
+            snapshot.bytes.extend_from_slice(&body.bytes);
+            while let Some(chunk) = snapshot.out_of_order.remove(&snapshot.bytes.len()) {
+                snapshot.bytes.extend_from_slice(&chunk);
+            }
+        } else if fits && offset > snapshot.bytes.len() {
+            snapshot.out_of_order.insert(offset, body.bytes.clone());
+        }
+
+        let ack = SnapshotAck {
+            frame: snapshot.frame,
+            received: snapshot.bytes.len() as u32,
+        };
+        if !snapshot.complete && snapshot.bytes.len() == snapshot.total_len {
+            snapshot.complete = true;
+            snapshot.out_of_order.clear();
+            self.event_queue.push_back(Event::Snapshot {
+                frame: snapshot.frame,
+                bytes: snapshot.bytes.clone(),
+            });
+        }
+        self.queue_message(MessageBody::SnapshotAck(ack));
+    }
+
+    /// Upon receiving a `SnapshotAck`, send the next chunks of the outgoing snapshot, or drop it if it has been received completely.
+    fn on_snapshot_ack(&mut self, body: &SnapshotAck) {
+        let snapshot = match self.outgoing_snapshot.as_mut() {
+            Some(snapshot) if snapshot.frame == body.frame => snapshot,
+            _ => return,
+        };
+        let received = body.received as usize;
+        if received >= snapshot.bytes.len() {
+            self.outgoing_snapshot = None;
+            return;
+        }
+        if received > snapshot.acked {
+            snapshot.acked = received;
+            snapshot.sent = core::cmp::max(snapshot.sent, received);
+            self.send_snapshot_chunks();
+        }
+    }
+
+    /// Upon receiving a `StateRequest`, hand it to the session, which decides whether to send the state.
+    fn on_state_request(&mut self, body: StateRequest) {
+        self.event_queue
+            .push_back(Event::StateRequested { frame: body.frame });
+    }
+
+    /// Upon receiving a `StateUnavailable`, stop requesting the state and tell the session.
+    fn on_state_unavailable(&mut self, body: StateUnavailable) {
+        if self.requested_state == Some(body.frame) {
+            self.requested_state = None;
+            self.event_queue
+                .push_back(Event::StateUnavailable { frame: body.frame });
+        }
+    }
+
+    /// Upon receiving a reliable message, hand it to the session if it is the next one in the stream, and acknowledge all messages received in order.
+    /// Messages arriving out of order are dropped, since they will be resent.
+    fn on_reliable(&mut self, body: &ReliableMessage) {
+        if body.sequence == self.next_recv_reliable_sequence {
+            self.next_recv_reliable_sequence += 1;
+            let event = match (body.pause, body.join) {
+                (Some(control), _) => Event::PauseControl(control),
+                (None, Some(join)) => Event::PlayerJoin(join),
+                (None, None) => Event::Message {
+                    bytes: body.bytes.clone(),
+                },
+            };
+            self.event_queue.push_back(event);
+        }
+        self.queue_message(MessageBody::ReliableAck(ReliableAck {
+            received: self.next_recv_reliable_sequence,
+        }));
+    }
+
+    /// Upon receiving a `ReliableAck`, stop resending all reliable messages that have been received.
+    fn on_reliable_ack(&mut self, body: ReliableAck) {
+        while let Some(msg) = self.reliable_output.front() {
+            if msg.sequence >= body.received {
+                break;
+            }
+            self.reliable_output.pop_front();
+        }
     }
 
     /// Returns the frame of the last received input
@@ -707,3 +1755,178 @@ impl<T: Config> UdpProtocol<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod protocol_tests {
+
+    use super::*;
+    use crate::RleInputCodec;
+    use bytemuck::{Pod, Zeroable};
+    use std::net::SocketAddr;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, PartialEq, Pod, Zeroable)]
+    struct TestInput {
+        inp: u8,
+    }
+
+    struct TestConfig;
+
+    impl Config for TestConfig {
+        type Input = TestInput;
+        type State = u8;
+        type Address = SocketAddr;
+    }
+
+    struct ZeroTime;
+
+    impl TimeSource for ZeroTime {
+        fn now(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct StepTime(Arc<std::sync::Mutex<Duration>>);
+
+    impl TimeSource for StepTime {
+        fn now(&self) -> Duration {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn endpoint() -> UdpProtocol<TestConfig> {
+        endpoint_with_time(Arc::new(ZeroTime))
+    }
+
+    fn endpoint_with_time(time_source: Arc<dyn TimeSource>) -> UdpProtocol<TestConfig> {
+        UdpProtocol::new(
+            vec![1],
+            "127.0.0.1:7777".parse().unwrap(),
+            2,
+            1,
+            8,
+            Duration::from_millis(2000),
+            Duration::from_millis(500),
+            60,
+            Arc::new(RleInputCodec),
+            time_source,
+            StdRng::seed_from_u64(0),
+        )
+    }
+
+    #[test]
+    fn test_sequence_stats() {
+        let mut endpoint = endpoint();
+        for sequence in [0, 1, 3, 4] {
+            endpoint.update_sequence_stats(sequence);
+        }
+        assert_eq!(endpoint.packets_lost, 1);
+
+        // the skipped message arrives late, duplicates are not counted
+        endpoint.update_sequence_stats(2);
+        endpoint.update_sequence_stats(2);
+        endpoint.update_sequence_stats(4);
+        assert_eq!(endpoint.packets_received, 5);
+        assert_eq!(endpoint.packets_lost, 0);
+        assert_eq!(endpoint.out_of_order_count, 1);
+    }
+
+    #[test]
+    fn test_sequence_stats_wrap_around() {
+        let mut endpoint = endpoint();
+        endpoint.next_recv_sequence = u32::MAX - 1;
+        for sequence in [u32::MAX - 1, u32::MAX, 1, 0] {
+            endpoint.update_sequence_stats(sequence);
+        }
+        assert_eq!(endpoint.packets_received, 4);
+        assert_eq!(endpoint.packets_lost, 0);
+        assert_eq!(endpoint.out_of_order_count, 1);
+        assert_eq!(endpoint.next_recv_sequence, 2);
+    }
+
+    #[test]
+    fn test_sequence_stats_reset() {
+        let mut endpoint = endpoint();
+        for sequence in [0, 1, 100_000, 100_001, 100_003] {
+            endpoint.update_sequence_stats(sequence);
+        }
+        // jumps beyond the window are not counted as lost, only the gaps after them
+        assert_eq!(endpoint.packets_received, 5);
+        assert_eq!(endpoint.packets_lost, 1);
+        assert_eq!(endpoint.out_of_order_count, 0);
+        assert_eq!(endpoint.next_recv_sequence, 100_004);
+
+        // neither are jumps back beyond the window counted as out of order
+        endpoint.update_sequence_stats(3);
+        assert_eq!(endpoint.packets_received, 6);
+        assert_eq!(endpoint.out_of_order_count, 0);
+        assert_eq!(endpoint.next_recv_sequence, 4);
+    }
+
+    #[test]
+    fn test_input_bytes_size() {
+        let input_bytes = |bytes: Vec<u8>| InputBytes { frame: 3, bytes };
+
+        // every player has an input byte and a flags byte
+        let inputs = input_bytes(vec![1, 0, 2, 1])
+            .to_player_inputs::<TestConfig>(2)
+            .unwrap();
+        assert_eq!(inputs.len(), 2);
+        assert!(inputs[0].input == TestInput { inp: 1 });
+        assert!(inputs[1].input == TestInput { inp: 2 });
+        assert_eq!(inputs[1].flags, 1);
+
+        // bytes of the wrong size are rejected instead of being misread
+        for bytes in [vec![], vec![1, 0, 2], vec![1, 0, 2, 0, 3, 0]] {
+            assert!(input_bytes(bytes)
+                .to_player_inputs::<TestConfig>(2)
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_state_request_gives_up() {
+        let time = StepTime::default();
+        let mut endpoint = endpoint_with_time(Arc::new(time.clone()));
+        endpoint.state = ProtocolState::Running;
+        let connect_status = vec![ConnectionStatus::default(); 2];
+        endpoint.request_state(10);
+
+        // the request is resent, even while inputs keep arriving, until the retries are used up
+        let mut unavailable = false;
+        for _ in 0..=STATE_REQUEST_RETRIES {
+            *time.0.lock().unwrap() += Duration::from_millis(250);
+            endpoint.running_last_input_recv = time.now();
+            unavailable |= endpoint
+                .poll(&connect_status)
+                .any(|event| matches!(event, Event::StateUnavailable { frame: 10 }));
+        }
+        assert!(unavailable);
+        assert_eq!(endpoint.requested_state, None);
+        assert!(
+            endpoint
+                .send_queue
+                .iter()
+                .filter(|msg| matches!(msg.body, MessageBody::StateRequest(_)))
+                .count() as u32
+                > STATE_REQUEST_RETRIES
+        );
+    }
+
+    #[test]
+    fn test_state_unavailable() {
+        let mut endpoint = endpoint();
+        endpoint.request_state(10);
+
+        // a reply for another frame is ignored
+        endpoint.on_state_unavailable(StateUnavailable { frame: 20 });
+        assert_eq!(endpoint.requested_state, Some(10));
+        endpoint.on_state_unavailable(StateUnavailable { frame: 10 });
+        assert_eq!(endpoint.requested_state, None);
+        assert!(matches!(
+            endpoint.event_queue.pop_front(),
+            Some(Event::StateUnavailable { frame: 10 })
+        ));
+    }
+}
@@ -0,0 +1,369 @@
+use core::fmt::{self, Display};
+
+use serde::ser::{self, Serialize};
+
+/// Returns the number of bytes the value takes up when serialized with the default options of `bincode` 1, which the sockets use.
+/// Since `bincode` requires `std`, the size is counted by a serializer that only adds up the sizes of the encoded values.
+pub(crate) fn serialized_size<S: Serialize>(value: &S) -> usize {
+    let mut counter = SizeCounter(0);
+    match value.serialize(&mut counter) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
+    }
+}
+
+struct SizeCounter(usize);
+
+/// The error of the [`SizeCounter`], only returned for sequences of unknown length, which `bincode` cannot serialize either.
+#[derive(Debug)]
+struct SizeError;
+
+impl Display for SizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The size of the value cannot be determined.")
+    }
+}
+
+impl ser::StdError for SizeError {}
+
+impl ser::Error for SizeError {
+    fn custom<T: Display>(_msg: T) -> Self {
+        SizeError
+    }
+}
+
+// lengths of sequences and maps are encoded as u64, enum variants as u32
+const LEN_SIZE: usize = 8;
+const VARIANT_SIZE: usize = 4;
+
+impl ser::Serializer for &mut SizeCounter {
+    type Ok = ();
+    type Error = SizeError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, _v: bool) -> Result<(), SizeError> {
+        self.0 += 1;
+        Ok(())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<(), SizeError> {
+        self.0 += 1;
+        Ok(())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<(), SizeError> {
+        self.0 += 2;
+        Ok(())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<(), SizeError> {
+        self.0 += 4;
+        Ok(())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<(), SizeError> {
+        self.0 += 8;
+        Ok(())
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result<(), SizeError> {
+        self.0 += 16;
+        Ok(())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<(), SizeError> {
+        self.0 += 1;
+        Ok(())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<(), SizeError> {
+        self.0 += 2;
+        Ok(())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<(), SizeError> {
+        self.0 += 4;
+        Ok(())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<(), SizeError> {
+        self.0 += 8;
+        Ok(())
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<(), SizeError> {
+        self.0 += 16;
+        Ok(())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), SizeError> {
+        self.0 += 4;
+        Ok(())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), SizeError> {
+        self.0 += 8;
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), SizeError> {
+        self.0 += v.len_utf8();
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SizeError> {
+        self.0 += LEN_SIZE + v.len();
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SizeError> {
+        self.0 += LEN_SIZE + v.len();
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), SizeError> {
+        self.0 += 1;
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), SizeError> {
+        self.0 += 1;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SizeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SizeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), SizeError> {
+        self.0 += VARIANT_SIZE;
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SizeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), SizeError> {
+        self.0 += VARIANT_SIZE;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, SizeError> {
+        len.ok_or(SizeError)?;
+        self.0 += LEN_SIZE;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, SizeError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, SizeError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, SizeError> {
+        self.0 += VARIANT_SIZE;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, SizeError> {
+        len.ok_or(SizeError)?;
+        self.0 += LEN_SIZE;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, SizeError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, SizeError> {
+        self.0 += VARIANT_SIZE;
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut SizeCounter {
+    type Ok = ();
+    type Error = SizeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SizeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SizeError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut SizeCounter {
+    type Ok = ();
+    type Error = SizeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SizeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SizeError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut SizeCounter {
+    type Ok = ();
+    type Error = SizeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SizeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SizeError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut SizeCounter {
+    type Ok = ();
+    type Error = SizeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SizeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SizeError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut SizeCounter {
+    type Ok = ();
+    type Error = SizeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), SizeError> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SizeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SizeError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut SizeCounter {
+    type Ok = ();
+    type Error = SizeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), SizeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SizeError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut SizeCounter {
+    type Ok = ();
+    type Error = SizeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), SizeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SizeError> {
+        Ok(())
+    }
+}
+
+// #########
+// # TESTS #
+// #########
+
+#[cfg(all(test, feature = "std"))]
+mod serialized_size_tests {
+    use super::*;
+    use crate::network::messages::{
+        ConnectionStatus, Input, Message, MessageBody, MessageHeader, QualityReport,
+        ReliableMessage,
+    };
+
+    #[test]
+    fn test_size_matches_bincode() {
+        let bodies = [
+            MessageBody::KeepAlive,
+            MessageBody::Input(Input {
+                peer_connect_status: vec![ConnectionStatus::default(); 3],
+                start_frame: 7,
+                bytes: vec![1, 2, 3, 4, 5],
+                ..Input::default()
+            }),
+            MessageBody::QualityReport(QualityReport {
+                payload: vec![9; 20],
+                ..QualityReport::default()
+            }),
+            MessageBody::Reliable(ReliableMessage {
+                bytes: vec![0; 100],
+                ..ReliableMessage::default()
+            }),
+        ];
+        for body in bodies {
+            let msg = Message {
+                header: MessageHeader::default(),
+                body,
+            };
+            assert_eq!(
+                serialized_size(&msg),
+                bincode::serialized_size(&msg).unwrap() as usize
+            );
+        }
+    }
+}
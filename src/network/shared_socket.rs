@@ -0,0 +1,282 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::{borrow::ToOwned, boxed::Box, vec::Vec};
+use hashbrown::HashMap;
+
+use crate::Mutex;
+
+use crate::network::messages::{Message, MessageBody, MessageHeader};
+use crate::{Config, GGRSError, NonBlockingSocket};
+
+/// The number of bytes routing adds to every message: the outer header, the message kind and the session id.
+pub(crate) const ROUTING_OVERHEAD: usize = 18;
+/// The maximum number of received messages kept for a session that does not collect them, the oldest ones are dropped beyond that.
+const MAX_MAILBOX_SIZE: usize = 1024;
+
+struct SharedState<T>
+where
+    T: Config,
+{
+    socket: Box<dyn NonBlockingSocket<T::Address>>,
+    /// Received messages that have not been collected yet, for every session using the socket.
+    mailboxes: HashMap<u64, VecDeque<(T::Address, Message)>>,
+}
+
+/// A [`SharedSocket`] lets many independent sessions send and receive through a single socket, e.g. for a server hosting many matches
+/// on one port. Every message is tagged with the id of the session that sent it, and received messages are handed to the session
+/// with the same id. Messages for ids without a session are dropped. Both ends of a connection need to use the same session id.
+/// If a session stops polling, only its newest 1024 messages are kept.
+///
+/// Cloning a [`SharedSocket`] returns another handle to the same socket.
+pub struct SharedSocket<T>
+where
+    T: Config,
+{
+    state: Arc<Mutex<SharedState<T>>>,
+}
+
+impl<T: Config> Clone for SharedSocket<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T: Config> SharedSocket<T> {
+    /// Wraps the given socket, so it can be shared between sessions.
+    pub fn new(socket: impl NonBlockingSocket<T::Address> + 'static) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SharedState {
+                socket: Box::new(socket),
+                mailboxes: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Returns a socket for the session with the given id, which you can pass to a [`SessionBuilder`]. The id is free again once
+    /// the returned socket is dropped, e.g. together with its session.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if a socket for the given session id already exists.
+    ///
+    /// [`SessionBuilder`]: crate::SessionBuilder
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn session(&self, session_id: u64) -> Result<SharedSocketSession<T>, GGRSError> {
+        let mut state = self.state.lock();
+        if state.mailboxes.contains_key(&session_id) {
+            return Err(GGRSError::InvalidRequest {
+                info: "The session id is already in use.".to_owned(),
+            });
+        }
+        state.mailboxes.insert(session_id, VecDeque::new());
+        Ok(SharedSocketSession {
+            session_id,
+            state: self.state.clone(),
+        })
+    }
+
+    /// Returns the number of sessions currently using the socket.
+    pub fn num_sessions(&self) -> usize {
+        self.state.lock().mailboxes.len()
+    }
+}
+
+/// The part of a [`SharedSocket`] used by a single session, created with [`SharedSocket::session()`].
+pub struct SharedSocketSession<T>
+where
+    T: Config,
+{
+    session_id: u64,
+    state: Arc<Mutex<SharedState<T>>>,
+}
+
+impl<T: Config> SharedSocketSession<T> {
+    /// Returns the id of the session using this socket.
+    pub fn session_id(&self) -> u64 {
+        self.session_id
+    }
+}
+
+impl<T: Config> NonBlockingSocket<T::Address> for SharedSocketSession<T> {
+    fn send_to(&mut self, msg: &Message, addr: &T::Address) {
+        // the header is sent together with the message, so the outer header carries no information
+        let routed = Message {
+            header: MessageHeader::default(),
+            body: MessageBody::Routed(self.session_id, Box::new(msg.clone())),
+        };
+        self.state.lock().socket.send_to(&routed, addr);
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.state.lock().socket.set_rng_seed(seed);
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(T::Address, Message)> {
+        let mut state = self.state.lock();
+        let state = &mut *state;
+        // hand out all messages that arrived for any session, so they do not pile up in the socket
+        for (from, msg) in state.socket.receive_all_messages() {
+            if let MessageBody::Routed(session_id, msg) = msg.body {
+                // a routed message should never contain another routed message
+                if matches!(msg.body, MessageBody::Routed(..)) {
+                    continue;
+                }
+                if let Some(mailbox) = state.mailboxes.get_mut(&session_id) {
+                    mailbox.push_back((from, *msg));
+                    if mailbox.len() > MAX_MAILBOX_SIZE {
+                        mailbox.pop_front();
+                    }
+                }
+            }
+        }
+        state
+            .mailboxes
+            .get_mut(&self.session_id)
+            .map(|mailbox| mailbox.drain(..).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl<T: Config> Drop for SharedSocketSession<T> {
+    fn drop(&mut self) {
+        self.state.lock().mailboxes.remove(&self.session_id);
+    }
+}
+
+// #########
+// # TESTS #
+// #########
+
+#[cfg(all(test, feature = "std"))]
+mod shared_socket_tests {
+    use super::*;
+    use crate::InMemoryNetwork;
+    use bytemuck::{Pod, Zeroable};
+    use std::net::SocketAddr;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, PartialEq, Pod, Zeroable)]
+    struct TestInput {
+        inp: u8,
+    }
+
+    struct TestConfig;
+
+    impl Config for TestConfig {
+        type Input = TestInput;
+        type State = u8;
+        type Address = SocketAddr;
+    }
+
+    fn keep_alive(sequence: u32) -> Message {
+        Message {
+            header: MessageHeader { magic: 1, sequence },
+            body: MessageBody::KeepAlive,
+        }
+    }
+
+    #[test]
+    fn test_route_by_session_id() {
+        let network = InMemoryNetwork::new(0);
+        let addr1: SocketAddr = "127.0.0.1:7000".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:7001".parse().unwrap();
+        let shared1 = SharedSocket::<TestConfig>::new(network.socket(addr1));
+        let shared2 = SharedSocket::<TestConfig>::new(network.socket(addr2));
+        let mut sender_a = shared1.session(1).unwrap();
+        let mut sender_b = shared1.session(2).unwrap();
+        let mut receiver_a = shared2.session(1).unwrap();
+        let mut receiver_b = shared2.session(2).unwrap();
+
+        sender_a.send_to(&keep_alive(10), &addr2);
+        sender_b.send_to(&keep_alive(20), &addr2);
+        sender_a.send_to(&keep_alive(11), &addr2);
+
+        // receiving for one session keeps the messages of the other session
+        assert_eq!(
+            receiver_b.receive_all_messages(),
+            vec![(addr1, keep_alive(20))]
+        );
+        assert_eq!(
+            receiver_a.receive_all_messages(),
+            vec![(addr1, keep_alive(10)), (addr1, keep_alive(11))]
+        );
+        assert!(receiver_b.receive_all_messages().is_empty());
+    }
+
+    #[test]
+    fn test_drop_unknown_and_unrouted() {
+        let network = InMemoryNetwork::new(0);
+        let addr1: SocketAddr = "127.0.0.1:7000".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:7001".parse().unwrap();
+        let mut plain_socket = network.socket(addr1);
+        let shared1 = SharedSocket::<TestConfig>::new(network.socket(addr2));
+        let mut sender = SharedSocket::<TestConfig>::new(network.socket(addr1))
+            .session(3)
+            .unwrap();
+        let mut receiver = shared1.session(1).unwrap();
+
+        plain_socket.send_to(&keep_alive(0), &addr2);
+        sender.send_to(&keep_alive(1), &addr2);
+        assert!(receiver.receive_all_messages().is_empty());
+    }
+
+    #[test]
+    fn test_routing_overhead() {
+        let network = InMemoryNetwork::new(0);
+        let shared =
+            SharedSocket::<TestConfig>::new(network.socket("127.0.0.1:7000".parse().unwrap()));
+        let session = shared.session(u64::MAX).unwrap();
+        let msg = keep_alive(0);
+        let routed = Message {
+            header: MessageHeader::default(),
+            body: MessageBody::Routed(session.session_id(), Box::new(msg.clone())),
+        };
+        let size = |msg: &Message| bincode::serialized_size(msg).unwrap() as usize;
+        assert_eq!(size(&routed) - size(&msg), ROUTING_OVERHEAD);
+    }
+
+    #[test]
+    fn test_mailbox_capped() {
+        let network = InMemoryNetwork::new(0);
+        let addr1: SocketAddr = "127.0.0.1:7000".parse().unwrap();
+        let addr2: SocketAddr = "127.0.0.1:7001".parse().unwrap();
+        let shared1 = SharedSocket::<TestConfig>::new(network.socket(addr1));
+        let shared2 = SharedSocket::<TestConfig>::new(network.socket(addr2));
+        let mut sender_a = shared1.session(1).unwrap();
+        let mut sender_b = shared1.session(2).unwrap();
+        let mut receiver_a = shared2.session(1).unwrap();
+        let mut receiver_b = shared2.session(2).unwrap();
+
+        // the messages for a session that does not poll pile up while another session polls
+        for sequence in 0..2000 {
+            sender_a.send_to(&keep_alive(sequence), &addr2);
+            sender_b.send_to(&keep_alive(sequence), &addr2);
+            assert_eq!(receiver_b.receive_all_messages().len(), 1);
+        }
+
+        // only the newest messages have been kept
+        let received = receiver_a.receive_all_messages();
+        assert_eq!(received.len(), MAX_MAILBOX_SIZE);
+        assert_eq!(
+            received[0],
+            (addr1, keep_alive(2000 - MAX_MAILBOX_SIZE as u32))
+        );
+    }
+
+    #[test]
+    fn test_session_ids() {
+        let network = InMemoryNetwork::new(0);
+        let shared =
+            SharedSocket::<TestConfig>::new(network.socket("127.0.0.1:7000".parse().unwrap()));
+        let session = shared.session(1).unwrap();
+        assert_eq!(session.session_id(), 1);
+        assert!(shared.session(1).is_err());
+        assert_eq!(shared.num_sessions(), 1);
+
+        // the id can be used again once its socket is dropped
+        drop(session);
+        assert_eq!(shared.num_sessions(), 0);
+        assert!(shared.session(1).is_ok());
+    }
+}
@@ -3,37 +3,173 @@ use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
 };
 
+#[cfg(feature = "netsim")]
+use instant::{Duration, Instant};
+#[cfg(feature = "netsim")]
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
 use crate::{network::messages::Message, NonBlockingSocket};
 
 const RECV_BUFFER_SIZE: usize = 4096;
 
-/// A simple non-blocking UDP socket tu use with GGRS Sessions. Listens to 0.0.0.0 on a given port.
+/// The network conditions a [`UdpNonBlockingSocket`] simulates for its outgoing packets, see [`UdpNonBlockingSocket::set_simulation()`].
+#[cfg(feature = "netsim")]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SimConfig {
+    /// The time every packet is delayed by before it is sent.
+    pub latency: Duration,
+    /// The maximum time a packet is delayed by more or less than the latency, chosen uniformly at random for every packet.
+    /// Packets can be reordered this way, like on a real network.
+    pub jitter: Duration,
+    /// The probability for every packet to be dropped, between 0 and 1.
+    pub loss: f64,
+}
+
+/// The packets held back by the network simulation, and the random numbers deciding their fate.
+#[cfg(feature = "netsim")]
+#[derive(Debug)]
+struct Simulation {
+    config: SimConfig,
+    rng: StdRng,
+    delayed: Vec<(Instant, SocketAddr, Vec<u8>)>,
+}
+
+/// A simple non-blocking UDP socket tu use with GGRS Sessions. Listens to 0.0.0.0 on a given port, or to any given IPv4 or IPv6 address.
+///
+/// A socket bound to an IPv6 address only talks to IPv6 peers, unless it is bound to the unspecified address `[::]` and the OS treats it as dual-stack.
+/// Whether it does depends on the OS defaults for `IPV6_V6ONLY`: Linux and macOS accept IPv4 peers as IPv4-mapped IPv6 addresses by default
+/// (on Linux, unless `net.ipv6.bindv6only` is set), while Windows only accepts IPv6 peers. In that case, the addresses of IPv4 peers appear as
+/// `[::ffff:a.b.c.d]`, so register remote players with the same form of address the socket receives from.
 #[derive(Debug)]
 pub struct UdpNonBlockingSocket {
     socket: UdpSocket,
     buffer: [u8; RECV_BUFFER_SIZE],
+    #[cfg(feature = "netsim")]
+    simulation: Option<Simulation>,
+    /// The seed of the network simulation, set with [`NonBlockingSocket::set_rng_seed()`].
+    #[cfg(feature = "netsim")]
+    rng_seed: Option<u64>,
 }
 
 impl UdpNonBlockingSocket {
     /// Binds an UDP Socket to 0.0.0.0:port and set it to non-blocking mode.
     pub fn bind_to_port(port: u16) -> Result<Self, std::io::Error> {
-        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port);
+        Self::bind_to_socket_addr(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port))
+    }
+
+    /// Binds an UDP Socket to the given IPv4 or IPv6 address and set it to non-blocking mode.
+    pub fn bind_to_socket_addr(addr: SocketAddr) -> Result<Self, std::io::Error> {
         let socket = UdpSocket::bind(addr)?;
         socket.set_nonblocking(true)?;
         Ok(Self {
             socket,
             buffer: [0; RECV_BUFFER_SIZE],
+            #[cfg(feature = "netsim")]
+            simulation: None,
+            #[cfg(feature = "netsim")]
+            rng_seed: None,
         })
     }
+
+    /// Returns the address the socket is bound to. This is useful to find out the port after binding to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr, std::io::Error> {
+        self.socket.local_addr()
+    }
+
+    /// Simulates the given network conditions for all packets sent from now on, e.g. to playtest lag locally.
+    /// Delayed packets are sent once their time has come while sending or receiving other messages, so they are sent on the next poll of the session at the latest.
+    /// Setting [`SimConfig::default()`] turns the simulation off, after sending all delayed packets.
+    /// The random numbers of the simulation are seeded from the system, unless a seed has been set with [`NonBlockingSocket::set_rng_seed()`],
+    /// e.g. by a session built with [`SessionBuilder::with_rng_seed()`]. With a seed, the same packets are dropped and delayed in every run.
+    ///
+    /// [`SessionBuilder::with_rng_seed()`]: crate::SessionBuilder#method.with_rng_seed
+    #[cfg(feature = "netsim")]
+    pub fn set_simulation(&mut self, config: SimConfig) {
+        self.send_delayed(true);
+        let rng = self
+            .rng_seed
+            .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+        self.simulation = (config != SimConfig::default()).then(|| Simulation {
+            config,
+            rng,
+            delayed: Vec::new(),
+        });
+    }
+
+    /// Sends all delayed packets whose time has come, or all of them if `all` is true.
+    #[cfg(feature = "netsim")]
+    fn send_delayed(&mut self, all: bool) {
+        let simulation = match self.simulation.as_mut() {
+            Some(simulation) => simulation,
+            None => return,
+        };
+        let now = Instant::now();
+        // the packets are sent in the order of their send time, so jitter can reorder them
+        simulation.delayed.sort_by_key(|(send_at, _, _)| *send_at);
+        let due = simulation
+            .delayed
+            .iter()
+            .take_while(|(send_at, _, _)| all || *send_at <= now)
+            .count();
+        for (_, addr, buf) in simulation.delayed.drain(..due) {
+            self.socket.send_to(&buf, addr).unwrap();
+        }
+    }
+
+    /// Resolves a `hostname:port` string, like `"example.com:7000"`, to a socket address via DNS. If the host has several addresses, the first one is returned.
+    /// This blocks until the lookup has finished, so you should not call it during the game loop.
+    ///
+    /// # Errors
+    /// - Returns an error if the lookup fails
+    /// - Returns an error of kind [`NotFound`] if the host has no addresses.
+    ///
+    /// [`NotFound`]: std::io::ErrorKind::NotFound
+    #[cfg(feature = "dns")]
+    pub fn resolve(host: &str) -> Result<SocketAddr, std::io::Error> {
+        use std::net::ToSocketAddrs;
+
+        host.to_socket_addrs()?
+            .next()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, "The host has no addresses."))
+    }
 }
 
 impl NonBlockingSocket<SocketAddr> for UdpNonBlockingSocket {
     fn send_to(&mut self, msg: &Message, addr: &SocketAddr) {
         let buf = bincode::serialize(&msg).unwrap();
+        #[cfg(feature = "netsim")]
+        if let Some(simulation) = self.simulation.as_mut() {
+            let config = simulation.config;
+            if config.loss > 0.0 && simulation.rng.gen_bool(config.loss.clamp(0.0, 1.0)) {
+                return;
+            }
+            let jitter = config.jitter.as_secs_f64();
+            let offset = if jitter > 0.0 {
+                simulation.rng.gen_range(-jitter..=jitter)
+            } else {
+                0.0
+            };
+            let delay = Duration::from_secs_f64((config.latency.as_secs_f64() + offset).max(0.0));
+            simulation
+                .delayed
+                .push((Instant::now() + delay, *addr, buf));
+            self.send_delayed(false);
+            return;
+        }
         self.socket.send_to(&buf, addr).unwrap();
     }
 
+    #[cfg(feature = "netsim")]
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.rng_seed = Some(seed);
+        if let Some(simulation) = self.simulation.as_mut() {
+            simulation.rng = StdRng::seed_from_u64(seed);
+        }
+    }
+
     fn receive_all_messages(&mut self) -> Vec<(SocketAddr, Message)> {
+        #[cfg(feature = "netsim")]
+        self.send_delayed(false);
         let mut received_messages = Vec::new();
         loop {
             match self.socket.recv_from(&mut self.buffer) {
@@ -53,3 +189,122 @@ impl NonBlockingSocket<SocketAddr> for UdpNonBlockingSocket {
         }
     }
 }
+
+// #########
+// # TESTS #
+// #########
+
+#[cfg(test)]
+mod udp_socket_tests {
+    use super::*;
+    use crate::network::messages::{MessageBody, MessageHeader};
+    use std::net::Ipv6Addr;
+
+    #[test]
+    fn test_ipv6_round_trip() {
+        let loopback = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 0);
+        let mut socket1 = UdpNonBlockingSocket::bind_to_socket_addr(loopback).unwrap();
+        let mut socket2 = UdpNonBlockingSocket::bind_to_socket_addr(loopback).unwrap();
+        let addr1 = socket1.local_addr().unwrap();
+        let addr2 = socket2.local_addr().unwrap();
+
+        let msg = Message {
+            header: MessageHeader {
+                magic: 1,
+                sequence: 0,
+            },
+            body: MessageBody::KeepAlive,
+        };
+        socket1.send_to(&msg, &addr2);
+
+        // the socket is non-blocking, so give the message some time to arrive
+        let mut received = Vec::new();
+        for _ in 0..100 {
+            received = socket2.receive_all_messages();
+            if !received.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(received, vec![(addr1, msg)]);
+    }
+
+    #[cfg(feature = "netsim")]
+    #[test]
+    fn test_simulation() {
+        let loopback = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        let mut socket1 = UdpNonBlockingSocket::bind_to_socket_addr(loopback).unwrap();
+        let mut socket2 = UdpNonBlockingSocket::bind_to_socket_addr(loopback).unwrap();
+        let addr2 = socket2.local_addr().unwrap();
+        let msg = Message {
+            header: MessageHeader {
+                magic: 1,
+                sequence: 0,
+            },
+            body: MessageBody::KeepAlive,
+        };
+
+        // all packets are lost
+        socket1.set_simulation(SimConfig {
+            loss: 1.0,
+            ..SimConfig::default()
+        });
+        socket1.send_to(&msg, &addr2);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(socket2.receive_all_messages().is_empty());
+
+        // the packet is held back until the latency has passed
+        socket1.set_simulation(SimConfig {
+            latency: Duration::from_millis(50),
+            ..SimConfig::default()
+        });
+        socket1.send_to(&msg, &addr2);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        socket1.receive_all_messages();
+        assert!(socket2.receive_all_messages().is_empty());
+
+        let mut received = Vec::new();
+        for _ in 0..100 {
+            socket1.receive_all_messages();
+            received = socket2.receive_all_messages();
+            if !received.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(received.len(), 1);
+    }
+
+    #[cfg(feature = "netsim")]
+    #[test]
+    fn test_simulation_seed() {
+        let loopback = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        let mut socket1 = UdpNonBlockingSocket::bind_to_socket_addr(loopback).unwrap();
+        let mut socket2 = UdpNonBlockingSocket::bind_to_socket_addr(loopback).unwrap();
+        let config = SimConfig {
+            loss: 0.5,
+            ..SimConfig::default()
+        };
+
+        // the seed applies to simulations started before and after setting it
+        socket1.set_rng_seed(7);
+        socket1.set_simulation(config);
+        socket2.set_simulation(config);
+        socket2.set_rng_seed(7);
+
+        let rng1 = &mut socket1.simulation.as_mut().unwrap().rng;
+        let rng2 = &mut socket2.simulation.as_mut().unwrap().rng;
+        for _ in 0..10 {
+            assert_eq!(rng1.gen::<u64>(), rng2.gen::<u64>());
+        }
+    }
+
+    #[cfg(feature = "dns")]
+    #[test]
+    fn test_resolve() {
+        let addr = UdpNonBlockingSocket::resolve("localhost:7000").unwrap();
+        assert!(addr.ip().is_loopback());
+        assert_eq!(addr.port(), 7000);
+        assert!(UdpNonBlockingSocket::resolve("localhost").is_err()); // no port
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
+
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{MessageEvent, RtcDataChannel, RtcDataChannelState, RtcDataChannelType};
+
+use crate::{network::messages::Message, NonBlockingSocket};
+
+/// The largest message that can be sent reliably through a data channel across all browsers.
+/// Larger messages might be split up or dropped, so GGRS refuses to send them.
+pub const MAX_DATA_CHANNEL_MESSAGE_SIZE: usize = 16 * 1024;
+// The amount of messages we buffer for a data channel that is not yet open
+const PENDING_MESSAGES_SIZE: usize = 128;
+
+/// Identifies a remote peer connected through a WebRTC data channel. How these ids are assigned is up to the user,
+/// but all players need to agree on them, e.g. by taking them from the signalling server.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PeerId(pub u32);
+
+struct PeerChannel {
+    channel: RtcDataChannel,
+    pending: VecDeque<Vec<u8>>,
+    // the callback must stay alive as long as the channel is in use
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl PeerChannel {
+    fn flush_pending(&mut self) {
+        if self.channel.ready_state() != RtcDataChannelState::Open {
+            return;
+        }
+        for buf in self.pending.drain(..) {
+            // a failed send is treated like a lost packet
+            let _ = self.channel.send_with_u8_array(&buf);
+        }
+    }
+}
+
+/// A non-blocking socket for use with GGRS Sessions in the browser. It sends and receives messages through WebRTC data channels.
+/// Establishing the peer connections (signalling) is up to the user, who registers one data channel per remote peer via [`add_peer()`].
+///
+/// To get the UDP-like behavior GGRS expects, the data channels should be created as unordered and unreliable
+/// (`ordered: false` and `maxRetransmits: 0`). Data channels deliver each message as a whole, so a message is either received completely or not at all.
+/// Sent messages that are larger than [`MAX_DATA_CHANNEL_MESSAGE_SIZE`] and received messages that are larger or cannot be deserialized are dropped.
+/// Messages sent before the data channel is open are queued and sent as soon as the channel opens.
+///
+/// [`add_peer()`]: Self#method.add_peer
+pub struct WebRtcNonBlockingSocket {
+    channels: HashMap<PeerId, PeerChannel>,
+    received: Rc<RefCell<Vec<(PeerId, Message)>>>,
+}
+
+impl Default for WebRtcNonBlockingSocket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebRtcNonBlockingSocket {
+    /// Creates a new socket without any registered peers.
+    pub fn new() -> Self {
+        Self {
+            channels: HashMap::new(),
+            received: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Registers the data channel to communicate with the given peer. If there already is a data channel for that peer, it will be replaced.
+    pub fn add_peer(&mut self, peer: PeerId, channel: RtcDataChannel) {
+        channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+
+        let received = self.received.clone();
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            let bytes = js_sys::Uint8Array::new(&event.data()).to_vec();
+            if bytes.len() > MAX_DATA_CHANNEL_MESSAGE_SIZE {
+                return;
+            }
+            if let Ok(msg) = bincode::deserialize(&bytes) {
+                received.borrow_mut().push((peer, msg));
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        let old = self.channels.insert(
+            peer,
+            PeerChannel {
+                channel,
+                pending: VecDeque::new(),
+                _on_message: on_message,
+            },
+        );
+        if let Some(old) = old {
+            old.channel.set_onmessage(None);
+        }
+    }
+
+    /// Removes the data channel of the given peer. Messages sent to that peer afterwards are dropped.
+    pub fn remove_peer(&mut self, peer: PeerId) {
+        if let Some(old) = self.channels.remove(&peer) {
+            old.channel.set_onmessage(None);
+        }
+    }
+}
+
+impl NonBlockingSocket<PeerId> for WebRtcNonBlockingSocket {
+    fn send_to(&mut self, msg: &Message, addr: &PeerId) {
+        let buf = bincode::serialize(&msg).unwrap();
+        // the data channel would refuse the message, so it is lost like a dropped packet
+        if buf.len() > MAX_DATA_CHANNEL_MESSAGE_SIZE {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(size = buf.len(), "dropping oversized message");
+            return;
+        }
+
+        // messages to unknown peers are lost
+        let peer = match self.channels.get_mut(addr) {
+            Some(peer) => peer,
+            None => return,
+        };
+
+        match peer.channel.ready_state() {
+            RtcDataChannelState::Connecting => {
+                peer.pending.push_back(buf);
+                while peer.pending.len() > PENDING_MESSAGES_SIZE {
+                    peer.pending.pop_front();
+                }
+            }
+            RtcDataChannelState::Open => {
+                peer.flush_pending();
+                // a failed send is treated like a lost packet
+                let _ = peer.channel.send_with_u8_array(&buf);
+            }
+            // the channel is closing or closed, so the message is lost
+            _ => (),
+        }
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(PeerId, Message)> {
+        // channels might have opened since the last time we sent something
+        for peer in self.channels.values_mut() {
+            peer.flush_pending();
+        }
+        std::mem::take(&mut *self.received.borrow_mut())
+    }
+}
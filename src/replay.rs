@@ -0,0 +1,329 @@
+use core::marker::PhantomData;
+use std::io::{self, Read, Write};
+
+use crate::frame_info::PlayerInput;
+use crate::{Config, Frame, InputStatus, NULL_FRAME};
+
+// a replay starts with these bytes, followed by the format version
+const REPLAY_MAGIC: &[u8; 8] = b"GGRSREPL";
+const REPLAY_VERSION: u8 = 3;
+// per-player status bytes of a frame record
+const STATUS_CONFIRMED: u8 = 0;
+const STATUS_DISCONNECTED: u8 = 1;
+
+/// The writer a replay is recorded to.
+#[cfg(feature = "sync-send")]
+pub(crate) type ReplayWrite = Box<dyn Write + Send + Sync>;
+/// The writer a replay is recorded to.
+#[cfg(not(feature = "sync-send"))]
+pub(crate) type ReplayWrite = Box<dyn Write>;
+
+/// Writes confirmed inputs to a replay stream. The stream consists of a header (magic bytes, version, number of players, input size and start frame),
+/// followed by one record per frame. Each record holds the frame number and a status byte, a flags byte and the input bytes for every player.
+/// Frames are always written in order and without gaps, starting at the start frame of the session.
+pub(crate) struct ReplayWriter {
+    writer: ReplayWrite,
+    num_players: usize,
+    start_frame: Frame,
+    next_frame: Frame,
+}
+
+impl ReplayWriter {
+    pub(crate) fn new(writer: ReplayWrite, num_players: usize, start_frame: Frame) -> Self {
+        Self {
+            writer,
+            num_players,
+            start_frame,
+            next_frame: start_frame,
+        }
+    }
+
+    /// The next frame the writer expects to receive inputs for.
+    pub(crate) fn next_frame(&self) -> Frame {
+        self.next_frame
+    }
+
+    /// Writes the confirmed inputs of all players for the next frame. Inputs with [`NULL_FRAME`] belong to disconnected players.
+    pub(crate) fn write_frame<I>(&mut self, inputs: &[PlayerInput<I>]) -> io::Result<()>
+    where
+        I: Copy + Clone + PartialEq + bytemuck::Pod + bytemuck::Zeroable,
+    {
+        assert_eq!(inputs.len(), self.num_players);
+
+        let input_size = core::mem::size_of::<I>();
+        let mut buf = Vec::with_capacity(4 + self.num_players * (2 + input_size));
+        if self.next_frame == self.start_frame {
+            buf.extend_from_slice(REPLAY_MAGIC);
+            buf.push(REPLAY_VERSION);
+            buf.extend_from_slice(&(self.num_players as u32).to_le_bytes());
+            buf.extend_from_slice(&(input_size as u32).to_le_bytes());
+            buf.extend_from_slice(&self.start_frame.to_le_bytes());
+        }
+
+        buf.extend_from_slice(&self.next_frame.to_le_bytes());
+        for input in inputs {
+            if input.frame == NULL_FRAME {
+                buf.push(STATUS_DISCONNECTED);
+                buf.push(0);
+                buf.extend_from_slice(bytemuck::bytes_of(&I::zeroed()));
+            } else {
+                assert_eq!(input.frame, self.next_frame);
+                buf.push(STATUS_CONFIRMED);
+                buf.push(input.flags);
+                buf.extend_from_slice(bytemuck::bytes_of(&input.input));
+            }
+        }
+
+        self.writer.write_all(&buf)?;
+        self.next_frame += 1;
+        Ok(())
+    }
+}
+
+/// A [`ReplayReader`] reads back the confirmed inputs recorded with [`P2PSession::set_replay_writer()`], one frame at a time.
+///
+/// [`P2PSession::set_replay_writer()`]: crate::P2PSession#method.set_replay_writer
+pub struct ReplayReader<T, R>
+where
+    T: Config,
+    R: Read,
+{
+    reader: R,
+    num_players: usize,
+    start_frame: Frame,
+    next_frame: Frame,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Config, R: Read> ReplayReader<T, R> {
+    /// Creates a new [`ReplayReader`] and reads the header of the replay.
+    ///
+    /// # Errors
+    /// - Returns an error of kind [`InvalidData`] if the stream does not start with a replay header or the recorded input size does not match `T::Input`.
+    /// - Returns any error the underlying reader returns.
+    ///
+    /// [`InvalidData`]: std::io::ErrorKind::InvalidData
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        let mut version = [0; 1];
+        reader.read_exact(&mut version)?;
+        if &magic != REPLAY_MAGIC || version[0] != REPLAY_VERSION {
+            return Err(invalid_data(
+                "The stream is not a replay of a supported version.",
+            ));
+        }
+
+        let num_players = read_u32(&mut reader)? as usize;
+        let input_size = read_u32(&mut reader)? as usize;
+        if input_size != core::mem::size_of::<T::Input>() {
+            return Err(invalid_data(
+                "The recorded input size does not match the size of the input type.",
+            ));
+        }
+        let start_frame = read_u32(&mut reader)? as Frame;
+        if start_frame < 0 {
+            return Err(invalid_data("The recorded start frame is negative."));
+        }
+
+        Ok(Self {
+            reader,
+            num_players,
+            start_frame,
+            next_frame: start_frame,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the number of players recorded in the replay.
+    pub fn num_players(&self) -> usize {
+        self.num_players
+    }
+
+    /// Returns the first recorded frame, which is the start frame of the recorded session.
+    pub fn start_frame(&self) -> Frame {
+        self.start_frame
+    }
+
+    /// Returns the frame number and the inputs of all players for the next recorded frame, or `None` if the replay has ended.
+    /// The status of each input is either [`InputStatus::ConfirmedRemote`] or [`InputStatus::Disconnected`].
+    ///
+    /// # Errors
+    /// - Returns an error of kind [`InvalidData`] if the record is malformed or out of order.
+    /// - Returns an error of kind [`UnexpectedEof`] if the replay ends in the middle of a record.
+    ///
+    /// [`InvalidData`]: std::io::ErrorKind::InvalidData
+    /// [`UnexpectedEof`]: std::io::ErrorKind::UnexpectedEof
+    #[allow(clippy::type_complexity)]
+    pub fn next_frame(&mut self) -> io::Result<Option<(Frame, Vec<(T::Input, InputStatus, u8)>)>> {
+        // a clean end of the stream is only allowed between two records
+        let mut frame_bytes = [0; 4];
+        let mut read = 0;
+        while read < frame_bytes.len() {
+            match self.reader.read(&mut frame_bytes[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let frame = Frame::from_le_bytes(frame_bytes);
+        if frame != self.next_frame {
+            return Err(invalid_data("The replay contains frames out of order."));
+        }
+
+        let mut inputs = Vec::with_capacity(self.num_players);
+        let mut input_bytes = vec![0; core::mem::size_of::<T::Input>()];
+        for _ in 0..self.num_players {
+            let mut status_and_flags = [0; 2];
+            self.reader.read_exact(&mut status_and_flags)?;
+            self.reader.read_exact(&mut input_bytes)?;
+            let input = bytemuck::pod_read_unaligned::<T::Input>(&input_bytes);
+            let [status, flags] = status_and_flags;
+            match status {
+                STATUS_CONFIRMED => inputs.push((input, InputStatus::ConfirmedRemote, flags)),
+                STATUS_DISCONNECTED => inputs.push((input, InputStatus::Disconnected, flags)),
+                _ => return Err(invalid_data("The replay contains an unknown input status.")),
+            }
+        }
+
+        self.next_frame += 1;
+        Ok(Some((frame, inputs)))
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// #########
+// # TESTS #
+// #########
+
+#[cfg(test)]
+mod replay_tests {
+    use super::*;
+    use alloc::sync::Arc;
+    use bytemuck::{Pod, Zeroable};
+    use parking_lot::Mutex;
+    use std::io::Cursor;
+    use std::net::SocketAddr;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, PartialEq, Debug, Pod, Zeroable)]
+    struct TestInput {
+        inp: u16,
+    }
+
+    struct TestConfig;
+
+    impl Config for TestConfig {
+        type Input = TestInput;
+        type State = u8;
+        type Address = SocketAddr;
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(frames: Frame) -> Vec<u8> {
+        record_from(0, frames)
+    }
+
+    fn record_from(start_frame: Frame, frames: Frame) -> Vec<u8> {
+        let buffer = SharedBuffer::default();
+        let mut writer = ReplayWriter::new(Box::new(buffer.clone()), 2, start_frame);
+        for i in start_frame..frames {
+            let inputs = [
+                PlayerInput::new(i, TestInput { inp: i as u16 }).with_flags(i as u8),
+                // the second player disconnects after frame 1
+                if i < 2 {
+                    PlayerInput::new(i, TestInput { inp: 100 })
+                } else {
+                    PlayerInput::blank_input(NULL_FRAME)
+                },
+            ];
+            writer.write_frame(&inputs).unwrap();
+        }
+        assert_eq!(writer.next_frame(), frames);
+        let bytes = buffer.0.lock().clone();
+        bytes
+    }
+
+    #[test]
+    fn test_write_read() {
+        let bytes = record(4);
+        let mut reader = ReplayReader::<TestConfig, _>::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.num_players(), 2);
+
+        for i in 0..4 {
+            let (frame, inputs) = reader.next_frame().unwrap().unwrap();
+            assert_eq!(frame, i);
+            assert_eq!(
+                inputs[0],
+                (
+                    TestInput { inp: i as u16 },
+                    InputStatus::ConfirmedRemote,
+                    i as u8
+                )
+            );
+            if i < 2 {
+                assert_eq!(
+                    inputs[1],
+                    (TestInput { inp: 100 }, InputStatus::ConfirmedRemote, 0)
+                );
+            } else {
+                assert_eq!(
+                    inputs[1],
+                    (TestInput::zeroed(), InputStatus::Disconnected, 0)
+                );
+            }
+        }
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_write_read_start_frame() {
+        let bytes = record_from(3, 5);
+        let mut reader = ReplayReader::<TestConfig, _>::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.start_frame(), 3);
+        assert_eq!(reader.next_frame().unwrap().unwrap().0, 3);
+        assert_eq!(reader.next_frame().unwrap().unwrap().0, 4);
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_truncated() {
+        let mut bytes = record(2);
+        bytes.pop();
+        let mut reader = ReplayReader::<TestConfig, _>::new(Cursor::new(bytes)).unwrap();
+        assert!(reader.next_frame().unwrap().is_some());
+        let err = reader.next_frame().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_read_invalid_header() {
+        let mut bytes = record(1);
+        bytes[0] = b'X';
+        assert!(ReplayReader::<TestConfig, _>::new(Cursor::new(bytes)).is_err());
+    }
+}
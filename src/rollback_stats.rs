@@ -0,0 +1,32 @@
+/// The `RollbackStats` struct contains statistics about the rollbacks a session has performed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RollbackStats {
+    /// The total number of rollbacks performed.
+    pub total_rollbacks: u64,
+    /// The total number of frames resimulated during all rollbacks.
+    pub total_frames_resimulated: u64,
+    /// The largest number of frames resimulated in a single rollback.
+    pub max_rollback_depth: u32,
+}
+
+impl RollbackStats {
+    /// Creates a new `RollbackStats` instance with default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the average number of frames resimulated per rollback, or 0 if no rollback has happened yet.
+    pub fn average_rollback_depth(&self) -> f64 {
+        if self.total_rollbacks == 0 {
+            return 0.0;
+        }
+        self.total_frames_resimulated as f64 / self.total_rollbacks as f64
+    }
+
+    pub(crate) fn add_rollback(&mut self, depth: u32) {
+        self.total_rollbacks += 1;
+        self.total_frames_resimulated += depth as u64;
+        self.max_rollback_depth = core::cmp::max(self.max_rollback_depth, depth);
+    }
+}
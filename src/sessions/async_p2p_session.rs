@@ -0,0 +1,124 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+use crate::Mutex;
+
+use crate::network::messages::Message;
+use crate::{AsyncNonBlockingSocket, Config, NonBlockingSocket, P2PSession};
+
+/// The messages exchanged between an [`AsyncP2PSession`] and the synchronous [`P2PSession`] it wraps.
+pub(crate) struct Buffers<T>
+where
+    T: Config,
+{
+    /// Messages received from the async socket that the session has not received yet.
+    inbox: Vec<(T::Address, Message)>,
+    /// Messages sent by the session that have not been sent through the async socket yet.
+    outbox: VecDeque<(Message, T::Address)>,
+}
+
+/// The socket of the wrapped [`P2PSession`], which only moves messages from and to the [`Buffers`].
+pub(crate) struct BufferedSocket<T>
+where
+    T: Config,
+{
+    buffers: Arc<Mutex<Buffers<T>>>,
+}
+
+impl<T: Config> BufferedSocket<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            buffers: Arc::new(Mutex::new(Buffers {
+                inbox: Vec::new(),
+                outbox: VecDeque::new(),
+            })),
+        }
+    }
+
+    pub(crate) fn buffers(&self) -> Arc<Mutex<Buffers<T>>> {
+        self.buffers.clone()
+    }
+}
+
+impl<T: Config> NonBlockingSocket<T::Address> for BufferedSocket<T> {
+    fn send_to(&mut self, msg: &Message, addr: &T::Address) {
+        self.buffers
+            .lock()
+            .outbox
+            .push_back((msg.clone(), addr.clone()));
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(T::Address, Message)> {
+        core::mem::take(&mut self.buffers.lock().inbox)
+    }
+}
+
+/// An [`AsyncP2PSession`] wraps a [`P2PSession`] to send and receive its messages through an [`AsyncNonBlockingSocket`], e.g. to run GGRS
+/// on an async server without spawning blocking threads. Only the socket IO is awaitable: Use [`poll_remote_clients_async()`] instead of
+/// [`P2PSession::poll_remote_clients()`] and access everything else, like advancing frames, through [`session_mut()`].
+///
+/// Messages the session sends while advancing a frame, most notably the local inputs, go out with the next call to [`poll_remote_clients_async()`],
+/// so you should poll at least once per frame.
+///
+/// [`poll_remote_clients_async()`]: Self#method.poll_remote_clients_async
+/// [`P2PSession::poll_remote_clients()`]: P2PSession#method.poll_remote_clients
+/// [`session_mut()`]: Self#method.session_mut
+pub struct AsyncP2PSession<T, S>
+where
+    T: Config,
+    S: AsyncNonBlockingSocket<T::Address>,
+{
+    session: P2PSession<T>,
+    socket: S,
+    buffers: Arc<Mutex<Buffers<T>>>,
+}
+
+impl<T, S> AsyncP2PSession<T, S>
+where
+    T: Config,
+    S: AsyncNonBlockingSocket<T::Address>,
+{
+    /// Wraps a session that has been built with a [`BufferedSocket`] with the given buffers.
+    pub(crate) fn new(session: P2PSession<T>, buffers: Arc<Mutex<Buffers<T>>>, socket: S) -> Self {
+        Self {
+            session,
+            socket,
+            buffers,
+        }
+    }
+
+    /// Sends all pending messages, receives all messages from the async socket and polls the session with them, see [`P2PSession::poll_remote_clients()`].
+    /// The messages sent by the session while polling are sent right away.
+    ///
+    /// [`P2PSession::poll_remote_clients()`]: P2PSession#method.poll_remote_clients
+    pub async fn poll_remote_clients_async(&mut self) {
+        self.send_pending_messages().await;
+        let received = self.socket.receive_all_messages().await;
+        self.buffers.lock().inbox.extend(received);
+        self.session.poll_remote_clients();
+        self.send_pending_messages().await;
+    }
+
+    /// Returns the wrapped session.
+    pub fn session(&self) -> &P2PSession<T> {
+        &self.session
+    }
+
+    /// Returns the wrapped session, e.g. to add local inputs and advance frames.
+    pub fn session_mut(&mut self) -> &mut P2PSession<T> {
+        &mut self.session
+    }
+
+    /// Returns the async socket.
+    pub fn socket(&self) -> &S {
+        &self.socket
+    }
+
+    async fn send_pending_messages(&mut self) {
+        // the lock is not held while awaiting
+        let outbox = core::mem::take(&mut self.buffers.lock().outbox);
+        for (msg, addr) in outbox {
+            self.socket.send_to(&msg, &addr).await;
+        }
+    }
+}
@@ -1,13 +1,36 @@
-use std::collections::HashMap;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::{borrow::ToOwned, boxed::Box, vec::Vec};
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::io::Read;
 
-use instant::Duration;
+use crate::Mutex;
+use core::time::Duration;
 
 use crate::{
-    network::protocol::UdpProtocol, sessions::p2p_session::PlayerRegistry, Config, GGRSError,
-    NonBlockingSocket, P2PSession, PlayerHandle, PlayerType, SpectatorSession, SyncTestSession,
+    network::protocol::{
+        EndpointSettings, UdpProtocol, MAX_PAYLOAD, NUM_SYNC_PACKETS, SYNC_RETRY_INTERVAL,
+    },
+    sessions::p2p_session::{P2PSettings, PlayerRegistry},
+    BuilderError, ChecksumKind, Config, DisconnectHook, Frame, GGRSError, GGRSEvent, InputCodec,
+    NonBlockingSocket, P2PSession, PlayerHandle, PlayerType, PredictionStrategy, RepeatLast,
+    RleInputCodec, RuntimeState, SessionConfigSummary, SharedSocket, SpectatorSession, StateStore,
+    SyncTestSession, TimeSource,
 };
+#[cfg(feature = "std")]
+use crate::{MessageCrypto, ReplayReader, ReplaySession};
 
-use super::p2p_spectator_session::SPECTATOR_BUFFER_SIZE;
+#[cfg(feature = "async")]
+use super::async_p2p_session::BufferedSocket;
+use super::p2p_spectator_session::{SnapshotDecoder, SPECTATOR_BUFFER_SIZE};
+use super::sync_test_session::StateSerializer;
+#[cfg(feature = "std")]
+use crate::network::crypto_socket::CryptoSocket;
+use crate::network::shared_socket::ROUTING_OVERHEAD;
+use crate::sync_layer::{DeltaApplier, SharedStateStore};
+#[cfg(feature = "std")]
+use crate::StdTimeSource;
 
 const DEFAULT_PLAYERS: usize = 2;
 const DEFAULT_SAVE_MODE: bool = false;
@@ -24,6 +47,22 @@ const DEFAULT_CATCHUP_SPEED: usize = 1;
 // The amount of events a spectator can buffer; should never be an issue if the user polls the events at every step
 pub(crate) const MAX_EVENT_QUEUE_SIZE: usize = 100;
 
+/// Discards the oldest events while there are more than `MAX_EVENT_QUEUE_SIZE`. Messages of the reliable stream are never discarded,
+/// since they are not sent again.
+pub(crate) fn discard_old_events<T: Config>(events: &mut VecDeque<GGRSEvent<T>>) {
+    while events.len() > MAX_EVENT_QUEUE_SIZE {
+        match events
+            .iter()
+            .position(|event| !matches!(event, GGRSEvent::Message { .. }))
+        {
+            Some(index) => {
+                events.remove(index);
+            }
+            None => break,
+        }
+    }
+}
+
 /// The [`SessionBuilder`] builds all GGRS Sessions. After setting all appropriate values, use `SessionBuilder::start_yxz_session(...)`
 /// to consume the builder and create a Session of desired type.
 pub struct SessionBuilder<T>
@@ -33,18 +72,94 @@ where
     num_players: usize,
     local_players: usize,
     max_prediction: usize,
+    /// If set, at most this many saved states are kept, instead of one for every frame of the prediction window.
+    max_saved_states: Option<usize>,
     /// FPS defines the expected update frequency of this session.
     fps: usize,
     sparse_saving: bool,
+    state_saved_events: bool,
+    time_sync: bool,
     /// The time until a remote player gets disconnected.
     disconnect_timeout: Duration,
     /// The time until the client will get a notification that a remote player is about to be disconnected.
     disconnect_notify_start: Duration,
+    /// The number of sync roundtrips with every remote client before the session starts.
+    sync_packets: u32,
+    /// The time after which unanswered sync requests are resent.
+    sync_interval: Duration,
+    /// The maximum number of bytes of snapshot chunks and reliable messages.
+    max_payload: usize,
     player_reg: PlayerRegistry<T>,
+    /// Player slots that are left empty until a player joins the running session.
+    reserved_players: Vec<PlayerHandle>,
+    /// Handles that have been added again after they were already in use.
+    duplicate_handles: Vec<PlayerHandle>,
+    /// The frame a [`P2PSession`] starts on, for clients joining or rejoining a running session.
+    start_frame: Frame,
     input_delay: usize,
     check_dist: usize,
     max_frames_behind: usize,
     catchup_speed: usize,
+    /// The codec used to compress inputs sent to remote players and spectators.
+    input_codec: Arc<dyn InputCodec>,
+    /// Decides which inputs are assumed for remote players while their inputs have not arrived yet.
+    prediction_strategy: Arc<dyn PredictionStrategy<T::Input>>,
+    /// The algorithm used to compute checksums of saved states if the user does not provide them.
+    checksum_kind: Option<ChecksumKind>,
+    /// Provides the current time for all timers of the session.
+    /// Without the `std` feature, there is no default time source, so it has to be provided.
+    time_source: Option<Arc<dyn TimeSource>>,
+    /// Checksums of a previous run that a [`SyncTestSession`] validates against.
+    recorded_checksums: Option<Vec<(Frame, Option<u128>)>>,
+    /// If set, all messages are sealed before sending and opened after receiving.
+    #[cfg(feature = "std")]
+    message_crypto: Option<Arc<dyn MessageCrypto>>,
+    /// If set, creates the states that all saved state cells are filled with in advance.
+    state_init: Option<fn() -> T::State>,
+    /// If set, all saved states are moved into this store instead of being kept in the cells.
+    state_store: Option<SharedStateStore<T::State>>,
+    /// If set, states can be saved as deltas, which are reconstructed with this function.
+    delta_applier: Option<DeltaApplier<T::State>>,
+    /// If set, the checksum over all confirmed inputs is compared with remote clients every this many frames.
+    input_desync_interval: Option<u32>,
+    /// If set, the host shares the checksum of its confirmed state with its spectators every this many frames, and spectators compare it with their own.
+    spectator_checksum_interval: Option<u32>,
+    /// If true, a [`SyncTestSession`] reports the first divergent frame and requires a checksum for every saved state.
+    determinism_checks: bool,
+    /// If set, a [`SyncTestSession`] also compares the states serialized with this function byte by byte.
+    state_serializer: Option<StateSerializer<T::State>>,
+    /// If true, a [`P2PSession`] sends a snapshot of the game state to spectators joining late.
+    spectator_snapshots: bool,
+    /// Deserializes the snapshot a [`SpectatorSession`] joining late receives from the host.
+    snapshot_decoder: Option<SnapshotDecoder<T::State>>,
+    /// If set, a [`P2PSession`] refuses to run more than this many frames ahead of the confirmed frame.
+    max_frame_lag: Option<usize>,
+    /// If set, the codec and number of batched frames used for the inputs sent to spectators.
+    spectator_compression: Option<(Arc<dyn InputCodec>, usize)>,
+    /// If set, all random numbers of the session are generated from this seed.
+    rng_seed: Option<u64>,
+    /// If set, creates the payload attached to every quality report sent to remote clients.
+    quality_report_payload: Option<fn() -> Vec<u8>>,
+    /// Sessions only synchronize with remote clients that have the same app id.
+    app_id: u64,
+    /// If set, sessions only synchronize with remote clients that have the same shared seed.
+    shared_seed: Option<u64>,
+    /// If set, the number of past frames each input packet re-includes.
+    input_redundancy: Option<usize>,
+    /// If true, a [`P2PSession`] recovers from desyncs by loading the state of the authoritative peer.
+    desync_recovery: bool,
+    /// The player handle whose client is the source of truth when the clients desync.
+    checksum_authority: PlayerHandle,
+    /// If set, the prediction window of a [`P2PSession`] follows the measured latency between these bounds.
+    adaptive_prediction: Option<(usize, usize)>,
+    /// Called by a [`P2PSession`] for every remote player it disconnects.
+    disconnect_hook: Option<Box<dyn DisconnectHook>>,
+    /// If set, a [`P2PSession`] only accepts inputs for which this returns true.
+    input_validator: Option<fn(&[u8]) -> bool>,
+    /// If true, a [`P2PSession`] rejects local inputs for frames that already have an input or do not follow the last one.
+    strict_input_ordering: bool,
+    /// The input sizes of players that do not use the whole input type.
+    input_sizes: HashMap<PlayerHandle, usize>,
 }
 
 impl<T: Config> Default for SessionBuilder<T> {
@@ -58,28 +173,68 @@ impl<T: Config> SessionBuilder<T> {
     pub fn new() -> Self {
         Self {
             player_reg: PlayerRegistry::new(),
+            reserved_players: Vec::new(),
+            duplicate_handles: Vec::new(),
+            start_frame: 0,
             local_players: 0,
             num_players: DEFAULT_PLAYERS,
             max_prediction: DEFAULT_MAX_PREDICTION_FRAMES,
+            max_saved_states: None,
             fps: DEFAULT_FPS,
             sparse_saving: DEFAULT_SAVE_MODE,
+            state_saved_events: false,
+            time_sync: true,
             disconnect_timeout: DEFAULT_DISCONNECT_TIMEOUT,
             disconnect_notify_start: DEFAULT_DISCONNECT_NOTIFY_START,
+            sync_packets: NUM_SYNC_PACKETS,
+            sync_interval: SYNC_RETRY_INTERVAL,
+            max_payload: MAX_PAYLOAD,
             input_delay: DEFAULT_INPUT_DELAY,
             check_dist: DEFAULT_CHECK_DISTANCE,
             max_frames_behind: DEFAULT_MAX_FRAMES_BEHIND,
             catchup_speed: DEFAULT_CATCHUP_SPEED,
+            input_codec: Arc::new(RleInputCodec),
+            prediction_strategy: Arc::new(RepeatLast),
+            checksum_kind: None,
+            time_source: default_time_source(),
+            recorded_checksums: None,
+            #[cfg(feature = "std")]
+            message_crypto: None,
+            state_init: None,
+            state_store: None,
+            delta_applier: None,
+            input_desync_interval: None,
+            spectator_checksum_interval: None,
+            determinism_checks: false,
+            state_serializer: None,
+            spectator_snapshots: false,
+            snapshot_decoder: None,
+            max_frame_lag: None,
+            spectator_compression: None,
+            rng_seed: None,
+            quality_report_payload: None,
+            app_id: 0,
+            shared_seed: None,
+            input_redundancy: None,
+            desync_recovery: false,
+            checksum_authority: 0,
+            adaptive_prediction: None,
+            disconnect_hook: None,
+            input_validator: None,
+            strict_input_ordering: false,
+            input_sizes: HashMap::new(),
         }
     }
 
     /// Must be called for each player in the session (e.g. in a 3 player session, must be called 3 times) before starting the session.
     /// Player handles for players should be between 0 and `num_players`, spectator handles should be higher than `num_players`.
     /// Later, you will need the player handle to add input, change parameters or disconnect the player or spectator.
+    /// If the handle is already in use, the player is not added. [`validate()`] reports this as [`BuilderError::DuplicateHandle`] and starting any session fails.
     ///
     /// # Errors
-    /// - Returns [`InvalidRequest`] if a player with that handle has been added before
     /// - Returns [`InvalidRequest`] if the handle is invalid for the given [`PlayerType`]
     ///
+    /// [`validate()`]: Self#method.validate
     /// [`InvalidRequest`]: GGRSError::InvalidRequest
     /// [`num_players`]: Self#structfield.num_players
     pub fn add_player(
@@ -87,11 +242,10 @@ impl<T: Config> SessionBuilder<T> {
         player_type: PlayerType<T::Address>,
         player_handle: PlayerHandle,
     ) -> Result<Self, GGRSError> {
-        // check if the player handle is already in use
-        if self.player_reg.handles.contains_key(&player_handle) {
-            return Err(GGRSError::InvalidRequest {
-                info: "Player handle already in use.".to_owned(),
-            });
+        // remember duplicate handles, so all of them can be reported at once
+        if self.handle_in_use(player_handle) {
+            self.duplicate_handles.push(player_handle);
+            return Ok(self);
         }
         // check if the player handle is valid for the given player type
         match player_type {
@@ -129,12 +283,141 @@ impl<T: Config> SessionBuilder<T> {
         Ok(self)
     }
 
-    /// Change the maximum prediction window. Default is 8.
+    /// Same as [`add_player()`], but the player only uses the first `input_size` bytes of the input type, e.g. an AI player with a 1-byte input
+    /// next to keyboard players with larger inputs. The remaining bytes of the player's inputs are always zero, so the input type has to fit the
+    /// largest player, but the unused bytes compress to almost nothing on the network. Adding a local input that uses more bytes returns
+    /// [`MismatchedInputSize`]. All clients of the session have to use the same input sizes.
+    ///
+    /// # Errors
+    /// - Returns the same errors as [`add_player()`].
+    /// - Returns [`InvalidRequest`] if the player is a spectator, or if `input_size` is 0 or larger than the input type.
+    ///
+    /// [`add_player()`]: Self#method.add_player
+    /// [`MismatchedInputSize`]: GGRSError::MismatchedInputSize
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn add_player_with_input_size(
+        self,
+        player_type: PlayerType<T::Address>,
+        player_handle: PlayerHandle,
+        input_size: usize,
+    ) -> Result<Self, GGRSError> {
+        if let PlayerType::Spectator(_) = player_type {
+            return Err(GGRSError::InvalidRequest {
+                info: "Spectators do not have inputs.".to_owned(),
+            });
+        }
+        if input_size < 1 || input_size > core::mem::size_of::<T::Input>() {
+            return Err(GGRSError::InvalidRequest {
+                info: format!(
+                    "The input size has to be between 1 and the {} bytes of the input type.",
+                    core::mem::size_of::<T::Input>()
+                ),
+            });
+        }
+        let duplicate = self.handle_in_use(player_handle);
+        let mut builder = self.add_player(player_type, player_handle)?;
+        if !duplicate {
+            builder.input_sizes.insert(player_handle, input_size);
+        }
+        Ok(builder)
+    }
+
+    /// Reserves a player slot that stays empty until a player joins the running [`P2PSession`] with [`P2PSession::add_player_at_frame()`].
+    /// Until then, the player is [`InputStatus::Disconnected`]. Reserved slots count towards `num_players`, and every client of the session has to reserve the same slots.
+    /// Like with [`add_player()`], a handle that is already in use is reported by [`validate()`] instead.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle is not between 0 and `num_players`
+    ///
+    /// [`add_player()`]: Self#method.add_player
+    /// [`validate()`]: Self#method.validate
+    /// [`P2PSession::add_player_at_frame()`]: crate::P2PSession#method.add_player_at_frame
+    /// [`InputStatus::Disconnected`]: crate::InputStatus::Disconnected
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn reserve_player(mut self, player_handle: PlayerHandle) -> Result<Self, GGRSError> {
+        if self.handle_in_use(player_handle) {
+            self.duplicate_handles.push(player_handle);
+            return Ok(self);
+        }
+        if player_handle >= self.num_players {
+            return Err(GGRSError::InvalidRequest {
+                info: "The player handle you provided is invalid. For a reserved player, the handle should be between 0 and num_players".to_owned(),
+            });
+        }
+        self.reserved_players.push(player_handle);
+        Ok(self)
+    }
+
+    /// Sets the frame a [`P2PSession`] starts on, so a client can join a running session at the frame the other clients added it on with
+    /// [`P2PSession::add_player_at_frame()`], or rejoin it at the frame they reconnected it on with [`P2PSession::reconnect_player()`].
+    /// Before advancing the first frame, load the state of that frame, which you have to receive from one of the other clients yourself.
+    /// Frames before the start frame cannot be rolled back to. Default is 0.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the frame is negative.
+    ///
+    /// [`P2PSession::add_player_at_frame()`]: crate::P2PSession#method.add_player_at_frame
+    /// [`P2PSession::reconnect_player()`]: crate::P2PSession#method.reconnect_player
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn with_start_frame(mut self, frame: Frame) -> Result<Self, GGRSError> {
+        if frame < 0 {
+            return Err(GGRSError::InvalidRequest {
+                info: "The start frame cannot be negative.".to_owned(),
+            });
+        }
+        self.start_frame = frame;
+        Ok(self)
+    }
+
+    /// Change the maximum prediction window. Default is 8. A [`P2PSession`] can lower the window at runtime, but never raise it above this value.
     pub fn with_max_prediction_window(mut self, window: usize) -> Self {
         self.max_prediction = window;
         self
     }
 
+    /// Lets the prediction window of a [`P2PSession`] follow the round-trip time and jitter measured to the remote players, clamped between `min` and `max`.
+    /// The window grows when the latency rises and shrinks when it falls, but never below the amount of frames currently predicted.
+    /// The session starts with a window of `max`, which replaces the window set with [`with_max_prediction_window()`].
+    /// The current window is returned by [`P2PSession::max_prediction()`].
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if `min` is 0 or greater than `max`.
+    ///
+    /// [`with_max_prediction_window()`]: Self#method.with_max_prediction_window
+    /// [`P2PSession::max_prediction()`]: crate::P2PSession#method.max_prediction
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn with_adaptive_prediction(mut self, min: usize, max: usize) -> Result<Self, GGRSError> {
+        if min == 0 || min > max {
+            return Err(GGRSError::InvalidRequest {
+                info: "The adaptive prediction window needs 0 < min <= max.".to_owned(),
+            });
+        }
+        self.adaptive_prediction = Some((min, max));
+        Ok(self)
+    }
+
+    /// Sets the maximum number of saved states a [`P2PSession`] or [`SyncTestSession`] keeps, independent of the prediction window.
+    /// By default, one state is kept for every frame of the prediction window. Keeping fewer states puts a hard ceiling on the memory used for saved states.
+    /// This needs [`with_sparse_saving_mode()`], where the session only needs the last saved state to roll back: Without it, starting a [`P2PSession`]
+    /// that keeps fewer states than its prediction window, or a [`SyncTestSession`] that keeps fewer states than its check distance, fails.
+    /// If a rollback still needs a state that is not kept anymore, advancing the session returns [`StateNotAvailable`], which is fatal.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the number of states is 0.
+    ///
+    /// [`with_sparse_saving_mode()`]: Self#method.with_sparse_saving_mode
+    /// [`StateNotAvailable`]: GGRSError::StateNotAvailable
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn with_max_saved_states(mut self, max_saved_states: usize) -> Result<Self, GGRSError> {
+        if max_saved_states < 1 {
+            return Err(GGRSError::InvalidRequest {
+                info: "Maximum number of saved states cannot be smaller than 1.".to_owned(),
+            });
+        }
+        self.max_saved_states = Some(max_saved_states);
+        Ok(self)
+    }
+
     /// Change the amount of frames GGRS will delay the inputs for local players.
     pub fn with_input_delay(mut self, delay: usize) -> Self {
         self.input_delay = delay;
@@ -155,6 +438,26 @@ impl<T: Config> SessionBuilder<T> {
         self
     }
 
+    /// If enabled, a [`P2PSession`] will send a [`GGRSEvent::StateSaved`] every time it requests to save a state. Default is `false`.
+    /// This is useful to verify which frames are saved with sparse saving turned on.
+    ///
+    /// [`GGRSEvent::StateSaved`]: crate::GGRSEvent::StateSaved
+    pub fn with_state_saved_events(mut self, state_saved_events: bool) -> Self {
+        self.state_saved_events = state_saved_events;
+        self
+    }
+
+    /// Enables or disables time synchronization for a [`P2PSession`]. Default is `true`.
+    /// If disabled, the session will never send a [`GGRSEvent::WaitRecommendation`], so pacing the game is up to you.
+    /// The frame advantage over remote players is still tracked and reported by [`P2PSession::frames_ahead()`].
+    ///
+    /// [`GGRSEvent::WaitRecommendation`]: crate::GGRSEvent::WaitRecommendation
+    /// [`P2PSession::frames_ahead()`]: crate::P2PSession#method.frames_ahead
+    pub fn with_time_sync(mut self, enabled: bool) -> Self {
+        self.time_sync = enabled;
+        self
+    }
+
     /// Sets the disconnect timeout. The session will automatically disconnect from a remote peer if it has not received a packet in the timeout window.
     pub fn with_disconnect_timeout(mut self, timeout: Duration) -> Self {
         self.disconnect_timeout = timeout;
@@ -167,6 +470,31 @@ impl<T: Config> SessionBuilder<T> {
         self
     }
 
+    /// Sets the number of sync roundtrips with every remote client and spectator before the session starts. Default is 5.
+    /// Fewer roundtrips synchronize faster, e.g. on a LAN, while more roundtrips give a better first estimate of the round-trip time
+    /// and make it less likely that a single fast or slow packet skews the initial frame advantage.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the count is 0.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn with_sync_packets(mut self, count: u32) -> Result<Self, GGRSError> {
+        if count == 0 {
+            return Err(GGRSError::InvalidRequest {
+                info: "At least one sync roundtrip is needed.".to_owned(),
+            });
+        }
+        self.sync_packets = count;
+        Ok(self)
+    }
+
+    /// Sets the time after which an unanswered sync request is sent again. Default is 200ms.
+    /// A shorter interval recovers faster from lost packets on lossy links, but resends requests that are merely slow if it is below the round-trip time.
+    pub fn with_sync_interval(mut self, interval: Duration) -> Self {
+        self.sync_interval = interval;
+        self
+    }
+
     /// Sets the FPS this session is used with. This influences estimations for frame synchronization between sessions.
     /// # Errors
     /// - Returns [`InvalidRequest`] if the fps is 0
@@ -182,6 +510,78 @@ impl<T: Config> SessionBuilder<T> {
         Ok(self)
     }
 
+    /// Returns an error if a handle has been added more than once, since every session would silently ignore the duplicates otherwise.
+    fn check_duplicate_handles(&self) -> Result<(), GGRSError> {
+        match self.duplicate_handles.first() {
+            Some(handle) => Err(GGRSError::InvalidRequest {
+                info: format!("Player handle {} has been added more than once.", handle),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if a player has been added or a slot has been reserved with the given handle.
+    fn handle_in_use(&self, player_handle: PlayerHandle) -> bool {
+        self.player_reg.handles.contains_key(&player_handle)
+            || self.reserved_players.contains(&player_handle)
+    }
+
+    /// Returns a summary of the settings the sessions started from this builder will use, e.g. to log it or compare it with the settings of other peers.
+    pub fn config_summary(&self) -> SessionConfigSummary {
+        SessionConfigSummary {
+            num_players: self.num_players,
+            input_delay: self.input_delay,
+            max_prediction: self.max_prediction,
+            sparse_saving: self.sparse_saving,
+            fps: self.fps,
+            disconnect_timeout: self.disconnect_timeout,
+            disconnect_notify_start: self.disconnect_notify_start,
+        }
+    }
+
+    /// Checks the registered players for setup mistakes and returns all problems found at once, so you can surface them before calling [`start_p2p_session()`].
+    /// Players must be registered with handles from 0 to `num_players - 1`, spectators with handles of `num_players` or higher, each handle only once,
+    /// and at least one player must be local.
+    ///
+    /// [`start_p2p_session()`]: Self#method.start_p2p_session
+    pub fn validate(&self) -> Result<(), Vec<BuilderError>> {
+        let mut errors = Vec::new();
+
+        for &handle in &self.duplicate_handles {
+            errors.push(BuilderError::DuplicateHandle { handle });
+        }
+
+        let mut handles: Vec<_> = self.player_reg.handles.iter().collect();
+        handles.sort_by_key(|(&handle, _)| handle);
+        for (&handle, player_type) in handles {
+            let in_range = match player_type {
+                PlayerType::Local | PlayerType::Remote(_) => handle < self.num_players,
+                PlayerType::Spectator(_) => handle >= self.num_players,
+            };
+            if !in_range {
+                errors.push(BuilderError::HandleOutOfRange { handle });
+            }
+        }
+
+        if self.player_reg.local_player_handles().is_empty() {
+            errors.push(BuilderError::NoLocalPlayer);
+        }
+
+        let registered = self.player_reg.num_players() + self.reserved_players.len();
+        if registered != self.num_players {
+            errors.push(BuilderError::PlayerCountMismatch {
+                expected: self.num_players,
+                registered,
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     /// Change the check distance. Default is 2.
     pub fn with_check_distance(mut self, check_distance: usize) -> Self {
         self.check_dist = check_distance;
@@ -227,24 +627,551 @@ impl<T: Config> SessionBuilder<T> {
         Ok(self)
     }
 
+    /// Sets the [`InputCodec`] used to compress inputs sent over the network. Default is [`RleInputCodec`].
+    /// The codec is used for both remote players and spectators, unless [`SessionBuilder::with_spectator_compression()`] is set.
+    /// All clients in a session must use the same codec.
+    ///
+    /// [`SessionBuilder::with_spectator_compression()`]: Self#method.with_spectator_compression
+    pub fn with_input_codec(mut self, codec: Box<dyn InputCodec>) -> Self {
+        self.input_codec = Arc::from(codec);
+        self
+    }
+
+    /// Sets a separate [`InputCodec`] for the inputs a [`P2PSession`] forwards to its spectators, and batches the inputs of `batch_frames` frames
+    /// into a single message. Spectators receive the inputs of all players, so they usually dominate the bandwidth of the host.
+    /// Since spectators only watch, they can tolerate the additional latency of batching: Inputs that do not fill a batch are sent at the latest
+    /// when pending inputs are resent. The [`SpectatorSession`] must be started with the same codec, and reports the received bandwidth
+    /// in [`NetworkStats::kbps_received`]. By default, spectators use the same codec as remote players and inputs are not batched.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if `batch_frames` is 0.
+    ///
+    /// [`NetworkStats::kbps_received`]: crate::NetworkStats#structfield.kbps_received
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn with_spectator_compression(
+        mut self,
+        codec: Box<dyn InputCodec>,
+        batch_frames: usize,
+    ) -> Result<Self, GGRSError> {
+        if batch_frames < 1 {
+            return Err(GGRSError::InvalidRequest {
+                info: "Spectator input batch cannot be smaller than 1 frame.".to_owned(),
+            });
+        }
+        self.spectator_compression = Some((Arc::from(codec), batch_frames));
+        Ok(self)
+    }
+
+    /// Sets the [`PredictionStrategy`] used to predict inputs of remote players that have not arrived yet. Default is [`RepeatLast`].
+    pub fn with_prediction_strategy(
+        mut self,
+        strategy: impl PredictionStrategy<T::Input> + 'static,
+    ) -> Self {
+        self.prediction_strategy = Arc::new(strategy);
+        self
+    }
+
+    /// Sets the [`ChecksumKind`] used to compute the checksum of a saved state from its bytes, if you save it through
+    /// [`GameStateCell::save_with_bytes()`] without providing a checksum yourself. By default, no checksum is computed.
+    ///
+    /// [`GameStateCell::save_with_bytes()`]: crate::GameStateCell#method.save_with_bytes
+    pub fn with_desync_checksum(mut self, kind: ChecksumKind) -> Self {
+        self.checksum_kind = Some(kind);
+        self
+    }
+
+    /// Enables input desync detection: The session keeps a rolling checksum over the confirmed inputs of all players and shares it with
+    /// the remote clients every `interval` frames. If a remote client confirmed different inputs for the same frames, the session sends a
+    /// [`GGRSEvent::DesyncDetected`]. This is independent of the game state and much cheaper than checksumming saved states. Disabled by default.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the interval is 0.
+    ///
+    /// [`GGRSEvent::DesyncDetected`]: crate::GGRSEvent::DesyncDetected
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn with_input_desync_detection(mut self, interval: u32) -> Result<Self, GGRSError> {
+        if interval < 1 {
+            return Err(GGRSError::InvalidRequest {
+                info: "Input desync detection interval cannot be smaller than 1.".to_owned(),
+            });
+        }
+        self.input_desync_interval = Some(interval);
+        Ok(self)
+    }
+
+    /// Enables spectator checksums to detect spectators diverging from their host: A [`P2PSession`] shares the checksum of its confirmed state
+    /// with its spectators every `interval` frames, and a [`SpectatorSession`] requests to save its state at the same frames with a
+    /// [`GGRSRequest::SaveGameState`] to compare the checksums. If they differ, the spectator sends a [`GGRSEvent::DesyncDetected`].
+    /// The host and its spectators need to use the same interval, and the states need to be saved with checksums. Disabled by default.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the interval is 0.
+    ///
+    /// [`GGRSRequest::SaveGameState`]: crate::GGRSRequest::SaveGameState
+    /// [`GGRSEvent::DesyncDetected`]: crate::GGRSEvent::DesyncDetected
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn with_spectator_checksums(mut self, interval: u32) -> Result<Self, GGRSError> {
+        if interval < 1 {
+            return Err(GGRSError::InvalidRequest {
+                info: "Spectator checksum interval cannot be smaller than 1.".to_owned(),
+            });
+        }
+        self.spectator_checksum_interval = Some(interval);
+        Ok(self)
+    }
+
+    /// Sets the checksums of a known-good run, as returned by [`SyncTestSession::checksum_log()`]. A [`SyncTestSession`] started with recorded checksums
+    /// runs in validation mode: Instead of simulating rollbacks, it compares the checksum of every saved state to the recorded checksum of that frame.
+    /// This lets you verify that changes to your simulation keep it bit-identical. The check distance is ignored in validation mode.
+    /// Call [`SyncTestSession::finish_validation()`] after the last frame to validate it as well.
+    ///
+    /// [`SyncTestSession::checksum_log()`]: SyncTestSession#method.checksum_log
+    /// [`SyncTestSession::finish_validation()`]: SyncTestSession#method.finish_validation
+    pub fn with_recorded_checksums(mut self, checksums: Vec<(Frame, Option<u128>)>) -> Self {
+        self.recorded_checksums = Some(checksums);
+        self
+    }
+
+    /// If set to true, a [`SyncTestSession`] runs additional checks to pin down non-determinism in your simulation, e.g. from floating point operations:
+    /// - If checksums diverge between the original and the resimulated states, the returned [`MismatchedChecksum`] names the first divergent frame,
+    ///   instead of the first frame found during the comparison.
+    /// - Every saved state needs a checksum, otherwise [`InvalidRequest`] is returned, since states without a checksum cannot be compared at all.
+    ///
+    /// Default is false. Other sessions are not affected.
+    ///
+    /// [`MismatchedChecksum`]: GGRSError::MismatchedChecksum
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn with_determinism_checks(mut self, checks: bool) -> Self {
+        self.determinism_checks = checks;
+        self
+    }
+
+    /// If set to true, a [`SyncTestSession`] serializes every saved state with [`bincode`] and compares the bytes of the original and the resimulated states,
+    /// in addition to their checksums. If they differ, [`MismatchedState`] is returned with the ranges of differing bytes, which helps to find
+    /// the part of your state that is not deterministic. This also detects mismatches of states saved without a checksum. Serializing and keeping
+    /// the bytes of every state is slow, so only use this while debugging. Requires the `serde` feature.
+    ///
+    /// Default is false. Other sessions are not affected.
+    ///
+    /// [`bincode`]: https://docs.rs/bincode
+    /// [`MismatchedState`]: GGRSError::MismatchedState
+    #[cfg(all(feature = "serde", feature = "std"))]
+    pub fn with_byte_diff(mut self, byte_diff: bool) -> Self
+    where
+        T::State: serde::Serialize,
+    {
+        self.state_serializer = if byte_diff {
+            Some(|state: &T::State| bincode::serialize(state).ok())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// If set to true, a [`P2PSession`] starts without waiting for its spectators to synchronize, so spectators can join while the match is running.
+    /// A spectator that synchronizes after the first inputs have been sent receives a snapshot of the state of the last confirmed frame, followed by all inputs from that frame on.
+    /// For this, you need to save all states with [`GameStateCell::save_with_bytes()`], and the spectator needs to be started with
+    /// [`SessionBuilder::with_snapshot_decoder()`]. Default is false.
+    ///
+    /// [`GameStateCell::save_with_bytes()`]: crate::GameStateCell#method.save_with_bytes
+    /// [`SessionBuilder::with_snapshot_decoder()`]: Self#method.with_snapshot_decoder
+    pub fn with_spectator_snapshots(mut self, snapshots: bool) -> Self {
+        self.spectator_snapshots = snapshots;
+        self
+    }
+
+    /// Sets the function a [`SpectatorSession`] uses to deserialize the snapshot of the game state it receives when joining a match late,
+    /// see [`SessionBuilder::with_spectator_snapshots()`]. The function receives the bytes the host gave to [`GameStateCell::save_with_bytes()`]
+    /// and returns `None` if they cannot be deserialized, in which case the snapshot is ignored. The spectator then starts with a
+    /// [`GGRSRequest::LoadGameState`] of the deserialized state. Without a decoder, a spectator joining late cannot start.
+    ///
+    /// [`SessionBuilder::with_spectator_snapshots()`]: Self#method.with_spectator_snapshots
+    /// [`GameStateCell::save_with_bytes()`]: crate::GameStateCell#method.save_with_bytes
+    /// [`GGRSRequest::LoadGameState`]: crate::GGRSRequest::LoadGameState
+    pub fn with_snapshot_decoder(mut self, decoder: fn(&[u8]) -> Option<T::State>) -> Self {
+        self.snapshot_decoder = Some(decoder);
+        self
+    }
+
+    /// If set to true, a [`P2PSession`] tries to recover from a [`GGRSEvent::DesyncDetected`] instead of leaving it to you: The session requests the
+    /// state of an upcoming frame from the authoritative peer, see [`SessionBuilder::with_checksum_authority()`], and loads it once it arrives.
+    /// All frames after it are resimulated and a [`GGRSEvent::DesyncRecovered`] is sent. Until then, the session keeps all inputs from that frame on,
+    /// so it may hit the prediction threshold while waiting for the state. If the authoritative peer reports that it cannot send the state,
+    /// does not answer within about ten seconds or disconnects, the session gives up and sends a [`GGRSEvent::DesyncRecoveryFailed`] instead.
+    /// This requires [`SessionBuilder::with_input_desync_detection()`] and [`SessionBuilder::with_snapshot_decoder()`], all clients need to save
+    /// their states with [`GameStateCell::save_with_bytes()`], and it cannot be combined with sparse saving. Default is false.
+    ///
+    /// [`GGRSEvent::DesyncDetected`]: crate::GGRSEvent::DesyncDetected
+    /// [`GGRSEvent::DesyncRecovered`]: crate::GGRSEvent::DesyncRecovered
+    /// [`GGRSEvent::DesyncRecoveryFailed`]: crate::GGRSEvent::DesyncRecoveryFailed
+    /// [`SessionBuilder::with_checksum_authority()`]: Self#method.with_checksum_authority
+    /// [`SessionBuilder::with_input_desync_detection()`]: Self#method.with_input_desync_detection
+    /// [`SessionBuilder::with_snapshot_decoder()`]: Self#method.with_snapshot_decoder
+    /// [`GameStateCell::save_with_bytes()`]: crate::GameStateCell#method.save_with_bytes
+    pub fn with_desync_recovery(mut self, recovery: bool) -> Self {
+        self.desync_recovery = recovery;
+        self
+    }
+
+    /// Sets the player whose client is the source of truth when the clients desync. Default is player handle 0.
+    /// With [`SessionBuilder::with_desync_recovery()`], only a desync with the client of this player triggers a recovery, and the state is requested from it.
+    /// The client of this player never recovers itself. All clients of the session need to agree on the same authority.
+    ///
+    /// [`SessionBuilder::with_desync_recovery()`]: Self#method.with_desync_recovery
+    pub fn with_checksum_authority(mut self, player_handle: PlayerHandle) -> Self {
+        self.checksum_authority = player_handle;
+        self
+    }
+
+    /// Sets the maximum number of frames the current frame of a [`P2PSession`] may be ahead of the confirmed frame, i.e. the last frame for which
+    /// all inputs have been received. Beyond that, [`P2PSession::add_local_input()`] and [`P2PSession::advance_frame()`] return [`FrameLagExceeded`].
+    /// This keeps a simulation from buffering frames forever when a remote client hangs. By default, the lag is not limited.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the lag is 0.
+    ///
+    /// [`P2PSession::add_local_input()`]: P2PSession#method.add_local_input
+    /// [`P2PSession::advance_frame()`]: P2PSession#method.advance_frame
+    /// [`FrameLagExceeded`]: GGRSError::FrameLagExceeded
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn with_max_frame_lag(mut self, max_frame_lag: usize) -> Result<Self, GGRSError> {
+        if max_frame_lag < 1 {
+            return Err(GGRSError::InvalidRequest {
+                info: "Maximum frame lag cannot be smaller than 1.".to_owned(),
+            });
+        }
+        self.max_frame_lag = Some(max_frame_lag);
+        Ok(self)
+    }
+
+    /// If set to true, all cells that states are saved to are filled with a default state when the session starts.
+    /// Together with [`GameStateCell::save_in_place()`], saving a state then reuses the allocations of the state previously saved to that cell,
+    /// so after all cells have been used once, saving does not allocate at all. This requires your state to implement [`Default`]. Default is false.
+    ///
+    /// [`GameStateCell::save_in_place()`]: crate::GameStateCell#method.save_in_place
+    pub fn with_state_reuse(mut self, reuse: bool) -> Self
+    where
+        T::State: Default,
+    {
+        self.state_init = if reuse { Some(T::State::default) } else { None };
+        self
+    }
+
+    /// Sets the [`StateStore`] that all saved states are moved into, e.g. to keep very large states in a memory pool or on disk.
+    /// Saving a [`GameStateCell`] then hands the state to the store and loading it retrieves the state from the store again,
+    /// so you fulfill the requests the same way as without a store. Reusing states with [`with_state_reuse()`] has no effect with a store.
+    /// By default, the states are kept in memory by the session.
+    ///
+    /// [`GameStateCell`]: crate::GameStateCell
+    /// [`with_state_reuse()`]: Self#method.with_state_reuse
+    pub fn with_state_store(mut self, store: impl StateStore<T::State> + 'static) -> Self {
+        self.state_store = Some(Arc::new(Mutex::new(store)));
+        self
+    }
+
+    /// Lets you save states as deltas against previously saved full states with [`GameStateCell::save_delta()`], which cuts the memory
+    /// and time spent on saving large states. The given function reconstructs a state from the full state a delta has been saved against and the
+    /// bytes of the delta when the cell is loaded. The session keeps full states as long as any saved delta refers to them.
+    /// Delta states cannot be combined with a [`StateStore`], [`with_spectator_snapshots()`] or [`with_desync_recovery()`], since those need the bytes
+    /// of full states. By default, states can only be saved in full.
+    ///
+    /// [`GameStateCell::save_delta()`]: crate::GameStateCell#method.save_delta
+    /// [`with_spectator_snapshots()`]: Self#method.with_spectator_snapshots
+    /// [`with_desync_recovery()`]: Self#method.with_desync_recovery
+    pub fn with_delta_states(mut self, apply: fn(&T::State, &[u8]) -> T::State) -> Self {
+        self.delta_applier = Some(apply);
+        self
+    }
+
+    /// Sets the [`MessageCrypto`] used to seal every message before it is sent and open every received message, for example to encrypt
+    /// and authenticate all traffic without implementing your own socket. Messages that are not sealed or cannot be opened are dropped.
+    /// Applies to P2P and spectator sessions alike, so all clients of a session must use the same crypto. By default, messages are not sealed.
+    /// Requires the `std` feature, since messages are serialized with `bincode`.
+    #[cfg(feature = "std")]
+    pub fn with_message_crypto(mut self, crypto: impl MessageCrypto + 'static) -> Self {
+        self.message_crypto = Some(Arc::new(crypto));
+        self
+    }
+
+    /// Sets the [`DisconnectHook`] a [`P2PSession`] calls synchronously for every remote player it disconnects, with the first frame the player is disconnected on.
+    /// Unlike [`GGRSEvent::Disconnected`], which you only handle after polling, the hook runs before the session resimulates the frames since the disconnect,
+    /// so you can switch the player to an AI at a precise frame. By default, there is no hook.
+    ///
+    /// [`GGRSEvent::Disconnected`]: crate::GGRSEvent::Disconnected
+    pub fn with_disconnect_hook(mut self, hook: impl DisconnectHook + 'static) -> Self {
+        self.disconnect_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets a validator a [`P2PSession`] applies to the bytes of every input before it is queued, e.g. to reject impossible inputs sent by cheaters.
+    /// Local inputs that fail validation are rejected with [`InvalidRequest`]. Remote inputs that fail validation are replaced with the last valid input
+    /// of that player, or the blank input if there is none, and the session sends a [`GGRSEvent::InvalidInput`]. By default, all inputs are accepted.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    /// [`GGRSEvent::InvalidInput`]: crate::GGRSEvent::InvalidInput
+    pub fn with_input_validator(mut self, validator: fn(&[u8]) -> bool) -> Self {
+        self.input_validator = Some(validator);
+        self
+    }
+
+    /// If set to true, a [`P2PSession`] returns [`NonMonotonicInput`] when a local input is added for a frame that already has one,
+    /// e.g. by calling [`P2PSession::add_local_input()`] twice before advancing, instead of silently replacing the earlier input.
+    /// The same goes for adding an input for a frame after the next one without an input with [`P2PSession::add_local_input_for_frame()`].
+    /// This catches game loops that submit inputs twice, e.g. after a rollback. Default is false.
+    ///
+    /// [`NonMonotonicInput`]: GGRSError::NonMonotonicInput
+    /// [`P2PSession::add_local_input()`]: P2PSession#method.add_local_input
+    /// [`P2PSession::add_local_input_for_frame()`]: P2PSession#method.add_local_input_for_frame
+    pub fn with_strict_input_ordering(mut self, strict: bool) -> Self {
+        self.strict_input_ordering = strict;
+        self
+    }
+
+    /// Sets the seed for all random numbers generated by the session, like the magic numbers identifying a connection and the random numbers
+    /// exchanged during synchronization. Together with [`SessionBuilder::with_time_source()`] and an [`InMemoryNetwork`] with a fixed seed,
+    /// this makes sessions behave the same in every run, e.g. to reproduce a failing soak test. The seed is also handed to the socket of the session
+    /// with [`NonBlockingSocket::set_rng_seed()`], so a [`UdpNonBlockingSocket`] simulating network conditions drops and delays the same packets in every run.
+    /// By default, the random numbers are seeded from the system, or from the time source without the `std` feature.
+    ///
+    /// [`SessionBuilder::with_time_source()`]: Self#method.with_time_source
+    /// [`InMemoryNetwork`]: crate::InMemoryNetwork
+    /// [`UdpNonBlockingSocket`]: crate::UdpNonBlockingSocket
+    pub fn with_rng_seed(mut self, seed: u64) -> Self {
+        self.rng_seed = Some(seed);
+        self
+    }
+
+    /// Attaches the bytes returned by the given function to every quality report the session sends to remote clients, a few times per second.
+    /// The remote clients receive them as [`GGRSEvent::QualityReportPayload`]. This is meant for small, non-critical data like telemetry:
+    /// Reports are not resent if they are lost, and payloads longer than [`MAX_QUALITY_REPORT_PAYLOAD`] bytes are not sent at all.
+    ///
+    /// [`GGRSEvent::QualityReportPayload`]: crate::GGRSEvent::QualityReportPayload
+    /// [`MAX_QUALITY_REPORT_PAYLOAD`]: crate::MAX_QUALITY_REPORT_PAYLOAD
+    pub fn with_quality_report_payload(mut self, payload: fn() -> Vec<u8>) -> Self {
+        self.quality_report_payload = Some(payload);
+        self
+    }
+
+    /// Sets how many past frames each input packet re-includes next to the newest frame. By default, every packet re-includes all frames
+    /// the remote client has not acknowledged yet, which uses the most bandwidth, but lets the remote client recover lost packets from the next one.
+    /// A smaller number saves bandwidth on good connections. Inputs that are lost nonetheless are resent after a short time, which can stall the remote client.
+    /// The number is limited to the prediction window, and the first inputs are always sent completely. See [`NetworkStats::inputs_recovered`].
+    ///
+    /// [`NetworkStats::inputs_recovered`]: crate::NetworkStats#structfield.inputs_recovered
+    pub fn with_input_redundancy(mut self, frames: usize) -> Self {
+        self.input_redundancy = Some(frames);
+        self
+    }
+
+    /// Sets an id that identifies compatible builds of your game, like a build hash or protocol version. The id is exchanged while synchronizing,
+    /// and sessions refuse to synchronize with remote clients that have a different id. Instead, you receive a [`GGRSEvent::IncompatiblePeer`].
+    /// Default is 0.
+    ///
+    /// [`GGRSEvent::IncompatiblePeer`]: crate::GGRSEvent::IncompatiblePeer
+    pub fn with_app_id(mut self, app_id: u64) -> Self {
+        self.app_id = app_id;
+        self
+    }
+
+    /// Sets a seed that all clients of the match need to agree on, e.g. to seed the random number generator of your game. Like the app id,
+    /// the seed is exchanged while synchronizing and sessions refuse to synchronize with remote clients that have a different seed or none at all.
+    /// Instead, you receive a [`GGRSEvent::IncompatiblePeer`]. Once running, all clients share the seed returned by [`P2PSession::shared_seed()`].
+    /// By default, there is no shared seed.
+    ///
+    /// [`GGRSEvent::IncompatiblePeer`]: crate::GGRSEvent::IncompatiblePeer
+    /// [`P2PSession::shared_seed()`]: P2PSession#method.shared_seed
+    pub fn with_shared_seed(mut self, seed: u64) -> Self {
+        self.shared_seed = Some(seed);
+        self
+    }
+
+    /// Sets the [`TimeSource`] used for all timers of the session, like disconnect timeouts and packet resending. Default is [`StdTimeSource`].
+    /// Without the `std` feature, there is no default, so you need to provide a time source to start a [`P2PSession`] or [`SpectatorSession`].
+    ///
+    /// [`StdTimeSource`]: crate::StdTimeSource
+    pub fn with_time_source(mut self, time_source: impl TimeSource + 'static) -> Self {
+        self.time_source = Some(Arc::new(time_source));
+        self
+    }
+
     /// Consumes the builder to construct a [`P2PSession`] and starts synchronization of endpoints.
+    /// If all players are local and there are no spectators, there is nothing to synchronize with, so the session starts in [`SessionState::Running`]
+    /// right away. This lets you test your game logic through the same code path as networked matches.
+    ///
     /// # Errors
     /// - Returns [`InvalidRequest`] if insufficient players have been registered.
     ///
     /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    /// [`SessionState::Running`]: crate::SessionState::Running
     pub fn start_p2p_session(
+        self,
+        socket: impl NonBlockingSocket<T::Address> + 'static,
+    ) -> Result<P2PSession<T>, GGRSError> {
+        self.build_p2p_session(socket, false)
+    }
+
+    /// Consumes the builder to construct a [`P2PSession`] that sends and receives through the given [`SharedSocket`], tagging all of its
+    /// messages with the given session id. The remote players need to use the same session id. This lets a single port serve many matches.
+    /// The session id takes up some bytes of every packet, so reliable messages and the encoded inputs of a packet can be at most [`MAX_PAYLOAD`] minus 18 bytes long.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if insufficient players have been registered or another session already uses the session id.
+    ///
+    /// [`MAX_PAYLOAD`]: crate::MAX_PAYLOAD
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn start_shared_p2p_session(
         mut self,
+        socket: &SharedSocket<T>,
+        session_id: u64,
+    ) -> Result<P2PSession<T>, GGRSError> {
+        let socket = socket.session(session_id)?;
+        self.max_payload = MAX_PAYLOAD - ROUTING_OVERHEAD;
+        self.build_p2p_session(socket, false)
+    }
+
+    /// Consumes the builder to construct an [`AsyncP2PSession`], which wraps a [`P2PSession`] to send and receive its messages through the given
+    /// [`AsyncNonBlockingSocket`]. Requires the `async` feature.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if insufficient players have been registered.
+    ///
+    /// [`AsyncP2PSession`]: crate::AsyncP2PSession
+    /// [`AsyncNonBlockingSocket`]: crate::AsyncNonBlockingSocket
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    #[cfg(feature = "async")]
+    pub fn start_async_p2p_session<S: crate::AsyncNonBlockingSocket<T::Address>>(
+        self,
+        socket: S,
+    ) -> Result<crate::AsyncP2PSession<T, S>, GGRSError> {
+        let buffered = BufferedSocket::<T>::new();
+        let buffers = buffered.buffers();
+        let session = self.build_p2p_session(buffered, false)?;
+        Ok(crate::AsyncP2PSession::new(session, buffers, socket))
+    }
+
+    /// Consumes the builder to construct a [`P2PSession`] in lockstep mode, e.g. for turn-based games. It uses the same transport and synchronization
+    /// as [`start_p2p_session()`], but never predicts remote inputs: [`P2PSession::advance_frame()`] only advances once the inputs of all remote players
+    /// for the current frame have been received, and returns [`WaitingForInputs`] otherwise. Since frames are never predicted, there are no rollbacks.
+    ///
+    /// Your local inputs are sent to the remote players the first time you call [`P2PSession::advance_frame()`] for a frame.
+    /// While waiting for the remote inputs of that frame, you still need to add local inputs before each call, but they are ignored.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if insufficient players have been registered.
+    ///
+    /// [`start_p2p_session()`]: Self#method.start_p2p_session
+    /// [`P2PSession::advance_frame()`]: P2PSession#method.advance_frame
+    /// [`WaitingForInputs`]: GGRSError::WaitingForInputs
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn start_lockstep_session(
+        self,
         socket: impl NonBlockingSocket<T::Address> + 'static,
     ) -> Result<P2PSession<T>, GGRSError> {
+        self.build_p2p_session(socket, true)
+    }
+
+    /// Consumes the builder to construct a [`P2PSession`] that continues a session exported with [`P2PSession::export_runtime_state()`],
+    /// sending and receiving through the given socket. The builder needs to be configured like the builder of the exported session, with the same
+    /// players and spectators at the same addresses. The session is running right away and does not synchronize with the remote clients again.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if insufficient players have been registered or the players differ from the exported session.
+    ///
+    /// [`P2PSession::export_runtime_state()`]: P2PSession#method.export_runtime_state
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn restore_p2p_session(
+        self,
+        socket: impl NonBlockingSocket<T::Address> + 'static,
+        state: &RuntimeState,
+    ) -> Result<P2PSession<T>, GGRSError> {
+        let mut session = self.build_p2p_session(socket, state.runtime.lockstep)?;
+        session.restore_runtime_state(state)?;
+        Ok(session)
+    }
+
+    fn build_p2p_session(
+        mut self,
+        socket: impl NonBlockingSocket<T::Address> + 'static,
+        lockstep: bool,
+    ) -> Result<P2PSession<T>, GGRSError> {
+        self.check_duplicate_handles()?;
+
         // check if all players are added
         for player_handle in 0..self.num_players {
-            if !self.player_reg.handles.contains_key(&player_handle) {
+            if !self.player_reg.handles.contains_key(&player_handle)
+                && !self.reserved_players.contains(&player_handle)
+            {
                 return Err(GGRSError::InvalidRequest{
                     info: "Not enough players have been added. Keep registering players up to the defined player number.".to_owned(),
                 });
             }
         }
 
+        if self.time_source.is_none() {
+            return Err(GGRSError::InvalidRequest {
+                info: "A time source is required without the std feature.".to_owned(),
+            });
+        }
+
+        if let Some((_, max)) = self.adaptive_prediction {
+            self.max_prediction = max;
+        }
+
+        // without sparse saving, a rollback can go back to any frame of the prediction window
+        if !lockstep
+            && !self.sparse_saving
+            && self
+                .max_saved_states
+                .is_some_and(|states| states < self.max_prediction)
+        {
+            return Err(GGRSError::InvalidRequest {
+                info: "Without sparse saving, a state needs to be kept for every frame of the prediction window.".to_owned(),
+            });
+        }
+
+        if self.delta_applier.is_some() && self.state_store.is_some() {
+            return Err(GGRSError::InvalidRequest {
+                info: "Delta states cannot be used with a state store.".to_owned(),
+            });
+        }
+        // delta states hold no bytes to send to spectators or peers recovering from a desync
+        if self.delta_applier.is_some() && (self.spectator_snapshots || self.desync_recovery) {
+            return Err(GGRSError::InvalidRequest {
+                info: "Delta states cannot be used with spectator snapshots or desync recovery."
+                    .to_owned(),
+            });
+        }
+
+        if self.checksum_authority >= self.num_players {
+            return Err(GGRSError::InvalidRequest {
+                info: "The checksum authority has to be a player.".to_owned(),
+            });
+        }
+
+        if self.start_frame > 0 && self.input_desync_interval.is_some() {
+            return Err(GGRSError::InvalidRequest {
+                info: "Input desync detection cannot be used with a start frame, since the inputs before it are unknown.".to_owned(),
+            });
+        }
+
+        if self.desync_recovery {
+            if self.input_desync_interval.is_none() {
+                return Err(GGRSError::InvalidRequest {
+                    info: "Desync recovery requires input desync detection.".to_owned(),
+                });
+            }
+            if self.snapshot_decoder.is_none() {
+                return Err(GGRSError::InvalidRequest {
+                    info: "Desync recovery requires a snapshot decoder.".to_owned(),
+                });
+            }
+            if self.sparse_saving {
+                return Err(GGRSError::InvalidRequest {
+                    info: "Desync recovery cannot be used with sparse saving.".to_owned(),
+                });
+            }
+        }
+
         // count the number of players per address
         let mut addr_count = HashMap::<PlayerType<T::Address>, Vec<PlayerHandle>>::new();
         for (handle, player_type) in self.player_reg.handles.iter() {
@@ -267,34 +1194,78 @@ impl<T: Config> SessionBuilder<T> {
                     );
                 }
                 PlayerType::Spectator(peer_addr) => {
-                    self.player_reg.spectators.insert(
+                    let endpoint = self.endpoint_settings().create_spectator_endpoint(
+                        handles,
                         peer_addr.clone(),
-                        self.create_endpoint(handles, peer_addr.clone(), self.num_players), // the host of the spectator sends inputs for all players
+                        self.num_players,
+                        self.max_prediction,
+                        self.fps,
                     );
+                    self.player_reg
+                        .spectators
+                        .insert(peer_addr.clone(), endpoint);
                 }
                 PlayerType::Local => (),
             }
         }
 
-        Ok(P2PSession::<T>::new(
-            self.num_players,
-            self.max_prediction,
-            Box::new(socket),
-            self.player_reg,
-            self.sparse_saving,
-            self.input_delay,
-        ))
+        let endpoint_settings = self.endpoint_settings();
+        let socket = self.wrap_socket(socket);
+        let settings = P2PSettings {
+            num_players: self.num_players,
+            max_prediction: self.max_prediction,
+            max_saved_states: self.max_saved_states.unwrap_or(self.max_prediction),
+            sparse_saving: self.sparse_saving,
+            state_saved_events: self.state_saved_events,
+            time_sync: self.time_sync,
+            fps: self.fps,
+            input_delay: self.input_delay,
+            prediction_strategy: self.prediction_strategy,
+            checksum_kind: self.checksum_kind,
+            state_init: self.state_init,
+            state_store: self.state_store,
+            delta_applier: self.delta_applier,
+            input_desync_interval: self.input_desync_interval,
+            spectator_checksum_interval: self.spectator_checksum_interval,
+            spectator_snapshots: self.spectator_snapshots,
+            max_frame_lag: self.max_frame_lag,
+            lockstep,
+            desync_recovery: self.snapshot_decoder.filter(|_| self.desync_recovery),
+            checksum_authority: self.checksum_authority,
+            min_adaptive_prediction: self.adaptive_prediction.map(|(min, _)| min),
+            disconnect_hook: self.disconnect_hook,
+            input_validator: self.input_validator,
+            strict_input_ordering: self.strict_input_ordering,
+            input_sizes: self.input_sizes,
+            endpoint_settings,
+            start_frame: self.start_frame,
+        };
+        Ok(P2PSession::<T>::new(settings, socket, self.player_reg))
     }
 
     /// Consumes the builder to create a new [`SpectatorSession`].
     /// A [`SpectatorSession`] provides all functionality to connect to a remote host in a peer-to-peer fashion.
     /// The host will broadcast all confirmed inputs to this session.
     /// This session can be used to spectate a session without contributing to the game input.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if a handle has been added more than once.
+    /// - Returns [`InvalidRequest`] if no time source has been set with [`SessionBuilder::with_time_source()`] without the `std` feature.
+    ///
+    /// [`SessionBuilder::with_time_source()`]: Self#method.with_time_source
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
     pub fn start_spectator_session(
         self,
         host_addr: T::Address,
         socket: impl NonBlockingSocket<T::Address> + 'static,
-    ) -> SpectatorSession<T> {
+    ) -> Result<SpectatorSession<T>, GGRSError> {
+        self.check_duplicate_handles()?;
+        if self.time_source.is_none() {
+            return Err(GGRSError::InvalidRequest {
+                info: "A time source is required without the std feature.".to_owned(),
+            });
+        }
+
         // create host endpoint
         let mut host = UdpProtocol::new(
             (0..self.num_players).collect(),
@@ -305,15 +1276,24 @@ impl<T: Config> SessionBuilder<T> {
             self.disconnect_timeout,
             self.disconnect_notify_start,
             self.fps,
+            self.spectator_compression
+                .as_ref()
+                .map_or_else(|| self.input_codec.clone(), |(codec, _)| codec.clone()),
+            self.time_source(),
+            self.endpoint_settings().rng(0),
         );
+        self.endpoint_settings().configure(&mut host);
         host.synchronize();
-        SpectatorSession::new(
+        let socket = self.wrap_socket(socket);
+        Ok(SpectatorSession::new(
             self.num_players,
-            Box::new(socket),
+            socket,
             host,
             self.max_frames_behind,
             self.catchup_speed,
-        )
+            self.snapshot_decoder,
+            self.spectator_checksum_interval,
+        ))
     }
 
     /// Consumes the builder to construct a new [`SyncTestSession`]. During a [`SyncTestSession`], GGRS will simulate a rollback every frame
@@ -322,39 +1302,135 @@ impl<T: Config> SessionBuilder<T> {
     /// Due to the decentralized nature of saving and loading gamestates, checksum comparisons can only be made if `check_distance` is 2 or higher.
     /// This is a great way to test if your system runs deterministically.
     /// After creating the session, add a local player, set input delay for them and then start the session.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if a handle has been added more than once, the check distance is not smaller than the prediction window,
+    ///   fewer states than the check distance are kept, or delta states are combined with a state store.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
     pub fn start_synctest_session(self) -> Result<SyncTestSession<T>, GGRSError> {
+        self.check_duplicate_handles()?;
         if self.check_dist >= self.max_prediction {
             return Err(GGRSError::InvalidRequest {
                 info: "Check distance too big.".to_owned(),
             });
         }
+        if self
+            .max_saved_states
+            .is_some_and(|states| states < self.check_dist)
+        {
+            return Err(GGRSError::InvalidRequest {
+                info: "A state needs to be kept for every frame of the check distance.".to_owned(),
+            });
+        }
+        if self.delta_applier.is_some() && self.state_store.is_some() {
+            return Err(GGRSError::InvalidRequest {
+                info: "Delta states cannot be used with a state store.".to_owned(),
+            });
+        }
         Ok(SyncTestSession::new(
             self.num_players,
             self.max_prediction,
+            self.max_saved_states.unwrap_or(self.max_prediction),
             self.check_dist,
             self.input_delay,
+            self.prediction_strategy,
+            self.checksum_kind,
+            self.recorded_checksums,
+            self.state_init,
+            self.state_store,
+            self.delta_applier,
+            self.determinism_checks,
+            self.state_serializer,
+            self.input_sizes,
         ))
     }
 
+    /// Consumes the builder to construct a new [`ReplaySession`], which plays back the inputs read by the given [`ReplayReader`].
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if a handle has been added more than once.
+    /// - Returns [`InvalidRequest`] if the number of players in the replay does not match the number of players of the builder.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    #[cfg(feature = "std")]
+    pub fn start_replay_session<R: Read>(
+        self,
+        reader: ReplayReader<T, R>,
+    ) -> Result<ReplaySession<T, R>, GGRSError> {
+        self.check_duplicate_handles()?;
+        if reader.num_players() != self.num_players {
+            return Err(GGRSError::InvalidRequest {
+                info: "The number of players in the replay does not match.".to_owned(),
+            });
+        }
+        Ok(ReplaySession::new(reader))
+    }
+
     fn create_endpoint(
         &self,
         handles: Vec<PlayerHandle>,
         peer_addr: T::Address,
         local_players: usize,
     ) -> UdpProtocol<T> {
-        // create the endpoint, set parameters
-        let mut endpoint = UdpProtocol::new(
+        self.endpoint_settings().create_endpoint(
             handles,
             peer_addr,
             self.num_players,
             local_players,
             self.max_prediction,
-            self.disconnect_timeout,
-            self.disconnect_notify_start,
             self.fps,
-        );
-        // start the synchronization
-        endpoint.synchronize();
-        endpoint
+        )
+    }
+
+    /// Returns the settings for the endpoints of the session.
+    fn endpoint_settings(&self) -> EndpointSettings {
+        EndpointSettings {
+            disconnect_timeout: self.disconnect_timeout,
+            disconnect_notify_start: self.disconnect_notify_start,
+            input_codec: self.input_codec.clone(),
+            time_source: self.time_source(),
+            rng_seed: self.rng_seed,
+            spectator_compression: self.spectator_compression.clone(),
+            quality_report_payload: self.quality_report_payload,
+            app_id: self.app_id,
+            shared_seed: self.shared_seed,
+            input_redundancy: self.input_redundancy,
+            sync_packets: self.sync_packets,
+            sync_interval: self.sync_interval,
+            max_payload: self.max_payload,
+        }
     }
+
+    /// Returns the time source for the endpoints of the session.
+    fn time_source(&self) -> Arc<dyn TimeSource> {
+        self.time_source
+            .clone()
+            .expect("A time source is required without the std feature.")
+    }
+
+    /// Seeds and boxes the socket, and wraps it so all messages are sealed and opened, if a [`MessageCrypto`] has been set.
+    fn wrap_socket(
+        &self,
+        mut socket: impl NonBlockingSocket<T::Address> + 'static,
+    ) -> Box<dyn NonBlockingSocket<T::Address>> {
+        if let Some(seed) = self.rng_seed {
+            socket.set_rng_seed(seed);
+        }
+        #[cfg(feature = "std")]
+        if let Some(crypto) = &self.message_crypto {
+            return Box::new(CryptoSocket::<T>::new(Box::new(socket), crypto.clone()));
+        }
+        Box::new(socket)
+    }
+}
+
+#[cfg(feature = "std")]
+fn default_time_source() -> Option<Arc<dyn TimeSource>> {
+    Some(Arc::new(StdTimeSource::new()))
+}
+
+#[cfg(not(feature = "std"))]
+fn default_time_source() -> Option<Arc<dyn TimeSource>> {
+    None
 }
@@ -1,22 +1,83 @@
+use alloc::{borrow::ToOwned, boxed::Box, vec::Vec};
+use bytemuck::Zeroable;
+
 use crate::error::GGRSError;
 use crate::frame_info::PlayerInput;
-use crate::network::messages::ConnectionStatus;
+use crate::input_queue::INPUT_QUEUE_LENGTH;
+use crate::network::messages::{ConnectionStatus, Message, MessageBody, PauseControl, PlayerJoin};
 use crate::network::network_stats::NetworkStats;
-use crate::network::protocol::UdpProtocol;
-use crate::sync_layer::SyncLayer;
+use crate::network::protocol::{EndpointSettings, UdpProtocol, MAX_RELIABLE_MESSAGES};
+#[cfg(feature = "std")]
+use crate::replay::{ReplayWrite, ReplayWriter};
+use crate::rollback_stats::RollbackStats;
+use crate::sessions::builder::discard_old_events;
+use crate::sessions::p2p_spectator_session::SnapshotDecoder;
+use crate::sessions::runtime_state::{RuntimeState, SessionRuntime};
+use crate::sync_layer::{DeltaApplier, SharedStateStore, SyncLayer};
 use crate::{
-    network::protocol::Event, Config, Frame, GGRSEvent, GGRSRequest, NonBlockingSocket,
-    PlayerHandle, PlayerType, SessionState, NULL_FRAME,
+    network::protocol::Event, ChecksumKind, Config, DesyncKind, DetailedSessionState,
+    DisconnectHook, Frame, GGRSEvent, GGRSRequest, InputStatus, NonBlockingSocket,
+    PlayerConnectionState, PlayerHandle, PlayerType, PredictionStrategy, SessionConfigSummary,
+    SessionState, NULL_FRAME,
 };
 
-use std::collections::vec_deque::Drain;
-use std::collections::HashMap;
-use std::collections::VecDeque;
-use std::convert::TryInto;
+use alloc::collections::vec_deque::Drain;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::convert::TryInto;
+use core::time::Duration;
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::io::Write;
 
 const RECOMMENDATION_INTERVAL: Frame = 60;
 const MIN_RECOMMENDATION: u32 = 3;
-const MAX_EVENT_QUEUE_SIZE: usize = 100;
+/// The maximum number of received messages kept for [`P2PSession::poll_remote_clients_limited()`], the oldest ones are dropped beyond that.
+const MAX_RECEIVED_MESSAGES: usize = 1024;
+const MAX_INPUT_CHECKSUMS: usize = 32;
+pub(crate) const MAX_CHECKSUM_HISTORY: usize = 128;
+/// The session warns about the frame counter running out this many seconds of frames before it does.
+const FRAME_LIMIT_WARNING_SECS: usize = 3600;
+
+/// The settings of a [`P2PSession`], collected by the [`SessionBuilder`].
+///
+/// [`SessionBuilder`]: crate::SessionBuilder
+pub(crate) struct P2PSettings<T>
+where
+    T: Config,
+{
+    pub(crate) num_players: usize,
+    pub(crate) max_prediction: usize,
+    pub(crate) max_saved_states: usize,
+    pub(crate) sparse_saving: bool,
+    pub(crate) state_saved_events: bool,
+    pub(crate) time_sync: bool,
+    pub(crate) fps: usize,
+    pub(crate) input_delay: usize,
+    pub(crate) prediction_strategy: Arc<dyn PredictionStrategy<T::Input>>,
+    pub(crate) checksum_kind: Option<ChecksumKind>,
+    pub(crate) state_init: Option<fn() -> T::State>,
+    pub(crate) state_store: Option<SharedStateStore<T::State>>,
+    pub(crate) delta_applier: Option<DeltaApplier<T::State>>,
+    pub(crate) input_desync_interval: Option<u32>,
+    pub(crate) spectator_checksum_interval: Option<u32>,
+    pub(crate) spectator_snapshots: bool,
+    pub(crate) max_frame_lag: Option<usize>,
+    pub(crate) lockstep: bool,
+    /// If set, desyncs are recovered from by loading the states of the checksum authority, decoded with this decoder.
+    pub(crate) desync_recovery: Option<SnapshotDecoder<T::State>>,
+    pub(crate) checksum_authority: PlayerHandle,
+    /// If set, the prediction window adapts to the connection quality, but never shrinks below this many frames.
+    pub(crate) min_adaptive_prediction: Option<usize>,
+    pub(crate) disconnect_hook: Option<Box<dyn DisconnectHook>>,
+    pub(crate) input_validator: Option<fn(&[u8]) -> bool>,
+    pub(crate) strict_input_ordering: bool,
+    /// The input sizes of players that do not use the whole input type.
+    pub(crate) input_sizes: HashMap<PlayerHandle, usize>,
+    pub(crate) endpoint_settings: EndpointSettings,
+    /// The frame the session starts on, all earlier frames count as confirmed.
+    pub(crate) start_frame: Frame,
+}
 
 pub(crate) struct PlayerRegistry<T>
 where
@@ -62,7 +123,7 @@ impl<T: Config> PlayerRegistry<T> {
         self.handles
             .iter()
             .filter_map(|(k, v)| match v {
-                PlayerType::Local => Some(*k),
+                PlayerType::Local => None,
                 PlayerType::Remote(_) => None,
                 PlayerType::Spectator(_) => Some(*k),
             })
@@ -102,6 +163,42 @@ impl<T: Config> PlayerRegistry<T> {
     }
 }
 
+/// The outcome of [`P2PSession::advance_frames()`].
+///
+/// [`P2PSession::advance_frames()`]: P2PSession#method.advance_frames
+pub struct FastForward<T>
+where
+    T: Config,
+{
+    /// The order-sensitive requests of all advanced frames. You should fulfill all of them in the exact order they are provided, even if `result` is an error.
+    pub requests: Vec<GGRSRequest<T>>,
+    /// The number of frames the session actually advanced.
+    pub frames_advanced: usize,
+    /// `Ok(())` if all frames have been advanced, otherwise the error that stopped the session from advancing further.
+    pub result: Result<(), GGRSError>,
+}
+
+/// A desync recovery in progress: the state of `frame` has been requested from the authoritative peer.
+struct Recovery<S> {
+    frame: Frame,
+    /// The received state, its serialized bytes and the input checksum of the authoritative peer up to the frame before.
+    state: Option<(S, Vec<u8>, u128)>,
+}
+
+/// The progress of a coordinated pause.
+enum PauseState<A> {
+    Unpaused,
+    /// The session stops at `local_frame` until it received the pause frames of all remote players.
+    Negotiating {
+        local_frame: Frame,
+        remote_frames: HashMap<A, Frame>,
+    },
+    /// All peers stop at the highest frame any of them proposed.
+    Agreed {
+        frame: Frame,
+    },
+}
+
 /// A [`P2PSession`] provides all functionality to connect to remote clients in a peer-to-peer fashion, exchange inputs and handle the gamestate by saving, loading and advancing.
 pub struct P2PSession<T>
 where
@@ -115,6 +212,16 @@ where
     sync_layer: SyncLayer<T>,
     /// With sparse saving, the session will only request to save the minimum confirmed frame.
     sparse_saving: bool,
+    /// If true, the session sends a [`GGRSEvent::StateSaved`] for every requested save.
+    state_saved_events: bool,
+    /// If true, the next advanced frame is saved, even with sparse saving.
+    force_save: bool,
+    /// If true, [`GGRSEvent::FrameCounterNearLimit`] has been sent already.
+    frame_limit_notified: bool,
+    /// If false, the session never sends a [`GGRSEvent::WaitRecommendation`].
+    time_sync: bool,
+    /// The expected update frequency of the game, used to recommend how long to sleep between frames.
+    fps: usize,
 
     /// If we receive a disconnect from another client, we have to rollback from that frame on in order to prevent wrong predictions
     disconnect_frame: Frame,
@@ -124,6 +231,8 @@ where
 
     /// The [`P2PSession`] uses this socket to send and receive all messages for remote players.
     socket: Box<dyn NonBlockingSocket<T::Address>>,
+    /// Messages that have been received from the socket, but not handled yet.
+    received_messages: VecDeque<(T::Address, Message)>,
     /// Handles players and their endpoints
     player_reg: PlayerRegistry<T>,
     /// This struct contains information about remote players, like connection status and the frame of last received input.
@@ -131,44 +240,188 @@ where
 
     /// notes which inputs have already been sent to the spectators
     next_spectator_frame: Frame,
+    /// If set, all confirmed inputs are recorded to this writer.
+    #[cfg(feature = "std")]
+    replay_writer: Option<ReplayWriter>,
     /// The soonest frame on which the session can send a [`GGRSEvent::WaitRecommendation`] again.
     next_recommended_sleep: Frame,
+    /// The frames to skip of the last [`GGRSEvent::WaitRecommendation`], spread over the frames until the next one can be sent.
+    recommended_skip_frames: u32,
     /// How many frames we estimate we are ahead of every remote client
     frames_ahead: i32,
+    /// The time passed to [`P2PSession::should_advance()`] that has not been used up by fixed steps yet.
+    accumulated_time: Duration,
+    /// If true, the saved states hold the dummy states of [`P2PSession::prewarm()`], which are discarded before advancing.
+    prewarmed: bool,
+    /// Statistics about all rollbacks performed so far.
+    rollback_stats: RollbackStats,
+    /// If set, the rolling checksum of all confirmed inputs is compared with the remote clients every this many frames.
+    input_desync_interval: Option<u32>,
+    /// The rolling checksum over all confirmed inputs before `next_input_checksum_frame`.
+    input_checksum: u128,
+    /// The next confirmed frame whose inputs are added to the `input_checksum`.
+    next_input_checksum_frame: Frame,
+    /// The most recent input checksums that remote clients can be compared with.
+    input_checksum_history: VecDeque<(Frame, u128)>,
+    /// If set, the checksum of the confirmed state is shared with the spectators every this many frames.
+    spectator_checksum_interval: Option<u32>,
+    /// The next frame whose state checksum is shared with the spectators.
+    next_spectator_checksum_frame: Frame,
+    /// The checksums of the most recent final states, ordered by frame.
+    checksum_history: Vec<(Frame, u128)>,
+    /// The next frame whose state checksum is added to the `checksum_history`.
+    next_checksum_history_frame: Frame,
+    /// If true, the session does not wait for spectators to synchronize and sends a snapshot of the game state to spectators joining late.
+    spectator_snapshots: bool,
+    /// Spectators that synchronized after the first inputs have been sent and still need a snapshot.
+    late_spectators: Vec<T::Address>,
+    /// If set, the session refuses to advance when the current frame is more than this many frames ahead of the confirmed frame.
+    max_frame_lag: Option<usize>,
+    /// If true, the session never predicts and only advances once all inputs for the current frame have been received.
+    lockstep: bool,
+    /// The last frame for which the local inputs have been added to the sync layer and sent to the remote players.
+    local_inputs_frame: Frame,
+    /// The last frame for which all remote players acknowledged the local inputs.
+    last_acked_frame: Frame,
+    /// If set, the session recovers from desyncs by loading the state of the authoritative peer, deserialized with this decoder.
+    desync_recovery: Option<SnapshotDecoder<T::State>>,
+    /// The player whose client is the source of truth when the clients desync.
+    checksum_authority: PlayerHandle,
+    /// If set, the prediction window follows the measured latency, down to this minimum.
+    /// The window the session was created with is the maximum.
+    min_adaptive_prediction: Option<usize>,
+    /// Called for every remote player the session disconnects.
+    disconnect_hook: Option<Box<dyn DisconnectHook>>,
+    /// If set, only inputs for which this returns true are queued.
+    input_validator: Option<fn(&[u8]) -> bool>,
+    /// If true, local inputs for frames that already have an input or do not follow the last one are rejected.
+    strict_input_ordering: bool,
+    /// The last remote input of each player that passed validation, used in place of invalid inputs.
+    last_valid_inputs: HashMap<PlayerHandle, PlayerInput<T::Input>>,
+    /// The desync recovery in progress, if any.
+    recovery: Option<Recovery<T::State>>,
+    /// The remote clients that requested a state to recover from a desync, and the frame of the requested state.
+    state_requests: Vec<(T::Address, Frame)>,
+    /// The coordinated pause in progress, if any.
+    pause: PauseState<T::Address>,
+    /// The number of pauses that have been resumed, so messages of earlier pauses can be told apart.
+    pause_round: u32,
+    /// How often each player has joined the session with [`add_player_at_frame()`] or [`reconnect_player()`], and on which frame they last joined.
+    ///
+    /// [`add_player_at_frame()`]: Self#method.add_player_at_frame
+    /// [`reconnect_player()`]: Self#method.reconnect_player
+    join_rounds: Vec<(u32, Frame)>,
+    /// Joins announced by remote clients that this session has not made yet.
+    remote_joins: HashMap<PlayerHandle, (PlayerJoin, T::Address)>,
+    /// If set, the session does not advance past this frame until all inputs for it have been confirmed.
+    prediction_barrier: Option<Frame>,
 
     /// Contains all events to be forwarded to the user.
     event_queue: VecDeque<GGRSEvent<T>>,
+    /// Requests of a call to `advance_frame()` that failed after rolling back, handed out with the next frame that advances.
+    pending_requests: Vec<GGRSRequest<T>>,
     /// Contains all local inputs not yet sent into the system. This should have inputs for every local player before calling advance_frame
     local_inputs: HashMap<PlayerHandle, PlayerInput<T::Input>>,
+    /// Local inputs added ahead of time for contiguous future frames, per local player.
+    future_inputs: HashMap<PlayerHandle, VecDeque<PlayerInput<T::Input>>>,
+    /// The inputs most recently used to advance each of the last frames, up to the prediction window.
+    #[allow(clippy::type_complexity)]
+    input_history: VecDeque<(Frame, Vec<(T::Input, InputStatus, u8)>)>,
+    /// Used to create the endpoints of players joining the running session.
+    endpoint_settings: EndpointSettings,
+    /// The confirmed inputs of all players for each of the last frames, up to the prediction window.
+    confirmed_history: VecDeque<(Frame, Vec<PlayerInput<T::Input>>)>,
+    /// The next frame whose confirmed inputs are added to the history.
+    next_confirmed_history_frame: Frame,
 }
 
 impl<T: Config> P2PSession<T> {
     /// Creates a new [`P2PSession`] for players who participate on the game input. After creating the session, add local and remote players,
     /// set input delay for local players and then start the session. The session will use the provided socket.
     pub(crate) fn new(
-        num_players: usize,
-        max_prediction: usize,
+        settings: P2PSettings<T>,
         socket: Box<dyn NonBlockingSocket<T::Address>>,
         players: PlayerRegistry<T>,
-        sparse_saving: bool,
-        input_delay: usize,
     ) -> Self {
-        // local connection status
+        let P2PSettings {
+            num_players,
+            max_prediction,
+            max_saved_states,
+            sparse_saving,
+            state_saved_events,
+            time_sync,
+            fps,
+            input_delay,
+            prediction_strategy,
+            checksum_kind,
+            state_init,
+            state_store,
+            delta_applier,
+            input_desync_interval,
+            spectator_checksum_interval,
+            spectator_snapshots,
+            max_frame_lag,
+            lockstep,
+            desync_recovery,
+            checksum_authority,
+            min_adaptive_prediction,
+            disconnect_hook,
+            input_validator,
+            strict_input_ordering,
+            input_sizes,
+            endpoint_settings,
+            start_frame,
+        } = settings;
+        // local connection status, all frames before the start frame are confirmed
         let mut local_connect_status = Vec::new();
         for _ in 0..num_players {
-            local_connect_status.push(ConnectionStatus::default());
+            local_connect_status.push(ConnectionStatus {
+                disconnected: false,
+                last_frame: start_frame - 1,
+            });
         }
 
         // sync layer & set input delay
-        let mut sync_layer = SyncLayer::new(num_players, max_prediction);
+        let mut sync_layer = SyncLayer::new(
+            num_players,
+            max_prediction,
+            max_saved_states,
+            prediction_strategy,
+            checksum_kind,
+        );
+        if let Some(init) = state_init {
+            sync_layer.prefill_states(init);
+        }
+        if let Some(store) = state_store {
+            sync_layer.set_state_store(store);
+        }
+        if let Some(apply) = delta_applier {
+            sync_layer.enable_delta_states(apply);
+        }
+        if spectator_snapshots || desync_recovery.is_some() {
+            sync_layer.keep_state_bytes();
+        }
+        sync_layer.set_start_frame(start_frame);
+        // reserved player slots have no inputs until a player joins
+        for player_handle in 0..num_players {
+            if !players.handles.contains_key(&player_handle) {
+                sync_layer.set_first_frame(player_handle, Frame::MAX);
+            }
+        }
         for (player_handle, player_type) in players.handles.iter() {
             if let PlayerType::Local = player_type {
+                sync_layer.set_local(*player_handle);
                 sync_layer.set_frame_delay(*player_handle, input_delay);
             }
         }
+        for (&player_handle, &size) in input_sizes.iter() {
+            sync_layer.set_input_size(player_handle, size);
+        }
 
-        // initial session state - if there are no endpoints, we don't need a synchronization phase
-        let state = if players.remotes.len() + players.spectators.len() == 0 {
+        // initial session state - if there are no endpoints, we don't need a synchronization phase. Spectators can join late with snapshots.
+        let state = if players.remotes.is_empty()
+            && (players.spectators.is_empty() || spectator_snapshots)
+        {
             SessionState::Running
         } else {
             SessionState::Synchronizing
@@ -179,16 +432,65 @@ impl<T: Config> P2PSession<T> {
             num_players,
             max_prediction,
             sparse_saving,
+            state_saved_events,
+            force_save: false,
+            frame_limit_notified: false,
+            time_sync,
+            fps,
             socket,
+            received_messages: VecDeque::new(),
             local_connect_status,
             next_recommended_sleep: 0,
-            next_spectator_frame: 0,
+            recommended_skip_frames: 0,
+            next_spectator_frame: start_frame,
+            #[cfg(feature = "std")]
+            replay_writer: None,
             frames_ahead: 0,
+            accumulated_time: Duration::ZERO,
+            prewarmed: false,
+            rollback_stats: RollbackStats::default(),
+            input_desync_interval,
+            input_checksum: 0,
+            next_input_checksum_frame: start_frame,
+            input_checksum_history: VecDeque::new(),
+            spectator_checksum_interval,
+            next_spectator_checksum_frame: spectator_checksum_interval.map_or(0, |interval| {
+                let interval = interval as Frame;
+                start_frame.saturating_add(interval - 1) / interval * interval
+            }),
+            checksum_history: Vec::new(),
+            next_checksum_history_frame: start_frame,
+            spectator_snapshots,
+            late_spectators: Vec::new(),
+            max_frame_lag,
+            lockstep,
+            local_inputs_frame: NULL_FRAME,
+            last_acked_frame: NULL_FRAME,
+            desync_recovery,
+            checksum_authority,
+            min_adaptive_prediction,
+            disconnect_hook,
+            input_validator,
+            strict_input_ordering,
+            last_valid_inputs: HashMap::new(),
+            recovery: None,
+            state_requests: Vec::new(),
+            pause: PauseState::Unpaused,
+            pause_round: 0,
+            join_rounds: vec![(0, NULL_FRAME); num_players],
+            remote_joins: HashMap::new(),
+            prediction_barrier: None,
             sync_layer,
             disconnect_frame: NULL_FRAME,
             player_reg: players,
             event_queue: VecDeque::new(),
+            pending_requests: Vec::new(),
             local_inputs: HashMap::new(),
+            future_inputs: HashMap::new(),
+            input_history: VecDeque::new(),
+            confirmed_history: VecDeque::new(),
+            next_confirmed_history_frame: start_frame,
+            endpoint_settings,
         }
     }
 
@@ -197,13 +499,37 @@ impl<T: Config> P2PSession<T> {
     ///
     /// # Errors
     /// - Returns [`InvalidRequest`] when the given handle does not refer to a local player.
+    /// - Returns [`FrameLagExceeded`] if the current frame is too far ahead of the confirmed frame, see [`SessionBuilder::with_max_frame_lag()`].
+    /// - Returns [`MismatchedInputSize`] when the input uses more bytes than the player, see [`SessionBuilder::add_player_with_input_size()`].
     ///
     /// [`advance_frame()`]: Self#method.advance_frame
     /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    /// [`FrameLagExceeded`]: GGRSError::FrameLagExceeded
+    /// [`SessionBuilder::with_max_frame_lag()`]: crate::SessionBuilder#method.with_max_frame_lag
+    /// [`MismatchedInputSize`]: GGRSError::MismatchedInputSize
+    /// [`SessionBuilder::add_player_with_input_size()`]: crate::SessionBuilder#method.add_player_with_input_size
     pub fn add_local_input(
         &mut self,
         player_handle: PlayerHandle,
         input: T::Input,
+    ) -> Result<(), GGRSError> {
+        self.add_local_input_with_flags(player_handle, input, 0)
+    }
+
+    /// Same as [`add_local_input()`], but attaches the given `flags` to the input. The flags are sent to the remote players along with the input
+    /// and handed back to you next to the input in [`GGRSRequest::AdvanceFrame`], without being part of your input type.
+    /// You can use them for out-of-band information, e.g. to mark whether an input was given by the user or synthesized by your game.
+    /// Since the flags are part of the input, a mispredicted flag causes a rollback like a mispredicted input.
+    ///
+    /// # Errors
+    /// - Returns the same errors as [`add_local_input()`].
+    ///
+    /// [`add_local_input()`]: Self#method.add_local_input
+    pub fn add_local_input_with_flags(
+        &mut self,
+        player_handle: PlayerHandle,
+        input: T::Input,
+        flags: u8,
     ) -> Result<(), GGRSError> {
         // make sure the input is for a registered local player
         if !self
@@ -216,48 +542,416 @@ impl<T: Config> P2PSession<T> {
                     .to_owned(),
             });
         }
-        let player_input = PlayerInput::<T::Input>::new(self.sync_layer.current_frame(), input);
+        self.check_not_ended()?;
+        self.check_frame_lag()?;
+        self.validate_local_input(&input)?;
+        self.check_input_ordering(player_handle)?;
+        self.sync_layer.check_input_size(player_handle, &input)?;
+        let player_input =
+            PlayerInput::<T::Input>::new(self.sync_layer.current_frame(), input).with_flags(flags);
         self.local_inputs.insert(player_handle, player_input);
         Ok(())
     }
 
+    /// Same as [`add_local_input()`], but takes any [`Pod`] type with the same size as the input of the player, e.g. a byte array
+    /// your input has been serialized to. The input is reinterpreted as the input type with [`bytemuck`], so no serialization is needed at the call site.
+    /// Players added with [`SessionBuilder::add_player_with_input_size()`] take inputs of their size, which fill the start of the input type.
+    ///
+    /// # Errors
+    /// - Returns [`MismatchedInputSize`] when the size of the given input does not match the input size of the player.
+    /// - Returns the same errors as [`add_local_input()`].
+    ///
+    /// [`add_local_input()`]: Self#method.add_local_input
+    /// [`Pod`]: bytemuck::Pod
+    /// [`SessionBuilder::add_player_with_input_size()`]: crate::SessionBuilder#method.add_player_with_input_size
+    /// [`MismatchedInputSize`]: GGRSError::MismatchedInputSize
+    pub fn add_local_input_typed<I: bytemuck::Pod>(
+        &mut self,
+        player_handle: PlayerHandle,
+        input: I,
+    ) -> Result<(), GGRSError> {
+        let expected = if player_handle < self.num_players {
+            self.sync_layer.input_size(player_handle)
+        } else {
+            core::mem::size_of::<T::Input>()
+        };
+        let got = core::mem::size_of::<I>();
+        if got != expected {
+            return Err(GGRSError::MismatchedInputSize { expected, got });
+        }
+        let mut full_input = T::Input::zeroed();
+        bytemuck::bytes_of_mut(&mut full_input)[..got].copy_from_slice(bytemuck::bytes_of(&input));
+        self.add_local_input(player_handle, full_input)
+    }
+
+    /// Adds the input of a local player for a future frame, e.g. to feed recorded inputs faster than real time.
+    /// When the session advances to that frame, the input is used as if it had been given with [`add_local_input()`], unless you add another input for this frame.
+    /// Inputs have to be added for contiguous frames: The first input for a player has to be for the next frame without an input,
+    /// and every further input for the frame after the previous one.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle does not refer to a local player, if the frame has already been advanced or its input has already been added,
+    ///   if the frame does not directly follow the last added input of the player, or if 128 inputs of the player are already waiting for their frames.
+    /// - Returns [`SessionEnded`] if the session has been ended with [`end_session()`].
+    /// - Returns [`MismatchedInputSize`] when the input uses more bytes than the player, see [`SessionBuilder::add_player_with_input_size()`].
+    ///
+    /// [`add_local_input()`]: Self#method.add_local_input
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    /// [`SessionEnded`]: GGRSError::SessionEnded
+    /// [`end_session()`]: Self#method.end_session
+    /// [`MismatchedInputSize`]: GGRSError::MismatchedInputSize
+    /// [`SessionBuilder::add_player_with_input_size()`]: crate::SessionBuilder#method.add_player_with_input_size
+    pub fn add_local_input_for_frame(
+        &mut self,
+        player_handle: PlayerHandle,
+        frame: Frame,
+        input: T::Input,
+    ) -> Result<(), GGRSError> {
+        if !self
+            .player_reg
+            .local_player_handles()
+            .contains(&player_handle)
+        {
+            return Err(GGRSError::InvalidRequest {
+                info: "The player handle you provided is not referring to a local player."
+                    .to_owned(),
+            });
+        }
+        self.check_not_ended()?;
+        self.validate_local_input(&input)?;
+        self.sync_layer.check_input_size(player_handle, &input)?;
+        let current_frame = self.sync_layer.current_frame();
+        let queue = self.future_inputs.entry(player_handle).or_default();
+        let next_frame = match queue.back() {
+            Some(last) => last.frame + 1,
+            // the input of the current frame might already have been added or, in lockstep mode, sent
+            None if self.local_inputs.contains_key(&player_handle)
+                || self.local_inputs_frame == current_frame =>
+            {
+                current_frame + 1
+            }
+            None => current_frame,
+        };
+        if self.strict_input_ordering && frame != next_frame {
+            return Err(GGRSError::NonMonotonicInput {
+                expected: next_frame,
+                got: frame,
+            });
+        }
+        if frame < next_frame {
+            return Err(GGRSError::InvalidRequest {
+                info: format!(
+                    "The input for frame {} has already been added or advanced.",
+                    frame
+                ),
+            });
+        }
+        if frame > next_frame {
+            return Err(GGRSError::InvalidRequest {
+                info: format!(
+                    "Inputs have to be added for contiguous frames, the next frame is {}.",
+                    next_frame
+                ),
+            });
+        }
+        if queue.len() >= INPUT_QUEUE_LENGTH {
+            return Err(GGRSError::InvalidRequest {
+                info: format!(
+                    "At most {} inputs can be added ahead of the current frame.",
+                    INPUT_QUEUE_LENGTH
+                ),
+            });
+        }
+        queue.push_back(PlayerInput::new(frame, input));
+        Ok(())
+    }
+
+    /// Changes the input delay of all local players. Unlike the delay given to [`SessionBuilder::with_input_delay()`], this can be changed while the session is running.
+    /// Inputs that have already been added are kept as they are:
+    /// - If the delay grows by `n` frames, the next input you add is scheduled `n` frames later than the previous one. The `n` frames in between
+    ///   repeat the last added input of each local player and are sent to the remote players like any other input.
+    /// - If the delay shrinks by `n` frames, the next `n` inputs you add are dropped, since their frames already have inputs scheduled.
+    ///
+    /// Either way, every frame receives exactly one input, so all peers keep simulating the same inputs.
+    /// All local players share the same delay, since their inputs are sent to the remote players together.
+    ///
+    /// [`SessionBuilder::with_input_delay()`]: crate::SessionBuilder#method.with_input_delay
+    pub fn set_input_delay(&mut self, delay: usize) {
+        for handle in self.player_reg.local_player_handles() {
+            self.sync_layer.set_frame_delay(handle, delay);
+        }
+    }
+
+    /// Returns a summary of the settings of this session, e.g. to log it or compare it with the settings of other peers.
+    /// The input delay is the current delay of the local players, see [`set_input_delay()`]. The timeouts are the ones the session has been
+    /// started with, even if they have been changed for single players with [`set_disconnect_timeout()`] or [`set_disconnect_notify_delay()`].
+    ///
+    /// [`set_input_delay()`]: Self#method.set_input_delay
+    /// [`set_disconnect_timeout()`]: Self#method.set_disconnect_timeout
+    /// [`set_disconnect_notify_delay()`]: Self#method.set_disconnect_notify_delay
+    pub fn config_summary(&self) -> SessionConfigSummary {
+        let input_delay = self
+            .player_reg
+            .local_player_handles()
+            .first()
+            .map_or(0, |&handle| self.sync_layer.frame_delay(handle));
+        SessionConfigSummary {
+            num_players: self.num_players,
+            input_delay,
+            max_prediction: self.sync_layer.max_prediction_limit(),
+            sparse_saving: self.sparse_saving,
+            fps: self.fps,
+            disconnect_timeout: self.endpoint_settings.disconnect_timeout,
+            disconnect_notify_start: self.endpoint_settings.disconnect_notify_start,
+        }
+    }
+
+    /// Returns the seed set with [`SessionBuilder::with_shared_seed()`]. Once the session is running, all remote clients have confirmed the same seed.
+    ///
+    /// [`SessionBuilder::with_shared_seed()`]: crate::SessionBuilder#method.with_shared_seed
+    pub fn shared_seed(&self) -> Option<u64> {
+        self.endpoint_settings.shared_seed
+    }
+
+    /// Registers local inputs for several local players at once. All handles are validated before any input is registered,
+    /// so if this returns an error, none of the given inputs have been registered.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] naming the first handle that does not refer to a local player.
+    /// - Returns [`FrameLagExceeded`] if the current frame is too far ahead of the confirmed frame, see [`SessionBuilder::with_max_frame_lag()`].
+    /// - Returns [`MismatchedInputSize`] when an input uses more bytes than its player, see [`SessionBuilder::add_player_with_input_size()`].
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    /// [`FrameLagExceeded`]: GGRSError::FrameLagExceeded
+    /// [`SessionBuilder::with_max_frame_lag()`]: crate::SessionBuilder#method.with_max_frame_lag
+    /// [`MismatchedInputSize`]: GGRSError::MismatchedInputSize
+    /// [`SessionBuilder::add_player_with_input_size()`]: crate::SessionBuilder#method.add_player_with_input_size
+    pub fn add_local_inputs(
+        &mut self,
+        inputs: &[(PlayerHandle, T::Input)],
+    ) -> Result<(), GGRSError> {
+        let local_handles = self.player_reg.local_player_handles();
+        if let Some((handle, _)) = inputs
+            .iter()
+            .find(|(handle, _)| !local_handles.contains(handle))
+        {
+            return Err(GGRSError::InvalidRequest {
+                info: format!(
+                    "The player handle {} you provided is not referring to a local player.",
+                    handle
+                ),
+            });
+        }
+        self.check_not_ended()?;
+        self.check_frame_lag()?;
+        for (handle, input) in inputs {
+            self.validate_local_input(input)?;
+            self.check_input_ordering(*handle)?;
+            self.sync_layer.check_input_size(*handle, input)?;
+        }
+        // the same player given twice in one call is a double submission as well
+        if self.strict_input_ordering {
+            let current_frame = self.sync_layer.current_frame();
+            for (i, (handle, _)) in inputs.iter().enumerate() {
+                if inputs[..i].iter().any(|(other, _)| other == handle) {
+                    return Err(GGRSError::NonMonotonicInput {
+                        expected: current_frame + 1,
+                        got: current_frame,
+                    });
+                }
+            }
+        }
+        for &(handle, input) in inputs {
+            let player_input = PlayerInput::<T::Input>::new(self.sync_layer.current_frame(), input);
+            self.local_inputs.insert(handle, player_input);
+        }
+        Ok(())
+    }
+
     /// You should call this to notify GGRS that you are ready to advance your gamestate by a single frame.
     /// Returns an order-sensitive [`Vec<GGRSRequest>`]. You should fulfill all requests in the exact order they are provided.
-    /// Failure to do so will cause panics later.
+    /// Failure to do so will cause panics later. If this returns an error after the session already rolled back, e.g. [`PredictionThreshold`],
+    /// the requests of the rollback are returned first the next time the session advances a frame.
     ///
     /// # Errors
-    /// - Returns [`InvalidRequest`] if the provided player handle refers to a remote player.
+    /// - Returns [`InvalidRequest`] if the provided player handle refers to a remote player, or if the frame counter reached [`Frame::MAX`],
+    ///   see [`GGRSEvent::FrameCounterNearLimit`].
     /// - Returns [`NotSynchronized`] if the session is not yet ready to accept input. In this case, you either need to start the session or wait for synchronization between clients.
+    /// - Returns [`EmptySaveState`] if the state to roll back to has not been saved. This is fatal, since the session cannot roll back without that state.
+    /// - Returns [`StateNotAvailable`] if the state to roll back to is not kept anymore, see [`SessionBuilder::with_max_saved_states()`]. This is fatal.
+    /// - Returns [`FrameLagExceeded`] if the current frame is too far ahead of the confirmed frame, see [`SessionBuilder::with_max_frame_lag()`].
+    /// - Returns [`WaitingForInputs`] in lockstep mode if the remote inputs for the current frame have not been received yet,
+    ///   see [`SessionBuilder::start_lockstep_session()`].
+    /// - Returns [`Paused`] if the session reached the frame of a coordinated pause, see [`pause()`].
+    /// - Returns [`PredictionThreshold`] if the session would predict past the frame given to [`set_prediction_barrier()`].
+    /// - Returns [`SessionEnded`] if the session has been ended with [`end_session()`].
     ///
     /// [`Vec<GGRSRequest>`]: GGRSRequest
+    /// [`Paused`]: GGRSError::Paused
+    /// [`pause()`]: Self#method.pause
+    /// [`PredictionThreshold`]: GGRSError::PredictionThreshold
+    /// [`set_prediction_barrier()`]: Self#method.set_prediction_barrier
     /// [`InvalidRequest`]: GGRSError::InvalidRequest
     /// [`NotSynchronized`]: GGRSError::NotSynchronized
+    /// [`EmptySaveState`]: GGRSError::EmptySaveState
+    /// [`StateNotAvailable`]: GGRSError::StateNotAvailable
+    /// [`SessionBuilder::with_max_saved_states()`]: crate::SessionBuilder#method.with_max_saved_states
+    /// [`FrameLagExceeded`]: GGRSError::FrameLagExceeded
+    /// [`SessionBuilder::with_max_frame_lag()`]: crate::SessionBuilder#method.with_max_frame_lag
+    /// [`WaitingForInputs`]: GGRSError::WaitingForInputs
+    /// [`SessionBuilder::start_lockstep_session()`]: crate::SessionBuilder#method.start_lockstep_session
+    /// [`SessionEnded`]: GGRSError::SessionEnded
+    /// [`end_session()`]: Self#method.end_session
     pub fn advance_frame(&mut self) -> Result<Vec<GGRSRequest<T>>, GGRSError> {
+        // This list of requests will be returned to the user
+        let mut requests = Vec::new();
+        self.advance_frame_into(&mut requests)?;
+        Ok(requests)
+    }
+
+    /// Same as [`advance_frame()`], but writes the requests into the given buffer instead of allocating a new [`Vec`] every frame.
+    /// The buffer is cleared first, so you need to fulfill all requests in it before advancing the session again.
+    /// If this returns an error, the buffer is empty and the requests of a rollback that already happened are handed out with the next frame, like in [`advance_frame()`].
+    ///
+    /// # Errors
+    /// - Returns the same errors as [`advance_frame()`].
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    pub fn advance_frame_into(
+        &mut self,
+        requests: &mut Vec<GGRSRequest<T>>,
+    ) -> Result<(), GGRSError> {
+        requests.clear();
+        requests.append(&mut self.pending_requests);
+        let result = self.push_frame_requests(requests);
+        if result.is_err() {
+            self.pending_requests.append(requests);
+        }
+        result
+    }
+
+    /// Advances the session by up to `n` frames at once, e.g. to catch up after your application has been in the background.
+    /// For every frame, `local_input` is called with the handle of each local player and the current frame to get their input.
+    /// All requests are collected in the returned [`FastForward`], so you can fulfill them in one go and only render the final state.
+    ///
+    /// If the session cannot advance all `n` frames, it stops early and returns the error in [`FastForward::result`], together with
+    /// the requests of all frames it did advance. Most notably, this is [`PredictionThreshold`] when the session would run too far ahead
+    /// of the remote players. In this case, you should wait for their inputs before advancing further.
+    ///
+    /// [`PredictionThreshold`]: GGRSError::PredictionThreshold
+    pub fn advance_frames(
+        &mut self,
+        n: usize,
+        mut local_input: impl FnMut(PlayerHandle, Frame) -> T::Input,
+    ) -> FastForward<T> {
+        let mut fast_forward = FastForward {
+            requests: core::mem::take(&mut self.pending_requests),
+            frames_advanced: 0,
+            result: Ok(()),
+        };
+        for _ in 0..n {
+            let frame = self.sync_layer.current_frame();
+            for handle in self.player_reg.local_player_handles() {
+                let input = local_input(handle, frame);
+                if let Err(e) = self
+                    .sync_layer
+                    .check_input_size(handle, &input)
+                    .and_then(|()| self.validate_local_input(&input))
+                {
+                    fast_forward.result = Err(e);
+                    return fast_forward;
+                }
+                let player_input = PlayerInput::<T::Input>::new(frame, input);
+                self.local_inputs.insert(handle, player_input);
+            }
+            // requests of a failed frame, e.g. a rollback, still need to be fulfilled
+            if let Err(e) = self.push_frame_requests(&mut fast_forward.requests) {
+                fast_forward.result = Err(e);
+                break;
+            }
+            fast_forward.frames_advanced += 1;
+        }
+        fast_forward
+    }
+
+    /// Advances the session by a single frame and appends the resulting requests.
+    fn push_frame_requests(&mut self, requests: &mut Vec<GGRSRequest<T>>) -> Result<(), GGRSError> {
+        // the states saved while prewarming are never loaded
+        if self.prewarmed {
+            self.sync_layer.discard_saved_states();
+            self.prewarmed = false;
+        }
+
         // receive info from remote players, trigger events and send messages
         self.poll_remote_clients();
 
         // session is not running and synchronized
-        if self.state != SessionState::Running {
-            return Err(GGRSError::NotSynchronized);
+        match self.state {
+            SessionState::Running => (),
+            SessionState::Synchronizing => return Err(GGRSError::NotSynchronized),
+            SessionState::Ended => return Err(GGRSError::SessionEnded),
         }
 
-        // This list of requests will be returned to the user
-        let mut requests = Vec::new();
+        if self.sync_layer.current_frame() == Frame::MAX {
+            return Err(GGRSError::InvalidRequest {
+                info: "The frame counter reached its limit.".to_owned(),
+            });
+        }
+
+        // stay at the frame of a coordinated pause. Inputs added meanwhile are ignored
+        if self.is_paused() {
+            self.local_inputs.clear();
+            return Err(GGRSError::Paused);
+        }
+
+        // refuse to run further ahead if the remote inputs stopped arriving
+        self.check_frame_lag()?;
+
+        // do not predict past a frame that must not be mispredicted
+        self.check_prediction_barrier()?;
+
+        // follow the latency to the remote players with the prediction window, if enabled
+        self.adapt_prediction_window();
+
+        // in lockstep mode, the local inputs are sent right away, so the remote players can confirm the frame we are waiting for
+        if self.lockstep {
+            if self.local_inputs_frame != self.sync_layer.current_frame() {
+                self.send_local_inputs()?;
+            }
+            // inputs added while waiting are ignored, since the inputs for the current frame have already been sent
+            self.local_inputs.clear();
+            if !self.next_frame_is_confirmed() {
+                return Err(GGRSError::WaitingForInputs);
+            }
+        }
+
+        // send the requested states to remote clients recovering from a desync, now that they are final
+        self.send_recovery_states();
+        self.send_state_checksums_to_spectators();
+        self.record_checksum_history();
 
         /*
          * ROLLBACKS AND GAME STATE MANAGEMENT
          */
 
         // if we are in the first frame, we have to save the state
-        if self.sync_layer.current_frame() == 0 {
-            requests.push(self.sync_layer.save_current_state());
+        if self.sync_layer.current_frame() == self.sync_layer.start_frame() {
+            requests.push(self.save_current_state());
         }
 
         // propagate disconnects to multiple players
         self.update_player_disconnects();
 
         // find the confirmed frame for which we received all inputs
-        let confirmed_frame = self.confirmed_frame();
+        let mut confirmed_frame = self.confirmed_frame();
+        // while recovering from a desync, keep all inputs from the requested frame on, so we can resimulate from there
+        if self.recovery.is_some() && self.authority_addr().is_none() {
+            // the authoritative peer disconnected
+            self.abandon_recovery();
+        }
+        if let Some(recovery) = &self.recovery {
+            confirmed_frame = core::cmp::min(confirmed_frame, recovery.frame - 1);
+        }
 
         // check game consistency and rollback, if necessary.
         // The disconnect frame indicates if a rollback is necessary due to a previously disconnected player
@@ -265,24 +959,40 @@ impl<T: Config> P2PSession<T> {
             .sync_layer
             .check_simulation_consistency(self.disconnect_frame);
         if first_incorrect != NULL_FRAME {
-            self.adjust_gamestate(first_incorrect, confirmed_frame, &mut requests);
+            self.adjust_gamestate(first_incorrect, confirmed_frame, requests)?;
             self.disconnect_frame = NULL_FRAME;
         }
 
+        // load the state received from the authoritative peer, if we are recovering from a desync
+        self.load_recovered_state(confirmed_frame, requests)?;
+
         let last_saved = self.sync_layer.last_saved_frame();
         if self.sparse_saving {
-            self.check_last_saved_state(last_saved, confirmed_frame, &mut requests);
+            self.check_last_saved_state(last_saved, confirmed_frame, requests)?;
+            // save the current frame on demand, unless it just has been saved anyway
+            if self.force_save
+                && self.sync_layer.last_saved_frame() != self.sync_layer.current_frame()
+            {
+                let request = self.sync_layer.checkpoint_current_state();
+                self.notify_state_saved();
+                requests.push(request);
+            }
         } else {
             // without sparse saving, always save the current frame after correcting and rollbacking
-            requests.push(self.sync_layer.save_current_state());
+            requests.push(self.save_current_state());
         }
 
         /*
          *  SEND OFF AND THROW AWAY INPUTS BEFORE THE CONFIRMED FRAME
          */
 
-        // send confirmed inputs to spectators before throwing them away
+        // send confirmed inputs to spectators and record them before throwing them away
+        self.send_snapshots_to_late_spectators();
         self.send_confirmed_inputs_to_spectators(confirmed_frame);
+        #[cfg(feature = "std")]
+        self.write_confirmed_inputs_to_replay(confirmed_frame);
+        self.record_confirmed_inputs(confirmed_frame);
+        self.check_input_checksums(confirmed_frame);
 
         // set the last confirmed frame and discard all saved inputs before that frame
         self.sync_layer
@@ -299,61 +1009,67 @@ impl<T: Config> P2PSession<T> {
          *  INPUTS
          */
 
-        // register local inputs in the system and send them
-        for handle in self.player_reg.local_player_handles() {
-            match self.local_inputs.get_mut(&handle) {
-                Some(player_input) => {
-                    // send the input into the sync layer
-                    let actual_frame = self.sync_layer.add_local_input(handle, *player_input)?;
-                    assert!(actual_frame != NULL_FRAME);
-                    // if not dropped, send the input to all other clients, but with the correct frame (influenced by input delay)
-                    player_input.frame = actual_frame;
-                    self.local_connect_status[handle].last_frame = actual_frame;
-                }
-                None => {
-                    return Err(GGRSError::InvalidRequest {
-                        info: "Missing local input while calling advance_frame().".to_owned(),
-                    });
-                }
-            }
-        }
-
-        // send the inputs to all clients
-        for endpoint in self.player_reg.remotes.values_mut() {
-            // send the input directly
-            endpoint.send_input(&self.local_inputs, &self.local_connect_status);
-            endpoint.send_all_messages(&mut self.socket);
+        // register local inputs in the system and send them, unless this already happened for the current frame in lockstep mode
+        if self.local_inputs_frame != self.sync_layer.current_frame() {
+            self.send_local_inputs()?;
         }
 
-        // clear the local inputs after sending them
-        self.local_inputs.clear();
-
         /*
          * ADVANCE THE STATE
          */
 
         // get correct inputs for the current frame
-        let inputs = self
-            .sync_layer
-            .synchronized_inputs(&self.local_connect_status);
+        let inputs = self.synchronized_inputs();
         // advance the frame count
         self.sync_layer.advance_frame();
         requests.push(GGRSRequest::AdvanceFrame { inputs });
+        // the frame has been advanced, so a requested save has been handed out
+        self.force_save = false;
+        self.check_frame_limit();
 
-        Ok(requests)
+        Ok(())
     }
 
     /// Should be called periodically by your application to give GGRS a chance to do internal work.
     /// GGRS will receive packets, distribute them to corresponding endpoints, handle all occurring events and send all outgoing packets.
     pub fn poll_remote_clients(&mut self) {
+        self.poll_remote_clients_limited(usize::MAX);
+    }
+
+    /// Same as [`poll_remote_clients()`], but handles at most `max_messages` received messages, so a burst of packets cannot blow your frame budget.
+    /// The remaining messages are kept and handled first the next time the session is polled. All other internal work is done as usual.
+    /// At most 1024 messages are kept, so if messages keep arriving faster than they are handled, the oldest ones are dropped like lost packets.
+    /// Returns true if received messages remain to be handled.
+    ///
+    /// [`poll_remote_clients()`]: Self#method.poll_remote_clients
+    pub fn poll_remote_clients_limited(&mut self, max_messages: usize) -> bool {
         // Get all packets and distribute them to associated endpoints.
         // The endpoints will handle their packets, which will trigger both events and UPD replies.
-        for (from_addr, msg) in &self.socket.receive_all_messages() {
-            if let Some(endpoint) = self.player_reg.remotes.get_mut(from_addr) {
-                endpoint.handle_message(msg);
+        self.received_messages
+            .extend(self.socket.receive_all_messages());
+        while self.received_messages.len() > MAX_RECEIVED_MESSAGES {
+            self.received_messages.pop_front();
+        }
+        let count = core::cmp::min(max_messages, self.received_messages.len());
+        for (from_addr, msg) in self.received_messages.drain(..count) {
+            if let Some(endpoint) = self.player_reg.remotes.get_mut(&from_addr) {
+                endpoint.handle_message(&msg);
             }
-            if let Some(endpoint) = self.player_reg.spectators.get_mut(from_addr) {
-                endpoint.handle_message(msg);
+            if let Some(endpoint) = self.player_reg.spectators.get_mut(&from_addr) {
+                // a disconnected spectator that synchronizes again reconnects and resumes from a snapshot
+                if endpoint.is_disconnected()
+                    && self.spectator_snapshots
+                    && matches!(msg.body, MessageBody::SyncRequest(_))
+                {
+                    *endpoint = self.endpoint_settings.create_spectator_endpoint(
+                        endpoint.handles().clone(),
+                        from_addr.clone(),
+                        self.num_players,
+                        self.sync_layer.max_prediction_limit(),
+                        self.fps,
+                    );
+                }
+                endpoint.handle_message(&msg);
             }
         }
 
@@ -365,15 +1081,20 @@ impl<T: Config> P2PSession<T> {
         }
 
         // run endpoint poll and get events from players and spectators. This will trigger additional packets to be sent.
+        // The endpoints are polled in order of their handles, so simultaneous events like disconnects are always handled in the same order
         let mut events = VecDeque::new();
-        for endpoint in self.player_reg.remotes.values_mut() {
+        let mut remotes: Vec<_> = self.player_reg.remotes.values_mut().collect();
+        remotes.sort_unstable_by_key(|endpoint| endpoint.handles()[0]);
+        for endpoint in remotes {
             let handles = endpoint.handles().clone();
             let addr = endpoint.peer_addr();
             for event in endpoint.poll(&self.local_connect_status) {
                 events.push_back((event, handles.clone(), addr.clone()))
             }
         }
-        for endpoint in self.player_reg.spectators.values_mut() {
+        let mut spectators: Vec<_> = self.player_reg.spectators.values_mut().collect();
+        spectators.sort_unstable_by_key(|endpoint| endpoint.handles()[0]);
+        for endpoint in spectators {
             let handles = endpoint.handles().clone();
             let addr = endpoint.peer_addr();
             for event in endpoint.poll(&self.local_connect_status) {
@@ -385,6 +1106,9 @@ impl<T: Config> P2PSession<T> {
         for (event, handles, addr) in events.drain(..) {
             self.handle_event(event, handles, addr);
         }
+        self.check_late_joins();
+
+        self.check_acked_inputs();
 
         // send all queued packets
         for endpoint in self.player_reg.remotes.values_mut() {
@@ -393,12 +1117,96 @@ impl<T: Config> P2PSession<T> {
         for endpoint in self.player_reg.spectators.values_mut() {
             endpoint.send_all_messages(&mut self.socket);
         }
+
+        !self.received_messages.is_empty()
+    }
+
+    /// Lets a remote player join the running session in a slot reserved with [`SessionBuilder::reserve_player()`]. The inputs of the player are used
+    /// from `frame` on, before that frame the player is [`InputStatus::Disconnected`]. For all clients to agree on the inputs, every client already
+    /// in the session has to add the player with the same frame before advancing that frame, so pick a frame far enough ahead to tell everyone in time,
+    /// e.g. with [`send_reliable()`]. If the player never connects, disconnect them with [`disconnect_player()`].
+    /// The session tells the other remote clients on which frame the player joins, and sends a [`GGRSEvent::PlayerJoinConflict`] if one of them
+    /// lets the player join on another frame or too late.
+    ///
+    /// The joining client starts its own session with all players on that frame, see [`SessionBuilder::with_start_frame()`].
+    /// Only players on a new address can join, since the inputs of all players on a client are sent together.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle does not refer to a reserved player slot that is still empty.
+    /// - Returns [`InvalidRequest`] if the player is not a remote player with an address new to the session.
+    /// - Returns [`InvalidRequest`] if the given frame has already been advanced.
+    /// - Returns [`InvalidRequest`] if a remote client already lets the player join on another frame.
+    ///
+    /// [`SessionBuilder::reserve_player()`]: crate::SessionBuilder#method.reserve_player
+    /// [`SessionBuilder::with_start_frame()`]: crate::SessionBuilder#method.with_start_frame
+    /// [`send_reliable()`]: Self#method.send_reliable
+    /// [`disconnect_player()`]: Self#method.disconnect_player
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn add_player_at_frame(
+        &mut self,
+        player_type: PlayerType<T::Address>,
+        player_handle: PlayerHandle,
+        frame: Frame,
+    ) -> Result<(), GGRSError> {
+        if player_handle >= self.num_players || self.player_reg.handles.contains_key(&player_handle)
+        {
+            return Err(GGRSError::InvalidRequest {
+                info: "The player handle does not refer to an empty reserved player slot."
+                    .to_owned(),
+            });
+        }
+        let addr = match player_type {
+            PlayerType::Remote(addr)
+                if !self.player_reg.remotes.contains_key(&addr)
+                    && !self.player_reg.spectators.contains_key(&addr) =>
+            {
+                addr
+            }
+            _ => {
+                return Err(GGRSError::InvalidRequest {
+                    info: "Only remote players with a new address can join a running session."
+                        .to_owned(),
+                })
+            }
+        };
+        if frame < self.sync_layer.current_frame() {
+            return Err(GGRSError::InvalidRequest {
+                info: "The frame the player joins on has already been advanced.".to_owned(),
+            });
+        }
+        self.check_join_conflicts(&[player_handle], frame)?;
+
+        let endpoint = self.endpoint_settings.create_endpoint(
+            vec![player_handle],
+            addr.clone(),
+            self.num_players,
+            self.player_reg.local_player_handles().len(),
+            self.sync_layer.max_prediction_limit(),
+            self.fps,
+        );
+        self.player_reg
+            .handles
+            .insert(player_handle, PlayerType::Remote(addr.clone()));
+        self.announce_joins(&[player_handle], frame);
+        self.player_reg.remotes.insert(addr, endpoint);
+        // all frames before the player joins are confirmed
+        self.sync_layer.set_first_frame(player_handle, frame);
+        self.local_connect_status[player_handle] = ConnectionStatus {
+            disconnected: false,
+            last_frame: frame - 1,
+        };
+        Ok(())
     }
 
     /// Disconnects a remote player and all other remote players with the same address from the session.
+    ///
+    /// After a disconnect, every client keeps simulating while treating the disconnected players as such, starting from the frame after their last confirmed input.
+    /// The players stay disconnected until they rejoin with [`reconnect_player()`].
+    ///
     /// # Errors
     /// - Returns [`InvalidRequest`] if you try to disconnect a local player or the provided handle is invalid.
     ///
+    /// [`reconnect_player()`]: Self#method.reconnect_player
     /// [`InvalidRequest`]: GGRSError::InvalidRequest
     pub fn disconnect_player(&mut self, player_handle: PlayerHandle) -> Result<(), GGRSError> {
         match self.player_reg.handles.get(&player_handle) {
@@ -428,71 +1236,774 @@ impl<T: Config> P2PSession<T> {
         }
     }
 
-    /// Returns a [`NetworkStats`] struct that gives information about the quality of the network connection.
+    /// Lets a disconnected remote player and all other players that were disconnected with them rejoin the session from the given address.
+    /// The session synchronizes with the address again and uses the inputs of the players from `frame` on. The frames between the disconnect
+    /// and `frame` stay [`InputStatus::Disconnected`]. As with [`add_player_at_frame()`], every client in the session has to reconnect the players
+    /// with the same frame before advancing that frame, and the rejoining client starts its own session on that frame with the current game state,
+    /// see [`SessionBuilder::with_start_frame()`].
+    ///
     /// # Errors
-    /// - Returns [`InvalidRequest`] if the handle not referring to a remote player or spectator.
-    /// - Returns [`NotSynchronized`] if the session is not connected to other clients yet.
+    /// - Returns [`InvalidRequest`] if the handle does not refer to a disconnected remote player.
+    /// - Returns [`InvalidRequest`] if the address belongs to another player or spectator of the session.
+    /// - Returns [`InvalidRequest`] if the given frame has already been advanced.
+    /// - Returns [`InvalidRequest`] if the frames before the disconnect have not all been confirmed yet. Try again after advancing a few frames.
+    /// - Returns [`InvalidRequest`] if a remote client already lets the players rejoin on another frame.
     ///
+    /// [`add_player_at_frame()`]: Self#method.add_player_at_frame
+    /// [`SessionBuilder::with_start_frame()`]: crate::SessionBuilder#method.with_start_frame
     /// [`InvalidRequest`]: GGRSError::InvalidRequest
-    /// [`NotSynchronized`]: GGRSError::NotSynchronized
-    pub fn network_stats(&self, player_handle: PlayerHandle) -> Result<NetworkStats, GGRSError> {
-        match self.player_reg.handles.get(&player_handle) {
-            Some(PlayerType::Remote(addr)) => self
-                .player_reg
-                .remotes
-                .get(addr)
-                .expect("Endpoint should exist for any registered player")
-                .network_stats(),
-            Some(PlayerType::Spectator(addr)) => self
-                .player_reg
-                .remotes
-                .get(addr)
-                .expect("Endpoint should exist for any registered player")
-                .network_stats(),
-            _ => Err(GGRSError::InvalidRequest {
-                info: "Given player handle not referring to a remote player or spectator"
-                    .to_owned(),
-            }),
-        }
-    }
-
-    /// Returns the highest confirmed frame. We have received all input for this frame and it is thus correct.
-    pub fn confirmed_frame(&self) -> Frame {
-        let mut confirmed_frame = i32::MAX;
-
-        for con_stat in &self.local_connect_status {
-            if !con_stat.disconnected {
-                confirmed_frame = std::cmp::min(confirmed_frame, con_stat.last_frame);
+    pub fn reconnect_player(
+        &mut self,
+        player_handle: PlayerHandle,
+        addr: T::Address,
+        frame: Frame,
+    ) -> Result<(), GGRSError> {
+        let old_addr = match self.player_reg.handles.get(&player_handle) {
+            Some(PlayerType::Remote(old_addr))
+                if self.local_connect_status[player_handle].disconnected =>
+            {
+                old_addr.clone()
             }
-        }
-
-        assert!(confirmed_frame < i32::MAX);
+            _ => {
+                return Err(GGRSError::InvalidRequest {
+                    info: "The player handle does not refer to a disconnected remote player."
+                        .to_owned(),
+                })
+            }
+        };
+        if (addr != old_addr && self.player_reg.remotes.contains_key(&addr))
+            || self.player_reg.spectators.contains_key(&addr)
+        {
+            return Err(GGRSError::InvalidRequest {
+                info: "The address belongs to another player or spectator.".to_owned(),
+            });
+        }
+        if frame < self.sync_layer.current_frame() {
+            return Err(GGRSError::InvalidRequest {
+                info: "The frame the player rejoins on has already been advanced.".to_owned(),
+            });
+        }
+        let handles = self
+            .player_reg
+            .remotes
+            .get(&old_addr)
+            .expect("There should be no address without registered endpoint")
+            .handles()
+            .clone();
+        // the inputs of the players are dropped, so they must not be needed for any rollback anymore
+        if handles.iter().any(|&handle| {
+            self.local_connect_status[handle].last_frame >= self.sync_layer.last_confirmed_frame()
+        }) {
+            return Err(GGRSError::InvalidRequest {
+                info: "The frames before the disconnect have not been confirmed yet.".to_owned(),
+            });
+        }
+        self.check_join_conflicts(&handles, frame)?;
+
+        self.player_reg.remotes.remove(&old_addr);
+        self.announce_joins(&handles, frame);
+        let endpoint = self.endpoint_settings.create_endpoint(
+            handles.clone(),
+            addr.clone(),
+            self.num_players,
+            self.player_reg.local_player_handles().len(),
+            self.sync_layer.max_prediction_limit(),
+            self.fps,
+        );
+        self.player_reg.remotes.insert(addr.clone(), endpoint);
+        for handle in handles {
+            self.player_reg
+                .handles
+                .insert(handle, PlayerType::Remote(addr.clone()));
+            self.last_valid_inputs.remove(&handle);
+            self.sync_layer.restart_player(handle, frame);
+            self.local_connect_status[handle] = ConnectionStatus {
+                disconnected: false,
+                last_frame: frame - 1,
+            };
+        }
+        Ok(())
+    }
+
+    /// Disconnects a spectator, so the session stops sending inputs to it and frees everything buffered for it.
+    /// Spectators are also disconnected automatically if the session does not hear from them for the disconnect timeout,
+    /// see [`set_disconnect_timeout()`]. In both cases, a [`GGRSEvent::Disconnected`] is not sent for spectators you disconnect yourself.
+    ///
+    /// With [`SessionBuilder::with_spectator_snapshots()`], a disconnected spectator can reconnect by starting a new [`SpectatorSession`] from the same address.
+    /// It resumes from a snapshot of the current state instead of replaying the match from the first frame.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle does not refer to a spectator.
+    ///
+    /// [`set_disconnect_timeout()`]: Self#method.set_disconnect_timeout
+    /// [`SessionBuilder::with_spectator_snapshots()`]: crate::SessionBuilder#method.with_spectator_snapshots
+    /// [`SpectatorSession`]: crate::SpectatorSession
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn disconnect_spectator(&mut self, player_handle: PlayerHandle) -> Result<(), GGRSError> {
+        let addr = match self.player_reg.handles.get(&player_handle) {
+            Some(PlayerType::Spectator(addr)) => addr.clone(),
+            _ => {
+                return Err(GGRSError::InvalidRequest {
+                    info: "The player handle does not refer to a spectator.".to_owned(),
+                })
+            }
+        };
+        let endpoint = self
+            .player_reg
+            .spectators
+            .get_mut(&addr)
+            .expect("There should be no address without registered endpoint");
+        endpoint.disconnect();
+        endpoint.release_buffers();
+        self.late_spectators.retain(|late| *late != addr);
+        Ok(())
+    }
+
+    /// Pauses the session together with all remote players, e.g. to show a pause menu. The remote players are asked to pause as well,
+    /// and every peer proposes the frame it is currently at. Once all proposals arrived, every peer receives a [`GGRSEvent::Paused`] with the
+    /// highest proposed frame. Every peer advances up to that frame, then [`advance_frame()`] returns [`Paused`] until the pause is resumed.
+    /// Peers that receive the request pause automatically, so you only need to call this on one of them. Keep polling while paused,
+    /// so quality reports and keep-alives are exchanged and the connections do not time out.
+    ///
+    /// The state at the pause frame might still be based on predicted inputs. It is corrected by the first advance after resuming.
+    ///
+    /// # Errors
+    /// - Returns [`NotSynchronized`] if the session is not running.
+    /// - Returns [`InvalidRequest`] if the session is already paused or pausing.
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    /// [`Paused`]: GGRSError::Paused
+    /// [`NotSynchronized`]: GGRSError::NotSynchronized
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn pause(&mut self) -> Result<(), GGRSError> {
+        if self.state != SessionState::Running {
+            return Err(GGRSError::NotSynchronized);
+        }
+        if !matches!(self.pause, PauseState::Unpaused) {
+            return Err(GGRSError::InvalidRequest {
+                info: "The session is already paused.".to_owned(),
+            });
+        }
+        self.start_pause();
+        Ok(())
+    }
+
+    /// Resumes a coordinated pause, or cancels it if the peers have not agreed on the pause frame yet. All remote players resume as well
+    /// and receive a [`GGRSEvent::Resumed`]. Every peer continues from the frame it stopped at, which is the same agreed frame for all peers.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the session is not paused.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn resume(&mut self) -> Result<(), GGRSError> {
+        if matches!(self.pause, PauseState::Unpaused) {
+            return Err(GGRSError::InvalidRequest {
+                info: "The session is not paused.".to_owned(),
+            });
+        }
+        let control = PauseControl::Resume {
+            round: self.pause_round,
+        };
+        for endpoint in self.player_reg.remotes.values_mut() {
+            endpoint.send_pause_control(control);
+        }
+        self.end_pause();
+        Ok(())
+    }
+
+    /// Returns true if the session stopped at the frame of a coordinated pause, see [`pause()`].
+    ///
+    /// [`pause()`]: Self#method.pause
+    pub fn is_paused(&self) -> bool {
+        let current_frame = self.sync_layer.current_frame();
+        match self.pause {
+            PauseState::Unpaused => false,
+            PauseState::Negotiating { local_frame, .. } => current_frame >= local_frame,
+            PauseState::Agreed { frame } => current_frame >= frame,
+        }
+    }
+
+    /// Ends the match gracefully. All inputs up to the last frame that is confirmed and has been advanced are sent to the spectators,
+    /// so they can watch the match up to its end instead of getting stuck a few frames short, and are written to the replay, if one is recorded.
+    /// All remote players and spectators receive a final message with a disconnect request, so they are notified without waiting for a timeout.
+    ///
+    /// Afterwards, the session is in [`SessionState::Ended`], and adding inputs or advancing the session returns [`SessionEnded`].
+    /// You should keep calling [`poll_remote_clients()`] for a while, so inputs the spectators did not acknowledge yet are resent
+    /// together with the disconnect request.
+    ///
+    /// # Errors
+    /// - Returns [`NotSynchronized`] if the session is not running yet.
+    /// - Returns [`SessionEnded`] if the session has already been ended.
+    ///
+    /// [`SessionEnded`]: GGRSError::SessionEnded
+    /// [`NotSynchronized`]: GGRSError::NotSynchronized
+    /// [`poll_remote_clients()`]: Self#method.poll_remote_clients
+    pub fn end_session(&mut self) -> Result<(), GGRSError> {
+        match self.state {
+            SessionState::Running => (),
+            SessionState::Synchronizing => return Err(GGRSError::NotSynchronized),
+            SessionState::Ended => return Err(GGRSError::SessionEnded),
+        }
+
+        // only inputs of frames that have been advanced are final
+        let final_frame =
+            core::cmp::min(self.confirmed_frame(), self.sync_layer.current_frame() - 1);
+        self.send_snapshots_to_late_spectators();
+        self.send_confirmed_inputs_to_spectators(final_frame);
+        #[cfg(feature = "std")]
+        self.write_confirmed_inputs_to_replay(final_frame);
+
+        for endpoint in self.player_reg.remotes.values_mut() {
+            endpoint.send_goodbye(&self.local_connect_status);
+            endpoint.send_all_messages(&mut self.socket);
+        }
+        for endpoint in self.player_reg.spectators.values_mut() {
+            endpoint.send_goodbye(&self.local_connect_status);
+            endpoint.send_all_messages(&mut self.socket);
+        }
+
+        self.state = SessionState::Ended;
+        Ok(())
+    }
+
+    /// Sets the time after which a remote player or spectator is disconnected if no packets have been received from them.
+    /// The timeout applies to all players with the same address and takes effect on the next poll. By default, the timeout given to the [`SessionBuilder`] is used.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle does not refer to a remote player or spectator.
+    ///
+    /// [`SessionBuilder`]: crate::SessionBuilder
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn set_disconnect_timeout(
+        &mut self,
+        player_handle: PlayerHandle,
+        timeout: Duration,
+    ) -> Result<(), GGRSError> {
+        self.endpoint_mut(player_handle)?
+            .set_disconnect_timeout(timeout);
+        Ok(())
+    }
+
+    /// Sets the time after which you receive a [`GGRSEvent::NetworkInterrupted`] if no packets have been received from a remote player or spectator.
+    /// The delay applies to all players with the same address and takes effect on the next poll. By default, the delay given to the [`SessionBuilder`] is used.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle does not refer to a remote player or spectator.
+    ///
+    /// [`SessionBuilder`]: crate::SessionBuilder
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn set_disconnect_notify_delay(
+        &mut self,
+        player_handle: PlayerHandle,
+        notify_delay: Duration,
+    ) -> Result<(), GGRSError> {
+        self.endpoint_mut(player_handle)?
+            .set_disconnect_notify_start(notify_delay);
+        Ok(())
+    }
+
+    /// Returns a [`NetworkStats`] struct that gives information about the quality of the network connection.
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle not referring to a remote player or spectator.
+    /// - Returns [`NotSynchronized`] if the session is not connected to other clients yet.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    /// [`NotSynchronized`]: GGRSError::NotSynchronized
+    pub fn network_stats(&self, player_handle: PlayerHandle) -> Result<NetworkStats, GGRSError> {
+        self.endpoint(player_handle)?.network_stats()
+    }
+
+    /// Returns the progress of synchronizing with the given remote player or spectator, as the fraction of the required sync roundtrips
+    /// that have been completed. This is the same information as in [`GGRSEvent::Synchronizing`], but can be queried at any time,
+    /// e.g. to draw a progress bar. Returns 1 once synchronized, and `None` if the handle does not refer to a remote player or spectator.
+    pub fn sync_progress(&self, player_handle: PlayerHandle) -> Option<f32> {
+        self.endpoint(player_handle)
+            .ok()
+            .map(|endpoint| endpoint.sync_progress())
+    }
+
+    /// Returns the estimated offset between the local clock and the clock of the given remote player or spectator, estimated from the timestamps of the
+    /// quality reports. Both clocks start at arbitrary points in time, so the offset is measured relative to the offset when the connection was established,
+    /// i.e. it is how far the clocks drifted apart since. As they drift, the estimated frame advantage is skewed, so time synchronization keeps recommending waits.
+    /// The offset is measured in either direction, so this is its magnitude.
+    ///
+    /// Returns `None` if the handle does not refer to a remote player or spectator, or if not enough quality reports have been answered to estimate the offset yet,
+    /// which takes a few seconds after synchronizing.
+    pub fn estimated_clock_offset(&self, player_handle: PlayerHandle) -> Option<Duration> {
+        self.endpoint(player_handle)
+            .ok()
+            .and_then(|endpoint| endpoint.clock_offset())
+    }
+
+    /// Returns the number of bytes buffered for sending to the given remote player or spectator: Messages that have not been handed to the socket yet,
+    /// plus the inputs that have not been acknowledged yet. If this keeps growing, the connection cannot keep up with the inputs sent over it.
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle not referring to a remote player or spectator.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn pending_send_bytes(&self, player_handle: PlayerHandle) -> Result<usize, GGRSError> {
+        Ok(self.endpoint(player_handle)?.pending_send_bytes())
+    }
+
+    /// Returns the sum of [`pending_send_bytes()`] over the connections to all remote players and spectators.
+    ///
+    /// [`pending_send_bytes()`]: Self#method.pending_send_bytes
+    pub fn total_pending_send_bytes(&self) -> usize {
+        self.player_reg
+            .remotes
+            .values()
+            .chain(self.player_reg.spectators.values())
+            .map(|endpoint| endpoint.pending_send_bytes())
+            .sum()
+    }
+
+    /// Sends a message to the given remote player or spectator over a reliable stream next to the inputs. The message is resent until it has been acknowledged,
+    /// and arrives as a [`GGRSEvent::Message`] exactly once and in the order it was sent. This is meant for small messages that are not part of the game input,
+    /// like emotes or ready flags. Messages are sent to the address of the handle, so players sharing an address receive them only once.
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle not referring to a remote player or spectator, if the message is longer than [`MAX_PAYLOAD`] bytes
+    ///   (18 bytes less in a session started with [`SessionBuilder::start_shared_p2p_session()`]),
+    ///   or if 64 messages to the remote client are still waiting to be acknowledged. Poll the session to receive the acknowledgements and try again later.
+    ///
+    /// [`MAX_PAYLOAD`]: crate::MAX_PAYLOAD
+    /// [`SessionBuilder::start_shared_p2p_session()`]: crate::SessionBuilder#method.start_shared_p2p_session
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn send_reliable(
+        &mut self,
+        player_handle: PlayerHandle,
+        bytes: &[u8],
+    ) -> Result<(), GGRSError> {
+        let endpoint = self.endpoint_mut(player_handle)?;
+        if bytes.len() > endpoint.max_payload() {
+            return Err(GGRSError::InvalidRequest {
+                info: format!(
+                    "Reliable messages cannot be longer than {} bytes.",
+                    endpoint.max_payload()
+                ),
+            });
+        }
+        if endpoint.unacked_reliable_messages() >= MAX_RELIABLE_MESSAGES {
+            return Err(GGRSError::InvalidRequest {
+                info: "Too many reliable messages are waiting to be acknowledged.".to_owned(),
+            });
+        }
+        endpoint.send_reliable(bytes.to_vec());
+        Ok(())
+    }
+
+    /// Resets the counters of the [`NetworkStats`] of the connection to the given remote player or spectator, like the number of lost packets.
+    /// Afterwards, the stats only cover the time since the reset.
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle not referring to a remote player or spectator.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn reset_network_stats(&mut self, player_handle: PlayerHandle) -> Result<(), GGRSError> {
+        self.endpoint_mut(player_handle)?.reset_network_stats();
+        Ok(())
+    }
+
+    /// Returns the highest confirmed frame. We have received all input for this frame and it is thus correct.
+    pub fn confirmed_frame(&self) -> Frame {
+        let mut confirmed_frame = i32::MAX;
+
+        for (handle, con_stat) in self.local_connect_status.iter().enumerate() {
+            // reserved player slots nobody joined yet do not hold back the confirmed frame
+            if !con_stat.disconnected && self.player_reg.handles.contains_key(&handle) {
+                confirmed_frame = core::cmp::min(confirmed_frame, con_stat.last_frame);
+            }
+        }
+
+        assert!(confirmed_frame < i32::MAX);
         confirmed_frame
     }
 
-    /// Returns the current frame of a session.
+    /// Returns the highest confirmed frame of a single player. For remote players, all inputs up to this frame have been received.
+    /// For local players, this is the last frame an input has been added for, including the input delay.
+    /// If the player has been disconnected, this stays at the last frame confirmed before the disconnect.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle does not refer to a local or remote player.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn confirmed_frame_for(&self, player_handle: PlayerHandle) -> Result<Frame, GGRSError> {
+        match self.player_reg.handles.get(&player_handle) {
+            Some(PlayerType::Local) | Some(PlayerType::Remote(_)) => {
+                Ok(self.local_connect_status[player_handle].last_frame)
+            }
+            _ => Err(GGRSError::InvalidRequest {
+                info: "Given player handle not referring to a local or remote player".to_owned(),
+            }),
+        }
+    }
+
+    /// Returns how many frames the most recently received input of each connected remote player is behind the current frame, ordered by handle.
+    /// This is the raw signal behind predictions and [`GGRSEvent::WaitRecommendation`], e.g. for network dashboards. A gap of 1 means
+    /// that the input for the frame before the current frame has been received, so no frame needs to be predicted for that player.
+    pub fn peer_frame_gaps(&self) -> Vec<(PlayerHandle, i32)> {
+        let current_frame = self.sync_layer.current_frame();
+        let mut handles = self.player_reg.remote_player_handles();
+        handles.sort_unstable();
+        handles
+            .into_iter()
+            .filter(|&handle| !self.local_connect_status[handle].disconnected)
+            .map(|handle| {
+                let gap = current_frame - self.local_connect_status[handle].last_frame;
+                (handle, gap)
+            })
+            .collect()
+    }
+
+    /// Returns the most recent frame the session requested to save. With sparse saving, this is usually behind the current frame.
+    /// Saves requested with [`request_save_next_advance()`] are not considered, since the session never rolls back to them.
+    ///
+    /// [`request_save_next_advance()`]: Self#method.request_save_next_advance
+    pub fn last_saved_frame(&self) -> Frame {
+        self.sync_layer.last_saved_frame()
+    }
+
+    /// Returns the oldest frame of which a state is still kept, i.e. the furthest the session could roll back to.
+    /// Frames before it are committed and never simulated again, so together with [`confirmed_frame()`] this tells you
+    /// which frames are safe to trigger effects for that cannot be rolled back. Returns [`NULL_FRAME`] if no state has been saved yet.
+    ///
+    /// [`confirmed_frame()`]: Self#method.confirmed_frame
+    /// [`NULL_FRAME`]: crate::NULL_FRAME
+    pub fn oldest_recoverable_frame(&self) -> Frame {
+        self.sync_layer.oldest_saved_frame()
+    }
+
+    /// Returns the frames and checksums of all currently saved states, ordered by frame.
+    /// When a desync is detected, comparing the checksum windows of both peers shows the first saved frame where they diverged.
+    pub fn checksums_window(&self) -> Vec<(Frame, Option<u128>)> {
+        self.sync_layer.saved_checksums()
+    }
+
+    /// Returns the frames and checksums of the most recent states that are final, i.e. all inputs leading to them are confirmed, ordered by frame.
+    /// Unlike [`checksums_window()`], this reaches further back than the saved states, so after a desync has been reported, the histories
+    /// of two peers can be exchanged out of band to find the first frame where they diverged. States without a checksum are skipped.
+    ///
+    /// [`checksums_window()`]: Self#method.checksums_window
+    pub fn checksum_history(&self) -> &[(Frame, u128)] {
+        &self.checksum_history
+    }
+
+    /// Writes a human-readable dump of the state window to `out`: the current and confirmed frame, the frame and checksum of every saved state,
+    /// and the inputs queued for every player with their flags. Attaching the dumps of all peers to a desync bug report shows where they diverged.
+    ///
+    /// # Errors
+    /// - Returns the error of `out` if writing fails.
+    #[cfg(feature = "std")]
+    pub fn dump_state_window(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "current frame: {}", self.sync_layer.current_frame())?;
+        writeln!(out, "confirmed frame: {}", self.confirmed_frame())?;
+        writeln!(out, "saved states:")?;
+        for (frame, checksum) in self.sync_layer.saved_checksums() {
+            match checksum {
+                Some(checksum) => writeln!(out, "  frame {frame}: checksum {checksum:032x}")?,
+                None => writeln!(out, "  frame {frame}: no checksum")?,
+            }
+        }
+        for handle in 0..self.num_players {
+            let kind = match self.player_reg.handles.get(&handle) {
+                Some(PlayerType::Local) => "local",
+                Some(PlayerType::Remote(_)) => "remote",
+                Some(PlayerType::Spectator(_)) => "spectator",
+                None => "reserved",
+            };
+            let status = &self.local_connect_status[handle];
+            let connection = if status.disconnected {
+                "disconnected"
+            } else {
+                "connected"
+            };
+            writeln!(
+                out,
+                "player {handle} ({kind}, {connection}, last frame {}):",
+                status.last_frame
+            )?;
+            for (frame, bytes, flags) in self.sync_layer.queued_inputs(handle) {
+                let bytes: Vec<_> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+                writeln!(
+                    out,
+                    "  frame {frame}: input [{}] flags {flags}",
+                    bytes.join(" ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a [`RollbackStats`] struct that gives information about the rollbacks performed so far.
+    pub fn rollback_stats(&self) -> RollbackStats {
+        self.rollback_stats
+    }
+
+    /// Returns the current frame of a session, i.e. the frame the next call to [`advance_frame()`] will advance.
+    /// This is always updated once [`advance_frame()`] returns, even if it rolled back and resimulated frames:
+    /// After fulfilling all requests, your game state is the state of this frame, and `current_frame() - 1` is the frame that has just been advanced.
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
     pub fn current_frame(&self) -> Frame {
         self.sync_layer.current_frame()
     }
 
-    /// Returns the maximum prediction window of a session.
+    /// Returns the maximum prediction window of a session. With [`SessionBuilder::with_adaptive_prediction()`], this is the current window,
+    /// which follows the latency to the remote players.
+    ///
+    /// [`SessionBuilder::with_adaptive_prediction()`]: crate::SessionBuilder#method.with_adaptive_prediction
     pub fn max_prediction(&self) -> usize {
         self.max_prediction
     }
 
+    /// Changes the maximum prediction window of a session. Once GGRS has predicted this many frames ahead of the last confirmed frame,
+    /// [`advance_frame()`] will return [`PredictionThreshold`]. The window can be lowered when the connection is bad and raised again later,
+    /// but it can never exceed the window the session was created with.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the window exceeds the window the session was created with or is smaller than the amount of frames currently predicted.
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    /// [`PredictionThreshold`]: GGRSError::PredictionThreshold
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn set_max_prediction_window(&mut self, window: usize) -> Result<(), GGRSError> {
+        self.sync_layer.set_max_prediction(window)?;
+        self.max_prediction = window;
+        Ok(())
+    }
+
+    /// Prevents the session from predicting past the given frame, e.g. for round starts or scripted moments that must not be mispredicted.
+    /// The frame itself can still be advanced with predicted inputs, but afterwards [`advance_frame()`] returns [`PredictionThreshold`]
+    /// until all inputs up to that frame have been confirmed. The barrier is lifted once this happens. Setting a new barrier replaces the previous one.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the session already advanced past the given frame.
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    /// [`PredictionThreshold`]: GGRSError::PredictionThreshold
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn set_prediction_barrier(&mut self, frame: Frame) -> Result<(), GGRSError> {
+        if frame < self.sync_layer.current_frame() - 1 {
+            return Err(GGRSError::InvalidRequest {
+                info: "The session already advanced past the given frame.".to_owned(),
+            });
+        }
+        self.prediction_barrier = Some(frame);
+        Ok(())
+    }
+
+    /// Returns the frame set with [`set_prediction_barrier()`], if the session is still waiting for its inputs to be confirmed.
+    ///
+    /// [`set_prediction_barrier()`]: Self#method.set_prediction_barrier
+    pub fn prediction_barrier(&self) -> Option<Frame> {
+        self.prediction_barrier
+    }
+
+    /// Returns a [`GGRSRequest::SaveGameState`] followed by a [`GGRSRequest::LoadGameState`] for every state the session keeps, so you can
+    /// make your game allocate the full ring of saved states during a loading screen, instead of at the start of the match. All requests are for
+    /// the current frame. Handle them like the ones of [`advance_frame()`]: Since every loaded state is the one just saved, your game state does not change.
+    ///
+    /// The states saved while prewarming are discarded once you call [`advance_frame()`] and never loaded again. The cells keep them though,
+    /// so saving with [`GameStateCell::save_in_place()`] reuses their allocations. Use [`SessionBuilder::with_state_reuse()`] instead
+    /// if your states can be created without your game. Prewarming works while the session is still synchronizing.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the session has already saved a state.
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    /// [`GameStateCell::save_in_place()`]: crate::GameStateCell#method.save_in_place
+    /// [`SessionBuilder::with_state_reuse()`]: crate::SessionBuilder#method.with_state_reuse
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn prewarm(&mut self) -> Result<Vec<GGRSRequest<T>>, GGRSError> {
+        if self.sync_layer.last_saved_frame() != NULL_FRAME && !self.prewarmed {
+            return Err(GGRSError::InvalidRequest {
+                info: "The session has already saved a state.".to_owned(),
+            });
+        }
+        self.prewarmed = true;
+        Ok(self.sync_layer.prewarm_requests())
+    }
+
+    /// Makes sure the next call to [`advance_frame()`] requests to save the state of the frame it advances, even with sparse saving.
+    /// This lets you checkpoint specific moments of the game, e.g. to take a snapshot for debugging, without turning off sparse saving.
+    /// With sparse saving, the session never rolls back to this state, since the frame might not be confirmed yet. The state is saved into a cell
+    /// reserved for these checkpoints, so it does not replace the state the session rolls back to.
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    pub fn request_save_next_advance(&mut self) {
+        self.force_save = true;
+    }
+
+    /// Exports the input queues, frame counters and connection metadata of the session, so you can drop it and continue the match with a session
+    /// restored by [`SessionBuilder::restore_p2p_session()`], e.g. to hot-swap your game logic during development. The socket is not part of the state.
+    /// Your game state is not part of it either: The restored session continues from the current frame, so keep the state you would load for it.
+    ///
+    /// Since the restored session can not roll back before its first frame, the session can only be exported once all inputs up to the previous frame
+    /// have been confirmed and no rollback is pending, i.e. usually right after [`advance_frame()`] while there is no prediction.
+    /// Local inputs added for upcoming frames, the replay writer and the rollback statistics are not exported.
+    ///
+    /// # Errors
+    /// - Returns [`NotSynchronized`] if the session is not running yet.
+    /// - Returns [`InvalidRequest`] if the session is paused, recovering from a desync, has unconfirmed inputs or a pending rollback.
+    ///
+    /// [`SessionBuilder::restore_p2p_session()`]: crate::SessionBuilder#method.restore_p2p_session
+    /// [`advance_frame()`]: Self#method.advance_frame
+    /// [`NotSynchronized`]: GGRSError::NotSynchronized
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn export_runtime_state(&self) -> Result<RuntimeState, GGRSError> {
+        if self.state != SessionState::Running {
+            return Err(GGRSError::NotSynchronized);
+        }
+        if !matches!(self.pause, PauseState::Unpaused) || self.recovery.is_some() {
+            return Err(GGRSError::InvalidRequest {
+                info:
+                    "The session cannot be exported while it is paused or recovering from a desync."
+                        .to_owned(),
+            });
+        }
+        let current_frame = self.sync_layer.current_frame();
+        if self.confirmed_frame() < current_frame - 1
+            || self
+                .sync_layer
+                .check_simulation_consistency(self.disconnect_frame)
+                != NULL_FRAME
+        {
+            return Err(GGRSError::InvalidRequest {
+                info: "The session cannot be exported while frames are predicted or a rollback is pending.".to_owned(),
+            });
+        }
+
+        let (first_frames, queues) = self.sync_layer.export_runtime();
+        let runtime = SessionRuntime {
+            num_players: self.num_players,
+            lockstep: self.lockstep,
+            current_frame,
+            first_frames,
+            queues,
+            local_connect_status: self.local_connect_status.clone(),
+            next_spectator_frame: self.next_spectator_frame,
+            local_inputs_frame: self.local_inputs_frame,
+            last_acked_frame: self.last_acked_frame,
+            input_checksum: self.input_checksum,
+            next_input_checksum_frame: self.next_input_checksum_frame,
+            input_checksum_history: self.input_checksum_history.clone(),
+            pause_round: self.pause_round,
+            join_rounds: self.join_rounds.clone(),
+            endpoints: self
+                .player_reg
+                .remotes
+                .values()
+                .map(UdpProtocol::export_runtime)
+                .collect(),
+            spectators: self
+                .player_reg
+                .spectators
+                .values()
+                .map(UdpProtocol::export_runtime)
+                .collect(),
+        };
+        Ok(RuntimeState { runtime })
+    }
+
+    /// Continues an exported session. The session must have just been created with the same players and spectators.
+    pub(crate) fn restore_runtime_state(&mut self, state: &RuntimeState) -> Result<(), GGRSError> {
+        let runtime = state.runtime.clone();
+        if runtime.num_players != self.num_players {
+            return Err(GGRSError::InvalidRequest {
+                info: "The exported session has a different number of players.".to_owned(),
+            });
+        }
+        let mut endpoints = runtime.endpoints;
+        let mut spectators = runtime.spectators;
+        let mut restored = Vec::new();
+        for endpoint in self
+            .player_reg
+            .remotes
+            .values()
+            .chain(self.player_reg.spectators.values())
+        {
+            let exported =
+                match endpoints
+                    .iter()
+                    .position(|e| e.handles() == endpoint.handles().as_slice())
+                {
+                    Some(pos) => endpoints.swap_remove(pos),
+                    None => match spectators
+                        .iter()
+                        .position(|e| e.handles() == endpoint.handles().as_slice())
+                    {
+                        Some(pos) => spectators.swap_remove(pos),
+                        None => return Err(GGRSError::InvalidRequest {
+                            info:
+                                "The remote players or spectators differ from the exported session."
+                                    .to_owned(),
+                        }),
+                    },
+                };
+            restored.push((endpoint.peer_addr(), exported));
+        }
+        if !endpoints.is_empty() || !spectators.is_empty() {
+            return Err(GGRSError::InvalidRequest {
+                info: "The remote players or spectators differ from the exported session."
+                    .to_owned(),
+            });
+        }
+
+        self.sync_layer.restore_runtime(
+            runtime.current_frame,
+            runtime.first_frames,
+            &runtime.queues,
+        )?;
+        for (addr, exported) in restored {
+            let endpoint = match self.player_reg.remotes.get_mut(&addr) {
+                Some(endpoint) => endpoint,
+                None => self
+                    .player_reg
+                    .spectators
+                    .get_mut(&addr)
+                    .expect("The endpoint should exist"),
+            };
+            endpoint.restore_runtime(exported);
+        }
+
+        self.state = SessionState::Running;
+        self.local_connect_status = runtime.local_connect_status;
+        self.next_spectator_frame = runtime.next_spectator_frame;
+        self.local_inputs_frame = runtime.local_inputs_frame;
+        self.last_acked_frame = runtime.last_acked_frame;
+        self.input_checksum = runtime.input_checksum;
+        self.next_input_checksum_frame = runtime.next_input_checksum_frame;
+        self.input_checksum_history = runtime.input_checksum_history;
+        self.pause_round = runtime.pause_round;
+        self.join_rounds = runtime.join_rounds;
+        self.next_confirmed_history_frame = runtime.current_frame;
+        self.next_checksum_history_frame = runtime.current_frame;
+        Ok(())
+    }
+
     /// Returns the current [`SessionState`] of a session.
     pub fn current_state(&self) -> SessionState {
         self.state
     }
 
-    /// Returns all events that happened since last queried for events. If the number of stored events exceeds `MAX_EVENT_QUEUE_SIZE`, the oldest events will be discarded.
-    pub fn events(&mut self) -> Drain<GGRSEvent<T>> {
+    /// Returns the current [`DetailedSessionState`] of a session, which also tells how many remote clients are left to synchronize with.
+    pub fn detailed_state(&self) -> DetailedSessionState {
+        match self.state {
+            SessionState::Synchronizing => DetailedSessionState::Synchronizing {
+                remaining_peers: self.unsynchronized_peers(),
+            },
+            SessionState::Running => DetailedSessionState::Running,
+            SessionState::Ended => DetailedSessionState::Ended,
+        }
+    }
+
+    /// Returns all events that happened since last queried for events. If the number of stored events exceeds `MAX_EVENT_QUEUE_SIZE`, the oldest events will be discarded,
+    /// except for [`GGRSEvent::Message`]s.
+    pub fn events(&mut self) -> Drain<'_, GGRSEvent<T>> {
         self.event_queue.drain(..)
     }
 
-    /// Returns the number of players added to this session
+    /// Returns the number of players of this session, including reserved player slots.
     pub fn num_players(&self) -> usize {
-        self.player_reg.num_players()
+        self.num_players
     }
 
     /// Return the number of spectators currently registered
@@ -515,16 +2026,287 @@ impl<T: Config> P2PSession<T> {
         self.player_reg.spectator_handles()
     }
 
+    /// Returns the connection state of every player and spectator that has been added, sorted by handle.
+    /// Remote players are [`Disconnected`] as soon as this session has disconnected them, even if their endpoint is still shutting down.
+    ///
+    /// [`Disconnected`]: PlayerConnectionState::Disconnected
+    pub fn player_connection_states(&self) -> Vec<(PlayerHandle, PlayerConnectionState)> {
+        let mut states: Vec<_> = self
+            .player_reg
+            .handles
+            .iter()
+            .map(|(&handle, player_type)| {
+                let state = match player_type {
+                    PlayerType::Local => PlayerConnectionState::Local,
+                    PlayerType::Remote(addr) => {
+                        let endpoint = &self.player_reg.remotes[addr];
+                        if self.local_connect_status[handle].disconnected
+                            || endpoint.is_disconnected()
+                        {
+                            PlayerConnectionState::Disconnected
+                        } else if endpoint.is_synchronized() {
+                            PlayerConnectionState::Synchronized
+                        } else {
+                            PlayerConnectionState::Synchronizing
+                        }
+                    }
+                    PlayerType::Spectator(addr) => {
+                        let endpoint = &self.player_reg.spectators[addr];
+                        if endpoint.is_disconnected() {
+                            PlayerConnectionState::Disconnected
+                        } else {
+                            PlayerConnectionState::Spectator {
+                                synchronized: endpoint.is_synchronized(),
+                            }
+                        }
+                    }
+                };
+                (handle, state)
+            })
+            .collect();
+        states.sort_by_key(|&(handle, _)| handle);
+        states
+    }
+
     /// Returns all handles associated to a certain address
     pub fn handles_by_address(&self, addr: T::Address) -> Vec<PlayerHandle> {
         self.player_reg.handles_by_address(addr)
     }
 
+    /// Returns how many frames the current frame is ahead of the [`confirmed_frame()`], i.e. the current prediction depth.
+    /// This is 0 if all inputs up to the current frame have been received. Once it reaches the [`max_prediction()`] window,
+    /// [`advance_frame()`] returns [`PredictionThreshold`].
+    ///
+    /// [`confirmed_frame()`]: Self#method.confirmed_frame
+    /// [`max_prediction()`]: Self#method.max_prediction
+    /// [`advance_frame()`]: Self#method.advance_frame
+    /// [`PredictionThreshold`]: GGRSError::PredictionThreshold
+    pub fn frames_predicted(&self) -> usize {
+        // remote inputs can arrive ahead of the current frame
+        (self.sync_layer.current_frame() - self.confirmed_frame()).max(0) as usize
+    }
+
+    /// Returns true if the inputs of all remote players for the current frame have been received, so the next call to [`advance_frame()`]
+    /// will not need to predict them. Disconnected players are not considered, and neither are local players, since you provide their inputs.
+    /// Inputs are received in [`poll_remote_clients()`], so you should call it first. Games that prefer waiting over predicting can use this
+    /// to decide whether to advance this tick.
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    /// [`poll_remote_clients()`]: Self#method.poll_remote_clients
+    pub fn next_frame_is_confirmed(&self) -> bool {
+        let current_frame = self.sync_layer.current_frame();
+        self.player_reg
+            .remote_player_handles()
+            .into_iter()
+            .map(|handle| &self.local_connect_status[handle])
+            .all(|con_stat| con_stat.disconnected || con_stat.last_frame >= current_frame)
+    }
+
     /// Returns the number of frames this session is estimated to be ahead of other sessions
     pub fn frames_ahead(&self) -> i32 {
         self.frames_ahead
     }
 
+    /// Returns how long you should sleep before advancing the next frame to keep the pace of the fps given to [`SessionBuilder::with_fps()`].
+    /// `elapsed` is the time that already passed since the previous frame was advanced, e.g. for simulating and rendering it, and is subtracted.
+    /// The sleep is a single frame, stretched while this session is ahead of the remote clients: The last [`GGRSEvent::WaitRecommendation`]
+    /// to skip `skip_frames` frames is honored by spreading them over the frames until the next recommendation, so you don't need to handle it yourself.
+    /// Returns [`Duration::ZERO`] if this session is behind the remote clients and should catch up, or if `elapsed` exceeds the sleep.
+    ///
+    /// [`SessionBuilder::with_fps()`]: crate::SessionBuilder#method.with_fps
+    pub fn recommended_sleep(&self, elapsed: Duration) -> Duration {
+        if self.frames_ahead < 0 {
+            return Duration::ZERO;
+        }
+        let frame = Duration::from_secs(1) / self.fps as u32;
+        // the recommendation expires once the next one could be sent
+        let skip_frames = if self.sync_layer.current_frame() <= self.next_recommended_sleep {
+            self.recommended_skip_frames
+        } else {
+            0
+        };
+        let sleep = frame + frame * skip_frames / RECOMMENDATION_INTERVAL as u32;
+        sleep.saturating_sub(elapsed)
+    }
+
+    /// Adds the time elapsed since the last call to an accumulator and returns how many fixed steps you should advance now, i.e. how many times
+    /// to add local inputs and call [`advance_frame()`], for games that render at a variable rate and simulate at the fps given to
+    /// [`SessionBuilder::with_fps()`]. Call it once per rendered frame. Like [`recommended_sleep()`], the steps are stretched while this session is
+    /// ahead of the remote clients and shortened while it is behind, so you don't need to handle [`GGRSEvent::WaitRecommendation`] yourself.
+    ///
+    /// After a long stall, at most as many steps as the prediction window holds are returned and the remaining time is dropped,
+    /// so the game does not fall further behind trying to catch up. Time passing before the session is running or while it is paused is ignored.
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    /// [`SessionBuilder::with_fps()`]: crate::SessionBuilder#method.with_fps
+    /// [`recommended_sleep()`]: Self#method.recommended_sleep
+    pub fn should_advance(&mut self, elapsed: Duration) -> u32 {
+        if self.state != SessionState::Running || !matches!(self.pause, PauseState::Unpaused) {
+            self.accumulated_time = Duration::ZERO;
+            return 0;
+        }
+
+        self.accumulated_time += elapsed;
+        let step = self.paced_step();
+        let steps = self.accumulated_time.as_nanos() / step.as_nanos();
+        let max_steps = self.max_prediction.max(1) as u128;
+        if steps > max_steps {
+            self.accumulated_time = Duration::ZERO;
+            return max_steps as u32;
+        }
+        self.accumulated_time -= step * steps as u32;
+        steps as u32
+    }
+
+    /// Returns the duration of a fixed step in [`should_advance()`]: A frame, stretched while ahead of the remote clients and shortened while behind.
+    ///
+    /// [`should_advance()`]: Self#method.should_advance
+    fn paced_step(&self) -> Duration {
+        let frame = Duration::from_secs(1) / self.fps as u32;
+        let interval = RECOMMENDATION_INTERVAL as u32;
+        match self.frames_ahead {
+            ahead if ahead.unsigned_abs() < MIN_RECOMMENDATION => frame,
+            ahead if ahead > 0 => frame + frame * ahead as u32 / interval,
+            // never shorten a step by more than half, so catching up stays smooth
+            ahead => frame - frame * ahead.unsigned_abs().min(interval / 2) / interval,
+        }
+    }
+
+    /// Returns the estimated frame advantage of this session over the given remote player, averaged over the last frames.
+    /// A positive value means this session is ahead of the remote player. [`frames_ahead()`] is the maximum of these values over all remote players.
+    /// Returns `None` if the handle does not refer to a connected remote player.
+    ///
+    /// [`frames_ahead()`]: Self#method.frames_ahead
+    pub fn frame_advantage(&self, player_handle: PlayerHandle) -> Option<i32> {
+        match self.player_reg.handles.get(&player_handle) {
+            Some(PlayerType::Remote(addr)) => {
+                if self.local_connect_status[player_handle].disconnected {
+                    return None;
+                }
+                self.player_reg
+                    .remotes
+                    .get(addr)
+                    .filter(|endpoint| endpoint.is_running())
+                    .map(|endpoint| endpoint.average_frame_advantage())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the inputs that were most recently used to advance the given frame, together with their [`InputStatus`] and flags.
+    /// After a rollback, these are the inputs of the resimulation. Only the frames within the prediction window are retained,
+    /// so this returns `None` for older frames and for frames that have not been advanced yet.
+    pub fn inputs_for_frame(&self, frame: Frame) -> Option<Vec<(T::Input, InputStatus, u8)>> {
+        self.input_history
+            .iter()
+            .find(|(f, _)| *f == frame)
+            .map(|(_, inputs)| inputs.clone())
+    }
+
+    /// Returns the confirmed inputs of the given player for the last `n` confirmed frames, oldest first, together with their flags.
+    /// Only the frames within the prediction window are retained, so fewer inputs are returned if `n` is larger than that.
+    /// Frames on which the player was disconnected are skipped.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the handle does not refer to a player.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn recent_confirmed_inputs(
+        &self,
+        player_handle: PlayerHandle,
+        n: usize,
+    ) -> Result<Vec<(Frame, T::Input, u8)>, GGRSError> {
+        if player_handle >= self.num_players {
+            return Err(GGRSError::InvalidRequest {
+                info: "The player handle you provided is not referring to a player.".to_owned(),
+            });
+        }
+        let skip = self.confirmed_history.len().saturating_sub(n);
+        Ok(self
+            .confirmed_history
+            .iter()
+            .skip(skip)
+            .map(|(_, inputs)| inputs[player_handle])
+            .filter(|input| input.frame != NULL_FRAME)
+            .map(|input| (input.frame, input.input, input.flags))
+            .collect())
+    }
+
+    /// Records the inputs of every frame to the given writer as soon as the frame is confirmed. Frames are recorded in order, even if
+    /// they are confirmed after a rollback. Disconnected players are recorded with [`InputStatus::Disconnected`].
+    /// The recording can be read back with a [`ReplayReader`]. If writing fails, the recording stops.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the session has already advanced beyond its start frame.
+    ///
+    /// [`InputStatus::Disconnected`]: crate::InputStatus::Disconnected
+    /// [`ReplayReader`]: crate::ReplayReader
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    #[cfg(all(feature = "std", feature = "sync-send"))]
+    pub fn set_replay_writer(
+        &mut self,
+        writer: Box<dyn std::io::Write + Send + Sync>,
+    ) -> Result<(), GGRSError> {
+        self.set_replay_writer_internal(writer)
+    }
+
+    /// Records the inputs of every frame to the given writer as soon as the frame is confirmed. Frames are recorded in order, even if
+    /// they are confirmed after a rollback. Disconnected players are recorded with [`InputStatus::Disconnected`].
+    /// The recording can be read back with a [`ReplayReader`]. If writing fails, the recording stops.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the session has already advanced beyond its start frame.
+    ///
+    /// [`InputStatus::Disconnected`]: crate::InputStatus::Disconnected
+    /// [`ReplayReader`]: crate::ReplayReader
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    #[cfg(all(feature = "std", not(feature = "sync-send")))]
+    pub fn set_replay_writer(&mut self, writer: Box<dyn std::io::Write>) -> Result<(), GGRSError> {
+        self.set_replay_writer_internal(writer)
+    }
+
+    #[cfg(feature = "std")]
+    fn set_replay_writer_internal(&mut self, writer: ReplayWrite) -> Result<(), GGRSError> {
+        // inputs of earlier frames might already be discarded
+        if self.sync_layer.current_frame() > self.sync_layer.start_frame() {
+            return Err(GGRSError::InvalidRequest {
+                info: "The replay writer has to be set before advancing the first frame."
+                    .to_owned(),
+            });
+        }
+        self.replay_writer = Some(ReplayWriter::new(
+            writer,
+            self.num_players,
+            self.sync_layer.start_frame(),
+        ));
+        Ok(())
+    }
+
+    fn endpoint(&self, player_handle: PlayerHandle) -> Result<&UdpProtocol<T>, GGRSError> {
+        let endpoint = match self.player_reg.handles.get(&player_handle) {
+            Some(PlayerType::Remote(addr)) => self.player_reg.remotes.get(addr),
+            Some(PlayerType::Spectator(addr)) => self.player_reg.spectators.get(addr),
+            _ => None,
+        };
+        endpoint.ok_or_else(|| GGRSError::InvalidRequest {
+            info: "Given player handle not referring to a remote player or spectator".to_owned(),
+        })
+    }
+
+    fn endpoint_mut(
+        &mut self,
+        player_handle: PlayerHandle,
+    ) -> Result<&mut UdpProtocol<T>, GGRSError> {
+        let endpoint = match self.player_reg.handles.get(&player_handle) {
+            Some(PlayerType::Remote(addr)) => self.player_reg.remotes.get_mut(addr),
+            Some(PlayerType::Spectator(addr)) => self.player_reg.spectators.get_mut(addr),
+            _ => None,
+        };
+        endpoint.ok_or_else(|| GGRSError::InvalidRequest {
+            info: "Given player handle not referring to a remote player or spectator".to_owned(),
+        })
+    }
+
     fn disconnect_player_at_frame(&mut self, player_handle: PlayerHandle, last_frame: Frame) {
         // disconnect the remote player
         match self
@@ -540,16 +2322,23 @@ impl<T: Config> P2PSession<T> {
                     .get_mut(addr)
                     .expect("There should be no address without registered endpoint");
 
-                // mark the affected players as disconnected
+                // mark the affected players as disconnected and let the game react at the frame they are disconnected on
                 for &handle in endpoint.handles() {
+                    if !self.local_connect_status[handle].disconnected {
+                        if let Some(hook) = self.disconnect_hook.as_mut() {
+                            hook.on_disconnect(handle, last_frame.saturating_add(1));
+                        }
+                    }
                     self.local_connect_status[handle].disconnected = true;
                 }
                 endpoint.disconnect();
 
-                if self.sync_layer.current_frame() > last_frame {
+                // the first disconnected frame has only been simulated if the session is beyond it. Otherwise, there is nothing to
+                // roll back, and the current frame cannot be loaded since it has not been saved yet
+                if self.sync_layer.current_frame() > last_frame.saturating_add(1) {
                     // remember to adjust simulation to account for the fact that the player disconnected a few frames ago,
                     // resimulating with correct disconnect flags (to account for user having some AI kick in).
-                    self.disconnect_frame = last_frame + 1;
+                    self.disconnect_frame = last_frame.saturating_add(1);
                 }
             }
             PlayerType::Spectator(addr) => {
@@ -567,6 +2356,34 @@ impl<T: Config> P2PSession<T> {
         self.check_initial_sync();
     }
 
+    /// Sends all local inputs from the first frame of the player who joined or rejoined the running session on,
+    /// since inputs are not sent to an endpoint that is still synchronizing.
+    fn send_inputs_to_joined_player(&mut self, addr: &T::Address) {
+        let local_handles = self.player_reg.local_player_handles();
+        let last_sent_frame = local_handles.first().map_or(NULL_FRAME, |&handle| {
+            self.local_connect_status[handle].last_frame
+        });
+        let endpoint = match self.player_reg.remotes.get_mut(addr) {
+            Some(endpoint) => endpoint,
+            None => return,
+        };
+        let first_frame = endpoint
+            .handles()
+            .iter()
+            .map(|&handle| self.sync_layer.first_frame(handle))
+            .min()
+            .unwrap_or(Frame::MAX);
+
+        for frame in first_frame..=last_sent_frame {
+            let inputs: HashMap<PlayerHandle, PlayerInput<T::Input>> = local_handles
+                .iter()
+                .map(|&handle| (handle, self.sync_layer.queued_input(handle, frame)))
+                .collect();
+            endpoint.send_input(&inputs, &self.local_connect_status);
+        }
+        endpoint.send_all_messages(&mut self.socket);
+    }
+
     /// Change the session state to [`SessionState::Running`] if all UDP endpoints are synchronized.
     fn check_initial_sync(&mut self) {
         // if we are not synchronizing, we don't need to do anything
@@ -575,28 +2392,80 @@ impl<T: Config> P2PSession<T> {
         }
 
         // if any endpoint is not synchronized, we continue synchronizing
-        for endpoint in self.player_reg.remotes.values_mut() {
-            if !endpoint.is_synchronized() {
-                return;
-            }
-        }
-        for endpoint in self.player_reg.spectators.values_mut() {
-            if !endpoint.is_synchronized() {
-                return;
-            }
+        if self.unsynchronized_peers() > 0 {
+            return;
         }
 
         // everyone is synchronized, so we can change state and accept input
         self.state = SessionState::Running;
     }
 
+    /// Returns the number of remote clients that are not synchronized yet and keep the session from running.
+    fn unsynchronized_peers(&self) -> usize {
+        let remotes = self
+            .player_reg
+            .remotes
+            .values()
+            .filter(|endpoint| !endpoint.is_synchronized())
+            .count();
+        // spectators can join late if they receive a snapshot
+        let spectators = if self.spectator_snapshots {
+            0
+        } else {
+            self.player_reg
+                .spectators
+                .values()
+                .filter(|endpoint| !endpoint.is_synchronized())
+                .count()
+        };
+        remotes + spectators
+    }
+
+    /// Creates the request to save the current frame and notifies the user about it, if desired.
+    fn save_current_state(&mut self) -> GGRSRequest<T> {
+        let request = self.sync_layer.save_current_state();
+        self.notify_state_saved();
+        request
+    }
+
+    /// Notifies the user that the current frame is requested to be saved, if desired.
+    fn notify_state_saved(&mut self) {
+        if self.state_saved_events {
+            self.event_queue.push_back(GGRSEvent::StateSaved {
+                frame: self.sync_layer.current_frame(),
+            });
+            discard_old_events(&mut self.event_queue);
+        }
+    }
+
+    /// Returns the inputs to advance the current frame with and remembers them for [`inputs_for_frame()`].
+    ///
+    /// [`inputs_for_frame()`]: Self#method.inputs_for_frame
+    fn synchronized_inputs(&mut self) -> Vec<(T::Input, InputStatus, u8)> {
+        let frame = self.sync_layer.current_frame();
+        let inputs = self
+            .sync_layer
+            .synchronized_inputs(&self.local_connect_status);
+
+        // inputs used for this and later frames before a rollback are outdated
+        while matches!(self.input_history.back(), Some((f, _)) if *f >= frame) {
+            self.input_history.pop_back();
+        }
+        self.input_history.push_back((frame, inputs.clone()));
+        while self.input_history.len() > self.max_prediction {
+            self.input_history.pop_front();
+        }
+
+        inputs
+    }
+
     /// Roll back to `min_confirmed` frame and resimulate the game with most up-to-date input data.
     fn adjust_gamestate(
         &mut self,
         first_incorrect: Frame,
         min_confirmed: Frame,
         requests: &mut Vec<GGRSRequest<T>>,
-    ) {
+    ) -> Result<(), GGRSError> {
         let current_frame = self.sync_layer.current_frame();
         // determine the frame to load
         let frame_to_load = if self.sparse_saving {
@@ -610,9 +2479,18 @@ impl<T: Config> P2PSession<T> {
         // we should always load a frame that is before or exactly the first incorrect frame
         assert!(frame_to_load <= first_incorrect);
         let count = current_frame - frame_to_load;
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("rollback", frame = frame_to_load, depth = count).entered();
 
         // request to load that frame
-        requests.push(self.sync_layer.load_frame(frame_to_load));
+        requests.push(self.sync_layer.load_frame(frame_to_load)?);
+        self.rollback_stats.add_rollback(count as u32);
+        self.event_queue.push_back(GGRSEvent::Rollback {
+            from_frame: current_frame,
+            to_frame: frame_to_load,
+        });
+        discard_old_events(&mut self.event_queue);
 
         // we are now at the desired frame
         assert_eq!(self.sync_layer.current_frame(), frame_to_load);
@@ -620,20 +2498,18 @@ impl<T: Config> P2PSession<T> {
 
         // step forward to the previous current state, but with updated inputs
         for i in 0..count {
-            let inputs = self
-                .sync_layer
-                .synchronized_inputs(&self.local_connect_status);
+            let inputs = self.synchronized_inputs();
 
             // decide wether to request a state save
             if self.sparse_saving {
                 // with sparse saving, we only save exactly the min_confirmed frame
                 if self.sync_layer.current_frame() == min_confirmed {
-                    requests.push(self.sync_layer.save_current_state());
+                    requests.push(self.save_current_state());
                 }
             } else {
                 // without sparse saving, we save every state except the very first (just loaded that))
                 if i > 0 {
-                    requests.push(self.sync_layer.save_current_state());
+                    requests.push(self.save_current_state());
                 }
             }
 
@@ -643,6 +2519,208 @@ impl<T: Config> P2PSession<T> {
         }
         // after all this, we should have arrived at the same frame where we started
         assert_eq!(self.sync_layer.current_frame(), current_frame);
+        Ok(())
+    }
+
+    /// Adds the local inputs for the current frame to the sync layer and sends all newly queued inputs to the remote players.
+    fn send_local_inputs(&mut self) -> Result<(), GGRSError> {
+        let local_handles = self.player_reg.local_player_handles();
+        // all local players share the same input delay, so their queues always hold the same frames
+        let last_sent_frame = local_handles.first().map_or(NULL_FRAME, |&handle| {
+            self.local_connect_status[handle].last_frame
+        });
+        let mut last_added_frame = last_sent_frame;
+        // take the inputs added ahead of time for the current frame, unless an input has been added directly
+        let current_frame = self.sync_layer.current_frame();
+        for &handle in local_handles.iter() {
+            if let Some(queue) = self.future_inputs.get_mut(&handle) {
+                if let Some(input) = queue.front().filter(|input| input.frame == current_frame) {
+                    self.local_inputs.entry(handle).or_insert(*input);
+                    queue.pop_front();
+                }
+            }
+        }
+        for &handle in local_handles.iter() {
+            match self.local_inputs.get(&handle) {
+                Some(&player_input) => {
+                    // send the input into the sync layer. If the input delay has been decreased, the input might be dropped
+                    let actual_frame = self.sync_layer.add_local_input(handle, player_input)?;
+                    if actual_frame != NULL_FRAME {
+                        self.local_connect_status[handle].last_frame = actual_frame;
+                        last_added_frame = actual_frame;
+                    }
+                }
+                None => {
+                    return Err(GGRSError::InvalidRequest {
+                        info: "Missing local input while calling advance_frame().".to_owned(),
+                    });
+                }
+            }
+        }
+
+        // send all newly queued inputs to all clients, with the correct frame (influenced by input delay).
+        // If the input delay has been increased, this includes the frames filled in between. Skipping the first frame avoids
+        // an overflow once the frame counter reached its limit.
+        for frame in (last_sent_frame..=last_added_frame).skip(1) {
+            let inputs: HashMap<PlayerHandle, PlayerInput<T::Input>> = local_handles
+                .iter()
+                .map(|&handle| (handle, self.sync_layer.queued_input(handle, frame)))
+                .collect();
+            for endpoint in self.player_reg.remotes.values_mut() {
+                endpoint.send_input(&inputs, &self.local_connect_status);
+            }
+        }
+        for endpoint in self.player_reg.remotes.values_mut() {
+            endpoint.send_all_messages(&mut self.socket);
+        }
+
+        // clear the local inputs after sending them
+        self.local_inputs.clear();
+        self.local_inputs_frame = self.sync_layer.current_frame();
+        Ok(())
+    }
+
+    /// Sends a [`GGRSEvent::InputAcked`] if all connected remote players acknowledged more local inputs since the last check.
+    fn check_acked_inputs(&mut self) {
+        let acked_frame = self
+            .player_reg
+            .remotes
+            .values()
+            .filter(|endpoint| {
+                !endpoint
+                    .handles()
+                    .iter()
+                    .any(|&handle| self.local_connect_status[handle].disconnected)
+            })
+            .map(|endpoint| endpoint.last_acked_frame())
+            .min();
+
+        if let Some(frame) = acked_frame {
+            if frame > self.last_acked_frame {
+                self.last_acked_frame = frame;
+                self.event_queue.push_back(GGRSEvent::InputAcked { frame });
+                discard_old_events(&mut self.event_queue);
+            }
+        }
+    }
+
+    /// Returns [`GGRSError::SessionEnded`] if the session has been ended.
+    fn check_not_ended(&self) -> Result<(), GGRSError> {
+        if self.state == SessionState::Ended {
+            return Err(GGRSError::SessionEnded);
+        }
+        Ok(())
+    }
+
+    /// Sets the prediction window to cover the round-trip time plus twice the jitter to the slowest remote player, within the adaptive bounds.
+    fn adapt_prediction_window(&mut self) {
+        let min = match self.min_adaptive_prediction {
+            Some(min) => min,
+            None => return,
+        };
+        let latency = self
+            .player_reg
+            .remotes
+            .values()
+            .filter(|endpoint| endpoint.is_running())
+            .map(|endpoint| endpoint.round_trip_time() as f32 + 2.0 * endpoint.jitter())
+            .fold(0.0, f32::max);
+        let frames = libm::ceilf(latency * self.fps as f32 / 1000.0) as usize + 1;
+        // the window cannot shrink below the frames that are currently predicted
+        let predicted = self.frames_predicted();
+        let window = frames
+            .clamp(min, self.sync_layer.max_prediction_limit())
+            .max(predicted);
+        // with sparse saving, the sync layer might still predict more frames, so the window is kept until it can shrink
+        if window != self.max_prediction && self.sync_layer.set_max_prediction(window).is_ok() {
+            self.max_prediction = window;
+        }
+    }
+
+    /// Sends [`GGRSEvent::FrameCounterNearLimit`] once the frame counter is less than an hour of frames away from [`Frame::MAX`].
+    fn check_frame_limit(&mut self) {
+        if self.frame_limit_notified {
+            return;
+        }
+        let frame = self.sync_layer.current_frame();
+        let warning_frames = self.fps.saturating_mul(FRAME_LIMIT_WARNING_SECS);
+        let frames_left = Frame::MAX.saturating_sub(frame) as usize;
+        if frames_left <= warning_frames {
+            self.frame_limit_notified = true;
+            self.event_queue
+                .push_back(GGRSEvent::FrameCounterNearLimit {
+                    frame,
+                    frames_left: frames_left as Frame,
+                });
+            discard_old_events(&mut self.event_queue);
+        }
+    }
+
+    /// Returns [`GGRSError::FrameLagExceeded`] if the current frame is more than `max_frame_lag` frames ahead of the confirmed frame.
+    fn check_frame_lag(&self) -> Result<(), GGRSError> {
+        let max_frame_lag = match self.max_frame_lag {
+            Some(max_frame_lag) => max_frame_lag,
+            None => return Ok(()),
+        };
+        let frame = self.sync_layer.current_frame();
+        let confirmed_frame = self.confirmed_frame();
+        if frame - confirmed_frame > max_frame_lag as i32 {
+            return Err(GGRSError::FrameLagExceeded {
+                frame,
+                confirmed_frame,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns [`GGRSError::InvalidRequest`] if the input does not pass the input validator.
+    fn validate_local_input(&self, input: &T::Input) -> Result<(), GGRSError> {
+        match self.input_validator {
+            Some(validator) if !validator(bytemuck::bytes_of(input)) => {
+                Err(GGRSError::InvalidRequest {
+                    info: "The input you provided did not pass the input validator.".to_owned(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// With strict input ordering, rejects a local input for the current frame if the player already has one.
+    fn check_input_ordering(&self, player_handle: PlayerHandle) -> Result<(), GGRSError> {
+        if !self.strict_input_ordering {
+            return Ok(());
+        }
+        let current_frame = self.sync_layer.current_frame();
+        let queue = self.future_inputs.get(&player_handle);
+        let added_ahead = queue
+            .and_then(|queue| queue.front())
+            .is_some_and(|input| input.frame == current_frame);
+        if self.local_inputs.contains_key(&player_handle) || added_ahead {
+            let expected = queue
+                .and_then(|queue| queue.back())
+                .map_or(current_frame + 1, |input| input.frame + 1);
+            return Err(GGRSError::NonMonotonicInput {
+                expected,
+                got: current_frame,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns [`GGRSError::PredictionThreshold`] if the session would advance past the prediction barrier before it is confirmed.
+    fn check_prediction_barrier(&mut self) -> Result<(), GGRSError> {
+        let barrier = match self.prediction_barrier {
+            Some(barrier) => barrier,
+            None => return Ok(()),
+        };
+        if self.confirmed_frame() >= barrier {
+            self.prediction_barrier = None;
+            return Ok(());
+        }
+        if self.sync_layer.current_frame() > barrier {
+            return Err(GGRSError::PredictionThreshold);
+        }
+        Ok(())
     }
 
     /// For each spectator, send all confirmed input up until the minimum confirmed frame.
@@ -655,7 +2733,7 @@ impl<T: Config> P2PSession<T> {
             let mut inputs = self
                 .sync_layer
                 .confirmed_inputs(self.next_spectator_frame, &self.local_connect_status);
-            assert_eq!(inputs.len(), self.num_players as usize);
+            assert_eq!(inputs.len(), self.num_players);
 
             let mut input_map = HashMap::new();
             for (handle, input) in inputs.iter_mut().enumerate() {
@@ -663,9 +2741,9 @@ impl<T: Config> P2PSession<T> {
                 input_map.insert(handle, *input);
             }
 
-            // send it to all spectators
-            for endpoint in self.player_reg.spectators.values_mut() {
-                if endpoint.is_running() {
+            // send it to all spectators, except those still waiting for their snapshot
+            for (addr, endpoint) in self.player_reg.spectators.iter_mut() {
+                if endpoint.is_running() && !self.late_spectators.contains(addr) {
                     endpoint.send_input(&input_map, &self.local_connect_status);
                 }
             }
@@ -675,10 +2753,498 @@ impl<T: Config> P2PSession<T> {
         }
     }
 
+    /// Shares the checksums of all states that are final with the spectators, every `spectator_checksum_interval` frames.
+    /// States that are not saved or have no checksum are skipped.
+    fn send_state_checksums_to_spectators(&mut self) {
+        let interval = match self.spectator_checksum_interval {
+            Some(interval) => interval as Frame,
+            None => return,
+        };
+        let last_confirmed = self.sync_layer.last_confirmed_frame();
+        let current_frame = self.sync_layer.current_frame();
+        while self.next_spectator_checksum_frame - 1 <= last_confirmed
+            && self.next_spectator_checksum_frame < current_frame
+        {
+            let frame = self.next_spectator_checksum_frame;
+            let checksum = self
+                .sync_layer
+                .saved_state_by_frame(frame)
+                .and_then(|cell| cell.checksum());
+            if let Some(checksum) = checksum {
+                for endpoint in self.player_reg.spectators.values_mut() {
+                    endpoint.set_state_checksum(frame, checksum);
+                }
+            }
+            self.next_spectator_checksum_frame =
+                self.next_spectator_checksum_frame.saturating_add(interval);
+        }
+    }
+
+    /// Adds the checksums of all states that became final to the `checksum_history`, keeping at most [`MAX_CHECKSUM_HISTORY`] entries.
+    fn record_checksum_history(&mut self) {
+        let last_confirmed = self.sync_layer.last_confirmed_frame();
+        let current_frame = self.sync_layer.current_frame();
+        while self.next_checksum_history_frame - 1 <= last_confirmed
+            && self.next_checksum_history_frame < current_frame
+        {
+            let frame = self.next_checksum_history_frame;
+            let checksum = self
+                .sync_layer
+                .saved_state_by_frame(frame)
+                .and_then(|cell| cell.checksum());
+            if let Some(checksum) = checksum {
+                self.checksum_history.push((frame, checksum));
+            }
+            self.next_checksum_history_frame += 1;
+        }
+        let excess = self
+            .checksum_history
+            .len()
+            .saturating_sub(MAX_CHECKSUM_HISTORY);
+        self.checksum_history.drain(..excess);
+    }
+
+    /// Proposes to pause at the current frame and asks all remote players to pause as well.
+    fn start_pause(&mut self) {
+        let local_frame = self.sync_layer.current_frame();
+        self.pause = PauseState::Negotiating {
+            local_frame,
+            remote_frames: HashMap::new(),
+        };
+        let control = PauseControl::Pause {
+            round: self.pause_round,
+            frame: local_frame,
+        };
+        for endpoint in self.player_reg.remotes.values_mut() {
+            endpoint.send_pause_control(control);
+        }
+        self.check_pause_agreement();
+    }
+
+    /// Agrees on the pause frame once all remote players that are still connected proposed one.
+    fn check_pause_agreement(&mut self) {
+        let (local_frame, remote_frames) = match &self.pause {
+            PauseState::Negotiating {
+                local_frame,
+                remote_frames,
+            } => (*local_frame, remote_frames),
+            _ => return,
+        };
+        let mut frame = local_frame;
+        for (addr, endpoint) in self.player_reg.remotes.iter() {
+            match remote_frames.get(addr) {
+                Some(&remote_frame) => frame = core::cmp::max(frame, remote_frame),
+                None if endpoint.is_running() => return,
+                None => (),
+            }
+        }
+        self.pause = PauseState::Agreed { frame };
+        self.event_queue.push_back(GGRSEvent::Paused { frame });
+    }
+
+    /// Leaves the current pause. Messages that still arrive for it are ignored.
+    fn end_pause(&mut self) {
+        self.pause = PauseState::Unpaused;
+        self.pause_round += 1;
+    }
+
+    /// Handles a message of a remote player to coordinate a pause.
+    fn handle_pause_control(&mut self, control: PauseControl, addr: T::Address) {
+        match control {
+            PauseControl::Pause { round, frame } if round == self.pause_round => {
+                if matches!(self.pause, PauseState::Unpaused) {
+                    self.start_pause();
+                }
+                if let PauseState::Negotiating { remote_frames, .. } = &mut self.pause {
+                    remote_frames.insert(addr, frame);
+                }
+                self.check_pause_agreement();
+            }
+            PauseControl::Resume { round }
+                if round == self.pause_round && !matches!(self.pause, PauseState::Unpaused) =>
+            {
+                self.end_pause();
+                self.event_queue.push_back(GGRSEvent::Resumed { addr });
+            }
+            // the message belongs to a pause that has already been resumed
+            _ => (),
+        }
+    }
+
+    /// Refuses to let the players join if a remote client already lets them join on another frame.
+    fn check_join_conflicts(
+        &self,
+        handles: &[PlayerHandle],
+        frame: Frame,
+    ) -> Result<(), GGRSError> {
+        for &handle in handles {
+            let round = self.join_rounds[handle].0 + 1;
+            if let Some((join, _)) = self.remote_joins.get(&handle) {
+                if join.round == round && join.frame != frame {
+                    return Err(GGRSError::InvalidRequest {
+                        info: format!(
+                            "A remote client lets player {} join on frame {}.",
+                            handle, join.frame
+                        ),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Tells all remote clients on which frame the players join. Call this before adding the endpoint of the joining players.
+    fn announce_joins(&mut self, handles: &[PlayerHandle], frame: Frame) {
+        for &handle in handles {
+            let round = self.join_rounds[handle].0 + 1;
+            self.join_rounds[handle] = (round, frame);
+            if self
+                .remote_joins
+                .get(&handle)
+                .is_some_and(|(join, _)| join.round <= round)
+            {
+                self.remote_joins.remove(&handle);
+            }
+            let join = PlayerJoin {
+                handle,
+                round,
+                frame,
+            };
+            for endpoint in self.player_reg.remotes.values_mut() {
+                endpoint.send_player_join(join);
+            }
+        }
+    }
+
+    /// Handles a remote client telling us on which frame a player joins. Joins of the same round have to be on the same frame,
+    /// later ones are kept until this session lets the player join as well.
+    fn handle_player_join(&mut self, join: PlayerJoin, addr: T::Address) {
+        let (round, frame) = match self.join_rounds.get(join.handle) {
+            Some(&joined) => joined,
+            None => return,
+        };
+        if join.round == round && join.frame != frame {
+            self.event_queue.push_back(GGRSEvent::PlayerJoinConflict {
+                addr,
+                handle: join.handle,
+                frame: join.frame,
+            });
+            discard_old_events(&mut self.event_queue);
+        } else if join.round > round {
+            self.remote_joins.insert(join.handle, (join, addr));
+        }
+        // joins of earlier rounds have been handled already
+    }
+
+    /// Sends a [`GGRSEvent::PlayerJoinConflict`] for all joins of remote clients on a frame this session advanced without letting the player join.
+    fn check_late_joins(&mut self) {
+        let current_frame = self.sync_layer.current_frame();
+        let mut late: Vec<PlayerHandle> = self
+            .remote_joins
+            .iter()
+            .filter(|(_, (join, _))| join.frame < current_frame)
+            .map(|(&handle, _)| handle)
+            .collect();
+        late.sort_unstable();
+        for handle in late {
+            if let Some((join, addr)) = self.remote_joins.remove(&handle) {
+                self.event_queue.push_back(GGRSEvent::PlayerJoinConflict {
+                    addr,
+                    handle,
+                    frame: join.frame,
+                });
+            }
+        }
+        discard_old_events(&mut self.event_queue);
+    }
+
+    /// Sends the state of the last frame sent to all other spectators to spectators that joined late, followed by the inputs of that frame.
+    /// All later inputs are sent to them like to any other spectator. If that state has not been saved with its bytes, we try again next frame.
+    fn send_snapshots_to_late_spectators(&mut self) {
+        let frame = self.next_spectator_frame - 1;
+        if self.late_spectators.is_empty() || frame < 0 {
+            return;
+        }
+        let bytes = match self
+            .sync_layer
+            .saved_state_by_frame(frame)
+            .and_then(|cell| cell.bytes())
+        {
+            Some(bytes) => bytes,
+            None => return,
+        };
+
+        let input_map: HashMap<PlayerHandle, PlayerInput<T::Input>> = self
+            .sync_layer
+            .confirmed_inputs(frame, &self.local_connect_status)
+            .into_iter()
+            .enumerate()
+            .collect();
+        for addr in self.late_spectators.drain(..) {
+            if let Some(endpoint) = self.player_reg.spectators.get_mut(&addr) {
+                endpoint.send_snapshot(frame, bytes.clone());
+                endpoint.send_input(&input_map, &self.local_connect_status);
+            }
+        }
+    }
+
+    /// Record all confirmed input up until the minimum confirmed frame.
+    #[cfg(feature = "std")]
+    fn write_confirmed_inputs_to_replay(&mut self, confirmed_frame: Frame) {
+        let writer = match self.replay_writer.as_mut() {
+            Some(writer) => writer,
+            None => return,
+        };
+
+        while writer.next_frame() <= confirmed_frame {
+            let inputs = self
+                .sync_layer
+                .confirmed_inputs(writer.next_frame(), &self.local_connect_status);
+            if writer.write_frame(&inputs).is_err() {
+                // the replay would be incomplete from here on, so we stop recording
+                self.replay_writer = None;
+                return;
+            }
+        }
+    }
+
+    /// Adds the confirmed inputs of all frames up to the given frame to the confirmed history, keeping only the frames within the prediction window.
+    fn record_confirmed_inputs(&mut self, confirmed_frame: Frame) {
+        while self.next_confirmed_history_frame <= confirmed_frame {
+            let frame = self.next_confirmed_history_frame;
+            let inputs = self
+                .sync_layer
+                .confirmed_inputs(frame, &self.local_connect_status);
+            self.confirmed_history.push_back((frame, inputs));
+            self.next_confirmed_history_frame += 1;
+        }
+        while self.confirmed_history.len() > self.max_prediction {
+            self.confirmed_history.pop_front();
+        }
+    }
+
+    /// Adds all confirmed inputs to the rolling input checksum, shares it with the remote clients every `input_desync_interval` frames
+    /// and compares it to the checksums they shared. Differing checksums mean that the clients received different inputs for the same frame.
+    fn check_input_checksums(&mut self, confirmed_frame: Frame) {
+        let interval = match self.input_desync_interval {
+            Some(interval) => interval as Frame,
+            None => return,
+        };
+
+        while self.next_input_checksum_frame <= confirmed_frame {
+            let frame = self.next_input_checksum_frame;
+            let inputs = self
+                .sync_layer
+                .confirmed_inputs(frame, &self.local_connect_status);
+            let mut bytes = self.input_checksum.to_le_bytes().to_vec();
+            for input in inputs.iter() {
+                bytes.extend_from_slice(bytemuck::bytes_of(&input.input));
+                bytes.push(input.flags);
+            }
+            self.input_checksum = ChecksumKind::Fnv1a128.checksum(&bytes);
+
+            if frame % interval == 0 {
+                self.input_checksum_history
+                    .push_back((frame, self.input_checksum));
+                if self.input_checksum_history.len() > MAX_INPUT_CHECKSUMS {
+                    self.input_checksum_history.pop_front();
+                }
+                for endpoint in self.player_reg.remotes.values_mut() {
+                    endpoint.set_input_checksum(frame, self.input_checksum);
+                }
+            }
+            self.next_input_checksum_frame += 1;
+        }
+
+        let newest = match self.input_checksum_history.back() {
+            Some(&(frame, _)) => frame,
+            None => return,
+        };
+        // only a desync with the authoritative peer means that our state is wrong
+        let authority = self.authority_addr();
+        let mut desynced = false;
+        for endpoint in self.player_reg.remotes.values_mut() {
+            for (frame, remote_checksum) in endpoint.take_remote_input_checksums(newest) {
+                // checksums older than our history cannot be compared anymore
+                let local_checksum = match self
+                    .input_checksum_history
+                    .iter()
+                    .find(|(f, _)| *f == frame)
+                {
+                    Some(&(_, checksum)) => checksum,
+                    None => continue,
+                };
+                if local_checksum != remote_checksum {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        frame,
+                        local_checksum,
+                        remote_checksum,
+                        handles = ?endpoint.handles(),
+                        "desync detected"
+                    );
+                    desynced |= authority.as_ref() == Some(&endpoint.peer_addr());
+                    self.event_queue.push_back(GGRSEvent::DesyncDetected {
+                        frame,
+                        kind: DesyncKind::Inputs,
+                        local_checksum,
+                        remote_checksum,
+                        addr: endpoint.peer_addr(),
+                    });
+                }
+            }
+        }
+        discard_old_events(&mut self.event_queue);
+
+        if desynced {
+            self.request_recovery(interval);
+        }
+    }
+
+    /// Returns the address of the authoritative peer, which is the remote client owning the checksum authority.
+    /// Returns `None` if that player is local or has been disconnected.
+    fn authority_addr(&self) -> Option<T::Address> {
+        let handle = self.checksum_authority;
+        match self.player_reg.handles.get(&handle) {
+            Some(PlayerType::Remote(addr)) if !self.local_connect_status[handle].disconnected => {
+                Some(addr.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Requests a state from the authoritative peer to recover from a desync, unless a recovery is in progress already.
+    /// We request the state of the frame after the next shared input checksum, so that the checksum can be taken over together with the state.
+    fn request_recovery(&mut self, interval: Frame) {
+        if self.desync_recovery.is_none() || self.recovery.is_some() {
+            return;
+        }
+        let addr = match self.authority_addr() {
+            Some(addr) => addr,
+            None => return,
+        };
+        let current_frame = self.sync_layer.current_frame();
+        // there is no frame to recover on beyond the limit of the frame counter
+        let frame = match current_frame
+            .checked_add(interval - 1)
+            .and_then(|frame| (frame / interval * interval).checked_add(1))
+        {
+            Some(frame) => frame,
+            None => return,
+        };
+        if let Some(endpoint) = self.player_reg.remotes.get_mut(&addr) {
+            endpoint.request_state(frame);
+            self.recovery = Some(Recovery { frame, state: None });
+        }
+    }
+
+    /// Sends the requested states to the remote clients recovering from a desync, preceded by the input checksum up to the frame before.
+    /// A state is only sent once the frame before it has been confirmed in an earlier frame, so the state has been saved with the correct inputs.
+    /// Requests that cannot be fulfilled because the state or the input checksum is not available anymore are answered with a `StateUnavailable`.
+    fn send_recovery_states(&mut self) {
+        let last_confirmed = self.sync_layer.last_confirmed_frame();
+        let current_frame = self.sync_layer.current_frame();
+        for (addr, frame) in core::mem::take(&mut self.state_requests) {
+            if frame - 1 > last_confirmed || frame >= current_frame {
+                self.state_requests.push((addr, frame));
+                continue;
+            }
+            let checksum = self
+                .input_checksum_history
+                .iter()
+                .find(|(f, _)| *f == frame - 1)
+                .map(|&(_, checksum)| checksum);
+            let bytes = self
+                .sync_layer
+                .saved_state_by_frame(frame)
+                .and_then(|cell| cell.bytes());
+            let endpoint = match self.player_reg.remotes.get_mut(&addr) {
+                Some(endpoint) => endpoint,
+                None => continue,
+            };
+            match (checksum, bytes) {
+                (Some(checksum), Some(bytes)) => {
+                    let mut payload = checksum.to_le_bytes().to_vec();
+                    payload.extend_from_slice(&bytes);
+                    endpoint.send_snapshot(frame, payload);
+                }
+                _ => endpoint.send_state_unavailable(frame),
+            }
+        }
+    }
+
+    /// Takes a state sent by the authoritative peer for the desync recovery in progress. The state is loaded in one of the next frames.
+    fn receive_recovery_state(&mut self, frame: Frame, payload: &[u8], addr: T::Address) {
+        let decode = match self.desync_recovery {
+            Some(decode) => decode,
+            None => return,
+        };
+        if self.authority_addr() != Some(addr) {
+            return;
+        }
+        let recovery = match self.recovery.as_mut() {
+            Some(recovery) if recovery.frame == frame && recovery.state.is_none() => recovery,
+            _ => return,
+        };
+        let (checksum, bytes) = payload.split_at(core::cmp::min(16, payload.len()));
+        match (checksum.try_into(), decode(bytes)) {
+            (Ok(checksum), Some(state)) => {
+                recovery.state = Some((state, bytes.to_vec(), u128::from_le_bytes(checksum)));
+            }
+            // the state cannot be used, so we give up on this recovery
+            _ => self.abandon_recovery(),
+        }
+    }
+
+    /// Gives up the desync recovery in progress and sends a [`GGRSEvent::DesyncRecoveryFailed`].
+    fn abandon_recovery(&mut self) {
+        if let Some(recovery) = self.recovery.take() {
+            self.event_queue.push_back(GGRSEvent::DesyncRecoveryFailed {
+                frame: recovery.frame,
+            });
+            discard_old_events(&mut self.event_queue);
+        }
+    }
+
+    /// Loads the state received from the authoritative peer and resimulates all frames after it, once the frame of the state lies in the past.
+    /// The input checksum of the authoritative peer is taken over as well, so the remote clients agree on all inputs before that frame.
+    fn load_recovered_state(
+        &mut self,
+        confirmed_frame: Frame,
+        requests: &mut Vec<GGRSRequest<T>>,
+    ) -> Result<(), GGRSError> {
+        let frame = match &self.recovery {
+            Some(Recovery {
+                frame,
+                state: Some(_),
+            }) if *frame < self.sync_layer.current_frame() => *frame,
+            _ => return Ok(()),
+        };
+        let (state, bytes, input_checksum) = self
+            .recovery
+            .take()
+            .and_then(|recovery| recovery.state)
+            .expect("the recovered state has just been checked");
+
+        self.sync_layer.replace_state(frame, state, &bytes);
+        self.adjust_gamestate(frame, confirmed_frame, requests)?;
+
+        self.input_checksum = input_checksum;
+        self.next_input_checksum_frame = frame;
+        self.input_checksum_history.clear();
+        self.event_queue
+            .push_back(GGRSEvent::DesyncRecovered { frame });
+        Ok(())
+    }
+
     /// Check if players are registered as disconnected for earlier frames on other remote players in comparison to our local assumption.
     /// Disconnect players that are disconnected for other players and update the frame they disconnected
     fn update_player_disconnects(&mut self) {
-        for handle in 0..self.num_players as usize {
+        for handle in 0..self.num_players {
+            // nobody joined this reserved player slot yet
+            if !self.player_reg.handles.contains_key(&handle) {
+                continue;
+            }
             let mut queue_connected = true;
             let mut queue_min_confirmed = i32::MAX;
 
@@ -688,11 +3254,13 @@ impl<T: Config> P2PSession<T> {
                     continue;
                 }
                 let con_status = endpoint.peer_connect_status(handle);
-                let connected = !con_status.disconnected;
+                // a client that has not reconnected the player yet still reports the disconnect before the player rejoined
+                let connected = !con_status.disconnected
+                    || con_status.last_frame < self.sync_layer.first_frame(handle) - 1;
                 let min_confirmed = con_status.last_frame;
 
                 queue_connected = queue_connected && connected;
-                queue_min_confirmed = std::cmp::min(queue_min_confirmed, min_confirmed);
+                queue_min_confirmed = core::cmp::min(queue_min_confirmed, min_confirmed);
             }
 
             // check our local info for that player
@@ -700,7 +3268,7 @@ impl<T: Config> P2PSession<T> {
             let local_min_confirmed = self.local_connect_status[handle].last_frame;
 
             if local_connected {
-                queue_min_confirmed = std::cmp::min(queue_min_confirmed, local_min_confirmed);
+                queue_min_confirmed = core::cmp::min(queue_min_confirmed, local_min_confirmed);
             }
 
             if !queue_connected {
@@ -720,7 +3288,7 @@ impl<T: Config> P2PSession<T> {
         for endpoint in self.player_reg.remotes.values() {
             for &handle in endpoint.handles() {
                 if !self.local_connect_status[handle].disconnected {
-                    interval = std::cmp::max(interval, endpoint.average_frame_advantage());
+                    interval = core::cmp::max(interval, endpoint.average_frame_advantage());
                 }
             }
         }
@@ -735,15 +3303,20 @@ impl<T: Config> P2PSession<T> {
 
     fn check_wait_recommendation(&mut self) {
         self.frames_ahead = self.max_frame_advantage();
-        if self.sync_layer.current_frame() > self.next_recommended_sleep
+        if self.time_sync
+            && self.sync_layer.current_frame() > self.next_recommended_sleep
             && self.frames_ahead >= MIN_RECOMMENDATION as i32
         {
-            self.next_recommended_sleep = self.sync_layer.current_frame() + RECOMMENDATION_INTERVAL;
+            self.next_recommended_sleep = self
+                .sync_layer
+                .current_frame()
+                .saturating_add(RECOMMENDATION_INTERVAL);
+            self.recommended_skip_frames = self
+                .frames_ahead
+                .try_into()
+                .expect("frames ahead is negative despite being positive.");
             self.event_queue.push_back(GGRSEvent::WaitRecommendation {
-                skip_frames: self
-                    .frames_ahead
-                    .try_into()
-                    .expect("frames ahead is negative despite being positive."),
+                skip_frames: self.recommended_skip_frames,
             });
         }
     }
@@ -753,25 +3326,26 @@ impl<T: Config> P2PSession<T> {
         last_saved: Frame,
         confirmed_frame: Frame,
         requests: &mut Vec<GGRSRequest<T>>,
-    ) {
+    ) -> Result<(), GGRSError> {
         // in sparse saving mode, we need to make sure not to lose the last saved frame
         if self.sync_layer.current_frame() - last_saved >= self.max_prediction as i32 {
             // check if the current frame is confirmed, otherwise we need to roll back
             if confirmed_frame >= self.sync_layer.current_frame() {
                 // the current frame is confirmed, save it
-                requests.push(self.sync_layer.save_current_state());
+                requests.push(self.save_current_state());
             } else {
                 // roll back to the last saved state, resimulate and save on the way
-                self.adjust_gamestate(last_saved, confirmed_frame, requests);
+                self.adjust_gamestate(last_saved, confirmed_frame, requests)?;
             }
 
             // after all this, we should have saved the confirmed state
             assert!(
                 confirmed_frame == NULL_FRAME
                     || self.sync_layer.last_saved_frame()
-                        == std::cmp::min(confirmed_frame, self.sync_layer.current_frame())
+                        == core::cmp::min(confirmed_frame, self.sync_layer.current_frame())
             );
         }
+        Ok(())
     }
 
     /// Handle events received from the UDP endpoints. Most events are being forwarded to the user for notification, but some require action.
@@ -799,8 +3373,72 @@ impl<T: Config> P2PSession<T> {
                 self.event_queue
                     .push_back(GGRSEvent::NetworkResumed { addr });
             }
+            // remote clients only send states to recover from desyncs
+            Event::Snapshot { frame, bytes } => self.receive_recovery_state(frame, &bytes, addr),
+            // remember the request, if we are the authoritative peer, otherwise tell the remote client we cannot help
+            Event::StateRequested { frame } => {
+                if self.desync_recovery.is_none()
+                    || self.player_reg.handles.get(&self.checksum_authority)
+                        != Some(&PlayerType::Local)
+                {
+                    if let Some(endpoint) = self.player_reg.remotes.get_mut(&addr) {
+                        endpoint.send_state_unavailable(frame);
+                    }
+                } else if !self.state_requests.contains(&(addr.clone(), frame)) {
+                    self.state_requests.push((addr, frame));
+                }
+            }
+            // the authoritative peer cannot send the state we requested
+            Event::StateUnavailable { frame } => {
+                if self.authority_addr() == Some(addr)
+                    && matches!(&self.recovery, Some(recovery) if recovery.frame == frame && recovery.state.is_none())
+                {
+                    self.abandon_recovery();
+                }
+            }
+            // forward to user
+            Event::Message { bytes } => {
+                self.event_queue
+                    .push_back(GGRSEvent::Message { from: addr, bytes });
+            }
+            // forward to user
+            Event::QualityReportPayload { bytes } => {
+                self.event_queue
+                    .push_back(GGRSEvent::QualityReportPayload { addr, bytes });
+            }
+            // pause or resume together with the remote player
+            Event::PauseControl(control) => self.handle_pause_control(control, addr),
+            // compare with the frame we let the player join on
+            Event::PlayerJoin(join) => self.handle_player_join(join, addr),
+            // forward to user
+            Event::IncompatiblePeer => {
+                self.event_queue.push_back(GGRSEvent::IncompatiblePeer {
+                    addr,
+                    handle: player_handles[0],
+                });
+            }
+            // forward to user
+            Event::ClockDriftWarning { offset } => {
+                self.event_queue.push_back(GGRSEvent::ClockDriftWarning {
+                    handle: player_handles[0],
+                    offset,
+                });
+            }
             // check if all remotes are synced, then forward to user
             Event::Synchronized => {
+                // a spectator that missed the first inputs needs a snapshot to start from
+                if self.spectator_snapshots
+                    && self.next_spectator_frame > 0
+                    && self.player_reg.spectators.contains_key(&addr)
+                {
+                    self.late_spectators.push(addr.clone());
+                }
+                // a player joining or rejoining the running session missed the inputs sent while synchronizing
+                if self.state == SessionState::Running
+                    && self.player_reg.remotes.contains_key(&addr)
+                {
+                    self.send_inputs_to_joined_player(&addr);
+                }
                 self.check_initial_sync();
                 self.event_queue.push_back(GGRSEvent::Synchronized { addr });
             }
@@ -815,14 +3453,19 @@ impl<T: Config> P2PSession<T> {
 
                     self.disconnect_player_at_frame(handle, last_frame);
                 }
+                // a pause does not wait for the proposal of a disconnected player
+                self.check_pause_agreement();
 
                 self.event_queue.push_back(GGRSEvent::Disconnected { addr });
             }
             // add the input and all associated information
-            Event::Input { input, player } => {
+            Event::Input { mut input, player } => {
                 // input only comes from remote players, not spectators
                 assert!(player < self.num_players as PlayerHandle);
-                if !self.local_connect_status[player].disconnected {
+                // inputs before the first frame of the player are not needed, e.g. if this session started late
+                if !self.local_connect_status[player].disconnected
+                    && input.frame >= self.sync_layer.first_frame(player)
+                {
                     // check if the input comes in the correct sequence
                     let current_remote_frame = self.local_connect_status[player].last_frame;
                     assert!(
@@ -831,15 +3474,36 @@ impl<T: Config> P2PSession<T> {
                     );
                     // update our info
                     self.local_connect_status[player].last_frame = input.frame;
-                    // add the remote input
-                    self.sync_layer.add_remote_input(player, input);
+                    // replace invalid inputs with the last valid input of the player
+                    match self.input_validator {
+                        Some(validator) if !validator(bytemuck::bytes_of(&input.input)) => {
+                            let frame = input.frame;
+                            input = match self.last_valid_inputs.get(&player) {
+                                Some(valid) => PlayerInput { frame, ..*valid },
+                                None => PlayerInput::blank_input(frame),
+                            };
+                            self.event_queue.push_back(GGRSEvent::InvalidInput {
+                                handle: player,
+                                frame,
+                            });
+                        }
+                        Some(_) => {
+                            self.last_valid_inputs.insert(player, input);
+                        }
+                        None => (),
+                    }
+                    // add the remote input and notify the user if it has been mispredicted
+                    if self.sync_layer.add_remote_input(player, input) {
+                        self.event_queue.push_back(GGRSEvent::PredictionMiss {
+                            frame: input.frame,
+                            player,
+                        });
+                    }
                 }
             }
         }
 
         // check event queue size and discard oldest events if too big
-        while self.event_queue.len() > MAX_EVENT_QUEUE_SIZE {
-            self.event_queue.pop_front();
-        }
+        discard_old_events(&mut self.event_queue);
     }
 }
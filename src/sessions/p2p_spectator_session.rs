@@ -1,4 +1,5 @@
-use std::collections::{vec_deque::Drain, VecDeque};
+use alloc::collections::{vec_deque::Drain, VecDeque};
+use alloc::{borrow::ToOwned, boxed::Box, vec::Vec};
 
 use crate::{
     frame_info::PlayerInput,
@@ -6,15 +7,20 @@ use crate::{
         messages::ConnectionStatus,
         protocol::{Event, UdpProtocol},
     },
-    sessions::builder::MAX_EVENT_QUEUE_SIZE,
-    Config, Frame, GGRSError, GGRSEvent, GGRSRequest, InputStatus, NetworkStats, NonBlockingSocket,
-    SessionState, NULL_FRAME,
+    sessions::builder::discard_old_events,
+    Config, DesyncKind, DetailedSessionState, Frame, GGRSError, GGRSEvent, GGRSRequest,
+    GameStateCell, InputStatus, NetworkStats, NonBlockingSocket, SessionState, NULL_FRAME,
 };
 
 // The amount of frames the spectator advances in a single step if not too far behind
 const NORMAL_SPEED: usize = 1;
 // The amount of inputs a spectator can buffer (a second worth of inputs)
 pub(crate) const SPECTATOR_BUFFER_SIZE: usize = 60;
+// The amount of saved states and host checksums a spectator keeps to compare them
+const MAX_CHECKSUMS: usize = 32;
+
+/// Deserializes the snapshot of the game state a spectator joining late receives from the host.
+pub(crate) type SnapshotDecoder<S> = fn(&[u8]) -> Option<S>;
 
 /// [`SpectatorSession`] provides all functionality to connect to a remote host in a peer-to-peer fashion.
 /// The host will broadcast all confirmed inputs to this session.
@@ -34,6 +40,17 @@ where
     last_recv_frame: Frame,
     max_frames_behind: usize,
     catchup_speed: usize,
+    /// The frame of the first input received from the host. If it is not 0, the spectator joined late and has to start from a snapshot.
+    first_recv_frame: Frame,
+    snapshot_decoder: Option<SnapshotDecoder<T::State>>,
+    /// The snapshot received from the host that has not been loaded yet.
+    pending_snapshot: Option<(Frame, GameStateCell<T::State>)>,
+    /// If set, the state is saved every this many frames to compare its checksum with the checksum of the host.
+    checksum_interval: Option<u32>,
+    /// The states saved to compare their checksums with the host, which the host has not sent a checksum for yet.
+    saved_checksums: VecDeque<(Frame, GameStateCell<T::State>)>,
+    /// The state checksums received from the host, which have not been compared yet.
+    host_checksums: VecDeque<(Frame, u128)>,
 }
 
 impl<T: Config> SpectatorSession<T> {
@@ -46,6 +63,8 @@ impl<T: Config> SpectatorSession<T> {
         host: UdpProtocol<T>,
         max_frames_behind: usize,
         catchup_speed: usize,
+        snapshot_decoder: Option<SnapshotDecoder<T::State>>,
+        checksum_interval: Option<u32>,
     ) -> Self {
         // host connection status
         let mut host_connect_status = Vec::new();
@@ -68,6 +87,12 @@ impl<T: Config> SpectatorSession<T> {
             last_recv_frame: NULL_FRAME,
             max_frames_behind,
             catchup_speed,
+            first_recv_frame: NULL_FRAME,
+            snapshot_decoder,
+            pending_snapshot: None,
+            checksum_interval,
+            saved_checksums: VecDeque::new(),
+            host_checksums: VecDeque::new(),
         }
     }
 
@@ -76,6 +101,17 @@ impl<T: Config> SpectatorSession<T> {
         self.state
     }
 
+    /// Returns the current [`DetailedSessionState`] of a session. While synchronizing, the host is the only remote client left.
+    pub fn detailed_state(&self) -> DetailedSessionState {
+        match self.state {
+            SessionState::Synchronizing => {
+                DetailedSessionState::Synchronizing { remaining_peers: 1 }
+            }
+            SessionState::Running => DetailedSessionState::Running,
+            SessionState::Ended => DetailedSessionState::Ended,
+        }
+    }
+
     /// Returns the number of frames behind the host
     pub fn frames_behind_host(&self) -> usize {
         let diff = self.last_recv_frame - self.current_frame;
@@ -83,6 +119,71 @@ impl<T: Config> SpectatorSession<T> {
         diff as usize
     }
 
+    /// Sets the maximum frames behind. If the spectator is more than this amount of frames behind the received inputs,
+    /// it will catch up with `catchup_speed` amount of frames per step.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the value is smaller than 1, not smaller than the spectator buffer size (60) or not larger than the catchup speed.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn set_max_frames_behind(&mut self, max_frames_behind: usize) -> Result<(), GGRSError> {
+        if max_frames_behind < 1 {
+            return Err(GGRSError::InvalidRequest {
+                info: "Max frames behind cannot be smaller than 1.".to_owned(),
+            });
+        }
+
+        if max_frames_behind >= SPECTATOR_BUFFER_SIZE {
+            return Err(GGRSError::InvalidRequest {
+                info: "Max frames behind cannot be larger or equal than the Spectator buffer size (60)"
+                    .to_owned(),
+            });
+        }
+
+        if self.catchup_speed >= max_frames_behind {
+            return Err(GGRSError::InvalidRequest {
+                info: "Max frames behind cannot be smaller or equal than the catchup speed."
+                    .to_owned(),
+            });
+        }
+        self.max_frames_behind = max_frames_behind;
+        Ok(())
+    }
+
+    /// Sets the amount of frames the spectator advances per step while it is more than `max_frames_behind` frames behind the host.
+    /// While catching up, the spectator skips ahead, so you will receive multiple [`GGRSRequest::AdvanceFrame`] at once.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the value is smaller than 1 or not smaller than the maximum frames behind.
+    ///
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn set_catchup_speed(&mut self, catchup_speed: usize) -> Result<(), GGRSError> {
+        if catchup_speed < 1 {
+            return Err(GGRSError::InvalidRequest {
+                info: "Catchup speed cannot be smaller than 1.".to_owned(),
+            });
+        }
+
+        if catchup_speed >= self.max_frames_behind {
+            return Err(GGRSError::InvalidRequest {
+                info: "Catchup speed cannot be larger or equal than the allowed maximum frames behind host"
+                    .to_owned(),
+            });
+        }
+        self.catchup_speed = catchup_speed;
+        Ok(())
+    }
+
+    /// Returns the maximum frames behind the host before the spectator starts catching up.
+    pub fn max_frames_behind(&self) -> usize {
+        self.max_frames_behind
+    }
+
+    /// Returns the amount of frames the spectator advances per step while catching up.
+    pub fn catchup_speed(&self) -> usize {
+        self.catchup_speed
+    }
+
     /// Used to fetch some statistics about the quality of the network connection.
     /// # Errors
     /// - Returns [`NotSynchronized`] if the session is not connected to other clients yet.
@@ -92,21 +193,53 @@ impl<T: Config> SpectatorSession<T> {
         self.host.network_stats()
     }
 
-    /// Returns all events that happened since last queried for events. If the number of stored events exceeds `MAX_EVENT_QUEUE_SIZE`, the oldest events will be discarded.
-    pub fn events(&mut self) -> Drain<GGRSEvent<T>> {
+    /// Resets the counters of the [`NetworkStats`] of the connection to the host, like the number of lost packets.
+    /// Afterwards, the stats only cover the time since the reset.
+    pub fn reset_network_stats(&mut self) {
+        self.host.reset_network_stats();
+    }
+
+    /// Returns all events that happened since last queried for events. If the number of stored events exceeds `MAX_EVENT_QUEUE_SIZE`, the oldest events will be discarded,
+    /// except for [`GGRSEvent::Message`]s.
+    pub fn events(&mut self) -> Drain<'_, GGRSEvent<T>> {
         self.event_queue.drain(..)
     }
 
     /// You should call this to notify GGRS that you are ready to advance your gamestate by a single frame.
     /// Returns an order-sensitive [`Vec<GGRSRequest>`]. You should fulfill all requests in the exact order they are provided.
-    /// Failure to do so will cause panics later.
+    /// Failure to do so will cause panics later. If the spectator joined the match late, the first requests start with a
+    /// [`GGRSRequest::LoadGameState`] of the snapshot received from the host. With [`SessionBuilder::with_spectator_checksums()`],
+    /// the requests also contain a [`GGRSRequest::SaveGameState`] every `interval` frames.
     /// # Errors
     /// - Returns [`NotSynchronized`] if the session is not yet ready to accept input.
-    /// In this case, you either need to start the session or wait for synchronization between clients.
+    ///   In this case, you either need to start the session or wait for synchronization between clients.
+    ///   A spectator that joined late also returns this until the snapshot of the host has arrived.
+    /// - Returns [`SpectatorTooFarBehind`] if the inputs of the snapshot frame have been overwritten by newer inputs
+    ///   before the snapshot arrived. The spectator cannot catch up anymore and needs to be restarted.
     ///
     /// [`Vec<GGRSRequest>`]: GGRSRequest
     /// [`NotSynchronized`]: GGRSError::NotSynchronized
+    /// [`SpectatorTooFarBehind`]: GGRSError::SpectatorTooFarBehind
+    /// [`SessionBuilder::with_spectator_checksums()`]: crate::SessionBuilder#method.with_spectator_checksums
     pub fn advance_frame(&mut self) -> Result<Vec<GGRSRequest<T>>, GGRSError> {
+        let mut requests = Vec::new();
+        self.advance_frame_into(&mut requests)?;
+        Ok(requests)
+    }
+
+    /// Same as [`advance_frame()`], but writes the requests into the given buffer instead of allocating a new [`Vec`] every frame.
+    /// The buffer is cleared first, so you need to fulfill all requests in it before advancing the session again.
+    ///
+    /// # Errors
+    /// - Returns the same errors as [`advance_frame()`].
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    pub fn advance_frame_into(
+        &mut self,
+        requests: &mut Vec<GGRSRequest<T>>,
+    ) -> Result<(), GGRSError> {
+        requests.clear();
+
         // receive info from host, trigger events and send messages
         self.poll_remote_clients();
 
@@ -114,10 +247,25 @@ impl<T: Config> SpectatorSession<T> {
             return Err(GGRSError::NotSynchronized);
         }
 
-        let mut requests = Vec::new();
+        // the states saved during the previous call have their checksums now
+        self.compare_checksums();
+
+        // a spectator joining late starts from the snapshot of the host
+        if let Some((frame, cell)) = self.pending_snapshot.take() {
+            requests.push(GGRSRequest::LoadGameState { cell, frame });
+            self.current_frame = frame - 1;
+        } else if self.current_frame == NULL_FRAME && self.first_recv_frame > 0 {
+            // the inputs of the snapshot frame are gone, so the snapshot would be useless
+            if self.last_recv_frame >= self.first_recv_frame + SPECTATOR_BUFFER_SIZE as Frame {
+                return Err(GGRSError::SpectatorTooFarBehind);
+            }
+            // the snapshot has not arrived yet, so we wait
+            return Err(GGRSError::NotSynchronized);
+        }
 
         let frames_to_advance = if self.frames_behind_host() > self.max_frames_behind {
-            self.catchup_speed
+            // never skip beyond the inputs we already received
+            core::cmp::min(self.catchup_speed, self.frames_behind_host())
         } else {
             NORMAL_SPEED
         };
@@ -133,9 +281,20 @@ impl<T: Config> SpectatorSession<T> {
 
             // advance the frame, but only if grabbing the inputs succeeded
             self.current_frame += 1;
+
+            // the advanced state is the state the host saved for the next frame
+            let frame = self.current_frame + 1;
+            if matches!(self.checksum_interval, Some(interval) if frame % interval as Frame == 0) {
+                let cell = GameStateCell::default();
+                self.saved_checksums.push_back((frame, cell.clone()));
+                while self.saved_checksums.len() > MAX_CHECKSUMS {
+                    self.saved_checksums.pop_front();
+                }
+                requests.push(GGRSRequest::SaveGameState { cell, frame });
+            }
         }
 
-        Ok(requests)
+        Ok(())
     }
 
     /// Receive UDP packages, distribute them to corresponding UDP endpoints, handle all occurring events and send all outgoing UDP packages.
@@ -165,6 +324,49 @@ impl<T: Config> SpectatorSession<T> {
         self.host.send_all_messages(&mut self.socket);
     }
 
+    /// Compares the checksums of the saved states with the checksums received from the host for the same frames.
+    /// States the host has not sent a checksum for yet are kept, unless they are older than the newest checksum of the host.
+    fn compare_checksums(&mut self) {
+        self.host_checksums
+            .extend(self.host.take_remote_state_checksums());
+        while self.host_checksums.len() > MAX_CHECKSUMS {
+            self.host_checksums.pop_front();
+        }
+
+        let host_checksums = &mut self.host_checksums;
+        let mut desyncs = Vec::new();
+        self.saved_checksums.retain(|(frame, cell)| {
+            let local_checksum = match cell.checksum() {
+                Some(checksum) => checksum,
+                None => return false,
+            };
+            match host_checksums.iter().position(|(f, _)| f == frame) {
+                Some(pos) => {
+                    let (_, remote_checksum) =
+                        host_checksums.remove(pos).expect("position is valid");
+                    if local_checksum != remote_checksum {
+                        desyncs.push((*frame, local_checksum, remote_checksum));
+                    }
+                    false
+                }
+                // the host skipped this frame
+                None => !matches!(host_checksums.back(), Some((f, _)) if f > frame),
+            }
+        });
+
+        let addr = self.host.peer_addr();
+        for (frame, local_checksum, remote_checksum) in desyncs {
+            self.event_queue.push_back(GGRSEvent::DesyncDetected {
+                frame,
+                kind: DesyncKind::State,
+                local_checksum,
+                remote_checksum,
+                addr: addr.clone(),
+            });
+        }
+        discard_old_events(&mut self.event_queue);
+    }
+
     /// Returns the number of players this session was constructed with.
     pub fn num_players(&self) -> usize {
         self.num_players
@@ -173,7 +375,7 @@ impl<T: Config> SpectatorSession<T> {
     fn inputs_at_frame(
         &self,
         frame_to_grab: Frame,
-    ) -> Result<Vec<(T::Input, InputStatus)>, GGRSError> {
+    ) -> Result<Vec<(T::Input, InputStatus, u8)>, GGRSError> {
         let player_inputs = &self.inputs[frame_to_grab as usize % SPECTATOR_BUFFER_SIZE];
 
         // We haven't received the input from the host yet. Wait.
@@ -193,9 +395,13 @@ impl<T: Config> SpectatorSession<T> {
                 if self.host_connect_status[handle].disconnected
                     && self.host_connect_status[handle].last_frame < frame_to_grab
                 {
-                    (player_input.input, InputStatus::Disconnected)
+                    (player_input.input, InputStatus::Disconnected, 0)
                 } else {
-                    (player_input.input, InputStatus::Confirmed)
+                    (
+                        player_input.input,
+                        InputStatus::ConfirmedRemote,
+                        player_input.flags,
+                    )
                 }
             })
             .collect())
@@ -229,26 +435,63 @@ impl<T: Config> SpectatorSession<T> {
             Event::Disconnected => {
                 self.event_queue.push_back(GGRSEvent::Disconnected { addr });
             }
+            // deserialize the snapshot, but only if we have not started without one
+            Event::Snapshot { frame, bytes } => {
+                if self.current_frame == NULL_FRAME {
+                    if let Some(state) = self.snapshot_decoder.and_then(|decode| decode(&bytes)) {
+                        let cell = GameStateCell::default();
+                        cell.save(frame, Some(state), None);
+                        self.pending_snapshot = Some((frame, cell));
+                    }
+                }
+            }
+            // only peers of the host request states
+            Event::StateRequested { .. } | Event::StateUnavailable { .. } => (),
+            // only peers of the host coordinate pauses
+            Event::PauseControl(_) => (),
+            // only peers of the host let players join
+            Event::PlayerJoin(_) => (),
+            // forward to user
+            Event::Message { bytes } => {
+                self.event_queue
+                    .push_back(GGRSEvent::Message { from: addr, bytes });
+            }
+            // forward to user
+            Event::QualityReportPayload { bytes } => {
+                self.event_queue
+                    .push_back(GGRSEvent::QualityReportPayload { addr, bytes });
+            }
+            // forward to user
+            Event::IncompatiblePeer => {
+                self.event_queue
+                    .push_back(GGRSEvent::IncompatiblePeer { addr, handle: 0 });
+            }
+            // forward to user
+            Event::ClockDriftWarning { offset } => {
+                self.event_queue
+                    .push_back(GGRSEvent::ClockDriftWarning { handle: 0, offset });
+            }
             // add the input and all associated information
             Event::Input { input, player } => {
                 // save the input
                 self.inputs[input.frame as usize % SPECTATOR_BUFFER_SIZE][player] = input;
                 assert!(input.frame >= self.last_recv_frame);
                 self.last_recv_frame = input.frame;
+                if self.first_recv_frame == NULL_FRAME {
+                    self.first_recv_frame = input.frame;
+                }
 
                 // update the frame advantage
                 self.host.update_local_frame_advantage(input.frame);
 
                 // update the host connection status
-                for i in 0..self.num_players as usize {
+                for i in 0..self.num_players {
                     self.host_connect_status[i] = self.host.peer_connect_status(i);
                 }
             }
         }
 
         // check event queue size and discard oldest events if too big
-        while self.event_queue.len() > MAX_EVENT_QUEUE_SIZE {
-            self.event_queue.pop_front();
-        }
+        discard_old_events(&mut self.event_queue);
     }
 }
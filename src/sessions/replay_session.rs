@@ -0,0 +1,124 @@
+use std::io::Read;
+
+use crate::error::GGRSError;
+use crate::sessions::p2p_session::MAX_CHECKSUM_HISTORY;
+use crate::{Config, Frame, GGRSRequest, GameStateCell, ReplayReader};
+
+/// A [`ReplaySession`] plays back inputs recorded with [`P2PSession::set_replay_writer()`]. It hands out the same kind of requests as a live session,
+/// but since all inputs are confirmed, it never needs to roll back. The checksums of the saved states are collected in [`checksum_history()`],
+/// so re-running a recorded match this way lets you verify that your game simulates deterministically by comparing them to [`P2PSession::checksum_history()`].
+///
+/// [`P2PSession::set_replay_writer()`]: crate::P2PSession#method.set_replay_writer
+/// [`P2PSession::checksum_history()`]: crate::P2PSession#method.checksum_history
+/// [`checksum_history()`]: Self#method.checksum_history
+pub struct ReplaySession<T, R>
+where
+    T: Config,
+    R: Read,
+{
+    num_players: usize,
+    current_frame: Frame,
+    reader: ReplayReader<T, R>,
+    finished: bool,
+    /// The state saved with the last request, its checksum is collected with the next frame.
+    last_saved: Option<(Frame, GameStateCell<T::State>)>,
+    checksum_history: Vec<(Frame, u128)>,
+}
+
+impl<T: Config, R: Read> ReplaySession<T, R> {
+    pub(crate) fn new(reader: ReplayReader<T, R>) -> Self {
+        Self {
+            num_players: reader.num_players(),
+            current_frame: reader.start_frame(),
+            reader,
+            finished: false,
+            last_saved: None,
+            checksum_history: Vec::new(),
+        }
+    }
+
+    /// Advances the replay by a single frame. Returns an order-sensitive [`Vec<GGRSRequest>`] to save the current state and advance with the recorded inputs.
+    /// You should fulfill all requests in the exact order they are provided.
+    /// Once the replay has ended, no more requests are returned and [`is_finished()`] returns true.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the recorded inputs could not be read or are out of order.
+    ///
+    /// [`Vec<GGRSRequest>`]: GGRSRequest
+    /// [`is_finished()`]: Self#method.is_finished
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn advance_frame(&mut self) -> Result<Vec<GGRSRequest<T>>, GGRSError> {
+        if self.finished {
+            return Ok(Vec::new());
+        }
+        self.record_checksum();
+
+        let inputs = match self.reader.next_frame() {
+            Ok(Some((frame, inputs))) if frame == self.current_frame => inputs,
+            Ok(Some(_)) => {
+                return Err(GGRSError::InvalidRequest {
+                    info: "The replay contains frames out of order.".to_owned(),
+                });
+            }
+            Ok(None) => {
+                self.finished = true;
+                return Ok(Vec::new());
+            }
+            Err(e) => {
+                return Err(GGRSError::InvalidRequest {
+                    info: format!("Could not read the replay: {}", e),
+                });
+            }
+        };
+
+        let cell = GameStateCell::default();
+        self.last_saved = Some((self.current_frame, cell.clone()));
+        let requests = vec![
+            GGRSRequest::SaveGameState {
+                cell,
+                frame: self.current_frame,
+            },
+            GGRSRequest::AdvanceFrame { inputs },
+        ];
+        self.current_frame += 1;
+        Ok(requests)
+    }
+
+    /// Returns the frames and checksums of the states saved while playing back, ordered by frame and keeping only the most recent ones.
+    /// States without a checksum are skipped. Since every recorded frame is confirmed, these are comparable to the
+    /// [`P2PSession::checksum_history()`] of the recording session.
+    ///
+    /// [`P2PSession::checksum_history()`]: crate::P2PSession#method.checksum_history
+    pub fn checksum_history(&self) -> &[(Frame, u128)] {
+        &self.checksum_history
+    }
+
+    /// Returns true if all recorded frames have been played back.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Returns the current frame of the replay.
+    pub fn current_frame(&self) -> Frame {
+        self.current_frame
+    }
+
+    /// Returns the number of players in the replay.
+    pub fn num_players(&self) -> usize {
+        self.num_players
+    }
+
+    /// Adds the checksum of the last saved state to the `checksum_history`, keeping at most [`MAX_CHECKSUM_HISTORY`] entries.
+    fn record_checksum(&mut self) {
+        if let Some((frame, cell)) = self.last_saved.take() {
+            if let Some(checksum) = cell.checksum() {
+                self.checksum_history.push((frame, checksum));
+            }
+        }
+        let excess = self
+            .checksum_history
+            .len()
+            .saturating_sub(MAX_CHECKSUM_HISTORY);
+        self.checksum_history.drain(..excess);
+    }
+}
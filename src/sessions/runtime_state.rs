@@ -0,0 +1,78 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::input_queue::QueueRuntime;
+use crate::network::messages::ConnectionStatus;
+use crate::network::protocol::EndpointRuntime;
+use crate::Frame;
+#[cfg(feature = "std")]
+use crate::GGRSError;
+
+/// Everything of a [`P2PSession`] that is exported into a [`RuntimeState`].
+///
+/// [`P2PSession`]: crate::P2PSession
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct SessionRuntime {
+    pub(crate) num_players: usize,
+    pub(crate) lockstep: bool,
+    pub(crate) current_frame: Frame,
+    pub(crate) first_frames: Vec<Frame>,
+    pub(crate) queues: Vec<QueueRuntime>,
+    pub(crate) local_connect_status: Vec<ConnectionStatus>,
+    pub(crate) next_spectator_frame: Frame,
+    pub(crate) local_inputs_frame: Frame,
+    pub(crate) last_acked_frame: Frame,
+    pub(crate) input_checksum: u128,
+    pub(crate) next_input_checksum_frame: Frame,
+    pub(crate) input_checksum_history: VecDeque<(Frame, u128)>,
+    pub(crate) pause_round: u32,
+    pub(crate) join_rounds: Vec<(u32, Frame)>,
+    pub(crate) endpoints: Vec<EndpointRuntime>,
+    pub(crate) spectators: Vec<EndpointRuntime>,
+}
+
+/// The input queues, frame counters and connection metadata of a running [`P2PSession`], exported with [`P2PSession::export_runtime_state()`].
+/// A session restored from it with [`SessionBuilder::restore_p2p_session()`] continues the match with the same remote clients, e.g. after
+/// hot-reloading your game logic during development. The remote clients do not notice, as long as the session is restored before they time out.
+///
+/// Use [`to_bytes()`] and [`from_bytes()`] to keep the state across a reload of your binary.
+///
+/// [`P2PSession`]: crate::P2PSession
+/// [`P2PSession::export_runtime_state()`]: crate::P2PSession#method.export_runtime_state
+/// [`SessionBuilder::restore_p2p_session()`]: crate::SessionBuilder#method.restore_p2p_session
+/// [`to_bytes()`]: Self#method.to_bytes
+/// [`from_bytes()`]: Self#method.from_bytes
+#[derive(Clone)]
+pub struct RuntimeState {
+    pub(crate) runtime: SessionRuntime,
+}
+
+impl RuntimeState {
+    /// Returns the frame the restored session continues on.
+    pub fn current_frame(&self) -> Frame {
+        self.runtime.current_frame
+    }
+
+    /// Serializes the state. Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(&self.runtime).expect("The runtime state should always be serializable")
+    }
+
+    /// Deserializes a state serialized with [`to_bytes()`]. Requires the `std` feature.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the bytes are not a serialized state.
+    ///
+    /// [`to_bytes()`]: Self#method.to_bytes
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    #[cfg(feature = "std")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GGRSError> {
+        let runtime = bincode::deserialize(bytes).map_err(|_| GGRSError::InvalidRequest {
+            info: "The bytes are not a serialized runtime state.".to_owned(),
+        })?;
+        Ok(Self { runtime })
+    }
+}
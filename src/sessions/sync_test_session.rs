@@ -1,13 +1,26 @@
-use std::collections::HashMap;
+use alloc::sync::Arc;
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::ops::Range;
+use hashbrown::HashMap;
 
 use crate::error::GGRSError;
 use crate::frame_info::PlayerInput;
 use crate::network::messages::ConnectionStatus;
-use crate::sync_layer::SyncLayer;
-use crate::{Config, Frame, GGRSRequest, PlayerHandle};
+use crate::sync_layer::{DeltaApplier, SharedStateStore, SyncLayer};
+use crate::{ChecksumKind, Config, Frame, GGRSRequest, PlayerHandle, PredictionStrategy};
+
+/// Serializes a state to compare it byte by byte, returning `None` if it cannot be serialized.
+pub(crate) type StateSerializer<S> = fn(&S) -> Option<Vec<u8>>;
 
 /// During a [`SyncTestSession`], GGRS will simulate a rollback every frame and resimulate the last n states, where n is the given check distance.
 /// The resimulated checksums will be compared with the original checksums and report if there was a mismatch.
+///
+/// If the session has been started with recorded checksums (see [`SessionBuilder::with_recorded_checksums()`]), it runs in validation mode instead:
+/// No rollbacks are simulated, but every state is saved and each checksum is compared to the recorded checksum of that frame.
+/// Call [`finish_validation()`] after the last frame to also check the last saved state and that no recorded frame was left out.
+///
+/// [`finish_validation()`]: Self#method.finish_validation
+/// [`SessionBuilder::with_recorded_checksums()`]: crate::SessionBuilder#method.with_recorded_checksums
 pub struct SyncTestSession<T>
 where
     T: Config,
@@ -18,25 +31,60 @@ where
     sync_layer: SyncLayer<T>,
     dummy_connect_status: Vec<ConnectionStatus>,
     checksum_history: HashMap<Frame, Option<u128>>,
+    checksum_log: Vec<(Frame, Option<u128>)>,
+    recorded_checksums: Option<HashMap<Frame, Option<u128>>>,
+    determinism_checks: bool,
+    state_serializer: Option<StateSerializer<T::State>>,
+    state_bytes_history: HashMap<Frame, Vec<u8>>,
     local_inputs: HashMap<PlayerHandle, PlayerInput<T::Input>>,
 }
 
 impl<T: Config> SyncTestSession<T> {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         num_players: usize,
         max_prediction: usize,
+        max_saved_states: usize,
         check_distance: usize,
         input_delay: usize,
+        prediction_strategy: Arc<dyn PredictionStrategy<T::Input>>,
+        checksum_kind: Option<ChecksumKind>,
+        recorded_checksums: Option<Vec<(Frame, Option<u128>)>>,
+        state_init: Option<fn() -> T::State>,
+        state_store: Option<SharedStateStore<T::State>>,
+        delta_applier: Option<DeltaApplier<T::State>>,
+        determinism_checks: bool,
+        state_serializer: Option<StateSerializer<T::State>>,
+        input_sizes: HashMap<PlayerHandle, usize>,
     ) -> Self {
         let mut dummy_connect_status = Vec::new();
         for _ in 0..num_players {
             dummy_connect_status.push(ConnectionStatus::default());
         }
 
-        let mut sync_layer = SyncLayer::new(num_players, max_prediction);
+        let mut sync_layer = SyncLayer::new(
+            num_players,
+            max_prediction,
+            max_saved_states,
+            prediction_strategy,
+            checksum_kind,
+        );
+        if let Some(init) = state_init {
+            sync_layer.prefill_states(init);
+        }
+        if let Some(store) = state_store {
+            sync_layer.set_state_store(store);
+        }
+        if let Some(apply) = delta_applier {
+            sync_layer.enable_delta_states(apply);
+        }
         for i in 0..num_players {
+            sync_layer.set_local(i);
             sync_layer.set_frame_delay(i, input_delay);
         }
+        for (&player_handle, &size) in input_sizes.iter() {
+            sync_layer.set_input_size(player_handle, size);
+        }
 
         Self {
             num_players,
@@ -45,6 +93,11 @@ impl<T: Config> SyncTestSession<T> {
             sync_layer,
             dummy_connect_status,
             checksum_history: HashMap::new(),
+            checksum_log: Vec::new(),
+            recorded_checksums: recorded_checksums.map(|log| log.into_iter().collect()),
+            determinism_checks,
+            state_serializer,
+            state_bytes_history: HashMap::new(),
             local_inputs: HashMap::new(),
         }
     }
@@ -55,20 +108,44 @@ impl<T: Config> SyncTestSession<T> {
     ///
     /// # Errors
     /// - Returns [`InvalidRequest`] when the given handle is not valid (i.e. not between 0 and num_players).
+    /// - Returns [`MismatchedInputSize`] when the input uses more bytes than the player, see [`SessionBuilder::add_player_with_input_size()`].
     ///
     /// [`advance_frame()`]: Self#method.advance_frame
     /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    /// [`MismatchedInputSize`]: GGRSError::MismatchedInputSize
+    /// [`SessionBuilder::add_player_with_input_size()`]: crate::SessionBuilder#method.add_player_with_input_size
     pub fn add_local_input(
         &mut self,
         player_handle: PlayerHandle,
         input: T::Input,
+    ) -> Result<(), GGRSError> {
+        self.add_local_input_with_flags(player_handle, input, 0)
+    }
+
+    /// Same as [`add_local_input()`], but attaches the given `flags` to the input, which are handed back to you next to the input in [`GGRSRequest::AdvanceFrame`].
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] when the given handle is not valid (i.e. not between 0 and num_players).
+    /// - Returns [`MismatchedInputSize`] when the input uses more bytes than the player, see [`SessionBuilder::add_player_with_input_size()`].
+    ///
+    /// [`add_local_input()`]: Self#method.add_local_input
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    /// [`MismatchedInputSize`]: GGRSError::MismatchedInputSize
+    /// [`SessionBuilder::add_player_with_input_size()`]: crate::SessionBuilder#method.add_player_with_input_size
+    pub fn add_local_input_with_flags(
+        &mut self,
+        player_handle: PlayerHandle,
+        input: T::Input,
+        flags: u8,
     ) -> Result<(), GGRSError> {
         if player_handle >= self.num_players {
             return Err(GGRSError::InvalidRequest {
                 info: "The player handle you provided is not valid.".to_owned(),
             });
         }
-        let player_input = PlayerInput::<T::Input>::new(self.sync_layer.current_frame(), input);
+        self.sync_layer.check_input_size(player_handle, &input)?;
+        let player_input =
+            PlayerInput::<T::Input>::new(self.sync_layer.current_frame(), input).with_flags(flags);
         self.local_inputs.insert(player_handle, player_input);
         Ok(())
     }
@@ -79,31 +156,64 @@ impl<T: Config> SyncTestSession<T> {
     ///
     /// # Errors
     /// - Returns [`MismatchedChecksum`] if checksums don't match after resimulation.
+    /// - Returns [`MismatchedChecksum`] if a checksum doesn't match the recorded checksum of that frame in validation mode.
+    /// - Returns [`EmptySaveState`] if the state to roll back to has not been saved, or in validation mode, if the state of the previous frame has not been saved.
+    /// - Returns [`InvalidRequest`] if the recorded checksums in validation mode do not contain the previous frame, but later ones.
+    /// - Returns [`StateNotAvailable`] if the state to roll back to is not kept anymore, see [`SessionBuilder::with_max_saved_states()`]. This is fatal.
+    /// - Returns [`InvalidRequest`] if determinism checks are enabled and a state has been saved without a checksum.
     ///
     /// [`Vec<GGRSRequest>`]: GGRSRequest
     /// [`MismatchedChecksum`]: GGRSError::MismatchedChecksum
+    /// [`EmptySaveState`]: GGRSError::EmptySaveState
+    /// [`StateNotAvailable`]: GGRSError::StateNotAvailable
+    /// [`SessionBuilder::with_max_saved_states()`]: crate::SessionBuilder#method.with_max_saved_states
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
     pub fn advance_frame(&mut self) -> Result<Vec<GGRSRequest<T>>, GGRSError> {
         let mut requests = Vec::new();
+        self.advance_frame_into(&mut requests)?;
+        Ok(requests)
+    }
+
+    /// Same as [`advance_frame()`], but writes the requests into the given buffer instead of allocating a new [`Vec`] every frame.
+    /// The buffer is cleared first, so you need to fulfill all requests in it before advancing the session again.
+    ///
+    /// # Errors
+    /// - Returns the same errors as [`advance_frame()`].
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    pub fn advance_frame_into(
+        &mut self,
+        requests: &mut Vec<GGRSRequest<T>>,
+    ) -> Result<(), GGRSError> {
+        requests.clear();
+
+        // the state of the previous frame has been saved for the first time, so its checksum is the original one
+        self.log_previous_checksum()?;
 
         // if we advanced far enough into the game do comparisons and rollbacks
-        if self.check_distance > 0 && self.sync_layer.current_frame() > self.check_distance as i32 {
-            // compare checksums of older frames to our checksum history (where only the first version of any checksum is recorded)
+        if !self.validation_mode()
+            && self.check_distance > 0
+            && self.sync_layer.current_frame() > self.check_distance as i32
+        {
+            // compare checksums of older frames to our checksum history (where only the first version of any checksum is recorded).
+            // With determinism checks, the oldest frame is compared first, so a mismatch names the first divergent frame
             for i in 0..=self.check_distance as i32 {
-                let frame_to_check = self.sync_layer.current_frame() - i;
-                if !self.checksums_consistent(frame_to_check) {
-                    return Err(GGRSError::MismatchedChecksum {
-                        frame: frame_to_check,
-                    });
-                }
+                let distance = if self.determinism_checks {
+                    self.check_distance as i32 - i
+                } else {
+                    i
+                };
+                let frame_to_check = self.sync_layer.current_frame() - distance;
+                self.check_checksum_consistent(frame_to_check)?;
             }
 
             // simulate rollbacks according to the check_distance
             let frame_to = self.sync_layer.current_frame() - self.check_distance as i32;
-            self.adjust_gamestate(frame_to, &mut requests);
+            self.adjust_gamestate(frame_to, requests)?;
         }
 
         // we require inputs for all players
-        if self.num_players as usize != self.local_inputs.len() {
+        if self.num_players != self.local_inputs.len() {
             return Err(GGRSError::InvalidRequest {
                 info: "Missing local input while calling advance_frame().".to_owned(),
             });
@@ -117,8 +227,8 @@ impl<T: Config> SyncTestSession<T> {
         self.local_inputs.clear();
 
         // save the current frame in the synchronization layer
-        // we can skip all the saving if the check_distance is 0
-        if self.check_distance > 0 {
+        // we can skip all the saving if the check_distance is 0, unless we need the checksums for validation
+        if self.check_distance > 0 || self.validation_mode() {
             requests.push(self.sync_layer.save_current_state());
         }
 
@@ -142,7 +252,7 @@ impl<T: Config> SyncTestSession<T> {
             con_stat.last_frame = self.sync_layer.current_frame();
         }
 
-        Ok(requests)
+        Ok(())
     }
 
     /// Returns the number of players this session was constructed with.
@@ -155,32 +265,173 @@ impl<T: Config> SyncTestSession<T> {
         self.max_prediction
     }
 
+    /// Returns the original checksum of every frame saved so far, in order. The checksums are whatever you provided when saving the states,
+    /// so they are only useful if you provide a checksum for each saved state. No states are saved if `check_distance` is 0, unless the session runs in validation mode.
+    ///
+    /// The log of a known-good run can be given to [`SessionBuilder::with_recorded_checksums()`] to validate later runs against it.
+    ///
+    /// [`SessionBuilder::with_recorded_checksums()`]: crate::SessionBuilder#method.with_recorded_checksums
+    pub fn checksum_log(&self) -> &[(Frame, Option<u128>)] {
+        &self.checksum_log
+    }
+
+    /// Ends the validation against the recorded checksums. This compares the checksum of the state saved in the last frame,
+    /// which [`advance_frame()`] only does when advancing the next frame. Frames after the last recorded frame are not validated.
+    ///
+    /// # Errors
+    /// - Returns the same errors for the last frame as [`advance_frame()`] in validation mode.
+    /// - Returns [`InvalidRequest`] if the session has not been started with recorded checksums or has not reached the last recorded frame.
+    ///
+    /// [`advance_frame()`]: Self#method.advance_frame
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn finish_validation(&mut self) -> Result<(), GGRSError> {
+        let last_recorded = match self.last_recorded_frame() {
+            Some(frame) => frame,
+            None => {
+                return Err(GGRSError::InvalidRequest {
+                    info: "The session has not been started with recorded checksums.".to_owned(),
+                })
+            }
+        };
+        self.log_previous_checksum()?;
+        match self.checksum_log.last() {
+            Some(&(frame, _)) if frame >= last_recorded => Ok(()),
+            _ => Err(GGRSError::InvalidRequest {
+                info: format!(
+                    "The session ended before the last recorded frame {}.",
+                    last_recorded
+                ),
+            }),
+        }
+    }
+
+    fn validation_mode(&self) -> bool {
+        self.recorded_checksums.is_some()
+    }
+
+    /// Returns the last frame of the recorded checksums, if there are any.
+    fn last_recorded_frame(&self) -> Option<Frame> {
+        self.recorded_checksums
+            .as_ref()
+            .and_then(|recorded| recorded.keys().max().copied())
+    }
+
+    /// Adds the checksum of the state saved in the previous frame to the `checksum_log` and compares it to the recorded checksum in validation mode
+    fn log_previous_checksum(&mut self) -> Result<(), GGRSError> {
+        let frame = self.sync_layer.current_frame() - 1;
+        // a failed call to `advance_frame()` might not have advanced the frame
+        if frame < 0 || matches!(self.checksum_log.last(), Some(&(last, _)) if last >= frame) {
+            return Ok(());
+        }
+        let got = match self.sync_layer.saved_state_by_frame(frame) {
+            Some(cell) => cell.checksum(),
+            // every frame has to be saved to be validated
+            None if self.validation_mode() => return Err(GGRSError::EmptySaveState { frame }),
+            None => return Ok(()),
+        };
+        self.checksum_log.push((frame, got));
+
+        let recorded = match self.recorded_checksums.as_ref() {
+            Some(recorded) => recorded,
+            None => return Ok(()),
+        };
+        match recorded.get(&frame) {
+            Some(&expected) if expected != got => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    frame,
+                    ?expected,
+                    ?got,
+                    "checksum does not match the recording"
+                );
+                Err(GGRSError::MismatchedChecksum {
+                    frame,
+                    expected,
+                    got,
+                })
+            }
+            // a gap in the recording would otherwise skip the frame silently
+            None if self.last_recorded_frame().is_some_and(|last| frame < last) => {
+                Err(GGRSError::InvalidRequest {
+                    info: format!("The recorded checksums do not contain frame {}.", frame),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Updates the `checksum_history` and checks if the checksum is identical if it already has been recorded once
-    fn checksums_consistent(&mut self, frame_to_check: Frame) -> bool {
+    fn check_checksum_consistent(&mut self, frame_to_check: Frame) -> Result<(), GGRSError> {
         // remove entries older than the `check_distance`
         let oldest_allowed_frame = self.sync_layer.current_frame() - self.check_distance as i32;
         self.checksum_history
             .retain(|&k, _| k >= oldest_allowed_frame);
+        self.state_bytes_history
+            .retain(|&k, _| k >= oldest_allowed_frame);
 
-        match self.sync_layer.saved_state_by_frame(frame_to_check) {
-            Some(latest_cell) => match self.checksum_history.get(&latest_cell.frame()) {
-                Some(&cs) => cs == latest_cell.checksum(),
+        if let Some(latest_cell) = self.sync_layer.saved_state_by_frame(frame_to_check) {
+            let got = latest_cell.checksum();
+            if self.determinism_checks && got.is_none() {
+                return Err(GGRSError::InvalidRequest {
+                    info: format!(
+                        "The state of frame {} has been saved without a checksum, so it cannot be checked for determinism.",
+                        frame_to_check
+                    ),
+                });
+            }
+            if let Some(serializer) = self.state_serializer {
+                if let Some(bytes) = latest_cell.load().and_then(|state| serializer(&state)) {
+                    match self.state_bytes_history.get(&latest_cell.frame()) {
+                        Some(expected_bytes) if *expected_bytes != bytes => {
+                            return Err(GGRSError::MismatchedState {
+                                frame: frame_to_check,
+                                expected: self
+                                    .checksum_history
+                                    .get(&latest_cell.frame())
+                                    .copied()
+                                    .flatten(),
+                                got,
+                                differing_bytes: differing_bytes(expected_bytes, &bytes),
+                            });
+                        }
+                        Some(_) => (),
+                        None => {
+                            self.state_bytes_history.insert(latest_cell.frame(), bytes);
+                        }
+                    }
+                }
+            }
+            match self.checksum_history.get(&latest_cell.frame()) {
+                Some(&expected) if expected != got => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(frame = frame_to_check, ?expected, ?got, "desync detected");
+                    return Err(GGRSError::MismatchedChecksum {
+                        frame: frame_to_check,
+                        expected,
+                        got,
+                    });
+                }
+                Some(_) => (),
                 None => {
-                    self.checksum_history
-                        .insert(latest_cell.frame(), latest_cell.checksum());
-                    true
+                    self.checksum_history.insert(latest_cell.frame(), got);
                 }
-            },
-            None => true,
+            }
         }
+        Ok(())
     }
 
-    fn adjust_gamestate(&mut self, frame_to: Frame, requests: &mut Vec<GGRSRequest<T>>) {
+    fn adjust_gamestate(
+        &mut self,
+        frame_to: Frame,
+        requests: &mut Vec<GGRSRequest<T>>,
+    ) -> Result<(), GGRSError> {
         let start_frame = self.sync_layer.current_frame();
         let count = start_frame - frame_to;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("rollback", frame = frame_to, depth = count).entered();
 
         // rollback to the first incorrect state
-        requests.push(self.sync_layer.load_frame(frame_to));
+        requests.push(self.sync_layer.load_frame(frame_to)?);
         self.sync_layer.reset_prediction();
         assert_eq!(self.sync_layer.current_frame(), frame_to);
 
@@ -200,5 +451,21 @@ impl<T: Config> SyncTestSession<T> {
             requests.push(GGRSRequest::AdvanceFrame { inputs });
         }
         assert_eq!(self.sync_layer.current_frame(), start_frame);
+        Ok(())
+    }
+}
+
+/// Returns the ranges of bytes that differ between `a` and `b`. If their lengths differ, the extra bytes form the last range.
+fn differing_bytes(a: &[u8], b: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for i in (0..a.len().min(b.len())).filter(|&i| a[i] != b[i]) {
+        match ranges.last_mut() {
+            Some(range) if range.end == i => range.end = i + 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+    if a.len() != b.len() {
+        ranges.push(a.len().min(b.len())..a.len().max(b.len()));
     }
+    ranges
 }
@@ -1,12 +1,32 @@
+use crate::Mutex;
+use alloc::sync::Arc;
+use alloc::{borrow::ToOwned, vec::Vec};
 use bytemuck::Zeroable;
-use parking_lot::Mutex;
-use std::sync::Arc;
+use hashbrown::{HashMap, HashSet};
 
 use crate::error::GGRSError;
 use crate::frame_info::{GameState, PlayerInput};
-use crate::input_queue::InputQueue;
+use crate::input_queue::{InputQueue, QueueRuntime};
 use crate::network::messages::ConnectionStatus;
-use crate::{Config, Frame, GGRSRequest, InputStatus, PlayerHandle, NULL_FRAME};
+use crate::{
+    ChecksumKind, Config, Frame, GGRSRequest, InputStatus, PlayerHandle, PredictionStrategy,
+    StateStore, NULL_FRAME,
+};
+
+/// A [`StateStore`] shared by all cells of a session.
+pub(crate) type SharedStateStore<S> = Arc<Mutex<dyn StateStore<S>>>;
+
+/// Reconstructs a state from the full state a delta has been saved against and the bytes of the delta.
+pub(crate) type DeltaApplier<S> = fn(&S, &[u8]) -> S;
+
+/// The full states that delta states are saved against, shared by all cells of a session with delta states.
+pub(crate) struct DeltaBases<S> {
+    apply: DeltaApplier<S>,
+    states: HashMap<Frame, S>,
+}
+
+/// The [`DeltaBases`] shared by all cells of a session.
+pub(crate) type SharedDeltaBases<S> = Arc<Mutex<DeltaBases<S>>>;
 
 /// An [`Arc<Mutex>`] that you can [`save()`]/[`load()`] a `T` to/from. These will be handed to the user as part of a [`GGRSRequest`].
 ///
@@ -17,26 +37,196 @@ pub struct GameStateCell<T: Clone>(Arc<Mutex<GameState<T>>>);
 impl<T: Clone> GameStateCell<T> {
     /// Saves a `T` the user creates into the cell.
     pub fn save(&self, frame: Frame, data: Option<T>, checksum: Option<u128>) {
+        self.save_with_meta(frame, data, checksum, None);
+    }
+
+    /// Saves a `T` the user creates into the cell, together with arbitrary metadata, e.g. a tag or a handle to what was rendered in that frame.
+    /// The metadata stays with the state through rollbacks and can be read with [`metadata()`] after loading, but is never sent over the network.
+    /// Otherwise, this behaves like [`save()`].
+    ///
+    /// [`metadata()`]: GameStateCell#method.metadata
+    /// [`save()`]: GameStateCell#method.save
+    pub fn save_with_meta(
+        &self,
+        frame: Frame,
+        data: Option<T>,
+        checksum: Option<u128>,
+        metadata: Option<Vec<u8>>,
+    ) {
         let mut state = self.0.lock();
         assert!(frame != NULL_FRAME);
         state.frame = frame;
         state.data = data;
         state.checksum = checksum;
+        state.metadata = metadata;
+        state.bytes = None;
+        Self::move_to_store(&mut state);
+    }
+
+    /// Saves a `T` the user creates into the cell, together with the serialized bytes of the state.
+    /// If no checksum is given and the session has been configured with [`SessionBuilder::with_desync_checksum()`],
+    /// the checksum is computed from the given bytes. If the session has been configured with [`SessionBuilder::with_spectator_snapshots()`],
+    /// the bytes are kept to be sent to spectators joining late. Otherwise, this behaves like [`save()`].
+    ///
+    /// [`SessionBuilder::with_desync_checksum()`]: crate::SessionBuilder#method.with_desync_checksum
+    /// [`SessionBuilder::with_spectator_snapshots()`]: crate::SessionBuilder#method.with_spectator_snapshots
+    /// [`save()`]: GameStateCell#method.save
+    pub fn save_with_bytes(
+        &self,
+        frame: Frame,
+        data: Option<T>,
+        checksum: Option<u128>,
+        state_bytes: &[u8],
+    ) {
+        let kind = self.0.lock().checksum_kind;
+        let checksum = checksum.or_else(|| kind.map(|kind| kind.checksum(state_bytes)));
+        self.save(frame, data, checksum);
+        let mut state = self.0.lock();
+        if state.keep_bytes {
+            state.bytes = Some(state_bytes.to_vec());
+        }
+    }
+
+    /// Saves a `T` into the cell by modifying the state stored in it. Other than [`save()`], this reuses the previous state
+    /// and its allocations, which is useful if your state holds heap-allocated data: Copy your current state over with [`Clone::clone_from()`]
+    /// and saving will not allocate once every cell holds a state. If the cell is empty, a [`Default`] state is modified instead.
+    /// Starting the session with [`SessionBuilder::with_state_reuse()`] fills all cells in advance.
+    ///
+    /// [`save()`]: GameStateCell#method.save
+    /// [`SessionBuilder::with_state_reuse()`]: crate::SessionBuilder#method.with_state_reuse
+    pub fn save_in_place(&self, frame: Frame, checksum: Option<u128>, update: impl FnOnce(&mut T))
+    where
+        T: Default,
+    {
+        let mut state = self.0.lock();
+        assert!(frame != NULL_FRAME);
+        state.frame = frame;
+        update(state.data.get_or_insert_with(T::default));
+        state.checksum = checksum;
+        state.metadata = None;
+        state.bytes = None;
+        Self::move_to_store(&mut state);
+    }
+
+    /// Saves the state of `frame` as a delta against the full state previously saved for `base_frame`, which is much cheaper than saving
+    /// a big state in full. Loading the cell reconstructs the state by applying the delta to the base state with the function given to
+    /// [`SessionBuilder::with_delta_states()`]. The session keeps a full state as long as its cell or any saved delta refers to it,
+    /// so you can save a full state every few frames and deltas against it in between.
+    ///
+    /// # Errors
+    /// - Returns [`InvalidRequest`] if the session has not been configured with delta states, or if no full state of `base_frame` before `frame` is kept.
+    ///   The cell is left unchanged, so you can save the state in full instead.
+    ///
+    /// [`SessionBuilder::with_delta_states()`]: crate::SessionBuilder#method.with_delta_states
+    /// [`InvalidRequest`]: GGRSError::InvalidRequest
+    pub fn save_delta(
+        &self,
+        frame: Frame,
+        base_frame: Frame,
+        delta: Vec<u8>,
+        checksum: Option<u128>,
+    ) -> Result<(), GGRSError> {
+        let mut state = self.0.lock();
+        assert!(frame != NULL_FRAME);
+        let bases = state
+            .delta_bases
+            .clone()
+            .ok_or_else(|| GGRSError::InvalidRequest {
+                info: "Saving delta states requires SessionBuilder::with_delta_states()."
+                    .to_owned(),
+            })?;
+        if base_frame >= frame || !bases.lock().states.contains_key(&base_frame) {
+            return Err(GGRSError::InvalidRequest {
+                info:
+                    "The base frame of a delta state has to be a kept full state before the frame."
+                        .to_owned(),
+            });
+        }
+        state.frame = frame;
+        state.data = None;
+        state.checksum = checksum;
+        state.metadata = None;
+        state.bytes = None;
+        state.is_base = false;
+        state.delta = Some((base_frame, delta));
+        Ok(())
     }
 
     /// Loads a `T` that the user previously saved into.
     pub fn load(&self) -> Option<T> {
         let state = self.0.lock();
-        state.data.clone()
+        if let Some(bases) = &state.delta_bases {
+            let bases = bases.lock();
+            if let Some((base_frame, delta)) = &state.delta {
+                return bases
+                    .states
+                    .get(base_frame)
+                    .map(|base| (bases.apply)(base, delta));
+            }
+            if state.is_base {
+                return bases.states.get(&state.frame).cloned();
+            }
+        }
+        match &state.store {
+            Some(store) if state.in_store => {
+                // the checksum saved in the cell is used, even if the store lost or changed it
+                let (data, _checksum) = store.lock().retrieve(state.frame)?;
+                #[cfg(feature = "tracing")]
+                if _checksum != state.checksum {
+                    tracing::warn!(
+                        frame = state.frame,
+                        "state store returned a different checksum"
+                    );
+                }
+                Some(data)
+            }
+            _ => state.data.clone(),
+        }
+    }
+
+    /// Returns the metadata that the user previously saved together with the state through [`save_with_meta()`].
+    ///
+    /// [`save_with_meta()`]: GameStateCell#method.save_with_meta
+    pub fn metadata(&self) -> Option<Vec<u8>> {
+        let state = self.0.lock();
+        state.metadata.clone()
     }
 
     pub(crate) fn frame(&self) -> Frame {
         self.0.lock().frame
     }
 
-    pub(crate) fn checksum(&self) -> Option<u128> {
+    /// Returns the checksum that was saved together with the state, without loading the state itself.
+    pub fn checksum(&self) -> Option<u128> {
         self.0.lock().checksum
     }
+
+    pub(crate) fn bytes(&self) -> Option<Vec<u8>> {
+        self.0.lock().bytes.clone()
+    }
+
+    /// Moves the saved state into the [`StateStore`] of the cell, if there is one. With a store, the cell never holds a state itself.
+    /// With delta states, the saved state is moved into the base states instead.
+    fn move_to_store(state: &mut GameState<T>) {
+        state.delta = None;
+        if let Some(bases) = &state.delta_bases {
+            state.is_base = match state.data.take() {
+                Some(data) => {
+                    bases.lock().states.insert(state.frame, data);
+                    true
+                }
+                None => false,
+            };
+        } else if let Some(store) = &state.store {
+            state.in_store = match state.data.take() {
+                Some(data) => {
+                    store.lock().store(state.frame, data, state.checksum);
+                    true
+                }
+                None => false,
+            };
+        }
+    }
 }
 
 impl<T: Clone> Default for GameStateCell<T> {
@@ -54,18 +244,27 @@ impl<T: Clone> Clone for GameStateCell<T> {
 #[derive(Clone)]
 pub(crate) struct SavedStates<T: Clone> {
     pub states: Vec<GameStateCell<T>>,
+    /// A cell of its own for checkpoints, so they never overwrite a state the session needs to roll back to.
+    pub checkpoint: GameStateCell<T>,
 }
 
 impl<T: Clone> SavedStates<T> {
-    fn new(max_pred: usize) -> Self {
-        // the states are two cells bigger than the max prediction frames in order to account for
-        // the next frame needing a space and still being able to rollback the max distance
-        let mut states = Vec::with_capacity(max_pred + 2);
-        for _ in 0..max_pred {
-            states.push(GameStateCell::default());
+    fn new(num_states: usize, checksum_kind: Option<ChecksumKind>) -> Self {
+        let mut states = Vec::with_capacity(num_states);
+        for _ in 0..num_states {
+            let cell = GameStateCell::default();
+            cell.0.lock().checksum_kind = checksum_kind;
+            states.push(cell);
         }
+        let checkpoint = GameStateCell::default();
+        checkpoint.0.lock().checksum_kind = checksum_kind;
 
-        Self { states }
+        Self { states, checkpoint }
+    }
+
+    /// Returns all cells, including the checkpoint cell.
+    fn cells(&self) -> impl Iterator<Item = &GameStateCell<T>> {
+        self.states.iter().chain(core::iter::once(&self.checkpoint))
     }
 
     fn get_cell(&self, frame: Frame) -> GameStateCell<T> {
@@ -73,6 +272,54 @@ impl<T: Clone> SavedStates<T> {
         let pos = frame as usize % self.states.len();
         self.states[pos].clone()
     }
+
+    /// Forgets the saved states of all cells. The states are kept in the cells, so saving into them can reuse their allocations.
+    fn discard(&self) {
+        for cell in self.cells() {
+            let mut state = cell.0.lock();
+            state.frame = NULL_FRAME;
+            state.checksum = None;
+            state.metadata = None;
+            state.bytes = None;
+            state.in_store = false;
+            state.is_base = false;
+            state.delta = None;
+        }
+    }
+
+    /// Forgets all base states of delta states that no cell refers to anymore, neither as its own state nor as the base of its delta.
+    fn prune_delta_bases(&self) {
+        let bases = match self.states.first() {
+            Some(cell) => match cell.0.lock().delta_bases.clone() {
+                Some(bases) => bases,
+                None => return,
+            },
+            None => return,
+        };
+        let mut referenced = HashSet::new();
+        for cell in self.cells() {
+            let state = cell.0.lock();
+            match &state.delta {
+                Some((base_frame, _)) => referenced.insert(*base_frame),
+                None if state.is_base => referenced.insert(state.frame),
+                None => false,
+            };
+        }
+        bases
+            .lock()
+            .states
+            .retain(|frame, _| referenced.contains(frame));
+    }
+
+    /// Returns the oldest frame that is still saved in any cell, or [`NULL_FRAME`] if no state has been saved yet.
+    fn oldest_frame(&self) -> Frame {
+        self.states
+            .iter()
+            .map(|cell| cell.0.lock().frame)
+            .filter(|&frame| frame != NULL_FRAME)
+            .min()
+            .unwrap_or(NULL_FRAME)
+    }
 }
 
 pub(crate) struct SyncLayer<T>
@@ -81,28 +328,46 @@ where
 {
     num_players: usize,
     max_prediction: usize,
+    /// The prediction window the sync layer was created with, which cannot be exceeded later.
+    max_prediction_limit: usize,
     saved_states: SavedStates<T::State>,
     last_confirmed_frame: Frame,
     last_saved_frame: Frame,
     current_frame: Frame,
+    /// The frame the sync layer started on.
+    start_frame: Frame,
+    /// The first frame with inputs of each player. Before it, the player is treated as disconnected.
+    first_frames: Vec<Frame>,
+    /// The number of bytes of the input type each player uses. The remaining bytes of their inputs are always zero.
+    input_sizes: Vec<usize>,
     input_queues: Vec<InputQueue<T>>,
 }
 
 impl<T: Config> SyncLayer<T> {
-    /// Creates a new `SyncLayer` instance with given values.
-    pub(crate) fn new(num_players: usize, max_prediction: usize) -> Self {
+    /// Creates a new `SyncLayer` instance with given values. The sync layer keeps up to `num_saved_states` saved states.
+    pub(crate) fn new(
+        num_players: usize,
+        max_prediction: usize,
+        num_saved_states: usize,
+        prediction_strategy: Arc<dyn PredictionStrategy<T::Input>>,
+        checksum_kind: Option<ChecksumKind>,
+    ) -> Self {
         // initialize input_queues
         let mut input_queues = Vec::new();
         for _ in 0..num_players {
-            input_queues.push(InputQueue::new());
+            input_queues.push(InputQueue::new(prediction_strategy.clone()));
         }
         Self {
             num_players,
             max_prediction,
+            max_prediction_limit: max_prediction,
             last_confirmed_frame: NULL_FRAME,
             last_saved_frame: NULL_FRAME,
             current_frame: 0,
-            saved_states: SavedStates::new(max_prediction),
+            start_frame: 0,
+            first_frames: vec![0; num_players],
+            input_sizes: vec![core::mem::size_of::<T::Input>(); num_players],
+            saved_states: SavedStates::new(num_saved_states, checksum_kind),
             input_queues,
         }
     }
@@ -111,12 +376,117 @@ impl<T: Config> SyncLayer<T> {
         self.current_frame
     }
 
+    pub(crate) fn start_frame(&self) -> Frame {
+        self.start_frame
+    }
+
+    /// Starts the sync layer on the given frame instead of frame 0, with all frames before it confirmed. No state before that frame can be loaded.
+    pub(crate) fn set_start_frame(&mut self, frame: Frame) {
+        self.current_frame = frame;
+        self.start_frame = frame;
+        self.last_confirmed_frame = frame - 1;
+        for handle in 0..self.num_players {
+            self.set_first_frame(handle, frame);
+        }
+    }
+
+    /// Returns the first frame with inputs of the given player.
+    pub(crate) fn first_frame(&self, player_handle: PlayerHandle) -> Frame {
+        self.first_frames[player_handle]
+    }
+
+    /// Sets the first frame with inputs of the given player. Before this frame, the player is treated as disconnected.
+    /// This can only be changed before any input of the player has been added.
+    pub(crate) fn set_first_frame(&mut self, player_handle: PlayerHandle, frame: Frame) {
+        self.first_frames[player_handle] = frame;
+        self.input_queues[player_handle].set_start_frame(frame);
+    }
+
+    /// Lets a player start over on the given frame, dropping all their queued inputs. Before this frame, the player is treated as disconnected,
+    /// so this must only be done once none of their earlier inputs are needed anymore.
+    pub(crate) fn restart_player(&mut self, player_handle: PlayerHandle, frame: Frame) {
+        self.first_frames[player_handle] = frame;
+        self.input_queues[player_handle].restart(frame);
+    }
+
+    /// Returns the first frames of all players and their inputs from the frame before the current one on, see [`restore_runtime()`].
+    ///
+    /// [`restore_runtime()`]: Self#method.restore_runtime
+    pub(crate) fn export_runtime(&self) -> (Vec<Frame>, Vec<QueueRuntime>) {
+        let queues = self
+            .input_queues
+            .iter()
+            .map(|queue| queue.export_runtime(self.current_frame - 1))
+            .collect();
+        (self.first_frames.clone(), queues)
+    }
+
+    /// Continues an exported sync layer on its current frame, with all frames before it confirmed. The sync layer must not have any inputs yet.
+    pub(crate) fn restore_runtime(
+        &mut self,
+        current_frame: Frame,
+        first_frames: Vec<Frame>,
+        queues: &[QueueRuntime],
+    ) -> Result<(), GGRSError> {
+        if first_frames.len() != self.num_players || queues.len() != self.num_players {
+            return Err(GGRSError::InvalidRequest {
+                info: "The exported inputs do not match the number of players.".to_owned(),
+            });
+        }
+        self.current_frame = current_frame;
+        self.start_frame = current_frame;
+        self.last_confirmed_frame = current_frame - 1;
+        self.first_frames = first_frames;
+        for (queue, runtime) in self.input_queues.iter_mut().zip(queues) {
+            queue.restore_runtime(runtime)?;
+        }
+        Ok(())
+    }
+
+    /// Fills every empty cell of the saved states with a state created by `init`, so saving into the cells can reuse it.
+    pub(crate) fn prefill_states(&mut self, init: fn() -> T::State) {
+        for cell in &self.saved_states.states {
+            let mut state = cell.0.lock();
+            if state.data.is_none() {
+                state.data = Some(init());
+            }
+        }
+    }
+
+    /// Moves all saved states into the given store instead of keeping them in the cells.
+    pub(crate) fn set_state_store(&mut self, store: SharedStateStore<T::State>) {
+        for cell in self.saved_states.cells() {
+            cell.0.lock().store = Some(store.clone());
+        }
+    }
+
+    /// Lets the cells save delta states with [`GameStateCell::save_delta()`], reconstructing them with the given function.
+    pub(crate) fn enable_delta_states(&mut self, apply: DeltaApplier<T::State>) {
+        let bases = Arc::new(Mutex::new(DeltaBases {
+            apply,
+            states: HashMap::new(),
+        }));
+        for cell in self.saved_states.cells() {
+            cell.0.lock().delta_bases = Some(bases.clone());
+        }
+    }
+
+    /// Keeps the serialized states given to [`GameStateCell::save_with_bytes()`] in the cells, so they can be sent to spectators.
+    pub(crate) fn keep_state_bytes(&mut self) {
+        for cell in self.saved_states.cells() {
+            cell.0.lock().keep_bytes = true;
+        }
+    }
+
     pub(crate) fn advance_frame(&mut self) {
         self.current_frame += 1;
     }
 
     pub(crate) fn save_current_state(&mut self) -> GGRSRequest<T> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(frame = self.current_frame, "saving state");
         self.last_saved_frame = self.current_frame;
+        self.saved_states.prune_delta_bases();
         let cell = self.saved_states.get_cell(self.current_frame);
         GGRSRequest::SaveGameState {
             cell,
@@ -124,38 +494,166 @@ impl<T: Config> SyncLayer<T> {
         }
     }
 
+    /// Returns a request to save and a request to load the current frame for every cell of the saved states.
+    /// The states saved by them need to be discarded with [`discard_saved_states()`] before saving any other state.
+    ///
+    /// [`discard_saved_states()`]: Self#method.discard_saved_states
+    pub(crate) fn prewarm_requests(&self) -> Vec<GGRSRequest<T>> {
+        let frame = self.current_frame;
+        let mut requests = Vec::new();
+        for cell in &self.saved_states.states {
+            requests.push(GGRSRequest::SaveGameState {
+                cell: cell.clone(),
+                frame,
+            });
+            requests.push(GGRSRequest::LoadGameState {
+                cell: cell.clone(),
+                frame,
+            });
+        }
+        requests
+    }
+
+    /// Forgets all saved states, keeping their allocations.
+    pub(crate) fn discard_saved_states(&mut self) {
+        self.saved_states.discard();
+        self.saved_states.prune_delta_bases();
+        self.last_saved_frame = NULL_FRAME;
+    }
+
+    /// Same as [`save_current_state()`], but does not change the last saved frame, so the state is never used to roll back to with sparse saving.
+    /// The state is saved into a cell reserved for checkpoints, so it cannot overwrite the state of the last saved frame.
+    ///
+    /// [`save_current_state()`]: Self#method.save_current_state
+    pub(crate) fn checkpoint_current_state(&mut self) -> GGRSRequest<T> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(frame = self.current_frame, "saving checkpoint state");
+        self.saved_states.prune_delta_bases();
+        let cell = self.saved_states.checkpoint.clone();
+        GGRSRequest::SaveGameState {
+            cell,
+            frame: self.current_frame,
+        }
+    }
+
+    /// Sets the amount of frames the sync layer may predict ahead of the last confirmed frame.
+    /// The window cannot exceed the amount of saved states and cannot be smaller than the amount of frames currently predicted.
+    pub(crate) fn set_max_prediction(&mut self, max_prediction: usize) -> Result<(), GGRSError> {
+        if max_prediction > self.max_prediction_limit {
+            return Err(GGRSError::InvalidRequest {
+                info:
+                    "The prediction window cannot exceed the window the session was created with."
+                        .to_owned(),
+            });
+        }
+        let predicted_frames = self.current_frame - self.last_confirmed_frame - 1;
+        if (max_prediction as i32) < predicted_frames {
+            return Err(GGRSError::InvalidRequest {
+                info: "The prediction window cannot be smaller than the amount of currently predicted frames."
+                    .to_owned(),
+            });
+        }
+        self.max_prediction = max_prediction;
+        Ok(())
+    }
+
+    /// Returns the prediction window the sync layer was created with, which cannot be exceeded.
+    pub(crate) fn max_prediction_limit(&self) -> usize {
+        self.max_prediction_limit
+    }
+
     pub(crate) fn set_frame_delay(&mut self, player_handle: PlayerHandle, delay: usize) {
         assert!(player_handle < self.num_players as PlayerHandle);
-        self.input_queues[player_handle as usize].set_frame_delay(delay);
+        self.input_queues[player_handle].set_frame_delay(delay);
+    }
+
+    /// Sets the number of bytes of the input type the given player uses.
+    pub(crate) fn set_input_size(&mut self, player_handle: PlayerHandle, size: usize) {
+        assert!(size <= core::mem::size_of::<T::Input>());
+        self.input_sizes[player_handle] = size;
+    }
+
+    /// Returns the number of bytes of the input type the given player uses.
+    pub(crate) fn input_size(&self, player_handle: PlayerHandle) -> usize {
+        self.input_sizes[player_handle]
+    }
+
+    /// Returns [`GGRSError::MismatchedInputSize`] if the input uses more bytes of the input type than the given player,
+    /// counting up to its last non-zero byte.
+    pub(crate) fn check_input_size(
+        &self,
+        player_handle: PlayerHandle,
+        input: &T::Input,
+    ) -> Result<(), GGRSError> {
+        let expected = self.input_sizes[player_handle];
+        let got = bytemuck::bytes_of(input)
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |last| last + 1);
+        if got > expected {
+            return Err(GGRSError::MismatchedInputSize { expected, got });
+        }
+        Ok(())
+    }
+
+    /// Zeroes the bytes of the input the player does not use, so they never differ between peers.
+    fn mask_input(
+        &self,
+        player_handle: PlayerHandle,
+        mut input: PlayerInput<T::Input>,
+    ) -> PlayerInput<T::Input> {
+        bytemuck::bytes_of_mut(&mut input.input)[self.input_sizes[player_handle]..].fill(0);
+        input
+    }
+
+    /// Marks the given player as a local player, whose confirmed inputs are [`InputStatus::ConfirmedLocal`].
+    pub(crate) fn set_local(&mut self, player_handle: PlayerHandle) {
+        self.input_queues[player_handle].set_local();
+    }
+
+    /// Returns the input delay of the given player.
+    pub(crate) fn frame_delay(&self, player_handle: PlayerHandle) -> usize {
+        self.input_queues[player_handle].frame_delay()
     }
 
     pub(crate) fn reset_prediction(&mut self) {
         for i in 0..self.num_players {
-            self.input_queues[i as usize].reset_prediction();
+            self.input_queues[i].reset_prediction();
         }
     }
 
-    /// Loads the gamestate indicated by `frame_to_load`.
-    pub(crate) fn load_frame(&mut self, frame_to_load: Frame) -> GGRSRequest<T> {
-        // The state should not be the current state or the state should not be in the future or too far away in the past
-        assert!(
-            frame_to_load != NULL_FRAME
-                && frame_to_load < self.current_frame
-                && frame_to_load >= self.current_frame - self.max_prediction as i32
-        );
+    /// Loads the gamestate indicated by `frame_to_load`. Returns [`GGRSError::EmptySaveState`] if the user did not save the state of that frame.
+    pub(crate) fn load_frame(&mut self, frame_to_load: Frame) -> Result<GGRSRequest<T>, GGRSError> {
+        // The state should not be the current state or the state should not be in the future
+        assert!(frame_to_load != NULL_FRAME && frame_to_load < self.current_frame);
 
         let cell = self.saved_states.get_cell(frame_to_load);
-        assert_eq!(cell.0.lock().frame, frame_to_load);
+        let saved_frame = cell.0.lock().frame;
+        // the cell has been reused for a newer state, since fewer states are kept than needed to roll back this far
+        if saved_frame > frame_to_load {
+            return Err(GGRSError::StateNotAvailable {
+                requested_frame: frame_to_load,
+                oldest_frame: self.saved_states.oldest_frame(),
+            });
+        }
+        if saved_frame != frame_to_load {
+            return Err(GGRSError::EmptySaveState {
+                frame: frame_to_load,
+            });
+        }
         self.current_frame = frame_to_load;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(frame = frame_to_load, "loading state");
 
-        GGRSRequest::LoadGameState {
+        Ok(GGRSRequest::LoadGameState {
             cell,
             frame: frame_to_load,
-        }
+        })
     }
 
     /// Adds local input to the corresponding input queue. Checks if the prediction threshold has been reached. Returns the frame number where the input is actually added to.
-    /// This number will only be different if the input delay was set to a number higher than 0.
+    /// This number will only be different if the input delay was set to a number higher than 0. If the input delay has been decreased, the input might be dropped,
+    /// in which case [`NULL_FRAME`] is returned.
     pub(crate) fn add_local_input(
         &mut self,
         player_handle: PlayerHandle,
@@ -170,28 +668,35 @@ impl<T: Config> SyncLayer<T> {
 
         // The input provided should match the current frame, we account for input delay later
         assert_eq!(input.frame, self.current_frame);
+        let input = self.mask_input(player_handle, input);
         Ok(self.input_queues[player_handle].add_input(input))
     }
 
-    /// Adds remote input to the corresponding input queue.
+    /// Adds remote input to the corresponding input queue. Returns true if the input differs from the prediction used for that frame.
     /// Unlike `add_local_input`, this will not check for correct conditions, as remote inputs have already been checked on another device.
     pub(crate) fn add_remote_input(
         &mut self,
         player_handle: PlayerHandle,
         input: PlayerInput<T::Input>,
-    ) {
-        self.input_queues[player_handle].add_input(input);
+    ) -> bool {
+        let input = self.mask_input(player_handle, input);
+        let queue = &mut self.input_queues[player_handle];
+        let prediction_miss = queue.is_prediction_miss(&input);
+        queue.add_input(input);
+        prediction_miss
     }
 
     /// Returns inputs for all players for the current frame of the sync layer. If there are none for a specific player, return predictions.
     pub(crate) fn synchronized_inputs(
         &mut self,
         connect_status: &[ConnectionStatus],
-    ) -> Vec<(T::Input, InputStatus)> {
+    ) -> Vec<(T::Input, InputStatus, u8)> {
         let mut inputs = Vec::new();
         for (i, con_stat) in connect_status.iter().enumerate() {
-            if con_stat.disconnected && con_stat.last_frame < self.current_frame {
-                inputs.push((T::Input::zeroed(), InputStatus::Disconnected));
+            if self.current_frame < self.first_frames[i]
+                || (con_stat.disconnected && con_stat.last_frame < self.current_frame)
+            {
+                inputs.push((T::Input::zeroed(), InputStatus::Disconnected, 0));
             } else {
                 inputs.push(self.input_queues[i].input(self.current_frame));
             }
@@ -199,6 +704,21 @@ impl<T: Config> SyncLayer<T> {
         inputs
     }
 
+    /// Returns the input of a single player that has already been added to the queue for the given frame.
+    pub(crate) fn queued_input(
+        &self,
+        player_handle: PlayerHandle,
+        frame: Frame,
+    ) -> PlayerInput<T::Input> {
+        self.input_queues[player_handle].confirmed_input(frame)
+    }
+
+    /// Returns the frames, bytes and flags of all inputs queued for the given player, oldest first.
+    #[cfg(feature = "std")]
+    pub(crate) fn queued_inputs(&self, player_handle: PlayerHandle) -> Vec<(Frame, Vec<u8>, u8)> {
+        self.input_queues[player_handle].queued_inputs().collect()
+    }
+
     /// Returns confirmed inputs for all players for the current frame of the sync layer.
     pub(crate) fn confirmed_inputs(
         &self,
@@ -207,7 +727,9 @@ impl<T: Config> SyncLayer<T> {
     ) -> Vec<PlayerInput<T::Input>> {
         let mut inputs = Vec::new();
         for (i, con_stat) in connect_status.iter().enumerate() {
-            if con_stat.disconnected && con_stat.last_frame < frame {
+            if frame < self.first_frames[i]
+                || (con_stat.disconnected && con_stat.last_frame < frame)
+            {
                 inputs.push(PlayerInput::blank_input(NULL_FRAME));
             } else {
                 inputs.push(self.input_queues[i].confirmed_input(frame));
@@ -220,8 +742,8 @@ impl<T: Config> SyncLayer<T> {
     pub(crate) fn set_last_confirmed_frame(&mut self, mut frame: Frame, sparse_saving: bool) {
         // don't set the last confirmed frame after the first incorrect frame before a rollback has happened
         let mut first_incorrect: Frame = NULL_FRAME;
-        for handle in 0..self.num_players as usize {
-            first_incorrect = std::cmp::max(
+        for handle in 0..self.num_players {
+            first_incorrect = core::cmp::max(
                 first_incorrect,
                 self.input_queues[handle].first_incorrect_frame(),
             );
@@ -229,7 +751,7 @@ impl<T: Config> SyncLayer<T> {
 
         // if sparse saving option is turned on, don't set the last confirmed frame after the last saved frame
         if sparse_saving {
-            frame = std::cmp::min(frame, self.last_saved_frame);
+            frame = core::cmp::min(frame, self.last_saved_frame);
         }
 
         // if we set the last confirmed frame beyond the first incorrect frame, we discard inputs that we need later for adjusting the gamestate.
@@ -238,14 +760,14 @@ impl<T: Config> SyncLayer<T> {
         self.last_confirmed_frame = frame;
         if self.last_confirmed_frame > 0 {
             for i in 0..self.num_players {
-                self.input_queues[i as usize].discard_confirmed_frames(frame - 1);
+                self.input_queues[i].discard_confirmed_frames(frame - 1);
             }
         }
     }
 
     /// Finds the earliest incorrect frame detected by the individual input queues
     pub(crate) fn check_simulation_consistency(&self, mut first_incorrect: Frame) -> Frame {
-        for handle in 0..self.num_players as usize {
+        for handle in 0..self.num_players {
             let incorrect = self.input_queues[handle].first_incorrect_frame();
             if incorrect != NULL_FRAME
                 && (first_incorrect == NULL_FRAME || incorrect < first_incorrect)
@@ -267,10 +789,43 @@ impl<T: Config> SyncLayer<T> {
         }
     }
 
+    /// Returns the frames and checksums of all saved states, ordered by frame.
+    pub(crate) fn saved_checksums(&self) -> Vec<(Frame, Option<u128>)> {
+        let mut checksums: Vec<(Frame, Option<u128>)> = self
+            .saved_states
+            .states
+            .iter()
+            .map(|cell| {
+                let state = cell.0.lock();
+                (state.frame, state.checksum)
+            })
+            .filter(|&(frame, _)| frame != NULL_FRAME)
+            .collect();
+        checksums.sort_unstable_by_key(|&(frame, _)| frame);
+        checksums
+    }
+
     /// Returns the latest saved frame
     pub(crate) fn last_saved_frame(&self) -> Frame {
         self.last_saved_frame
     }
+
+    /// Returns the oldest frame that is still saved and can be loaded, or [`NULL_FRAME`] if no state has been saved yet.
+    pub(crate) fn oldest_saved_frame(&self) -> Frame {
+        self.saved_states.oldest_frame()
+    }
+
+    /// Returns the last confirmed frame, before which all inputs have been discarded.
+    pub(crate) fn last_confirmed_frame(&self) -> Frame {
+        self.last_confirmed_frame
+    }
+
+    /// Replaces the saved state of the given frame with a state received from elsewhere, so it can be loaded like any saved state.
+    pub(crate) fn replace_state(&mut self, frame: Frame, state: T::State, bytes: &[u8]) {
+        self.saved_states
+            .get_cell(frame)
+            .save_with_bytes(frame, Some(state), None, bytes);
+    }
 }
 
 // #########
@@ -281,6 +836,7 @@ impl<T: Config> SyncLayer<T> {
 mod sync_layer_tests {
 
     use super::*;
+    use crate::RepeatLast;
     use bytemuck::{Pod, Zeroable};
     use std::net::SocketAddr;
 
@@ -301,7 +857,7 @@ mod sync_layer_tests {
     #[test]
     #[should_panic]
     fn test_reach_prediction_threshold() {
-        let mut sync_layer = SyncLayer::<TestConfig>::new(2, 8);
+        let mut sync_layer = SyncLayer::<TestConfig>::new(2, 8, 8, Arc::new(RepeatLast), None);
         for i in 0..20 {
             let game_input = PlayerInput::new(i, TestInput { inp: i as u8 });
             sync_layer.add_local_input(0, game_input).unwrap(); // should crash at frame 7
@@ -311,7 +867,7 @@ mod sync_layer_tests {
 
     #[test]
     fn test_different_delays() {
-        let mut sync_layer = SyncLayer::<TestConfig>::new(2, 8);
+        let mut sync_layer = SyncLayer::<TestConfig>::new(2, 8, 8, Arc::new(RepeatLast), None);
         let p1_delay = 2;
         let p2_delay = 0;
         sync_layer.set_frame_delay(0, p1_delay);
@@ -341,4 +897,142 @@ mod sync_layer_tests {
             sync_layer.advance_frame();
         }
     }
+
+    #[test]
+    fn test_set_max_prediction() {
+        let mut sync_layer = SyncLayer::<TestConfig>::new(2, 8, 8, Arc::new(RepeatLast), None);
+        // cannot grow beyond the saved states
+        assert!(sync_layer.set_max_prediction(9).is_err());
+
+        for i in 0..4 {
+            let game_input = PlayerInput::new(i, TestInput { inp: i as u8 });
+            sync_layer.add_local_input(0, game_input).unwrap();
+            sync_layer.advance_frame();
+        }
+        // frames 0 to 3 are predicted
+        assert!(sync_layer.set_max_prediction(3).is_err());
+        assert!(sync_layer.set_max_prediction(4).is_ok());
+
+        let game_input = PlayerInput::new(4, TestInput { inp: 4 });
+        assert_eq!(
+            sync_layer.add_local_input(0, game_input),
+            Err(GGRSError::PredictionThreshold)
+        );
+    }
+
+    #[test]
+    fn test_save_with_bytes() {
+        let mut sync_layer =
+            SyncLayer::<TestConfig>::new(2, 8, 8, Arc::new(RepeatLast), Some(ChecksumKind::Crc64));
+        let expected = ChecksumKind::Crc64.checksum(&[1, 2, 3]);
+
+        let cell = match sync_layer.save_current_state() {
+            GGRSRequest::SaveGameState { cell, .. } => cell,
+            _ => unreachable!(),
+        };
+        cell.save_with_bytes(0, Some(1), None, &[1, 2, 3]);
+        assert_eq!(cell.checksum(), Some(expected));
+
+        // a checksum given by the user is kept
+        cell.save_with_bytes(0, Some(1), Some(5), &[1, 2, 3]);
+        assert_eq!(cell.checksum(), Some(5));
+    }
+
+    #[test]
+    fn test_checkpoint_keeps_last_saved_state() {
+        let mut sync_layer = SyncLayer::<TestConfig>::new(2, 8, 2, Arc::new(RepeatLast), None);
+        let save = |request: GGRSRequest<TestConfig>, state: u8| match request {
+            GGRSRequest::SaveGameState { cell, frame } => cell.save(frame, Some(state), None),
+            _ => unreachable!(),
+        };
+        save(sync_layer.save_current_state(), 0);
+        sync_layer.advance_frame();
+        sync_layer.advance_frame();
+
+        // frame 2 would fall into the cell of frame 0, which is still needed to roll back to
+        save(sync_layer.checkpoint_current_state(), 2);
+        assert_eq!(sync_layer.last_saved_frame(), 0);
+        let cell = sync_layer.saved_state_by_frame(0).unwrap();
+        assert_eq!(cell.load(), Some(0));
+    }
+
+    #[test]
+    fn test_save_in_place() {
+        let mut sync_layer = SyncLayer::<TestConfig>::new(2, 8, 8, Arc::new(RepeatLast), None);
+        sync_layer.prefill_states(|| 7);
+
+        let cell = match sync_layer.save_current_state() {
+            GGRSRequest::SaveGameState { cell, .. } => cell,
+            _ => unreachable!(),
+        };
+        cell.save_in_place(0, Some(3), |state| {
+            assert_eq!(*state, 7);
+            *state += 1;
+        });
+        assert_eq!(cell.load(), Some(8));
+        assert_eq!(cell.checksum(), Some(3));
+
+        // an empty cell starts from the default state
+        let cell = GameStateCell::<u8>::default();
+        cell.save_in_place(0, None, |state| *state += 1);
+        assert_eq!(cell.load(), Some(1));
+    }
+
+    #[test]
+    fn test_save_with_meta() {
+        let cell = GameStateCell::<u8>::default();
+        cell.save_with_meta(0, Some(4), None, Some(vec![1, 2, 3]));
+        assert_eq!(cell.load(), Some(4));
+        assert_eq!(cell.metadata(), Some(vec![1, 2, 3]));
+
+        // saving without metadata clears the previous metadata
+        cell.save(1, Some(5), None);
+        assert_eq!(cell.metadata(), None);
+    }
+
+    #[test]
+    fn test_delta_states() {
+        let mut sync_layer = SyncLayer::<TestConfig>::new(2, 8, 8, Arc::new(RepeatLast), None);
+        sync_layer.enable_delta_states(|base, delta| base + delta[0]);
+
+        // a full state every 4 frames and deltas against it in between
+        for frame in 0..=20 {
+            let cell = match sync_layer.save_current_state() {
+                GGRSRequest::SaveGameState { cell, .. } => cell,
+                _ => unreachable!(),
+            };
+            if frame % 4 == 0 {
+                cell.save(frame, Some(frame as u8), None);
+            } else {
+                cell.save_delta(frame, frame - frame % 4, vec![(frame % 4) as u8], None)
+                    .unwrap();
+            }
+            sync_layer.advance_frame();
+        }
+
+        // the deltas of frame 13 to 15 keep their base state, even though its cell holds frame 20 now
+        for frame in 13..=20 {
+            let cell = sync_layer.saved_state_by_frame(frame).unwrap();
+            assert_eq!(cell.load(), Some(frame as u8));
+        }
+        let bases = sync_layer.saved_states.states[0]
+            .0
+            .lock()
+            .delta_bases
+            .clone()
+            .unwrap();
+        let mut frames: Vec<Frame> = bases.lock().states.keys().copied().collect();
+        frames.sort_unstable();
+        assert_eq!(frames, vec![12, 16, 20]);
+
+        // deltas against forgotten or later states are refused and leave the cell unchanged
+        let cell = sync_layer.saved_state_by_frame(20).unwrap();
+        assert!(cell.save_delta(21, 8, vec![1], None).is_err());
+        assert!(cell.save_delta(21, 24, vec![1], None).is_err());
+        assert_eq!(cell.load(), Some(20));
+
+        // base states are forgotten together with the cells
+        sync_layer.discard_saved_states();
+        assert!(bases.lock().states.is_empty());
+    }
 }
@@ -0,0 +1,30 @@
+use instant::{Duration, Instant};
+
+use crate::TimeSource;
+
+/// The default [`TimeSource`] used by GGRS. It measures the time since its creation with a monotonic clock.
+#[derive(Debug, Clone, Copy)]
+pub struct StdTimeSource {
+    start: Instant,
+}
+
+impl StdTimeSource {
+    /// Creates a new [`StdTimeSource`] that starts counting now.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for StdTimeSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for StdTimeSource {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
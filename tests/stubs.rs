@@ -1,3 +1,5 @@
+#![allow(clippy::new_without_default)]
+
 use rand::{prelude::ThreadRng, thread_rng, Rng};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
@@ -39,7 +41,7 @@ impl GameStub {
     }
 
     #[allow(dead_code)]
-    pub fn handle_requests(&mut self, requests: Vec<GGRSRequest<StubConfig>>) {
+    pub fn handle_requests(&mut self, requests: impl IntoIterator<Item = GGRSRequest<StubConfig>>) {
         for request in requests {
             match request {
                 GGRSRequest::LoadGameState { cell, .. } => self.load_game_state(cell),
@@ -59,7 +61,7 @@ impl GameStub {
         self.gs = cell.load().unwrap();
     }
 
-    fn advance_frame(&mut self, inputs: Vec<(StubInput, InputStatus)>) {
+    fn advance_frame(&mut self, inputs: Vec<(StubInput, InputStatus, u8)>) {
         self.gs.advance_frame(inputs);
     }
 }
@@ -100,26 +102,27 @@ impl RandomChecksumGameStub {
         self.gs = cell.load().expect("No data found.");
     }
 
-    fn advance_frame(&mut self, inputs: Vec<(StubInput, InputStatus)>) {
+    fn advance_frame(&mut self, inputs: Vec<(StubInput, InputStatus, u8)>) {
         self.gs.advance_frame(inputs);
     }
 }
 
 #[derive(Default, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StateStub {
     pub frame: i32,
     pub state: i32,
 }
 
 impl StateStub {
-    fn advance_frame(&mut self, inputs: Vec<(StubInput, InputStatus)>) {
+    fn advance_frame(&mut self, inputs: Vec<(StubInput, InputStatus, u8)>) {
         let p0_inputs = inputs[0].0.inp;
         let p1_inputs = inputs[1].0.inp;
 
-        if (p0_inputs + p1_inputs) % 2 == 0 {
-            self.state += 2;
-        } else {
+        if (p0_inputs + p1_inputs) % 2 == 1 {
             self.state -= 1;
+        } else {
+            self.state += 2;
         }
         self.frame += 1;
     }
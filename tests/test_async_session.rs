@@ -0,0 +1,90 @@
+#![cfg(all(feature = "async", feature = "std"))]
+
+mod stubs;
+
+use ggrs::{
+    AsyncNonBlockingSocket, GGRSError, InMemoryNetwork, InMemorySocket, Message, NonBlockingSocket,
+    PlayerType, SessionBuilder, SessionState,
+};
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+use stubs::{StubConfig, StubInput};
+
+/// An async socket whose futures are always ready, backed by an in-memory socket.
+struct AsyncInMemorySocket(InMemorySocket);
+
+impl AsyncNonBlockingSocket<SocketAddr> for AsyncInMemorySocket {
+    fn send_to(&mut self, msg: &Message, addr: &SocketAddr) -> impl Future<Output = ()> {
+        self.0.send_to(msg, addr);
+        std::future::ready(())
+    }
+
+    fn receive_all_messages(&mut self) -> impl Future<Output = Vec<(SocketAddr, Message)>> {
+        std::future::ready(self.0.receive_all_messages())
+    }
+}
+
+/// Runs a future that never has to wait to completion.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[test]
+fn test_async_session() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_async_p2p_session(AsyncInMemorySocket(network.socket(addr1)))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_async_p2p_session(AsyncInMemorySocket(network.socket(addr2)))?;
+
+    // with the `sync-send` feature, polling can be spawned on multi-threaded runtimes
+    #[cfg(feature = "sync-send")]
+    {
+        fn assert_send<F: Send>(_: &F) {}
+        assert_send(&sess1.poll_remote_clients_async());
+    }
+
+    for _ in 0..10 {
+        block_on(sess1.poll_remote_clients_async());
+        block_on(sess2.poll_remote_clients_async());
+    }
+    assert_eq!(sess1.session().current_state(), SessionState::Running);
+    assert_eq!(sess2.session().current_state(), SessionState::Running);
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..20 {
+        sess1
+            .session_mut()
+            .add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.session_mut().advance_frame()?);
+        sess2
+            .session_mut()
+            .add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.session_mut().advance_frame()?);
+        block_on(sess1.poll_remote_clients_async());
+        block_on(sess2.poll_remote_clients_async());
+    }
+
+    // the inputs have been exchanged through the async sockets
+    assert!(sess1.session().confirmed_frame() > 10);
+    assert!(sess2.session().confirmed_frame() > 10);
+    assert_eq!(stub1.gs.frame, stub2.gs.frame);
+
+    Ok(())
+}
@@ -1,8 +1,21 @@
+#![cfg(feature = "std")]
+
 mod stubs;
 
-use ggrs::{GGRSError, PlayerType, SessionBuilder, SessionState, UdpNonBlockingSocket};
+use ggrs::{
+    BuilderError, DesyncKind, DetailedSessionState, GGRSError, GGRSEvent, GGRSRequest,
+    InMemoryNetwork, InMemorySocket, InputCodec, InputStatus, Message, MessageCrypto, NetworkStats,
+    NonBlockingSocket, P2PSession, PlayerConnectionState, PlayerType, ReplayReader, RleInputCodec,
+    RuntimeState, SessionBuilder, SessionConfigSummary, SessionState, SharedSocket, TimeSource,
+    UdpNonBlockingSocket, MAX_PAYLOAD, MAX_QUALITY_REPORT_PAYLOAD, NULL_FRAME,
+};
 use serial_test::serial;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use stubs::{StubConfig, StubInput};
 
 #[test]
@@ -25,6 +38,62 @@ fn test_add_more_players() -> Result<(), GGRSError> {
     Ok(())
 }
 
+#[test]
+fn test_validate_builder() -> Result<(), GGRSError> {
+    let remote_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
+
+    let builder = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(remote_addr), 1)?;
+    assert!(builder.validate().is_ok());
+
+    // changing the number of players afterwards invalidates the handles
+    let builder = SessionBuilder::<StubConfig>::new()
+        .with_num_players(3)
+        .add_player(PlayerType::Remote(remote_addr), 2)?
+        .add_player(PlayerType::Spectator(remote_addr), 3)?
+        .with_num_players(2);
+    assert_eq!(
+        builder.validate(),
+        Err(vec![
+            BuilderError::HandleOutOfRange { handle: 2 },
+            BuilderError::NoLocalPlayer,
+            BuilderError::PlayerCountMismatch {
+                expected: 2,
+                registered: 1
+            },
+        ])
+    );
+
+    // handles can only be used once, and sessions with duplicate handles do not start
+    let builder = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(remote_addr), 1)?
+        .add_player(PlayerType::Remote(remote_addr), 1)?;
+    assert_eq!(
+        builder.validate(),
+        Err(vec![BuilderError::DuplicateHandle { handle: 1 }])
+    );
+    let network = InMemoryNetwork::new(0);
+    assert!(builder
+        .start_p2p_session(network.socket(remote_addr))
+        .is_err());
+
+    // no other session starts with duplicate handles either
+    let builder = || {
+        SessionBuilder::<StubConfig>::new()
+            .add_player(PlayerType::Local, 0)?
+            .add_player(PlayerType::Local, 1)?
+            .add_player(PlayerType::Local, 1)
+    };
+    assert!(builder()?.start_synctest_session().is_err());
+    assert!(builder()?
+        .start_spectator_session(remote_addr, network.socket(remote_addr))
+        .is_err());
+
+    Ok(())
+}
+
 #[test]
 #[serial]
 fn test_start_session() -> Result<(), GGRSError> {
@@ -94,6 +163,37 @@ fn test_synchronize_p2p_sessions() -> Result<(), GGRSError> {
     Ok(())
 }
 
+#[test]
+fn test_sync_progress() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    assert_eq!(sess1.sync_progress(0), None); // local player
+    assert_eq!(sess1.sync_progress(1), Some(0.0));
+
+    let mut progress = Vec::new();
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        progress.extend(sess1.sync_progress(1));
+    }
+    assert!(progress.windows(2).all(|w| w[0] <= w[1]));
+    assert!(progress.iter().any(|&p| p > 0.0 && p < 1.0));
+    assert_eq!(sess1.sync_progress(1), Some(1.0));
+
+    Ok(())
+}
+
 #[test]
 #[serial]
 fn test_advance_frame_p2p_sessions() -> Result<(), GGRSError> {
@@ -144,3 +244,4514 @@ fn test_advance_frame_p2p_sessions() -> Result<(), GGRSError> {
 
     Ok(())
 }
+
+struct XorCrypto(u8);
+
+impl MessageCrypto for XorCrypto {
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        plaintext.iter().map(|b| b ^ self.0).collect()
+    }
+
+    fn open(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+        Some(ciphertext.iter().map(|b| b ^ self.0).collect())
+    }
+}
+
+#[test]
+fn test_message_crypto() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_message_crypto(XorCrypto(42))
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_message_crypto(XorCrypto(42))
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    // a client without the crypto cannot talk to the others
+    let mut sess3 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr3))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        sess3.poll_remote_clients();
+    }
+
+    assert_eq!(sess1.current_state(), SessionState::Running);
+    assert_eq!(sess2.current_state(), SessionState::Running);
+    assert_eq!(sess3.current_state(), SessionState::Synchronizing);
+
+    Ok(())
+}
+
+#[test]
+fn test_detailed_state() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_num_players(3)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .add_player(PlayerType::Remote(addr3), 2)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_num_players(3)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Remote(addr3), 2)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    assert_eq!(
+        sess1.detailed_state(),
+        DetailedSessionState::Synchronizing { remaining_peers: 2 }
+    );
+
+    // the third client has not started yet
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    let state = sess1.detailed_state();
+    assert_eq!(
+        state,
+        DetailedSessionState::Synchronizing { remaining_peers: 1 }
+    );
+    assert_eq!(state.coarse(), SessionState::Synchronizing);
+
+    let mut sess3 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_num_players(3)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .add_player(PlayerType::Local, 2)?
+        .start_p2p_session(network.socket(addr3))?;
+
+    // the sync requests sent before the third client started are resent after a while
+    for _ in 0..20 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        sess3.poll_remote_clients();
+    }
+    assert_eq!(sess1.detailed_state(), DetailedSessionState::Running);
+    assert_eq!(sess1.detailed_state().coarse(), sess1.current_state());
+
+    sess1.end_session()?;
+    assert_eq!(sess1.detailed_state(), DetailedSessionState::Ended);
+    assert_eq!(sess1.detailed_state().coarse(), SessionState::Ended);
+
+    Ok(())
+}
+
+#[test]
+fn test_advance_frame_in_memory_sessions() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    assert_eq!(sess1.current_state(), SessionState::Running);
+    assert_eq!(sess2.current_state(), SessionState::Running);
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..100 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+
+        // gamestate evolves
+        assert_eq!(stub1.gs.frame, i as i32 + 1);
+        assert_eq!(stub2.gs.frame, i as i32 + 1);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_input_size_per_player() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    // invalid input sizes are rejected
+    assert!(SessionBuilder::<StubConfig>::new()
+        .add_player_with_input_size(PlayerType::Local, 0, 0)
+        .is_err());
+    assert!(SessionBuilder::<StubConfig>::new()
+        .add_player_with_input_size(PlayerType::Local, 0, 5)
+        .is_err());
+
+    // player 1 only uses a single byte of the input type
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player_with_input_size(PlayerType::Remote(addr2), 1, 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player_with_input_size(PlayerType::Local, 1, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // inputs cannot use more bytes than the player
+    assert_eq!(
+        sess2.add_local_input(1, StubInput { inp: u32::MAX }),
+        Err(GGRSError::MismatchedInputSize {
+            expected: 1,
+            got: 4
+        })
+    );
+    assert_eq!(
+        sess2.add_local_input_typed(1, 7u32),
+        Err(GGRSError::MismatchedInputSize {
+            expected: 1,
+            got: 4
+        })
+    );
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..10 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        let inp = u32::from_ne_bytes([i as u8, 0, 0, 0]);
+        sess2.add_local_input(1, StubInput { inp })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        assert_eq!(stub1.gs.frame, i as i32 + 1);
+        assert_eq!(stub2.gs.frame, i as i32 + 1);
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_add_local_inputs() -> Result<(), GGRSError> {
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(InMemoryNetwork::new(0).socket(addr))?;
+
+    sess.add_local_inputs(&[(0, StubInput { inp: 3 }), (1, StubInput { inp: 4 })])?;
+    // an invalid handle rejects the whole batch, so the input of player 0 is not overwritten
+    let res = sess.add_local_inputs(&[(0, StubInput { inp: 9 }), (5, StubInput { inp: 9 })]);
+    assert!(res.is_err());
+
+    stub.handle_requests(sess.advance_frame()?);
+    let inputs = sess.inputs_for_frame(0).unwrap();
+    assert_eq!(inputs[0].0.inp, 3);
+    assert_eq!(inputs[1].0.inp, 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_all_local_session_starts_running() -> Result<(), GGRSError> {
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(InMemoryNetwork::new(0).socket(addr))?;
+
+    // no polling needed before advancing
+    assert_eq!(sess.current_state(), SessionState::Running);
+    for i in 0..10 {
+        sess.add_local_inputs(&[(0, StubInput { inp: i }), (1, StubInput { inp: i })])?;
+        stub.handle_requests(sess.advance_frame()?);
+    }
+    assert_eq!(stub.gs.frame, 10);
+
+    Ok(())
+}
+
+#[test]
+fn test_add_local_input_typed() -> Result<(), GGRSError> {
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(InMemoryNetwork::new(0).socket(addr))?;
+
+    // inputs of a different size are rejected
+    assert_eq!(
+        sess.add_local_input_typed(0, 7u8),
+        Err(GGRSError::MismatchedInputSize {
+            expected: 4,
+            got: 1
+        })
+    );
+
+    sess.add_local_input_typed(0, 7u32.to_ne_bytes())?;
+    sess.add_local_input(1, StubInput { inp: 2 })?;
+    stub.handle_requests(sess.advance_frame()?);
+    assert_eq!(sess.inputs_for_frame(0).unwrap()[0].0.inp, 7);
+
+    Ok(())
+}
+
+#[test]
+fn test_replay_recording() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    let buffer = SharedBuffer::default();
+    sess1.set_replay_writer(Box::new(buffer.clone()))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..50 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i * 2 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+
+    // the writer cannot be set after the session advanced
+    assert!(sess1
+        .set_replay_writer(Box::new(SharedBuffer::default()))
+        .is_err());
+
+    let bytes = buffer.0.lock().unwrap().clone();
+    let mut reader = ReplayReader::<StubConfig, _>::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(reader.num_players(), 2);
+
+    let mut frames = 0;
+    while let Some((frame, inputs)) = reader.next_frame().unwrap() {
+        assert_eq!(frame, frames);
+        assert!(inputs[0].0 == StubInput { inp: frame as u32 });
+        assert!(
+            inputs[1].0
+                == StubInput {
+                    inp: frame as u32 * 2
+                }
+        );
+        assert!(inputs.iter().all(|(_, s, _)| s.is_confirmed()));
+        frames += 1;
+    }
+    // all but the most recent frames have been confirmed
+    assert!(frames > 40);
+
+    Ok(())
+}
+
+#[test]
+fn test_change_input_delay() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_input_delay(2)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    // both peers record their confirmed inputs, which must be identical
+    let buffer1 = SharedBuffer::default();
+    let buffer2 = SharedBuffer::default();
+    sess1.set_replay_writer(Box::new(buffer1.clone()))?;
+    sess2.set_replay_writer(Box::new(buffer2.clone()))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..100 {
+        match i {
+            20 => sess1.set_input_delay(3),
+            30 => sess2.set_input_delay(0),
+            40 => sess1.set_input_delay(1),
+            60 => sess2.set_input_delay(4),
+            _ => (),
+        }
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i * 3 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let bytes1 = buffer1.0.lock().unwrap().clone();
+    let bytes2 = buffer2.0.lock().unwrap().clone();
+    let len = bytes1.len().min(bytes2.len());
+    assert!(len > 0);
+    assert_eq!(bytes1[..len], bytes2[..len]);
+
+    // every frame received exactly one input per player
+    let mut reader = ReplayReader::<StubConfig, _>::new(Cursor::new(bytes1)).unwrap();
+    let mut frames = 0;
+    while let Some((frame, _)) = reader.next_frame().unwrap() {
+        assert_eq!(frame, frames);
+        frames += 1;
+    }
+    assert!(frames > 90);
+
+    Ok(())
+}
+
+#[test]
+fn test_confirmed_frame_for() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_input_delay(2)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..10 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess1.poll_remote_clients();
+
+    // the local input is delayed by two frames
+    assert_eq!(sess1.confirmed_frame_for(0)?, 11);
+    assert_eq!(sess1.confirmed_frame_for(1)?, 9);
+    assert!(sess1.confirmed_frame_for(2).is_err()); // invalid handle
+
+    // the confirmed frame of a disconnected player is frozen
+    sess1.disconnect_player(1)?;
+    for i in 10..15 {
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        sess1.poll_remote_clients();
+    }
+    assert_eq!(sess1.confirmed_frame_for(1)?, 9);
+
+    Ok(())
+}
+
+#[test]
+fn test_inputs_for_frame() -> Result<(), GGRSError> {
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_max_prediction_window(8)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(InMemoryNetwork::new(0).socket(addr))?;
+
+    let mut stub = stubs::GameStub::new();
+    for i in 0..20 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i + 1 })?;
+        stub.handle_requests(sess.advance_frame()?);
+    }
+
+    let inputs = sess.inputs_for_frame(19).expect("inputs of the last frame");
+    assert!(inputs[0].0 == StubInput { inp: 19 });
+    assert!(inputs[1].0 == StubInput { inp: 20 });
+    assert!(inputs.iter().all(|(_, s, _)| s.is_confirmed()));
+
+    assert!(sess.inputs_for_frame(12).is_some());
+    // outside of the prediction window
+    assert!(sess.inputs_for_frame(11).is_none());
+    // not advanced yet
+    assert!(sess.inputs_for_frame(20).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_rollback_stats() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(sess1.rollback_stats().total_rollbacks, 0);
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..50 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+
+        // the current frame matches the game state, even after rollbacks
+        assert_eq!(sess1.current_frame(), i as i32 + 1);
+        assert_eq!(stub1.gs.frame, sess1.current_frame());
+    }
+
+    // the inputs of the second session change every frame, so the first session has to roll back
+    let stats = sess1.rollback_stats();
+    assert!(stats.total_rollbacks > 0);
+    assert!(stats.total_frames_resimulated >= stats.total_rollbacks);
+    assert!(stats.max_rollback_depth as usize <= sess1.max_prediction());
+    assert!(stats.average_rollback_depth() >= 1.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_state_saved_events_sparse_saving() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_sparse_saving_mode(true)
+        .with_state_saved_events(true)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    sess1.events().for_each(drop);
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..50 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        let requests = sess1.advance_frame()?;
+        let saved_frames: Vec<_> = requests
+            .iter()
+            .filter_map(|r| match r {
+                GGRSRequest::SaveGameState { frame, .. } => Some(*frame),
+                _ => None,
+            })
+            .collect();
+        stub1.handle_requests(requests);
+
+        // every save request comes with an event
+        let event_frames: Vec<_> = sess1
+            .events()
+            .filter_map(|e| match e {
+                GGRSEvent::StateSaved { frame } => Some(frame),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(saved_frames, event_frames);
+        if let Some(last) = saved_frames.last() {
+            assert_eq!(*last, sess1.last_saved_frame());
+        }
+        // with sparse saving, the confirmed frame is saved at the latest once the prediction window is exceeded
+        assert!(sess1.current_frame() - sess1.last_saved_frame() <= sess1.max_prediction() as i32);
+
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_frame_advantage() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    // not synchronized yet
+    assert_eq!(sess1.frame_advantage(1), None);
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    assert!(sess1.frame_advantage(1).is_some());
+    assert_eq!(sess1.frame_advantage(0), None); // local player
+    assert_eq!(sess1.frame_advantage(5), None); // invalid handle
+
+    sess1.disconnect_player(1)?;
+    assert_eq!(sess1.frame_advantage(1), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_recommended_sleep() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_fps(50)?
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_fps(50)?
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    // without any frame advantage, the session sleeps a single frame
+    assert_eq!(
+        sess1.recommended_sleep(Duration::ZERO),
+        Duration::from_millis(20)
+    );
+    // the time already spent on the frame is subtracted
+    assert_eq!(
+        sess1.recommended_sleep(Duration::from_millis(15)),
+        Duration::from_millis(5)
+    );
+    assert_eq!(
+        sess1.recommended_sleep(Duration::from_millis(30)),
+        Duration::ZERO
+    );
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // sess1 gets a head start and stays ahead of sess2
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..5 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    let mut skip_frames = None;
+    for i in 0..60 {
+        *clock.0.lock().unwrap() += Duration::from_millis(20);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        for event in sess1.events() {
+            if let GGRSEvent::WaitRecommendation { skip_frames: skip } = event {
+                skip_frames = Some(skip);
+            }
+        }
+    }
+
+    // the frames of the last wait recommendation are spread over the next 60 frames
+    assert!(sess1.frames_ahead() >= 3);
+    let skip_frames = skip_frames.expect("sess1 should recommend to wait");
+    assert_eq!(
+        sess1.recommended_sleep(Duration::ZERO),
+        Duration::from_millis(20) + Duration::from_millis(20) * skip_frames / 60
+    );
+    assert!(sess2.frames_ahead() < 0);
+    assert_eq!(sess2.recommended_sleep(Duration::ZERO), Duration::ZERO);
+
+    Ok(())
+}
+
+#[test]
+fn test_time_sync_disabled() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+
+    // counts the wait recommendations for a session that stays ahead of its peer
+    let wait_recommendations = |time_sync: bool| -> Result<usize, GGRSError> {
+        let network = InMemoryNetwork::new(0);
+        let clock = FakeClock::default();
+        let mut sess1 = SessionBuilder::<StubConfig>::new()
+            .with_time_sync(time_sync)
+            .with_time_source(clock.clone())
+            .add_player(PlayerType::Local, 0)?
+            .add_player(PlayerType::Remote(addr2), 1)?
+            .start_p2p_session(network.socket(addr1))?;
+        let mut sess2 = SessionBuilder::<StubConfig>::new()
+            .with_time_source(clock.clone())
+            .add_player(PlayerType::Remote(addr1), 0)?
+            .add_player(PlayerType::Local, 1)?
+            .start_p2p_session(network.socket(addr2))?;
+        for _ in 0..10 {
+            sess1.poll_remote_clients();
+            sess2.poll_remote_clients();
+        }
+
+        let mut stub1 = stubs::GameStub::new();
+        let mut stub2 = stubs::GameStub::new();
+        for i in 0..5 {
+            sess1.add_local_input(0, StubInput { inp: i })?;
+            stub1.handle_requests(sess1.advance_frame()?);
+        }
+        let mut count = 0;
+        for i in 0..120 {
+            *clock.0.lock().unwrap() += Duration::from_millis(20);
+            sess1.poll_remote_clients();
+            sess2.poll_remote_clients();
+            sess1.add_local_input(0, StubInput { inp: i })?;
+            stub1.handle_requests(sess1.advance_frame()?);
+            sess2.add_local_input(1, StubInput { inp: i })?;
+            stub2.handle_requests(sess2.advance_frame()?);
+            count += sess1
+                .events()
+                .filter(|event| matches!(event, GGRSEvent::WaitRecommendation { .. }))
+                .count();
+        }
+        // the frame advantage is tracked either way
+        assert!(sess1.frames_ahead() >= 3);
+        Ok(count)
+    };
+
+    assert!(wait_recommendations(true)? > 0);
+    assert_eq!(wait_recommendations(false)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_should_advance() -> Result<(), GGRSError> {
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let network = InMemoryNetwork::new(0);
+
+    // time does not accumulate before the session is running
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_fps(50)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr), 1)?
+        .start_p2p_session(network.socket("127.0.0.1:8888".parse().unwrap()))?;
+    assert_eq!(sess.should_advance(Duration::from_millis(100)), 0);
+
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_fps(50)?
+        .with_max_prediction_window(8)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr))?;
+
+    // steps of 20ms, the remaining time is carried over
+    assert_eq!(sess.should_advance(Duration::from_millis(15)), 0);
+    assert_eq!(sess.should_advance(Duration::from_millis(15)), 1);
+    assert_eq!(sess.should_advance(Duration::from_millis(50)), 3);
+    assert_eq!(sess.should_advance(Duration::ZERO), 0);
+
+    // after a stall, the steps are capped at the prediction window and the rest of the time is dropped
+    assert_eq!(sess.should_advance(Duration::from_secs(10)), 8);
+    assert_eq!(sess.should_advance(Duration::from_millis(15)), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_player_connection_states() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    assert_eq!(
+        sess1.player_connection_states(),
+        vec![
+            (0, PlayerConnectionState::Local),
+            (1, PlayerConnectionState::Synchronizing),
+            (
+                2,
+                PlayerConnectionState::Spectator {
+                    synchronized: false
+                }
+            ),
+        ]
+    );
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(
+        sess1.player_connection_states()[1],
+        (1, PlayerConnectionState::Synchronized)
+    );
+
+    sess1.disconnect_player(1)?;
+    sess1.disconnect_player(2)?;
+    assert_eq!(
+        sess1.player_connection_states(),
+        vec![
+            (0, PlayerConnectionState::Local),
+            (1, PlayerConnectionState::Disconnected),
+            (2, PlayerConnectionState::Disconnected),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_disconnect_timeout_per_player() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(sess1.current_state(), SessionState::Running);
+
+    assert!(sess1
+        .set_disconnect_timeout(0, Duration::from_millis(0))
+        .is_err());
+    sess1.set_disconnect_notify_delay(1, Duration::from_millis(0))?;
+    sess1.set_disconnect_timeout(1, Duration::from_millis(0))?;
+
+    // sess2 is not polled anymore, so sess1 disconnects player 1 right away
+    std::thread::sleep(Duration::from_millis(1));
+    sess1.poll_remote_clients();
+    assert!(sess1
+        .events()
+        .any(|e| matches!(e, GGRSEvent::Disconnected { addr } if addr == addr2)));
+
+    Ok(())
+}
+
+#[derive(Clone, Default)]
+struct FakeClock(Arc<Mutex<Duration>>);
+
+impl TimeSource for FakeClock {
+    fn now(&self) -> Duration {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[test]
+fn test_packet_loss_stats() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(3).with_packet_loss(0.2);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..100 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let stats = sess1.network_stats(1)?;
+    assert!(stats.packets_received > 0);
+    assert!(stats.packets_lost > 0);
+    assert_eq!(stats.out_of_order_count, 0); // the network never reorders messages
+
+    sess1.reset_network_stats(1)?;
+    *clock.0.lock().unwrap() += Duration::from_secs(1);
+    let stats = sess1.network_stats(1)?;
+    assert_eq!(stats.packets_received, 0);
+    assert_eq!(stats.packets_lost, 0);
+    assert!(sess1.reset_network_stats(0).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_bandwidth_breakdown() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..100 {
+        *clock.0.lock().unwrap() += Duration::from_millis(20);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        if sess1.current_state() == SessionState::Running
+            && sess2.current_state() == SessionState::Running
+        {
+            sess1.add_local_input(0, StubInput { inp: i })?;
+            stub1.handle_requests(sess1.advance_frame()?);
+            sess2.add_local_input(1, StubInput { inp: i })?;
+            stub2.handle_requests(sess2.advance_frame()?);
+        }
+    }
+
+    let stats = sess1.network_stats(1)?;
+    assert!(stats.input_bytes_per_sec > 0);
+    assert!(stats.quality_report_bytes_per_sec > 0);
+    assert!(stats.sync_bytes_per_sec > 0);
+    assert_eq!(stats.reliable_bytes_per_sec, 0);
+    // the breakdown leaves out the UDP headers, which the total includes
+    let breakdown = stats.input_bytes_per_sec
+        + stats.quality_report_bytes_per_sec
+        + stats.sync_bytes_per_sec
+        + stats.reliable_bytes_per_sec;
+    assert!(breakdown <= (stats.kbps_sent + 1) * 1024);
+
+    sess1.send_reliable(1, &[1, 2, 3])?;
+    sess1.poll_remote_clients();
+    assert!(sess1.network_stats(1)?.reliable_bytes_per_sec > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_clock_drift() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock1 = FakeClock::default();
+    let clock2 = FakeClock::default();
+    *clock2.0.lock().unwrap() = Duration::from_secs(1000);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock1.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock2.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    // both clocks run at the same speed, so the different starting points do not count as drift
+    for _ in 0..200 {
+        *clock1.0.lock().unwrap() += Duration::from_millis(20);
+        *clock2.0.lock().unwrap() += Duration::from_millis(20);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(sess1.current_state(), SessionState::Running);
+    assert!(sess1.estimated_clock_offset(1).unwrap() < Duration::from_millis(20));
+    assert!(sess1.estimated_clock_offset(0).is_none());
+    assert!(sess1
+        .events()
+        .all(|event| !matches!(event, GGRSEvent::ClockDriftWarning { .. })));
+
+    // the clock of the second session runs 10% faster
+    for _ in 0..200 {
+        *clock1.0.lock().unwrap() += Duration::from_millis(20);
+        *clock2.0.lock().unwrap() += Duration::from_millis(22);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    let offset = sess1.estimated_clock_offset(1).unwrap();
+    assert!(offset > Duration::from_millis(300) && offset < Duration::from_millis(500));
+    let warnings: Vec<_> = sess1
+        .events()
+        .filter(|event| matches!(event, GGRSEvent::ClockDriftWarning { handle: 1, .. }))
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    // the drift is measured in either direction
+    assert!(sess2.estimated_clock_offset(0).unwrap() > Duration::from_millis(300));
+
+    Ok(())
+}
+
+/// Starts two sessions, the second one after the first has sent its first sync request, and returns how often both have been polled
+/// until they are running, with the clock advancing 50ms between polls.
+fn polls_until_synchronized(
+    builder: fn() -> SessionBuilder<StubConfig>,
+) -> Result<usize, GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = builder()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    sess1.poll_remote_clients();
+    let mut sess2 = builder()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for polls in 1..100 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        if sess1.current_state() == SessionState::Running
+            && sess2.current_state() == SessionState::Running
+        {
+            return Ok(polls);
+        }
+    }
+    panic!("the sessions did not synchronize");
+}
+
+#[test]
+fn test_sync_settings() -> Result<(), GGRSError> {
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_sync_packets(0)
+        .is_err());
+
+    // fewer roundtrips synchronize faster
+    let default_polls = polls_until_synchronized(SessionBuilder::new)?;
+    let single_polls = polls_until_synchronized(|| {
+        SessionBuilder::new()
+            .with_sync_packets(1)
+            .expect("one sync packet is valid")
+    })?;
+    assert!(single_polls < default_polls);
+
+    // the first sync request of the first session is lost, so a shorter interval resends it sooner
+    let fast_polls = polls_until_synchronized(|| {
+        SessionBuilder::new().with_sync_interval(Duration::from_millis(10))
+    })?;
+    assert!(fast_polls < default_polls);
+
+    Ok(())
+}
+
+#[test]
+fn test_jitter_stats() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    // the replies always take the same time to arrive
+    for _ in 0..100 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    let stats = sess1.network_stats(1)?;
+    assert_eq!(stats.ping, 50);
+    assert_eq!(stats.jitter, 0.0);
+
+    // the replies take varying times to arrive
+    for i in 0..100 {
+        *clock.0.lock().unwrap() += Duration::from_millis(20 + 30 * (i % 3));
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    let stats = sess1.network_stats(1)?;
+    assert!(stats.jitter > 0.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_send_reliable() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(1).with_packet_loss(0.3);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    // only remote players and spectators can receive messages, and messages have to fit into a single packet
+    assert!(sess1.send_reliable(0, &[1]).is_err());
+    assert!(sess1.send_reliable(1, &[0; 468]).is_err());
+
+    for i in 0..10 {
+        sess1.send_reliable(1, &[i])?;
+    }
+
+    let mut received = Vec::new();
+    for _ in 0..100 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        for event in sess2.events() {
+            if let GGRSEvent::Message { from, bytes } = event {
+                assert_eq!(from, addr1);
+                received.push(bytes);
+            }
+        }
+    }
+
+    // despite the lost packets, every message arrived exactly once and in order
+    let expected: Vec<Vec<u8>> = (0..10).map(|i| vec![i]).collect();
+    assert_eq!(received, expected);
+    // all messages have been acknowledged, so nothing is left to resend
+    assert_eq!(sess1.pending_send_bytes(1)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_reliable_messages_kept() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for round in 0..2u8 {
+        // only a limited number of messages can wait for an acknowledgement
+        for i in 0..64 {
+            sess1.send_reliable(1, &[round, i])?;
+        }
+        assert!(sess1.send_reliable(1, &[round, 64]).is_err());
+
+        for _ in 0..20 {
+            *clock.0.lock().unwrap() += Duration::from_millis(50);
+            sess1.poll_remote_clients();
+            sess2.poll_remote_clients();
+        }
+    }
+
+    // more messages than fit into the event queue arrived, but none of them have been discarded
+    let received: Vec<Vec<u8>> = sess2
+        .events()
+        .filter_map(|event| match event {
+            GGRSEvent::Message { bytes, .. } => Some(bytes),
+            _ => None,
+        })
+        .collect();
+    let expected: Vec<Vec<u8>> = (0..2u8)
+        .flat_map(|round| (0..64).map(move |i| vec![round, i]))
+        .collect();
+    assert_eq!(received, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_quality_report_payload() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_quality_report_payload(|| b"fps=60".to_vec())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_quality_report_payload(|| vec![0; MAX_QUALITY_REPORT_PAYLOAD + 1])
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    let mut received1 = 0;
+    let mut received2 = 0;
+    for _ in 0..20 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        received1 += sess1
+            .events()
+            .filter(|e| matches!(e, GGRSEvent::QualityReportPayload { .. }))
+            .count();
+        for event in sess2.events() {
+            if let GGRSEvent::QualityReportPayload { addr, bytes } = event {
+                assert_eq!(addr, addr1);
+                assert_eq!(bytes, b"fps=60");
+                received2 += 1;
+            }
+        }
+    }
+
+    // payloads arrive with the regular quality reports, but payloads that are too big are never sent
+    assert!(received2 > 0);
+    assert_eq!(received1, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_incompatible_app_id() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_app_id(1)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_app_id(2)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    let mut incompatible1 = Vec::new();
+    let mut incompatible2 = Vec::new();
+    for _ in 0..20 {
+        *clock.0.lock().unwrap() += Duration::from_millis(100);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        for event in sess1.events() {
+            if let GGRSEvent::IncompatiblePeer { addr, handle } = event {
+                incompatible1.push((addr, handle));
+            }
+        }
+        for event in sess2.events() {
+            if let GGRSEvent::IncompatiblePeer { addr, handle } = event {
+                incompatible2.push((addr, handle));
+            }
+        }
+    }
+
+    // both sides are notified once and never synchronize
+    assert_eq!(incompatible1, vec![(addr2, 1)]);
+    assert_eq!(incompatible2, vec![(addr1, 0)]);
+    assert_eq!(sess1.current_state(), SessionState::Synchronizing);
+    assert_eq!(sess2.current_state(), SessionState::Synchronizing);
+
+    Ok(())
+}
+
+/// Starts two sessions with the given shared seeds and returns the first one after polling both, together with whether it saw an incompatible peer.
+fn start_with_shared_seeds(
+    seed1: Option<u64>,
+    seed2: Option<u64>,
+) -> Result<(P2PSession<StubConfig>, bool), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let builder = |seed: Option<u64>| match seed {
+        Some(seed) => SessionBuilder::<StubConfig>::new().with_shared_seed(seed),
+        None => SessionBuilder::<StubConfig>::new(),
+    };
+
+    let mut sess1 = builder(seed1)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = builder(seed2)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    let mut incompatible = false;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        incompatible |= sess1
+            .events()
+            .any(|event| matches!(event, GGRSEvent::IncompatiblePeer { handle: 1, .. }));
+    }
+    Ok((sess1, incompatible))
+}
+
+#[test]
+fn test_shared_seed() -> Result<(), GGRSError> {
+    let (sess, incompatible) = start_with_shared_seeds(Some(42), Some(42))?;
+    assert!(!incompatible);
+    assert_eq!(sess.current_state(), SessionState::Running);
+    assert_eq!(sess.shared_seed(), Some(42));
+
+    // a different seed or no seed at all keeps the peers from synchronizing
+    for (seed1, seed2) in [(Some(42), Some(7)), (Some(42), None), (None, Some(42))] {
+        let (sess, incompatible) = start_with_shared_seeds(seed1, seed2)?;
+        assert!(incompatible);
+        assert_eq!(sess.current_state(), SessionState::Synchronizing);
+    }
+
+    let (sess, _) = start_with_shared_seeds(None, None)?;
+    assert_eq!(sess.current_state(), SessionState::Running);
+    assert_eq!(sess.shared_seed(), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_config_summary() -> Result<(), GGRSError> {
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let builder = SessionBuilder::<StubConfig>::new()
+        .with_input_delay(2)
+        .with_max_prediction_window(6)
+        .with_sparse_saving_mode(true)
+        .with_disconnect_timeout(Duration::from_secs(5))
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?;
+    let summary = builder.config_summary();
+    assert_eq!(
+        summary,
+        SessionConfigSummary {
+            num_players: 2,
+            input_delay: 2,
+            max_prediction: 6,
+            sparse_saving: true,
+            fps: 60,
+            disconnect_timeout: Duration::from_secs(5),
+            disconnect_notify_start: Duration::from_millis(500),
+        }
+    );
+
+    // the session reports the settings it has been started with, and the current input delay
+    let mut sess = builder.start_p2p_session(network.socket(addr2))?;
+    assert_eq!(sess.config_summary(), summary);
+    sess.set_input_delay(4);
+    assert_eq!(sess.config_summary().input_delay, 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_pause_and_resume() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    assert!(sess1.pause().is_err());
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert!(sess1.resume().is_err());
+
+    // the first peer runs ahead of the second one
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..10 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        if i < 6 {
+            sess2.add_local_input(1, StubInput { inp: i })?;
+            stub2.handle_requests(sess2.advance_frame()?);
+        }
+    }
+
+    // the first peer stops right away, the second one keeps advancing up to the agreed frame
+    sess1.pause()?;
+    assert!(sess1.pause().is_err());
+    sess1.add_local_input(0, StubInput { inp: 10 })?;
+    assert_eq!(sess1.advance_frame().err(), Some(GGRSError::Paused));
+    let mut frames2 = 6;
+    loop {
+        sess1.poll_remote_clients();
+        sess2.add_local_input(1, StubInput { inp: frames2 })?;
+        match sess2.advance_frame() {
+            Ok(requests) => stub2.handle_requests(requests),
+            Err(GGRSError::Paused) => break,
+            Err(e) => return Err(e),
+        }
+        frames2 += 1;
+    }
+    assert_eq!(sess1.current_frame(), 10);
+    assert_eq!(sess2.current_frame(), 10);
+    for sess in [&mut sess1, &mut sess2] {
+        assert!(sess.is_paused());
+        assert!(sess
+            .events()
+            .any(|e| matches!(e, GGRSEvent::Paused { frame: 10 })));
+    }
+
+    // the connection stays alive while paused
+    for _ in 0..50 {
+        *clock.0.lock().unwrap() += Duration::from_millis(100);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        assert_eq!(sess1.advance_frame().err(), Some(GGRSError::Paused));
+    }
+    assert!(sess1
+        .events()
+        .all(|e| !matches!(e, GGRSEvent::Disconnected { .. })));
+
+    // one peer resumes for both, and both continue from the agreed frame
+    sess2.resume()?;
+    sess2.poll_remote_clients();
+    sess1.poll_remote_clients();
+    assert!(sess1
+        .events()
+        .any(|e| matches!(e, GGRSEvent::Resumed { addr } if addr == addr2)));
+    assert!(!sess1.is_paused());
+    assert!(!sess2.is_paused());
+    for i in 10..20 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    assert_eq!(stub1.gs.frame, 20);
+    assert_eq!(stub2.gs.frame, 20);
+
+    Ok(())
+}
+
+#[test]
+fn test_rollback_event() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // the first peer predicts blank inputs for the second one
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..4 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    for _ in 0..4 {
+        sess2.add_local_input(1, StubInput { inp: 5 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    assert!(sess1
+        .events()
+        .all(|e| !matches!(e, GGRSEvent::Rollback { .. })));
+
+    // once the inputs arrive, the first peer rolls back to the first mispredicted frame and re-simulates up to the current frame
+    sess1.poll_remote_clients();
+    sess1.add_local_input(0, StubInput { inp: 4 })?;
+    let requests = sess1.advance_frame()?;
+    let rollbacks: Vec<_> = sess1
+        .events()
+        .filter_map(|e| match e {
+            GGRSEvent::Rollback {
+                from_frame,
+                to_frame,
+            } => Some((from_frame, to_frame)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(rollbacks, vec![(4, 0)]);
+    assert!(matches!(
+        requests.first(),
+        Some(GGRSRequest::LoadGameState { frame: 0, .. })
+    ));
+    let advances = requests
+        .iter()
+        .filter(|r| matches!(r, GGRSRequest::AdvanceFrame { .. }))
+        .count();
+    assert_eq!(advances, 5);
+    stub1.handle_requests(requests);
+
+    Ok(())
+}
+
+#[test]
+fn test_confirmed_input_status() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // the local input is confirmed right away, the remote input is predicted
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..3 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    let inputs = sess1.inputs_for_frame(2).unwrap();
+    assert_eq!(inputs[0].1, InputStatus::ConfirmedLocal);
+    assert_eq!(inputs[1].1, InputStatus::Predicted);
+    for _ in 0..3 {
+        sess2.add_local_input(1, StubInput { inp: 5 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+
+    // while resimulating, the local input stays local and the received input is confirmed
+    sess1.poll_remote_clients();
+    sess1.add_local_input(0, StubInput { inp: 3 })?;
+    let requests = sess1.advance_frame()?;
+    let statuses: Vec<_> = requests
+        .iter()
+        .filter_map(|r| match r {
+            GGRSRequest::AdvanceFrame { inputs } => Some((inputs[0].1, inputs[1].1)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        statuses[..3],
+        [(InputStatus::ConfirmedLocal, InputStatus::ConfirmedRemote); 3]
+    );
+    assert_eq!(
+        statuses[3],
+        (InputStatus::ConfirmedLocal, InputStatus::Predicted)
+    );
+    stub1.handle_requests(requests);
+
+    Ok(())
+}
+
+#[test]
+fn test_rollback_requests_after_error() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // the first peer predicts up to the prediction window
+    let mut stub1 = stubs::GameStub::new();
+    for _ in 0..8 {
+        sess1.add_local_input(0, StubInput { inp: 0 })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+
+    // a single mispredicted input causes a rollback, but the session is still too far ahead to advance
+    let mut stub2 = stubs::GameStub::new();
+    sess2.add_local_input(1, StubInput { inp: 1 })?;
+    stub2.handle_requests(sess2.advance_frame()?);
+    sess1.poll_remote_clients();
+    sess1.add_local_input(0, StubInput { inp: 0 })?;
+    assert!(matches!(
+        sess1.advance_frame(),
+        Err(GGRSError::PredictionThreshold)
+    ));
+
+    // the requests of the rollback come first once the session advances again
+    for _ in 0..8 {
+        sess2.add_local_input(1, StubInput { inp: 1 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess1.poll_remote_clients();
+    sess1.add_local_input(0, StubInput { inp: 0 })?;
+    let requests = sess1.advance_frame()?;
+    assert!(matches!(
+        requests.first(),
+        Some(GGRSRequest::LoadGameState { frame: 0, .. })
+    ));
+    stub1.handle_requests(requests);
+    assert_eq!(stub1.gs.frame, stub2.gs.frame);
+    assert_eq!(stub1.gs.state, stub2.gs.state);
+
+    Ok(())
+}
+
+#[test]
+fn test_prediction_barrier() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    sess1.set_prediction_barrier(2)?;
+    assert_eq!(sess1.prediction_barrier(), Some(2));
+
+    // the first peer can predict up to the barrier, but not beyond it
+    let mut stub1 = stubs::GameStub::new();
+    for i in 0..3 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    sess1.add_local_input(0, StubInput { inp: 3 })?;
+    assert!(matches!(
+        sess1.advance_frame(),
+        Err(GGRSError::PredictionThreshold)
+    ));
+    assert_eq!(sess1.current_frame(), 3);
+
+    // once the barrier frame is confirmed, the session advances again
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..3 {
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess1.add_local_input(0, StubInput { inp: 3 })?;
+    stub1.handle_requests(sess1.advance_frame()?);
+    assert_eq!(sess1.current_frame(), 4);
+    assert_eq!(sess1.prediction_barrier(), None);
+
+    // barriers in the past are refused
+    assert!(sess1.set_prediction_barrier(1).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_error_classification() -> Result<(), GGRSError> {
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let socket = UdpNonBlockingSocket::bind_to_port(7777).unwrap();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(socket)?;
+
+    sess.add_local_input(0, StubInput { inp: 0 })?;
+    let err = sess.advance_frame().err().unwrap();
+    assert_eq!(err, GGRSError::NotSynchronized);
+    assert!(err.is_retryable());
+    assert!(!err.is_fatal());
+
+    let err = sess.add_local_input(1, StubInput { inp: 0 }).unwrap_err();
+    assert!(!err.is_retryable());
+    assert!(!err.is_fatal());
+
+    let err = GGRSError::MismatchedChecksum {
+        frame: 0,
+        expected: None,
+        got: None,
+    };
+    assert!(err.is_fatal());
+    assert!(!err.is_retryable());
+    assert!(GGRSError::SessionEnded.is_fatal());
+
+    Ok(())
+}
+
+#[test]
+fn test_delta_states_need_full_states() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let builder = || {
+        SessionBuilder::<StubConfig>::new()
+            .with_delta_states(|base, _| *base)
+            .add_player(PlayerType::Local, 0)?
+            .add_player(PlayerType::Remote(addr2), 1)
+    };
+
+    // spectator snapshots and desync recovery send the bytes of full states
+    let result = builder()?
+        .with_spectator_snapshots(true)
+        .start_p2p_session(network.socket(addr1));
+    assert!(matches!(result, Err(GGRSError::InvalidRequest { .. })));
+    let result = builder()?
+        .with_input_desync_detection(10)?
+        .with_desync_recovery(true)
+        .with_snapshot_decoder(|_| None)
+        .start_p2p_session(network.socket(addr1));
+    assert!(matches!(result, Err(GGRSError::InvalidRequest { .. })));
+    assert!(builder()?.start_p2p_session(network.socket(addr1)).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_socket() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let shared1 = SharedSocket::<StubConfig>::new(network.socket(addr1));
+    let shared2 = SharedSocket::<StubConfig>::new(network.socket(addr2));
+
+    // two independent matches between the same two hosts over a single socket each
+    let mut sessions = Vec::new();
+    for session_id in 0..2 {
+        let sess1 = SessionBuilder::<StubConfig>::new()
+            .add_player(PlayerType::Local, 0)?
+            .add_player(PlayerType::Remote(addr2), 1)?
+            .start_shared_p2p_session(&shared1, session_id)?;
+        let sess2 = SessionBuilder::<StubConfig>::new()
+            .add_player(PlayerType::Remote(addr1), 0)?
+            .add_player(PlayerType::Local, 1)?
+            .start_shared_p2p_session(&shared2, session_id)?;
+        sessions.push((sess1, sess2));
+    }
+    assert!(SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_shared_p2p_session(&shared1, 0)
+        .is_err());
+
+    for _ in 0..10 {
+        for (sess1, sess2) in sessions.iter_mut() {
+            sess1.poll_remote_clients();
+            sess2.poll_remote_clients();
+        }
+    }
+
+    for (i, (sess1, sess2)) in sessions.iter_mut().enumerate() {
+        assert_eq!(sess1.current_state(), SessionState::Running);
+        assert_eq!(sess2.current_state(), SessionState::Running);
+
+        let mut stub1 = stubs::GameStub::new();
+        let mut stub2 = stubs::GameStub::new();
+        for _ in 0..10 {
+            sess1.add_local_input(0, StubInput { inp: i as u32 })?;
+            stub1.handle_requests(sess1.advance_frame()?);
+            sess2.add_local_input(1, StubInput { inp: i as u32 + 10 })?;
+            stub2.handle_requests(sess2.advance_frame()?);
+        }
+        // the inputs of the other match never arrive here
+        let confirmed = sess1.recent_confirmed_inputs(1, 8)?;
+        assert!(!confirmed.is_empty());
+        assert!(confirmed
+            .iter()
+            .all(|(_, input, _)| input.inp == i as u32 + 10));
+
+        // the session id takes up some of the bytes of a packet
+        assert!(sess1.send_reliable(1, &[0; MAX_PAYLOAD - 18]).is_ok());
+        assert!(sess1.send_reliable(1, &[0; MAX_PAYLOAD - 17]).is_err());
+    }
+
+    sessions.clear();
+    assert_eq!(shared1.num_sessions(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_input_validator() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    // inputs above 100 are impossible
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_input_validator(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) <= 100)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // invalid local inputs are rejected
+    assert!(sess1.add_local_input(0, StubInput { inp: 200 }).is_err());
+
+    // invalid remote inputs are replaced with the last valid input
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..6 {
+        let inp = if i < 3 { 5 } else { 200 };
+        sess2.add_local_input(1, StubInput { inp })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    let mut stub1 = stubs::GameStub::new();
+    for _ in 0..6 {
+        sess1.add_local_input(0, StubInput { inp: 1 })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+
+    let invalid: Vec<_> = sess1
+        .events()
+        .filter_map(|e| match e {
+            GGRSEvent::InvalidInput { handle, frame } => Some((handle, frame)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(invalid, vec![(1, 3), (1, 4), (1, 5)]);
+    let confirmed = sess1.recent_confirmed_inputs(1, 6)?;
+    assert!(confirmed.iter().any(|(frame, _, _)| *frame == 4));
+    assert!(confirmed.iter().all(|(_, input, _)| input.inp == 5));
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_frame_gaps() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(sess1.peer_frame_gaps(), vec![(1, 1)]);
+
+    // the gap grows while the remote inputs do not arrive
+    let mut stub1 = stubs::GameStub::new();
+    for i in 0..4 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    assert_eq!(sess1.peer_frame_gaps(), vec![(1, 5)]);
+
+    // and shrinks once they do
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..3 {
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess1.poll_remote_clients();
+    assert_eq!(sess1.peer_frame_gaps(), vec![(1, 2)]);
+
+    Ok(())
+}
+
+/// Runs two peers over a lossy network for 200 frames, and returns the number of frames the second peer advanced
+/// and its stats for the connection to the first one.
+fn lossy_input_stats(redundancy: Option<usize>) -> Result<(i32, NetworkStats), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(3).with_packet_loss(0.3);
+    let clock = FakeClock::default();
+
+    let builder = || {
+        let builder = SessionBuilder::<StubConfig>::new().with_time_source(clock.clone());
+        match redundancy {
+            Some(frames) => builder.with_input_redundancy(frames),
+            None => builder,
+        }
+    };
+    let mut sess1 = builder()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = builder()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..50 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(sess1.current_state(), SessionState::Running);
+    assert_eq!(sess2.current_state(), SessionState::Running);
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for _ in 0..200 {
+        *clock.0.lock().unwrap() += Duration::from_millis(16);
+        for (sess, stub, handle) in [(&mut sess1, &mut stub1, 0), (&mut sess2, &mut stub2, 1)] {
+            sess.add_local_input(handle, StubInput { inp: 1 })?;
+            match sess.advance_frame() {
+                Ok(requests) => stub.handle_requests(requests),
+                Err(GGRSError::PredictionThreshold) => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok((stub2.gs.frame, sess2.network_stats(0)?))
+}
+
+#[test]
+fn test_input_redundancy() -> Result<(), GGRSError> {
+    // without re-included frames, every lost input has to be resent, which stalls the session
+    let (frames_without, stats) = lossy_input_stats(Some(0))?;
+    assert_eq!(stats.input_redundancy, 0);
+    assert_eq!(stats.inputs_recovered, 0);
+
+    // by default, inputs lost on the way arrive with the next packet
+    let (frames_with, stats) = lossy_input_stats(None)?;
+    assert!(stats.inputs_recovered > 0);
+    assert!(frames_with > frames_without);
+
+    Ok(())
+}
+
+#[test]
+fn test_adaptive_prediction() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_adaptive_prediction(0, 8)
+        .is_err());
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_adaptive_prediction(5, 4)
+        .is_err());
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_adaptive_prediction(2, 8)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    // the session starts with the maximum window
+    assert_eq!(sess1.max_prediction(), 8);
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    // returns the prediction window of the adaptive session after running
+    let mut run = |step: Duration, steps: u32| -> Result<usize, GGRSError> {
+        for i in 0..steps {
+            *clock.0.lock().unwrap() += step;
+            // advancing polls the remote clients, so replies only arrive one step later
+            if sess1.current_state() != SessionState::Running
+                || sess2.current_state() != SessionState::Running
+            {
+                sess1.poll_remote_clients();
+                sess2.poll_remote_clients();
+                continue;
+            }
+            sess1.add_local_input(0, StubInput { inp: i })?;
+            stub1.handle_requests(sess1.advance_frame()?);
+            sess2.add_local_input(1, StubInput { inp: i })?;
+            stub2.handle_requests(sess2.advance_frame()?);
+        }
+        Ok(sess1.max_prediction())
+    };
+
+    // a round-trip time of 50ms covers 3 frames at 60 fps, plus one frame of slack
+    assert_eq!(run(Duration::from_millis(50), 100)?, 4);
+
+    // a round-trip time of 200ms needs more frames than allowed
+    assert_eq!(run(Duration::from_millis(200), 40)?, 8);
+
+    // a short round-trip time shrinks the window down to the minimum, once the jitter of the last round-trip times has settled
+    assert_eq!(run(Duration::from_millis(10), 400)?, 2);
+
+    // the other session keeps its fixed window
+    assert_eq!(sess2.max_prediction(), 8);
+
+    Ok(())
+}
+
+#[test]
+fn test_frame_counter_near_limit() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    // at this frame rate, an hour of frames is more than the frame counter can hold
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_fps(1_000_000)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    sess1.events().for_each(drop);
+    sess2.events().for_each(drop);
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    let mut warnings = Vec::new();
+    for i in 0..5 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        warnings.extend(sess1.events().filter_map(|e| match e {
+            GGRSEvent::FrameCounterNearLimit { frame, frames_left } => Some((frame, frames_left)),
+            _ => None,
+        }));
+        // the other session runs at 60 fps and has plenty of frames left
+        assert!(!sess2
+            .events()
+            .any(|e| matches!(e, GGRSEvent::FrameCounterNearLimit { .. })));
+    }
+
+    // the warning is only sent once
+    assert_eq!(warnings, vec![(1, i32::MAX - 1)]);
+
+    Ok(())
+}
+
+#[test]
+fn test_advance_to_frame_limit() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let start_frame = i32::MAX - 10;
+
+    // with input delay, the last local inputs would be delayed beyond the limit
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_start_frame(start_frame)?
+        .with_input_delay(2)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_start_frame(start_frame)?
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    stub1.gs.frame = start_frame;
+    stub2.gs.frame = start_frame;
+    for i in 0..10 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    assert_eq!(sess1.current_frame(), i32::MAX);
+    assert!(sess1
+        .events()
+        .any(|e| matches!(e, GGRSEvent::FrameCounterNearLimit { .. })));
+
+    // the session cannot advance past the limit
+    sess1.add_local_input(0, StubInput { inp: 10 })?;
+    assert!(matches!(
+        sess1.advance_frame(),
+        Err(GGRSError::InvalidRequest { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_simultaneous_disconnects() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let disconnects = Arc::new(Mutex::new(Vec::new()));
+    let hook_disconnects = disconnects.clone();
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_num_players(3)
+        .with_disconnect_hook(move |handle, frame| {
+            hook_disconnects.lock().unwrap().push((handle, frame))
+        })
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr3), 2)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_num_players(3)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Remote(addr3), 2)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    let mut sess3 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_num_players(3)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .add_player(PlayerType::Local, 2)?
+        .start_p2p_session(network.socket(addr3))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        sess3.poll_remote_clients();
+    }
+    assert_eq!(sess1.current_state(), SessionState::Running);
+    sess1.events().for_each(drop);
+
+    // both remote clients time out in the same poll
+    *clock.0.lock().unwrap() += Duration::from_secs(3);
+    sess1.poll_remote_clients();
+
+    let disconnected: Vec<_> = sess1
+        .events()
+        .filter_map(|e| match e {
+            GGRSEvent::Disconnected { addr } => Some(addr),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(disconnected, vec![addr2, addr3]);
+    // no input has been received, so the players are disconnected from the first frame on
+    assert_eq!(*disconnects.lock().unwrap(), vec![(1, 0), (2, 0)]);
+
+    Ok(())
+}
+
+#[test]
+fn test_disconnect_without_missing_inputs() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let disconnects = Arc::new(Mutex::new(Vec::new()));
+    let hook_disconnects = disconnects.clone();
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_disconnect_hook(move |handle, frame| {
+            hook_disconnects.lock().unwrap().push((handle, frame))
+        })
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // the inputs never change, so the predictions are always correct
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for _ in 0..5 {
+        sess1.add_local_input(0, StubInput { inp: 0 })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: 0 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(sess1.confirmed_frame(), 4);
+
+    // all inputs of the player up to the current frame have been received, so no frame has to be simulated again
+    sess1.disconnect_player(1)?;
+    assert_eq!(*disconnects.lock().unwrap(), vec![(1, 5)]);
+    sess1.add_local_input(0, StubInput { inp: 0 })?;
+    let requests = sess1.advance_frame()?;
+    assert!(!requests
+        .iter()
+        .any(|r| matches!(r, GGRSRequest::LoadGameState { .. })));
+    stub1.handle_requests(requests);
+
+    Ok(())
+}
+
+#[test]
+fn test_disconnect_with_fake_clock() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(sess1.current_state(), SessionState::Running);
+    sess1.events().for_each(drop);
+
+    // without time passing, nothing happens
+    sess1.poll_remote_clients();
+    assert_eq!(sess1.events().count(), 0);
+
+    // sess2 stops responding, so sess1 disconnects them after the default timeout of 2 seconds
+    *clock.0.lock().unwrap() += Duration::from_millis(1000);
+    sess1.poll_remote_clients();
+    assert!(sess1
+        .events()
+        .all(|e| matches!(e, GGRSEvent::NetworkInterrupted { .. })));
+
+    *clock.0.lock().unwrap() += Duration::from_millis(1500);
+    sess1.poll_remote_clients();
+    assert!(sess1
+        .events()
+        .any(|e| matches!(e, GGRSEvent::Disconnected { addr } if addr == addr2)));
+
+    Ok(())
+}
+
+#[test]
+fn test_advance_frames() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // without inputs from the remote player, the session stops at the prediction threshold
+    let mut stub1 = stubs::GameStub::new();
+    let fast_forward = sess1.advance_frames(20, |_, frame| StubInput { inp: frame as u32 });
+    assert_eq!(fast_forward.frames_advanced, 8);
+    assert_eq!(fast_forward.result, Err(GGRSError::PredictionThreshold));
+    stub1.handle_requests(fast_forward.requests);
+    assert_eq!(stub1.gs.frame, 8);
+
+    // the remote player catches up in one go
+    let mut stub2 = stubs::GameStub::new();
+    let fast_forward = sess2.advance_frames(8, |_, frame| StubInput { inp: frame as u32 });
+    assert_eq!(fast_forward.frames_advanced, 8);
+    assert_eq!(fast_forward.result, Ok(()));
+    stub2.handle_requests(fast_forward.requests);
+    assert_eq!(stub2.gs.frame, 8);
+
+    // with the remote inputs, the session can advance further
+    let fast_forward = sess1.advance_frames(4, |_, frame| StubInput { inp: frame as u32 });
+    assert_eq!(fast_forward.frames_advanced, 4);
+    assert_eq!(fast_forward.result, Ok(()));
+    stub1.handle_requests(fast_forward.requests);
+    assert_eq!(stub1.gs.frame, 12);
+
+    Ok(())
+}
+
+/// Decodes all inputs as if they never changed, so the remote inputs differ from what the remote client confirmed.
+struct ZeroingCodec;
+
+impl InputCodec for ZeroingCodec {
+    fn encode(&self, inputs: &[u8]) -> Vec<u8> {
+        RleInputCodec.encode(inputs)
+    }
+
+    fn decode(&self, bytes: &[u8], input_size: usize) -> Vec<u8> {
+        vec![0; RleInputCodec.decode(bytes, input_size).len()]
+    }
+}
+
+fn desync_events(codec: Box<dyn InputCodec>) -> Result<Vec<GGRSEvent<StubConfig>>, GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_input_codec(codec)
+        .with_input_desync_detection(10)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_input_desync_detection(10)?
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    let mut events = Vec::new();
+    for i in 0..60 {
+        // quality reports are sent every 200 ms
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        events.extend(sess1.events());
+        events.extend(sess2.events());
+    }
+    Ok(events)
+}
+
+#[test]
+fn test_input_desync_detection() -> Result<(), GGRSError> {
+    let events = desync_events(Box::new(RleInputCodec))?;
+    assert!(!events
+        .iter()
+        .any(|e| matches!(e, GGRSEvent::DesyncDetected { .. })));
+
+    // the first session decodes all inputs of the second player as zero
+    let events = desync_events(Box::new(ZeroingCodec))?;
+    assert!(events.iter().any(|e| matches!(
+        e,
+        GGRSEvent::DesyncDetected { frame, kind: DesyncKind::Inputs, local_checksum, remote_checksum, .. }
+            if frame % 10 == 0 && local_checksum != remote_checksum
+    )));
+
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_input_desync_detection(0)
+        .is_err());
+
+    Ok(())
+}
+
+/// Pads the encoded inputs to 200 bytes per input, so only two inputs fit into a packet. If `packet_len` is set,
+/// every packet is padded to that length instead.
+struct PaddingCodec {
+    packet_len: Option<usize>,
+}
+
+impl InputCodec for PaddingCodec {
+    fn encode(&self, inputs: &[u8]) -> Vec<u8> {
+        let encoded = RleInputCodec.encode(inputs);
+        let mut bytes = (encoded.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(encoded);
+        let len = self.packet_len.unwrap_or(inputs.len() * 50);
+        bytes.resize(std::cmp::max(len, bytes.len()), 0);
+        bytes
+    }
+
+    fn decode(&self, bytes: &[u8], input_size: usize) -> Vec<u8> {
+        let len = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+        RleInputCodec.decode(&bytes[4..4 + len], input_size)
+    }
+}
+
+/// Starts two sessions encoding their inputs with a [`PaddingCodec`], sharing a [`SharedSocket`] with other sessions if `shared` is set.
+fn padded_sessions(
+    packet_len: Option<usize>,
+    shared: bool,
+) -> Result<(P2PSession<StubConfig>, P2PSession<StubConfig>), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let builder1 = SessionBuilder::<StubConfig>::new()
+        .with_input_codec(Box::new(PaddingCodec { packet_len }))
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?;
+    let builder2 = SessionBuilder::<StubConfig>::new()
+        .with_input_codec(Box::new(PaddingCodec { packet_len }))
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?;
+    let (mut sess1, mut sess2) = if shared {
+        (
+            builder1.start_shared_p2p_session(&SharedSocket::new(network.socket(addr1)), 0)?,
+            builder2.start_shared_p2p_session(&SharedSocket::new(network.socket(addr2)), 0)?,
+        )
+    } else {
+        (
+            builder1.start_p2p_session(network.socket(addr1))?,
+            builder2.start_p2p_session(network.socket(addr2))?,
+        )
+    };
+
+    for _ in 0..50 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(sess1.current_state(), SessionState::Running);
+    assert_eq!(sess2.current_state(), SessionState::Running);
+    Ok((sess1, sess2))
+}
+
+#[test]
+fn test_inputs_split_to_payload() -> Result<(), GGRSError> {
+    let (mut sess1, mut sess2) = padded_sessions(None, false)?;
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+
+    // without acknowledgements, the pending inputs of the first session do not fit into a single packet anymore
+    for i in 0..6 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+
+    // the pending inputs arrive over several packets
+    for i in 0..6 {
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        sess1.add_local_input(0, StubInput { inp: 6 + i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert!(sess2.confirmed_frame_for(0)? >= 5);
+    assert!(!sess1
+        .events()
+        .chain(sess2.events())
+        .any(|e| matches!(e, GGRSEvent::Disconnected { .. })));
+
+    Ok(())
+}
+
+#[test]
+fn test_oversized_input_disconnects() -> Result<(), GGRSError> {
+    let (mut sess1, _sess2) = padded_sessions(Some(MAX_PAYLOAD + 1), false)?;
+    let mut stub1 = stubs::GameStub::new();
+
+    // a single input never fits into a packet, so the session disconnects instead of sending it
+    sess1.add_local_input(0, StubInput { inp: 0 })?;
+    stub1.handle_requests(sess1.advance_frame()?);
+    sess1.poll_remote_clients();
+    assert!(sess1
+        .events()
+        .any(|e| matches!(e, GGRSEvent::Disconnected { .. })));
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_socket_input_payload() -> Result<(), GGRSError> {
+    // a shared socket adds 18 bytes to every packet, so inputs are limited to the remaining payload
+    let shared_payload = MAX_PAYLOAD - 18;
+
+    // inputs at the limit reach the remote client
+    let (mut sess1, mut sess2) = padded_sessions(Some(shared_payload), true)?;
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..4 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert!(sess2.confirmed_frame_for(0)? >= 2);
+    assert!(!sess1
+        .events()
+        .chain(sess2.events())
+        .any(|e| matches!(e, GGRSEvent::Disconnected { .. })));
+
+    // inputs that would only fit into a packet of an unshared socket are never sent
+    let (mut sess1, _sess2) = padded_sessions(Some(shared_payload + 1), true)?;
+    let mut stub1 = stubs::GameStub::new();
+    sess1.add_local_input(0, StubInput { inp: 0 })?;
+    stub1.handle_requests(sess1.advance_frame()?);
+    sess1.poll_remote_clients();
+    assert!(sess1
+        .events()
+        .any(|e| matches!(e, GGRSEvent::Disconnected { .. })));
+
+    Ok(())
+}
+
+/// Flips a bit of every input in two consecutive packets, like packets corrupted on the way. Since inputs are delta-encoded against
+/// the previous input, flipping the bit again in the next packet restores all later inputs, so only the inputs of the first packet differ.
+#[derive(Default)]
+struct CorruptOnceCodec(AtomicUsize);
+
+impl InputCodec for CorruptOnceCodec {
+    fn encode(&self, inputs: &[u8]) -> Vec<u8> {
+        RleInputCodec.encode(inputs)
+    }
+
+    fn decode(&self, bytes: &[u8], input_size: usize) -> Vec<u8> {
+        let mut inputs = RleInputCodec.decode(bytes, input_size);
+        if matches!(self.0.fetch_add(1, Ordering::Relaxed), 20 | 21) {
+            for input in inputs.chunks_mut(input_size) {
+                input[0] ^= 1;
+            }
+        }
+        inputs
+    }
+}
+
+fn decode_state(bytes: &[u8]) -> Option<stubs::StateStub> {
+    Some(stubs::StateStub {
+        frame: i32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?),
+        state: i32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?),
+    })
+}
+
+/// Advances the session with the current frame as input and saves all states with their bytes, recording the latest state of each frame.
+fn advance_with_bytes(
+    sess: &mut P2PSession<StubConfig>,
+    stub: &mut stubs::GameStub,
+    handle: usize,
+    states: &mut HashMap<i32, (i32, i32)>,
+) -> Result<(), GGRSError> {
+    let inp = sess.current_frame() as u32;
+    sess.add_local_input(handle, StubInput { inp })?;
+    for request in sess.advance_frame()? {
+        match request {
+            GGRSRequest::SaveGameState { cell, frame } => {
+                let gs = stub.gs;
+                let bytes = [gs.frame.to_le_bytes(), gs.state.to_le_bytes()].concat();
+                cell.save_with_bytes(frame, Some(gs), None, &bytes);
+                states.insert(frame, (gs.frame, gs.state));
+            }
+            request => stub.handle_requests(vec![request]),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_desync_recovery() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    // recovery needs input desync detection
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_desync_recovery(true)
+        .with_snapshot_decoder(decode_state)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))
+        .is_err());
+
+    // the first session is the authoritative peer, since it owns handle 0 by default
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_input_desync_detection(10)?
+        .with_desync_recovery(true)
+        .with_snapshot_decoder(decode_state)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    // the second session receives a corrupted packet once
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_input_codec(Box::new(CorruptOnceCodec::default()))
+        .with_input_desync_detection(10)?
+        .with_desync_recovery(true)
+        .with_snapshot_decoder(decode_state)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    let mut states1 = HashMap::new();
+    let mut states2 = HashMap::new();
+    let mut events = Vec::new();
+    for _ in 0..150 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        // the recovering session may reach the prediction threshold while waiting for the state
+        for (sess, stub, handle, states) in [
+            (&mut sess1, &mut stub1, 0, &mut states1),
+            (&mut sess2, &mut stub2, 1, &mut states2),
+        ] {
+            match advance_with_bytes(sess, stub, handle, states) {
+                Ok(()) | Err(GGRSError::PredictionThreshold) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        events.extend(sess2.events());
+    }
+
+    let desync = events
+        .iter()
+        .position(|e| matches!(e, GGRSEvent::DesyncDetected { .. }))
+        .expect("the corrupted packet should cause a desync");
+    let (recovered, recovered_frame) = events
+        .iter()
+        .enumerate()
+        .find_map(|(i, e)| match e {
+            GGRSEvent::DesyncRecovered { frame } => Some((i, *frame)),
+            _ => None,
+        })
+        .expect("the session should recover from the desync");
+    assert!(desync < recovered);
+    assert!(!events[recovered..]
+        .iter()
+        .any(|e| matches!(e, GGRSEvent::DesyncDetected { .. })));
+
+    // the states diverged before the recovery
+    assert!((0..recovered_frame).any(|frame| states1.get(&frame) != states2.get(&frame)));
+    // after the recovered frame, whose state has been loaded instead of saved, both sessions agree on all confirmed states
+    let confirmed = std::cmp::min(sess1.confirmed_frame(), sess2.confirmed_frame());
+    assert!(confirmed > recovered_frame + 10);
+    for frame in recovered_frame + 1..=confirmed {
+        assert_eq!(states1.get(&frame), states2.get(&frame));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_desync_recovery_failed() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    // the authoritative peer does not recover from desyncs, so it cannot send states
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_input_desync_detection(10)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_input_codec(Box::new(CorruptOnceCodec::default()))
+        .with_input_desync_detection(10)?
+        .with_desync_recovery(true)
+        .with_snapshot_decoder(decode_state)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    let mut states1 = HashMap::new();
+    let mut states2 = HashMap::new();
+    let mut events = Vec::new();
+    for _ in 0..100 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        for (sess, stub, handle, states) in [
+            (&mut sess1, &mut stub1, 0, &mut states1),
+            (&mut sess2, &mut stub2, 1, &mut states2),
+        ] {
+            match advance_with_bytes(sess, stub, handle, states) {
+                Ok(()) | Err(GGRSError::PredictionThreshold) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        events.extend(sess2.events());
+    }
+
+    // the session gives up instead of waiting for the state forever, and keeps running
+    let desync = events
+        .iter()
+        .position(|e| matches!(e, GGRSEvent::DesyncDetected { .. }))
+        .expect("the corrupted packet should cause a desync");
+    let failed = events
+        .iter()
+        .position(|e| matches!(e, GGRSEvent::DesyncRecoveryFailed { .. }))
+        .expect("the recovery should fail");
+    assert!(desync < failed);
+    assert!(!events
+        .iter()
+        .any(|e| matches!(e, GGRSEvent::DesyncRecovered { .. })));
+    assert!(sess2.current_frame() > 90);
+
+    Ok(())
+}
+
+#[test]
+fn test_checksum_authority() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    // the authority has to be a player
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_checksum_authority(2)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))
+        .is_err());
+
+    // the first session receives a corrupted packet once, even though it owns handle 0
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_input_codec(Box::new(CorruptOnceCodec::default()))
+        .with_input_desync_detection(10)?
+        .with_desync_recovery(true)
+        .with_checksum_authority(1)
+        .with_snapshot_decoder(decode_state)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    // the second session is the authoritative peer
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_input_desync_detection(10)?
+        .with_desync_recovery(true)
+        .with_checksum_authority(1)
+        .with_snapshot_decoder(decode_state)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    let mut states1 = HashMap::new();
+    let mut states2 = HashMap::new();
+    let mut events1 = Vec::new();
+    let mut events2 = Vec::new();
+    for _ in 0..150 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        for (sess, stub, handle, states) in [
+            (&mut sess1, &mut stub1, 0, &mut states1),
+            (&mut sess2, &mut stub2, 1, &mut states2),
+        ] {
+            match advance_with_bytes(sess, stub, handle, states) {
+                Ok(()) | Err(GGRSError::PredictionThreshold) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        events1.extend(sess1.events());
+        events2.extend(sess2.events());
+    }
+
+    // both sessions detect the desync, but only the first session recovers
+    for events in [&events1, &events2] {
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, GGRSEvent::DesyncDetected { .. })));
+    }
+    assert!(!events2
+        .iter()
+        .any(|e| matches!(e, GGRSEvent::DesyncRecovered { .. })));
+    let recovered_frame = events1
+        .iter()
+        .find_map(|e| match e {
+            GGRSEvent::DesyncRecovered { frame } => Some(*frame),
+            _ => None,
+        })
+        .expect("the first session should recover from the desync");
+
+    let confirmed = std::cmp::min(sess1.confirmed_frame(), sess2.confirmed_frame());
+    assert!(confirmed > recovered_frame + 10);
+    for frame in recovered_frame + 1..=confirmed {
+        assert_eq!(states1.get(&frame), states2.get(&frame));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_max_frame_lag() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_max_frame_lag(0)
+        .is_err());
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_max_frame_lag(4)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // without inputs from the remote player, the session stops well before the prediction threshold
+    let mut stub1 = stubs::GameStub::new();
+    for i in 0..4 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    let lag_exceeded = Err(GGRSError::FrameLagExceeded {
+        frame: 4,
+        confirmed_frame: -1,
+    });
+    assert_eq!(sess1.add_local_input(0, StubInput { inp: 4 }), lag_exceeded);
+    assert_eq!(sess1.advance_frame().map(|_| ()), lag_exceeded);
+
+    // once the remote inputs arrive, the session can advance again
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..4 {
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess1.poll_remote_clients();
+    sess1.add_local_input(0, StubInput { inp: 4 })?;
+    stub1.handle_requests(sess1.advance_frame()?);
+    assert_eq!(stub1.gs.frame, 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_frames_predicted() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_input_delay(2)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // without remote inputs, every advanced frame is predicted
+    let mut stub1 = stubs::GameStub::new();
+    for i in 0..5 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    assert_eq!(sess1.frames_predicted(), 6);
+
+    // the remote player runs ahead and the local inputs are delayed, so all inputs for the current frame are known
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..8 {
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess1.poll_remote_clients();
+    assert_eq!(sess1.frames_predicted(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_next_frame_is_confirmed() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert!(!sess1.next_frame_is_confirmed());
+
+    // the remote player advances one frame, so the input for frame 0 arrives
+    let mut stub2 = stubs::GameStub::new();
+    sess2.add_local_input(1, StubInput { inp: 0 })?;
+    stub2.handle_requests(sess2.advance_frame()?);
+    sess1.poll_remote_clients();
+    assert!(sess1.next_frame_is_confirmed());
+
+    let mut stub1 = stubs::GameStub::new();
+    sess1.add_local_input(0, StubInput { inp: 0 })?;
+    stub1.handle_requests(sess1.advance_frame()?);
+    assert!(!sess1.next_frame_is_confirmed());
+
+    // inputs of disconnected players are not waited for
+    sess1.disconnect_player(1)?;
+    assert!(sess1.next_frame_is_confirmed());
+
+    Ok(())
+}
+
+#[test]
+fn test_lockstep_session() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_lockstep_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_lockstep_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // the first session sends its input, but has to wait for the input of the second session
+    sess1.add_local_input(0, StubInput { inp: 1 })?;
+    assert_eq!(
+        sess1.advance_frame().map(|_| ()),
+        Err(GGRSError::WaitingForInputs)
+    );
+    // inputs added while waiting are ignored
+    sess1.add_local_input(0, StubInput { inp: 9 })?;
+    assert_eq!(
+        sess1.advance_frame().map(|_| ()),
+        Err(GGRSError::WaitingForInputs)
+    );
+    assert_eq!(sess1.current_frame(), 0);
+
+    // the second session already received the input of the first session
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    sess2.add_local_input(1, StubInput { inp: 2 })?;
+    stub2.handle_requests(sess2.advance_frame()?);
+    sess1.add_local_input(0, StubInput { inp: 9 })?;
+    stub1.handle_requests(sess1.advance_frame()?);
+
+    // both sessions advanced frame 0 with the same confirmed inputs and never predicted
+    for sess in [&sess1, &sess2] {
+        let inputs = sess.inputs_for_frame(0).unwrap();
+        assert_eq!((inputs[0].0.inp, inputs[0].1.is_confirmed()), (1, true));
+        assert_eq!((inputs[1].0.inp, inputs[1].1.is_confirmed()), (2, true));
+    }
+
+    // advancing in turns never needs a rollback
+    for i in 1..20 {
+        while sess1.current_frame() == i || sess2.current_frame() == i {
+            if sess1.current_frame() == i {
+                sess1.add_local_input(0, StubInput { inp: i as u32 })?;
+                match sess1.advance_frame() {
+                    Ok(requests) => stub1.handle_requests(requests),
+                    Err(e) => assert_eq!(e, GGRSError::WaitingForInputs),
+                }
+            }
+            if sess2.current_frame() == i {
+                sess2.add_local_input(1, StubInput { inp: i as u32 })?;
+                match sess2.advance_frame() {
+                    Ok(requests) => stub2.handle_requests(requests),
+                    Err(e) => assert_eq!(e, GGRSError::WaitingForInputs),
+                }
+            }
+        }
+    }
+    assert_eq!(stub1.gs.frame, 20);
+    assert_eq!(stub1.gs.state, stub2.gs.state);
+    assert_eq!(sess1.rollback_stats().total_rollbacks, 0);
+    assert_eq!(sess2.rollback_stats().total_rollbacks, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_end_session() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..10 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess2.events().for_each(drop);
+
+    sess1.end_session()?;
+    assert_eq!(sess1.current_state(), SessionState::Ended);
+    assert_eq!(sess1.end_session(), Err(GGRSError::SessionEnded));
+    assert_eq!(
+        sess1.add_local_input(0, StubInput { inp: 10 }),
+        Err(GGRSError::SessionEnded)
+    );
+    assert_eq!(
+        sess1.advance_frame().map(|_| ()),
+        Err(GGRSError::SessionEnded)
+    );
+
+    // the remote player is notified right away
+    sess2.poll_remote_clients();
+    assert!(sess2
+        .events()
+        .any(|event| matches!(event, GGRSEvent::Disconnected { addr } if addr == addr1)));
+
+    Ok(())
+}
+
+#[test]
+fn test_pending_send_bytes() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_spectator_snapshots(true)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert!(sess1.pending_send_bytes(0).is_err()); // local player
+    assert_eq!(sess1.pending_send_bytes(1)?, 0);
+    assert_eq!(sess1.pending_send_bytes(2)?, 0);
+    // querying the stats of a spectator does not panic
+    sess1.network_stats(2).ok();
+
+    // the inputs and their flags are buffered until the remote player acknowledges them
+    let mut stub1 = stubs::GameStub::new();
+    for i in 0..4 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    assert_eq!(
+        sess1.pending_send_bytes(1)?,
+        4 * (std::mem::size_of::<StubInput>() + 1)
+    );
+    assert_eq!(
+        sess1.total_pending_send_bytes(),
+        sess1.pending_send_bytes(1)?
+    );
+
+    let mut stub2 = stubs::GameStub::new();
+    sess2.add_local_input(1, StubInput { inp: 0 })?;
+    stub2.handle_requests(sess2.advance_frame()?);
+    sess1.poll_remote_clients();
+    assert_eq!(sess1.pending_send_bytes(1)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_input_acked_events() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_num_players(3)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .add_player(PlayerType::Remote(addr3), 2)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_num_players(3)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Remote(addr3), 2)?
+        .start_p2p_session(network.socket(addr2))?;
+    // the third client only synchronizes and never polls again
+    let mut sess3 = SessionBuilder::<StubConfig>::new()
+        .with_num_players(3)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .add_player(PlayerType::Local, 2)?
+        .start_p2p_session(network.socket(addr3))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        sess3.poll_remote_clients();
+    }
+
+    let acked_frames = |sess: &mut ggrs::P2PSession<StubConfig>| -> Vec<i32> {
+        sess.events()
+            .filter_map(|event| match event {
+                GGRSEvent::InputAcked { frame } => Some(frame),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let mut stub1 = stubs::GameStub::new();
+    for i in 0..3 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    sess2.poll_remote_clients();
+    sess1.poll_remote_clients();
+
+    // the second client acknowledged the inputs, but the third did not
+    assert!(acked_frames(&mut sess1).is_empty());
+
+    // once the third client is disconnected, it is not waited for anymore
+    sess1.disconnect_player(2)?;
+    sess1.poll_remote_clients();
+    assert_eq!(acked_frames(&mut sess1), vec![2]);
+
+    Ok(())
+}
+
+/// Records all sent messages and never receives any.
+#[derive(Clone, Default)]
+struct RecordingSocket(Arc<Mutex<Vec<Message>>>);
+
+impl NonBlockingSocket<SocketAddr> for RecordingSocket {
+    fn send_to(&mut self, msg: &Message, _addr: &SocketAddr) {
+        self.0.lock().unwrap().push(msg.clone());
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(SocketAddr, Message)> {
+        Vec::new()
+    }
+}
+
+fn sent_sync_messages(seed: u64) -> Result<Vec<Message>, GGRSError> {
+    let remote_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let socket = RecordingSocket::default();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_rng_seed(seed)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(remote_addr), 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(socket.clone())?;
+    sess.poll_remote_clients();
+    let mut messages = socket.0.lock().unwrap().clone();
+    // the order in which the endpoints send is not fixed
+    messages.sort_by_key(|msg| format!("{:?}", msg));
+    Ok(messages)
+}
+
+#[test]
+fn test_rng_seed() -> Result<(), GGRSError> {
+    let messages = sent_sync_messages(42)?;
+    assert_eq!(messages.len(), 2);
+    // each endpoint uses its own random numbers
+    assert_ne!(messages[0], messages[1]);
+    assert_eq!(messages, sent_sync_messages(42)?);
+    assert_ne!(messages, sent_sync_messages(43)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_input_flags() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..20 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        // the first player marks every third input, the second player never sets any flags
+        sess1.add_local_input_with_flags(0, StubInput { inp: 1 }, (i % 3 == 0) as u8)?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: 2 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+
+        // the flags arrived at the second session without changing the input
+        let inputs = sess2.inputs_for_frame(i).unwrap();
+        assert_eq!(inputs[0].0.inp, 1);
+        assert_eq!(
+            (inputs[0].1, inputs[0].2),
+            (InputStatus::ConfirmedRemote, (i % 3 == 0) as u8)
+        );
+        assert_eq!(inputs[1].2, 0);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_request_save_next_advance() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_sparse_saving_mode(true)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..40 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        // the first session advances first, so its current frame is never confirmed and only saved on request
+        if i % 10 == 5 {
+            sess1.request_save_next_advance();
+        }
+        let frame = sess1.current_frame();
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        let requests = sess1.advance_frame()?;
+        let saved_current = requests
+            .iter()
+            .any(|r| matches!(r, GGRSRequest::SaveGameState { frame: f, .. } if *f == frame));
+        stub1.handle_requests(requests);
+        assert_eq!(saved_current, i % 10 == 5 || frame == 0);
+
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_checksums_window() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    assert!(sess1.checksums_window().is_empty());
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..20 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        let requests = sess1.advance_frame()?;
+        let cells: Vec<_> = requests
+            .iter()
+            .filter_map(|r| match r {
+                GGRSRequest::SaveGameState { cell, .. } => Some(cell.clone()),
+                _ => None,
+            })
+            .collect();
+        stub1.handle_requests(requests);
+        // the checksum can be read without loading the state
+        assert!(cells.iter().all(|cell| cell.checksum().is_some()));
+
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+
+    let window1 = sess1.checksums_window();
+    let window2 = sess2.checksums_window();
+    assert!(!window1.is_empty());
+    assert!(window1.windows(2).all(|w| w[0].0 < w[1].0));
+
+    // confirmed frames have been simulated with the same inputs, so their checksums match on both peers
+    let confirmed = sess1.confirmed_frame().min(sess2.confirmed_frame());
+    let mut compared = 0;
+    for (frame, checksum) in window1.iter().filter(|(frame, _)| *frame <= confirmed) {
+        if let Some((_, other)) = window2.iter().find(|(f, _)| f == frame) {
+            assert_eq!(checksum, other);
+            compared += 1;
+        }
+    }
+    assert!(compared > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_dump_state_window() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..5 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut dump = Vec::new();
+    sess1.dump_state_window(&mut dump).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    assert!(dump.starts_with("current frame: 5\n"));
+    for (frame, checksum) in sess1.checksums_window() {
+        let checksum = checksum.unwrap();
+        assert!(dump.contains(&format!("  frame {frame}: checksum {checksum:032x}\n")));
+    }
+    assert!(dump.contains("player 0 (local, connected"));
+    assert!(dump.contains("player 1 (remote, connected"));
+    // the input of every frame is dumped with its bytes
+    assert!(dump.contains("  frame 4: input [04 00 00 00] flags 0\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_checksum_history() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..200 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // the history is bounded, ordered and reaches further back than the saved states
+    let history1 = sess1.checksum_history();
+    let history2 = sess2.checksum_history();
+    assert_eq!(history1.len(), 128);
+    assert!(history1.windows(2).all(|pair| pair[0].0 + 1 == pair[1].0));
+    assert!(history1[0].0 < sess1.oldest_recoverable_frame());
+    assert!(history1.last().unwrap().0 <= sess1.confirmed_frame() + 1);
+
+    // both peers agree on every frame they have in common
+    for (frame, checksum) in history1 {
+        if let Some((_, other)) = history2.iter().find(|(other, _)| other == frame) {
+            assert_eq!(checksum, other);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_oldest_recoverable_frame() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_max_prediction_window(4)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_max_prediction_window(4)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    assert_eq!(sess1.oldest_recoverable_frame(), NULL_FRAME);
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..20 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+
+        // the oldest kept state moves along with the saved states
+        let oldest = sess1.oldest_recoverable_frame();
+        assert_eq!(Some(oldest), sess1.checksums_window().first().map(|w| w.0));
+        assert!(oldest <= sess1.last_saved_frame());
+    }
+    // only the last few states are kept, so the first frames cannot be rolled back anymore
+    assert!(sess1.oldest_recoverable_frame() > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_add_local_input_for_frame_limit() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    // inputs can be added as far ahead as the input queue reaches
+    for frame in 0..128 {
+        sess.add_local_input_for_frame(0, frame, StubInput { inp: 0 })?;
+    }
+    assert!(matches!(
+        sess.add_local_input_for_frame(0, 128, StubInput { inp: 0 }),
+        Err(GGRSError::InvalidRequest { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_add_local_input_for_frame() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // inputs have to be added for local players and contiguous frames
+    assert!(sess1
+        .add_local_input_for_frame(1, 0, StubInput { inp: 0 })
+        .is_err());
+    assert!(sess1
+        .add_local_input_for_frame(0, 1, StubInput { inp: 0 })
+        .is_err());
+    for frame in 0..5 {
+        sess1.add_local_input_for_frame(
+            0,
+            frame,
+            StubInput {
+                inp: 10 + frame as u32,
+            },
+        )?;
+    }
+    assert!(sess1
+        .add_local_input_for_frame(0, 3, StubInput { inp: 0 })
+        .is_err());
+    assert!(sess1
+        .add_local_input_for_frame(0, 6, StubInput { inp: 0 })
+        .is_err());
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..8 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        // the inputs added ahead of time are used until they run out
+        if i >= 5 {
+            sess1.add_local_input(0, StubInput { inp: i })?;
+        }
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    assert!(sess1.advance_frame().is_err());
+
+    // the remote session received the inputs added ahead of time
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    let expected = [10, 11, 12, 13, 14, 5, 6, 7];
+    for (frame, &inp) in expected.iter().enumerate() {
+        let inputs = sess2.inputs_for_frame(frame as i32).unwrap();
+        assert_eq!(inputs[0].0.inp, inp);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_strict_input_ordering() -> Result<(), GGRSError> {
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let network = InMemoryNetwork::new(0);
+
+    // by default, adding an input twice replaces the first one
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr))?;
+    sess.add_local_input(0, StubInput { inp: 0 })?;
+    sess.add_local_input(0, StubInput { inp: 1 })?;
+
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_strict_input_ordering(true)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr))?;
+    sess.add_local_input(0, StubInput { inp: 0 })?;
+    assert_eq!(
+        sess.add_local_input(0, StubInput { inp: 1 }),
+        Err(GGRSError::NonMonotonicInput {
+            expected: 1,
+            got: 0
+        })
+    );
+    assert_eq!(
+        sess.add_local_inputs(&[(1, StubInput { inp: 0 }), (1, StubInput { inp: 1 })]),
+        Err(GGRSError::NonMonotonicInput {
+            expected: 1,
+            got: 0
+        })
+    );
+    sess.add_local_input(1, StubInput { inp: 0 })?;
+    let mut stub = stubs::GameStub::new();
+    stub.handle_requests(sess.advance_frame()?);
+
+    // inputs for future frames cannot skip a frame, and the current frame cannot be given again
+    assert_eq!(
+        sess.add_local_input_for_frame(0, 3, StubInput { inp: 3 }),
+        Err(GGRSError::NonMonotonicInput {
+            expected: 1,
+            got: 3
+        })
+    );
+    sess.add_local_input_for_frame(0, 1, StubInput { inp: 1 })?;
+    sess.add_local_input_for_frame(0, 2, StubInput { inp: 2 })?;
+    assert_eq!(
+        sess.add_local_input(0, StubInput { inp: 1 }),
+        Err(GGRSError::NonMonotonicInput {
+            expected: 3,
+            got: 1
+        })
+    );
+    sess.add_local_input(1, StubInput { inp: 1 })?;
+    stub.handle_requests(sess.advance_frame()?);
+    assert_eq!(sess.current_frame(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_recent_confirmed_inputs() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    assert!(sess1.recent_confirmed_inputs(2, 1).is_err());
+    assert!(sess1.recent_confirmed_inputs(1, 5)?.is_empty());
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..20 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input_with_flags(1, StubInput { inp: 100 + i }, 1)?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+
+    // the last confirmed frames come oldest first, with the inputs of the remote player
+    let recent = sess1.recent_confirmed_inputs(1, 3)?;
+    assert_eq!(recent.len(), 3);
+    let confirmed_frame = sess1.confirmed_frame();
+    for (i, (frame, input, flags)) in recent.into_iter().enumerate() {
+        assert_eq!(frame, confirmed_frame - 2 + i as i32);
+        assert_eq!(input.inp, 100 + frame as u32);
+        assert_eq!(flags, 1);
+    }
+
+    // only the frames within the prediction window are kept
+    assert_eq!(sess1.recent_confirmed_inputs(0, 100)?.len(), 8);
+
+    Ok(())
+}
+
+#[test]
+fn test_max_saved_states() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_max_saved_states(0)
+        .is_err());
+
+    // with sparse saving, a single state is enough to roll back
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_sparse_saving_mode(true)
+        .with_max_saved_states(1)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    // without sparse saving, rolling back needs a state for every frame of the prediction window
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_max_saved_states(2)?
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))
+        .is_err());
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_max_prediction_window(4)
+        .with_max_saved_states(4)?
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..4 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    for i in 0..4 {
+        sess2.add_local_input(1, StubInput { inp: i + 1 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    // the first session mispredicted all inputs of the second session and rolls back to its only state
+    for i in 4..8 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    assert_eq!(sess1.rollback_stats().total_rollbacks, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_prewarm() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_sparse_saving_mode(true)
+        .with_max_saved_states(4)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    // every cell is saved and loaded once with the current frame, while the session is still synchronizing
+    let mut stub1 = stubs::GameStub::new();
+    let requests = sess1.prewarm()?;
+    let frames: Vec<_> = requests
+        .iter()
+        .map(|r| match r {
+            GGRSRequest::SaveGameState { frame, .. } => ("save", *frame),
+            GGRSRequest::LoadGameState { frame, .. } => ("load", *frame),
+            GGRSRequest::AdvanceFrame { .. } => ("advance", NULL_FRAME),
+        })
+        .collect();
+    assert_eq!(frames, [("save", 0), ("load", 0)].repeat(4));
+    stub1.handle_requests(requests);
+    assert_eq!(stub1.gs.frame, 0);
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // the prewarmed states are discarded once the session advances
+    sess1.add_local_input(0, StubInput { inp: 0 })?;
+    stub1.handle_requests(sess1.advance_frame()?);
+    assert_eq!(sess1.last_saved_frame(), 0);
+    assert_eq!(sess1.checksums_window().len(), 1);
+    assert!(matches!(
+        sess1.prewarm(),
+        Err(GGRSError::InvalidRequest { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_poll_remote_clients_limited() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    // every advanced frame sends the inputs in a separate message
+    let mut stub1 = stubs::GameStub::new();
+    for i in 0..4 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+
+    // the second session handles one message per poll
+    let mut polls = 1;
+    while sess2.poll_remote_clients_limited(1) {
+        polls += 1;
+    }
+    assert!(polls >= 4);
+    assert!(!sess2.poll_remote_clients_limited(0));
+
+    // the inputs of the first session have been received, so frame 0 is confirmed right away
+    let mut stub2 = stubs::GameStub::new();
+    sess2.add_local_input(1, StubInput { inp: 0 })?;
+    stub2.handle_requests(sess2.advance_frame()?);
+    assert_eq!(sess2.confirmed_frame(), 0);
+
+    Ok(())
+}
+
+/// Receives every message many times over.
+struct FloodSocket(InMemorySocket);
+
+impl NonBlockingSocket<SocketAddr> for FloodSocket {
+    fn send_to(&mut self, msg: &Message, addr: &SocketAddr) {
+        self.0.send_to(msg, addr);
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(SocketAddr, Message)> {
+        self.0
+            .receive_all_messages()
+            .into_iter()
+            .flat_map(|received| std::iter::repeat_n(received, 1000))
+            .collect()
+    }
+}
+
+#[test]
+fn test_received_messages_capped() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(FloodSocket(network.socket(addr2)))?;
+
+    // the flood of messages that is never handled does not pile up
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients_limited(0);
+    }
+    let mut polls = 0;
+    while sess2.poll_remote_clients_limited(1) {
+        polls += 1;
+    }
+    assert!(polls <= 1024);
+
+    Ok(())
+}
+
+#[test]
+fn test_add_player_at_frame() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    // reserved slots cannot be used twice
+    assert_eq!(
+        SessionBuilder::<StubConfig>::new()
+            .with_num_players(3)
+            .add_player(PlayerType::Local, 0)?
+            .add_player(PlayerType::Remote(addr2), 1)?
+            .reserve_player(2)?
+            .add_player(PlayerType::Local, 2)?
+            .validate(),
+        Err(vec![BuilderError::DuplicateHandle { handle: 2 }])
+    );
+    assert!(SessionBuilder::<StubConfig>::new()
+        .reserve_player(2)
+        .is_err());
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_num_players(3)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .reserve_player(2)?
+        .start_p2p_session(network.socket(addr1))?;
+
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_num_players(3)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .reserve_player(2)?
+        .start_p2p_session(network.socket(addr2))?;
+
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(sess1.current_state(), SessionState::Running);
+    assert_eq!(sess1.num_players(), 3);
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    let advance = |sess: &mut P2PSession<StubConfig>, stub: &mut stubs::GameStub, handle| {
+        sess.add_local_input(handle, StubInput { inp: 0 })?;
+        stub.handle_requests(sess.advance_frame()?);
+        Ok::<(), GGRSError>(())
+    };
+    for _ in 0..10 {
+        advance(&mut sess1, &mut stub1, 0)?;
+        advance(&mut sess2, &mut stub2, 1)?;
+    }
+
+    // the player has to be remote, new to the session and join on a frame that has not been advanced yet
+    assert!(sess1.add_player_at_frame(PlayerType::Local, 2, 20).is_err());
+    assert!(sess1
+        .add_player_at_frame(PlayerType::Remote(addr2), 2, 20)
+        .is_err());
+    assert!(sess1
+        .add_player_at_frame(PlayerType::Remote(addr3), 2, 5)
+        .is_err());
+    assert!(sess1
+        .add_player_at_frame(PlayerType::Remote(addr3), 1, 20)
+        .is_err());
+    sess1.add_player_at_frame(PlayerType::Remote(addr3), 2, 20)?;
+    sess2.add_player_at_frame(PlayerType::Remote(addr3), 2, 20)?;
+    assert!(sess1
+        .add_player_at_frame(PlayerType::Remote(addr3), 2, 20)
+        .is_err());
+
+    for _ in 0..10 {
+        advance(&mut sess1, &mut stub1, 0)?;
+        advance(&mut sess2, &mut stub2, 1)?;
+    }
+    assert_eq!(sess1.current_frame(), 20);
+    assert_eq!(
+        sess1.inputs_for_frame(19).unwrap()[2].1,
+        InputStatus::Disconnected
+    );
+
+    // the joining client starts on the agreed frame with the state of that frame
+    let mut sess3 = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_num_players(3)
+        .with_start_frame(20)?
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .add_player(PlayerType::Local, 2)?
+        .start_p2p_session(network.socket(addr3))?;
+    let mut stub3 = stubs::GameStub::new();
+    stub3.gs = stub1.gs;
+
+    for _ in 0..20 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+        sess3.poll_remote_clients();
+    }
+    assert_eq!(sess3.current_state(), SessionState::Running);
+    assert_eq!(sess3.current_frame(), 20);
+
+    for _ in 0..20 {
+        advance(&mut sess1, &mut stub1, 0)?;
+        advance(&mut sess2, &mut stub2, 1)?;
+        sess3.add_local_input(2, StubInput { inp: 7 })?;
+        stub3.handle_requests(sess3.advance_frame()?);
+    }
+
+    // everyone uses the inputs of the new player from the agreed frame on
+    for sess in [&sess1, &sess2] {
+        let recent = sess.recent_confirmed_inputs(2, 100)?;
+        assert!(!recent.is_empty());
+        assert!(recent
+            .iter()
+            .all(|(frame, input, _)| *frame >= 20 && input.inp == 7));
+    }
+    assert!(sess3.confirmed_frame() > 30);
+    assert_eq!(stub1.gs.frame, stub3.gs.frame);
+    assert_eq!(stub1.gs.state, stub3.gs.state);
+
+    Ok(())
+}
+
+#[test]
+fn test_player_join_conflict() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let addr4 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 6666);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_num_players(4)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .reserve_player(2)?
+        .reserve_player(3)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_num_players(4)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .reserve_player(2)?
+        .reserve_player(3)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    let conflicts = |sess: &mut P2PSession<StubConfig>| {
+        sess.events()
+            .filter_map(|event| match event {
+                GGRSEvent::PlayerJoinConflict {
+                    addr,
+                    handle,
+                    frame,
+                } => Some((addr, handle, frame)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // once a remote client told us the frame, the player cannot join on another one
+    sess1.add_player_at_frame(PlayerType::Remote(addr3), 2, 20)?;
+    for _ in 0..5 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert!(sess2
+        .add_player_at_frame(PlayerType::Remote(addr3), 2, 25)
+        .is_err());
+    sess2.add_player_at_frame(PlayerType::Remote(addr3), 2, 20)?;
+
+    // without knowing of each other, both let the next player join on different frames
+    sess1.add_player_at_frame(PlayerType::Remote(addr4), 3, 30)?;
+    sess2.add_player_at_frame(PlayerType::Remote(addr4), 3, 40)?;
+    for _ in 0..5 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert_eq!(conflicts(&mut sess1), vec![(addr2, 3, 40)]);
+    assert_eq!(conflicts(&mut sess2), vec![(addr1, 3, 30)]);
+
+    Ok(())
+}
+
+#[test]
+fn test_late_player_join() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .with_num_players(3)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .reserve_player(2)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .with_num_players(3)
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .reserve_player(2)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for _ in 0..2 {
+        sess1.add_local_input(0, StubInput { inp: 0 })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    for _ in 0..6 {
+        sess2.add_local_input(1, StubInput { inp: 0 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess2.events().for_each(drop);
+
+    // the remote session already advanced the frame the player joins on
+    sess1.add_player_at_frame(PlayerType::Remote(addr3), 2, 4)?;
+    for _ in 0..5 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    assert!(sess2.events().any(|event| matches!(
+        event,
+        GGRSEvent::PlayerJoinConflict {
+            handle: 2,
+            frame: 4,
+            ..
+        }
+    )));
+    assert!(sess2
+        .add_player_at_frame(PlayerType::Remote(addr3), 2, 4)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_reconnect_player() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9999);
+    let network = InMemoryNetwork::new(0);
+
+    let mut sess1 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Remote(addr2), 1)?
+        .start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for _ in 0..10 {
+        sess1.add_local_input(0, StubInput { inp: 0 })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: 0 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess1.poll_remote_clients();
+
+    // only disconnected players can reconnect
+    assert!(sess1.reconnect_player(1, addr3, 20).is_err());
+    sess1.disconnect_player(1)?;
+    drop(sess2);
+
+    // the frames before the disconnect are confirmed once the session advances
+    assert!(sess1.reconnect_player(1, addr3, 20).is_err());
+    for _ in 0..2 {
+        sess1.add_local_input(0, StubInput { inp: 0 })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    assert!(sess1.reconnect_player(0, addr3, 20).is_err());
+    assert!(sess1.reconnect_player(1, addr3, 5).is_err());
+    // the socket of the rejoining client receives the sync requests of the session right away
+    let socket3 = network.socket(addr3);
+    sess1.reconnect_player(1, addr3, 20)?;
+    assert!(sess1.reconnect_player(1, addr3, 20).is_err());
+
+    while sess1.current_frame() < 20 {
+        sess1.add_local_input(0, StubInput { inp: 0 })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+    }
+    assert_eq!(
+        sess1.inputs_for_frame(19).unwrap()[1].1,
+        InputStatus::Disconnected
+    );
+
+    // the rejoining client starts on the agreed frame with the state of that frame
+    let mut sess3 = SessionBuilder::<StubConfig>::new()
+        .with_start_frame(20)?
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(socket3)?;
+    let mut stub3 = stubs::GameStub::new();
+    stub3.gs = stub1.gs;
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess3.poll_remote_clients();
+    }
+    assert_eq!(sess3.current_state(), SessionState::Running);
+
+    for _ in 0..20 {
+        sess1.add_local_input(0, StubInput { inp: 0 })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess3.add_local_input(1, StubInput { inp: 7 })?;
+        stub3.handle_requests(sess3.advance_frame()?);
+    }
+    sess1.poll_remote_clients();
+
+    // the inputs of the rejoined player are used again
+    assert!(sess1.confirmed_frame() > 30);
+    let inputs = sess1
+        .inputs_for_frame(35)
+        .expect("inputs of a recent frame");
+    assert!(inputs[1].0 == StubInput { inp: 7 });
+
+    Ok(())
+}
+
+#[test]
+fn test_restore_runtime_state() -> Result<(), GGRSError> {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let builder1 = || {
+        SessionBuilder::<StubConfig>::new()
+            .add_player(PlayerType::Local, 0)?
+            .add_player(PlayerType::Remote(addr2), 1)
+    };
+    let mut sess1 = builder1()?.start_p2p_session(network.socket(addr1))?;
+    let mut sess2 = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Remote(addr1), 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(network.socket(addr2))?;
+    assert!(matches!(
+        sess1.export_runtime_state(),
+        Err(GGRSError::NotSynchronized)
+    ));
+    for _ in 0..10 {
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+
+    let mut stub1 = stubs::GameStub::new();
+    let mut stub2 = stubs::GameStub::new();
+    for i in 0..10 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i * 2 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    sess1.poll_remote_clients();
+
+    // while a frame is predicted, the session cannot be exported
+    sess1.add_local_input(0, StubInput { inp: 10 })?;
+    stub1.handle_requests(sess1.advance_frame()?);
+    assert!(matches!(
+        sess1.export_runtime_state(),
+        Err(GGRSError::InvalidRequest { .. })
+    ));
+    // once the remote inputs arrived, the rollback is still pending until the next frame is advanced
+    for i in 10..12 {
+        sess2.add_local_input(1, StubInput { inp: i * 2 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+    }
+    sess2.poll_remote_clients();
+    sess1.poll_remote_clients();
+    assert!(sess1.export_runtime_state().is_err());
+    sess1.add_local_input(0, StubInput { inp: 11 })?;
+    stub1.handle_requests(sess1.advance_frame()?);
+
+    // replace the session with one restored from the exported bytes
+    let bytes = sess1.export_runtime_state()?.to_bytes();
+    drop(sess1);
+    let state = RuntimeState::from_bytes(&bytes)?;
+    assert_eq!(state.current_frame(), 12);
+    let mut sess1 = builder1()?.restore_p2p_session(network.socket(addr1), &state)?;
+    assert_eq!(sess1.current_state(), SessionState::Running);
+    assert_eq!(sess1.current_frame(), 12);
+    assert_eq!(sess1.confirmed_frame(), 11);
+
+    for i in 12..40 {
+        sess1.add_local_input(0, StubInput { inp: i })?;
+        stub1.handle_requests(sess1.advance_frame()?);
+        sess2.add_local_input(1, StubInput { inp: i * 2 })?;
+        stub2.handle_requests(sess2.advance_frame()?);
+        sess1.poll_remote_clients();
+        sess2.poll_remote_clients();
+    }
+    sess1.poll_remote_clients();
+    sess2.poll_remote_clients();
+
+    // the match went on as if nothing happened
+    assert!(sess1
+        .events()
+        .chain(sess2.events())
+        .all(|event| !matches!(event, GGRSEvent::Disconnected { .. })));
+    assert!(sess1.confirmed_frame() > 30);
+    assert!(sess2.confirmed_frame() > 30);
+    assert_eq!(stub1.gs.frame, stub2.gs.frame);
+    assert_eq!(stub1.gs.state, stub2.gs.state);
+
+    Ok(())
+}
@@ -1,16 +1,27 @@
+#![cfg(feature = "std")]
+
 mod stubs;
 
-use ggrs::{GGRSError, PlayerType, SessionBuilder, SessionState, UdpNonBlockingSocket};
+use ggrs::{
+    DesyncKind, GGRSError, GGRSEvent, GGRSRequest, InMemoryNetwork, NonBlockingSocket, PlayerType,
+    RleInputCodec, SessionBuilder, SessionState, TimeSource, UdpNonBlockingSocket,
+};
 use serial_test::serial;
+use std::collections::HashMap;
+use std::convert::TryInto;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use stubs::StubConfig;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use stubs::{StateStub, StubConfig, StubInput};
 
 #[test]
 #[serial]
 fn test_start_session() {
     let host_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
     let socket = UdpNonBlockingSocket::bind_to_port(9999).unwrap();
-    let spec_sess = SessionBuilder::<StubConfig>::new().start_spectator_session(host_addr, socket);
+    let spec_sess = SessionBuilder::<StubConfig>::new()
+        .start_spectator_session(host_addr, socket)
+        .unwrap();
     assert!(spec_sess.current_state() == SessionState::Synchronizing);
 }
 
@@ -29,7 +40,7 @@ fn test_synchronize_with_host() -> Result<(), GGRSError> {
 
     let socket2 = UdpNonBlockingSocket::bind_to_port(8888).unwrap();
     let mut spec_sess =
-        SessionBuilder::<StubConfig>::new().start_spectator_session(host_addr, socket2);
+        SessionBuilder::<StubConfig>::new().start_spectator_session(host_addr, socket2)?;
 
     assert_eq!(spec_sess.current_state(), SessionState::Synchronizing);
     assert_eq!(host_sess.current_state(), SessionState::Synchronizing);
@@ -44,3 +55,567 @@ fn test_synchronize_with_host() -> Result<(), GGRSError> {
 
     Ok(())
 }
+
+#[test]
+fn test_spectator_catchup() -> Result<(), GGRSError> {
+    let host_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut host_sess = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(host_addr))?;
+    let mut spec_sess = SessionBuilder::<StubConfig>::new()
+        .start_spectator_session(host_addr, network.socket(spec_addr))?;
+
+    for _ in 0..10 {
+        spec_sess.poll_remote_clients();
+        host_sess.poll_remote_clients();
+    }
+    assert_eq!(spec_sess.current_state(), SessionState::Running);
+
+    // invalid settings are rejected
+    assert!(spec_sess.set_catchup_speed(0).is_err());
+    assert!(spec_sess.set_max_frames_behind(60).is_err());
+    spec_sess.set_max_frames_behind(5)?;
+    assert!(spec_sess.set_catchup_speed(5).is_err());
+    spec_sess.set_catchup_speed(3)?;
+    assert_eq!(spec_sess.max_frames_behind(), 5);
+    assert_eq!(spec_sess.catchup_speed(), 3);
+
+    let mut host_stub = stubs::GameStub::new();
+    for i in 0..20 {
+        host_sess.add_local_input(0, StubInput { inp: i })?;
+        host_sess.add_local_input(1, StubInput { inp: i })?;
+        host_stub.handle_requests(host_sess.advance_frame()?);
+    }
+    host_sess.poll_remote_clients();
+    spec_sess.poll_remote_clients();
+    assert!(spec_sess.frames_behind_host() > 5);
+
+    // the spectator catches up with three frames per step
+    let requests = spec_sess.advance_frame()?;
+    assert_eq!(requests.len(), 3);
+
+    Ok(())
+}
+
+fn encode_state(gs: &StateStub) -> Vec<u8> {
+    let mut bytes = [gs.frame.to_le_bytes(), gs.state.to_le_bytes()].concat();
+    // pad the state, so it has to be sent in several chunks
+    bytes.resize(2000, 0);
+    bytes
+}
+
+fn decode_state(bytes: &[u8]) -> Option<StateStub> {
+    Some(StateStub {
+        frame: i32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?),
+        state: i32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?),
+    })
+}
+
+#[test]
+fn test_late_spectator_snapshot() -> Result<(), GGRSError> {
+    let host_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    // the host does not wait for the spectator
+    let mut host_sess = SessionBuilder::<StubConfig>::new()
+        .with_spectator_snapshots(true)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(host_addr))?;
+    assert_eq!(host_sess.current_state(), SessionState::Running);
+
+    let mut host_stub = stubs::GameStub::new();
+    let mut host_states = HashMap::new();
+    let mut advance_host = |host_sess: &mut ggrs::P2PSession<StubConfig>, i: u32| {
+        host_sess.add_local_input(0, StubInput { inp: i })?;
+        host_sess.add_local_input(1, StubInput { inp: i + 1 })?;
+        for request in host_sess.advance_frame()? {
+            match request {
+                GGRSRequest::SaveGameState { cell, frame } => {
+                    let gs = host_stub.gs;
+                    cell.save_with_bytes(frame, Some(gs), None, &encode_state(&gs));
+                    host_states.insert(frame, gs);
+                }
+                request => host_stub.handle_requests(vec![request]),
+            }
+        }
+        Ok::<(), GGRSError>(())
+    };
+    for i in 0..30 {
+        advance_host(&mut host_sess, i)?;
+    }
+
+    // the spectator joins late
+    let mut spec_sess = SessionBuilder::<StubConfig>::new()
+        .with_snapshot_decoder(decode_state)
+        .start_spectator_session(host_addr, network.socket(spec_addr))?;
+    // the first sync request of the host got lost, so the host synchronizes with the next retry
+    let mut host_synchronized = false;
+    for _ in 0..100 {
+        spec_sess.poll_remote_clients();
+        host_sess.poll_remote_clients();
+        host_synchronized |= host_sess
+            .events()
+            .any(|e| matches!(e, GGRSEvent::Synchronized { addr } if addr == spec_addr));
+        if host_synchronized {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(host_synchronized);
+    assert_eq!(spec_sess.current_state(), SessionState::Running);
+
+    let mut spec_stub = stubs::GameStub::new();
+    let mut loaded = false;
+    for i in 30..60 {
+        advance_host(&mut host_sess, i)?;
+        spec_sess.poll_remote_clients();
+        match spec_sess.advance_frame() {
+            Ok(requests) => {
+                if !loaded {
+                    assert!(matches!(
+                        requests.first(),
+                        Some(GGRSRequest::LoadGameState { .. })
+                    ));
+                    loaded = true;
+                }
+                spec_stub.handle_requests(requests);
+            }
+            Err(GGRSError::PredictionThreshold | GGRSError::NotSynchronized) => (),
+            Err(e) => return Err(e),
+        }
+    }
+    assert!(loaded);
+
+    // the spectator simulates the same states as the host
+    let host_gs = host_states[&spec_stub.gs.frame];
+    assert!(spec_stub.gs.frame > 30);
+    assert_eq!(spec_stub.gs.state, host_gs.state);
+
+    Ok(())
+}
+
+#[test]
+fn test_late_spectator_without_snapshot() -> Result<(), GGRSError> {
+    let host_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut host_sess = SessionBuilder::<StubConfig>::new()
+        .with_spectator_snapshots(true)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(host_addr))?;
+    let mut host_stub = stubs::GameStub::new();
+    let mut advance_host = |host_sess: &mut ggrs::P2PSession<StubConfig>, i: u32| {
+        host_sess.add_local_input(0, StubInput { inp: i })?;
+        host_sess.add_local_input(1, StubInput { inp: i })?;
+        for request in host_sess.advance_frame()? {
+            match request {
+                GGRSRequest::SaveGameState { cell, frame } => {
+                    let gs = host_stub.gs;
+                    cell.save_with_bytes(frame, Some(gs), None, &encode_state(&gs));
+                }
+                request => host_stub.handle_requests(vec![request]),
+            }
+        }
+        Ok::<(), GGRSError>(())
+    };
+    for i in 0..30 {
+        advance_host(&mut host_sess, i)?;
+    }
+
+    // the spectator joins late, but cannot decode the snapshot
+    let mut spec_sess = SessionBuilder::<StubConfig>::new()
+        .with_snapshot_decoder(|_| None)
+        .start_spectator_session(host_addr, network.socket(spec_addr))?;
+    for _ in 0..100 {
+        spec_sess.poll_remote_clients();
+        host_sess.poll_remote_clients();
+        if spec_sess.current_state() == SessionState::Running && spec_sess.frames_behind_host() > 0
+        {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(spec_sess.current_state(), SessionState::Running);
+
+    // the spectator waits for the snapshot until the inputs of the snapshot frame are overwritten
+    let mut result = Ok(Vec::new());
+    for i in 30..120 {
+        advance_host(&mut host_sess, i)?;
+        spec_sess.poll_remote_clients();
+        result = spec_sess.advance_frame();
+        match result {
+            Err(GGRSError::PredictionThreshold | GGRSError::NotSynchronized) => (),
+            _ => break,
+        }
+    }
+    assert!(matches!(result, Err(GGRSError::SpectatorTooFarBehind)));
+
+    Ok(())
+}
+
+#[derive(Clone, Default)]
+struct FakeClock(Arc<Mutex<Duration>>);
+
+impl TimeSource for FakeClock {
+    fn now(&self) -> Duration {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Returns the number of packets the spectator received while the host advanced 32 frames.
+fn spectator_packets(batch_frames: usize) -> Result<usize, GGRSError> {
+    let host_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut host_sess = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_spectator_compression(Box::new(RleInputCodec), batch_frames)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(host_addr))?;
+    let mut spec_sess = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_spectator_compression(Box::new(RleInputCodec), batch_frames)?
+        .start_spectator_session(host_addr, network.socket(spec_addr))?;
+
+    for _ in 0..10 {
+        spec_sess.poll_remote_clients();
+        host_sess.poll_remote_clients();
+    }
+    assert_eq!(spec_sess.current_state(), SessionState::Running);
+    spec_sess.reset_network_stats();
+
+    let mut host_stub = stubs::GameStub::new();
+    let mut spec_stub = stubs::GameStub::new();
+    for i in 0..32 {
+        host_sess.add_local_input(0, StubInput { inp: i })?;
+        host_sess.add_local_input(1, StubInput { inp: i })?;
+        host_stub.handle_requests(host_sess.advance_frame()?);
+        host_sess.poll_remote_clients();
+        spec_sess.poll_remote_clients();
+    }
+    // inputs that do not fill a batch are sent when pending inputs are resent
+    *clock.0.lock().unwrap() += Duration::from_millis(250);
+    host_sess.poll_remote_clients();
+    spec_sess.poll_remote_clients();
+
+    // the spectator receives the inputs of all frames confirmed before the last advance
+    while spec_sess.frames_behind_host() > 0 {
+        spec_stub.handle_requests(spec_sess.advance_frame()?);
+    }
+    assert_eq!(spec_stub.gs.frame, 31);
+
+    *clock.0.lock().unwrap() += Duration::from_secs(1);
+    Ok(spec_sess.network_stats()?.packets_received)
+}
+
+#[test]
+fn test_spectator_compression() -> Result<(), GGRSError> {
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_spectator_compression(Box::new(RleInputCodec), 0)
+        .is_err());
+
+    // batching the inputs of several frames sends fewer messages
+    assert!(spectator_packets(4)? < spectator_packets(1)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_end_session_flushes_spectator() -> Result<(), GGRSError> {
+    let host_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+
+    let mut host_sess = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(host_addr))?;
+    let mut spec_sess = SessionBuilder::<StubConfig>::new()
+        .start_spectator_session(host_addr, network.socket(spec_addr))?;
+
+    for _ in 0..10 {
+        spec_sess.poll_remote_clients();
+        host_sess.poll_remote_clients();
+    }
+
+    let mut host_stub = stubs::GameStub::new();
+    for i in 0..10 {
+        host_sess.add_local_input(0, StubInput { inp: i })?;
+        host_sess.add_local_input(1, StubInput { inp: i })?;
+        host_stub.handle_requests(host_sess.advance_frame()?);
+    }
+
+    // without ending the session, the inputs of the last frame have not been sent yet
+    host_sess.poll_remote_clients();
+    spec_sess.poll_remote_clients();
+    assert_eq!(spec_sess.frames_behind_host(), 9);
+
+    host_sess.end_session()?;
+    host_sess.poll_remote_clients();
+    spec_sess.poll_remote_clients();
+    assert_eq!(spec_sess.frames_behind_host(), 10);
+
+    // the spectator is told that the host left
+    assert!(spec_sess
+        .events()
+        .any(|e| matches!(e, GGRSEvent::Disconnected { addr } if addr == host_addr)));
+
+    // the spectator can watch the match up to its end
+    let mut spec_stub = stubs::GameStub::new();
+    while spec_sess.frames_behind_host() > 0 {
+        spec_stub.handle_requests(spec_sess.advance_frame()?);
+    }
+    assert_eq!(spec_stub.gs.frame, host_stub.gs.frame);
+    assert_eq!(spec_stub.gs.state, host_stub.gs.state);
+
+    Ok(())
+}
+
+#[test]
+fn test_end_session_resends_to_spectator() -> Result<(), GGRSError> {
+    let host_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(3).with_packet_loss(0.5);
+    let clock = FakeClock::default();
+
+    let mut host_sess = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(host_addr))?;
+    let mut spec_sess = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .start_spectator_session(host_addr, network.socket(spec_addr))?;
+    for _ in 0..100 {
+        spec_sess.poll_remote_clients();
+        host_sess.poll_remote_clients();
+        *clock.0.lock().unwrap() += Duration::from_millis(250);
+    }
+    assert_eq!(spec_sess.current_state(), SessionState::Running);
+
+    let mut host_stub = stubs::GameStub::new();
+    for i in 0..10 {
+        host_sess.add_local_input(0, StubInput { inp: i })?;
+        host_sess.add_local_input(1, StubInput { inp: i })?;
+        host_stub.handle_requests(host_sess.advance_frame()?);
+    }
+    host_sess.end_session()?;
+
+    // the lost inputs and the disconnect request are resent while the host keeps polling
+    let mut disconnected = false;
+    for _ in 0..20 {
+        *clock.0.lock().unwrap() += Duration::from_millis(250);
+        host_sess.poll_remote_clients();
+        spec_sess.poll_remote_clients();
+        disconnected |= spec_sess
+            .events()
+            .any(|e| matches!(e, GGRSEvent::Disconnected { addr } if addr == host_addr));
+    }
+    assert!(disconnected);
+    assert_eq!(spec_sess.frames_behind_host(), 10);
+
+    Ok(())
+}
+
+#[test]
+fn test_spectator_checksums() -> Result<(), GGRSError> {
+    let host_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_spectator_checksums(0)
+        .is_err());
+    let mut host_sess = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_spectator_checksums(4)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(host_addr))?;
+    let mut spec_sess = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_spectator_checksums(4)?
+        .start_spectator_session(host_addr, network.socket(spec_addr))?;
+    for _ in 0..10 {
+        spec_sess.poll_remote_clients();
+        host_sess.poll_remote_clients();
+    }
+    assert_eq!(spec_sess.current_state(), SessionState::Running);
+
+    let mut host_stub = stubs::GameStub::new();
+    let mut spec_stub = stubs::GameStub::new();
+    let mut desyncs = Vec::new();
+    let mut diverged = None;
+    for i in 0..80 {
+        *clock.0.lock().unwrap() += Duration::from_millis(50);
+        host_sess.add_local_input(0, StubInput { inp: i })?;
+        host_sess.add_local_input(1, StubInput { inp: i })?;
+        host_stub.handle_requests(host_sess.advance_frame()?);
+        host_sess.poll_remote_clients();
+        spec_sess.poll_remote_clients();
+
+        // the state of the spectator diverges from the state of the host
+        if i == 40 {
+            spec_stub.gs.state += 1;
+            diverged = Some(spec_stub.gs.frame);
+        }
+        let requests = match spec_sess.advance_frame() {
+            Ok(requests) => requests,
+            Err(GGRSError::PredictionThreshold | GGRSError::NotSynchronized) => continue,
+            Err(e) => return Err(e),
+        };
+        // the spectator saves its state every four frames
+        let saves = requests
+            .iter()
+            .filter(|r| matches!(r, GGRSRequest::SaveGameState { frame, .. } if frame % 4 == 0))
+            .count();
+        assert!(saves <= 1);
+        spec_stub.handle_requests(requests);
+        desyncs.extend(spec_sess.events().filter_map(|event| match event {
+            GGRSEvent::DesyncDetected {
+                frame, kind, addr, ..
+            } => {
+                assert_eq!(kind, DesyncKind::State);
+                assert_eq!(addr, host_addr);
+                Some(frame)
+            }
+            _ => None,
+        }));
+    }
+
+    // only the frames after the divergence are reported
+    assert!(!desyncs.is_empty());
+    assert!(desyncs.iter().all(|&frame| frame > diverged.unwrap()));
+
+    Ok(())
+}
+
+#[test]
+fn test_spectator_reconnect() -> Result<(), GGRSError> {
+    let host_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7777);
+    let spec_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8888);
+    let network = InMemoryNetwork::new(0);
+    let clock = FakeClock::default();
+
+    let mut host_sess = SessionBuilder::<StubConfig>::new()
+        .with_time_source(clock.clone())
+        .with_spectator_snapshots(true)
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .add_player(PlayerType::Spectator(spec_addr), 2)?
+        .start_p2p_session(network.socket(host_addr))?;
+    let spec_builder = || {
+        SessionBuilder::<StubConfig>::new()
+            .with_time_source(clock.clone())
+            .with_snapshot_decoder(decode_state)
+    };
+    let mut spec_sess =
+        spec_builder().start_spectator_session(host_addr, network.socket(spec_addr))?;
+    for _ in 0..10 {
+        spec_sess.poll_remote_clients();
+        host_sess.poll_remote_clients();
+    }
+    assert_eq!(spec_sess.current_state(), SessionState::Running);
+
+    let mut host_stub = stubs::GameStub::new();
+    let mut host_states = HashMap::new();
+    let mut advance_host = |host_sess: &mut ggrs::P2PSession<StubConfig>, i: u32| {
+        host_sess.add_local_input(0, StubInput { inp: i })?;
+        host_sess.add_local_input(1, StubInput { inp: i + 1 })?;
+        for request in host_sess.advance_frame()? {
+            match request {
+                GGRSRequest::SaveGameState { cell, frame } => {
+                    let gs = host_stub.gs;
+                    cell.save_with_bytes(frame, Some(gs), None, &encode_state(&gs));
+                    host_states.insert(frame, gs);
+                }
+                request => host_stub.handle_requests(vec![request]),
+            }
+        }
+        Ok::<(), GGRSError>(())
+    };
+    for i in 0..20 {
+        advance_host(&mut host_sess, i)?;
+    }
+
+    // the spectator goes away without saying goodbye, so the host times it out
+    drop(spec_sess);
+    *clock.0.lock().unwrap() += Duration::from_secs(3);
+    host_sess.poll_remote_clients();
+    assert!(host_sess
+        .events()
+        .any(|e| matches!(e, GGRSEvent::Disconnected { addr } if addr == spec_addr)));
+    assert_eq!(host_sess.spectator_handles(), vec![2]);
+    assert!(host_sess.disconnect_spectator(0).is_err());
+    assert!(host_sess.disconnect_spectator(2).is_ok());
+    for i in 20..40 {
+        advance_host(&mut host_sess, i)?;
+    }
+
+    // the spectator reconnects from the same address and resumes from a snapshot,
+    // with a fresh socket that does not receive what was sent to the old one
+    let mut spec_socket = network.socket(spec_addr);
+    spec_socket.receive_all_messages();
+    let mut spec_sess = spec_builder().start_spectator_session(host_addr, spec_socket)?;
+    let mut host_synchronized = false;
+    for _ in 0..20 {
+        spec_sess.poll_remote_clients();
+        host_sess.poll_remote_clients();
+        host_synchronized |= host_sess
+            .events()
+            .any(|e| matches!(e, GGRSEvent::Synchronized { addr } if addr == spec_addr));
+        if host_synchronized {
+            break;
+        }
+        *clock.0.lock().unwrap() += Duration::from_millis(200);
+    }
+    assert!(host_synchronized);
+    assert_eq!(spec_sess.current_state(), SessionState::Running);
+
+    let mut spec_stub = stubs::GameStub::new();
+    let mut loaded = false;
+    for i in 40..70 {
+        advance_host(&mut host_sess, i)?;
+        spec_sess.poll_remote_clients();
+        match spec_sess.advance_frame() {
+            Ok(requests) => {
+                if !loaded {
+                    assert!(matches!(
+                        requests.first(),
+                        Some(GGRSRequest::LoadGameState { .. })
+                    ));
+                    loaded = true;
+                }
+                spec_stub.handle_requests(requests);
+            }
+            Err(GGRSError::PredictionThreshold | GGRSError::NotSynchronized) => (),
+            Err(e) => return Err(e),
+        }
+    }
+    assert!(loaded);
+
+    let host_gs = host_states[&spec_stub.gs.frame];
+    assert!(spec_stub.gs.frame > 40);
+    assert_eq!(spec_stub.gs.state, host_gs.state);
+
+    Ok(())
+}
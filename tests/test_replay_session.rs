@@ -0,0 +1,130 @@
+#![cfg(feature = "std")]
+
+mod stubs;
+
+use ggrs::{GGRSError, GGRSRequest, InMemoryNetwork, PlayerType, ReplayReader, SessionBuilder};
+use std::io::{Cursor, Write};
+use std::sync::{Arc, Mutex};
+use stubs::{StubConfig, StubInput};
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn record(frames: u32) -> Result<(Vec<u8>, stubs::GameStub, Vec<(i32, u128)>), GGRSError> {
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(InMemoryNetwork::new(0).socket("127.0.0.1:7000".parse().unwrap()))?;
+
+    let buffer = SharedBuffer::default();
+    sess.set_replay_writer(Box::new(buffer.clone()))?;
+
+    let mut stub = stubs::GameStub::new();
+    for i in 0..frames {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i * 3 })?;
+        stub.handle_requests(sess.advance_frame()?);
+    }
+
+    let bytes = buffer.0.lock().unwrap().clone();
+    Ok((bytes, stub, sess.checksum_history().to_vec()))
+}
+
+#[test]
+fn test_replay_matches_recording() -> Result<(), GGRSError> {
+    let (bytes, _, recorded_checksums) = record(100)?;
+    // the last frame is only confirmed when advancing once more
+    let (_, recorded_stub, _) = record(99)?;
+
+    let reader = ReplayReader::<StubConfig, _>::new(Cursor::new(bytes)).unwrap();
+    let mut sess = SessionBuilder::new().start_replay_session(reader)?;
+    assert_eq!(sess.num_players(), 2);
+
+    let mut stub = stubs::GameStub::new();
+    while !sess.is_finished() {
+        let requests = sess.advance_frame()?;
+        // nothing is ever rolled back, so no states need to be loaded
+        assert!(!requests
+            .iter()
+            .any(|request| matches!(request, GGRSRequest::LoadGameState { .. })));
+        stub.handle_requests(requests);
+    }
+
+    assert_eq!(sess.current_frame(), 99);
+    assert_eq!(stub.gs.frame, recorded_stub.gs.frame);
+    assert_eq!(stub.gs.state, recorded_stub.gs.state);
+    // the replayed states have the same checksums as the recorded ones
+    let replayed_frames: Vec<_> = sess.checksum_history().iter().map(|(f, _)| *f).collect();
+    assert_eq!(replayed_frames, (0..99).collect::<Vec<_>>());
+    for (frame, checksum) in &recorded_checksums {
+        if *frame < 99 {
+            assert_eq!(
+                sess.checksum_history()[*frame as usize],
+                (*frame, *checksum)
+            );
+        }
+    }
+    assert!(!recorded_checksums.is_empty());
+    // advancing a finished replay does nothing
+    assert!(sess.advance_frame()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_replay_player_mismatch() -> Result<(), GGRSError> {
+    let (bytes, _, _) = record(10)?;
+    let reader = ReplayReader::<StubConfig, _>::new(Cursor::new(bytes)).unwrap();
+    assert!(SessionBuilder::new()
+        .with_num_players(3)
+        .start_replay_session(reader)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_replay_start_frame() -> Result<(), GGRSError> {
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_start_frame(20)?
+        .add_player(PlayerType::Local, 0)?
+        .add_player(PlayerType::Local, 1)?
+        .start_p2p_session(InMemoryNetwork::new(0).socket("127.0.0.1:7000".parse().unwrap()))?;
+
+    // a session starting late can still record from its start frame
+    let buffer = SharedBuffer::default();
+    sess.set_replay_writer(Box::new(buffer.clone()))?;
+
+    let mut stub = stubs::GameStub::new();
+    stub.gs.frame = 20;
+    for i in 0..5 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        stub.handle_requests(sess.advance_frame()?);
+    }
+    assert!(sess
+        .set_replay_writer(Box::new(SharedBuffer::default()))
+        .is_err());
+
+    let bytes = buffer.0.lock().unwrap().clone();
+    let reader = ReplayReader::<StubConfig, _>::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(reader.start_frame(), 20);
+    let mut sess = SessionBuilder::new().start_replay_session(reader)?;
+    assert_eq!(sess.current_frame(), 20);
+    while !sess.is_finished() {
+        sess.advance_frame()?;
+    }
+    assert_eq!(sess.current_frame(), 24);
+
+    Ok(())
+}
@@ -1,6 +1,8 @@
 mod stubs;
 
-use ggrs::{GGRSError, GGRSRequest, SessionBuilder};
+use ggrs::{Frame, GGRSError, GGRSRequest, RequestKind, SessionBuilder, StateStore};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use stubs::{StubConfig, StubInput};
 
 #[test]
@@ -63,6 +65,186 @@ fn test_advance_frame_with_rollbacks() -> Result<(), GGRSError> {
     Ok(())
 }
 
+#[test]
+fn test_request_kind_and_frame() -> Result<(), GGRSError> {
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::new()
+        .with_check_distance(2)
+        .start_synctest_session()?;
+
+    for i in 0..3 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        stub.handle_requests(sess.advance_frame()?);
+    }
+
+    sess.add_local_input(0, StubInput { inp: 3 })?;
+    sess.add_local_input(1, StubInput { inp: 3 })?;
+    let requests = sess.advance_frame()?;
+    let kinds: Vec<_> = requests.iter().map(|r| r.kind()).collect();
+    let frames: Vec<_> = requests.iter().map(|r| r.frame()).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            RequestKind::Load,
+            RequestKind::Advance,
+            RequestKind::Save,
+            RequestKind::Advance,
+            RequestKind::Save,
+            RequestKind::Advance,
+        ]
+    );
+    assert_eq!(frames, vec![Some(1), None, Some(2), None, Some(3), None]);
+    // inspecting the requests does not consume them
+    stub.handle_requests(requests);
+    assert_eq!(stub.gs.frame, 4);
+
+    Ok(())
+}
+
+/// Keeps all stored states in a map that the test can inspect.
+#[derive(Clone, Default)]
+struct MapStore(Arc<Mutex<HashMap<Frame, StoredState>>>);
+
+type StoredState = (stubs::StateStub, Option<u128>);
+
+impl StateStore<stubs::StateStub> for MapStore {
+    fn store(&mut self, frame: Frame, state: stubs::StateStub, checksum: Option<u128>) {
+        self.0.lock().unwrap().insert(frame, (state, checksum));
+    }
+
+    fn retrieve(&mut self, frame: Frame) -> Option<StoredState> {
+        self.0.lock().unwrap().get(&frame).copied()
+    }
+}
+
+#[test]
+fn test_max_saved_states() -> Result<(), GGRSError> {
+    // every frame of the check distance needs a state to roll back to
+    assert!(SessionBuilder::<StubConfig>::new()
+        .with_check_distance(4)
+        .with_max_saved_states(3)?
+        .start_synctest_session()
+        .is_err());
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_check_distance(4)
+        .with_max_saved_states(4)?
+        .start_synctest_session()?;
+    let mut stub = stubs::GameStub::new();
+    for i in 0..20 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        stub.handle_requests(sess.advance_frame()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_state_store() -> Result<(), GGRSError> {
+    let store = MapStore::default();
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::new()
+        .with_check_distance(2)
+        .with_state_store(store.clone())
+        .start_synctest_session()?;
+
+    // every frame rolls back and loads a state from the store
+    for i in 0..50 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        stub.handle_requests(sess.advance_frame()?);
+        assert_eq!(stub.gs.frame, i as i32 + 1);
+    }
+
+    let stored = store.0.lock().unwrap();
+    assert_eq!(stored.len(), 50);
+    assert!(stored
+        .iter()
+        .all(|(&frame, (state, _))| state.frame == frame));
+
+    Ok(())
+}
+
+/// Keeps the stored states, but forgets their checksums.
+struct ForgetfulStore(HashMap<Frame, stubs::StateStub>);
+
+impl StateStore<stubs::StateStub> for ForgetfulStore {
+    fn store(&mut self, frame: Frame, state: stubs::StateStub, _checksum: Option<u128>) {
+        self.0.insert(frame, state);
+    }
+
+    fn retrieve(&mut self, frame: Frame) -> Option<StoredState> {
+        self.0.get(&frame).map(|&state| (state, None))
+    }
+}
+
+#[test]
+fn test_state_store_without_checksums() -> Result<(), GGRSError> {
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::new()
+        .with_check_distance(2)
+        .with_state_store(ForgetfulStore(HashMap::new()))
+        .start_synctest_session()?;
+
+    // the checksums saved in the cells are still compared
+    for i in 0..20 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        stub.handle_requests(sess.advance_frame()?);
+        assert_eq!(stub.gs.frame, i as i32 + 1);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_delta_states() -> Result<(), GGRSError> {
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_check_distance(2)
+        .with_delta_states(|base, delta| stubs::StateStub {
+            frame: base.frame + i32::from(delta[0]),
+            state: base.state + i32::from_le_bytes(delta[1..5].try_into().unwrap()),
+        })
+        .start_synctest_session()?;
+
+    // a full state every 4 frames and deltas against it in between, every frame rolls back and loads one of them
+    let mut bases = HashMap::new();
+    for i in 0..50 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        for request in sess.advance_frame()? {
+            match request {
+                GGRSRequest::SaveGameState { cell, frame } => {
+                    let checksum = Some(stub.gs.state as u32 as u128);
+                    let base_frame = frame - frame % 4;
+                    if frame == base_frame {
+                        bases.insert(frame, stub.gs);
+                        cell.save(frame, Some(stub.gs), checksum);
+                    } else {
+                        let base = bases[&base_frame];
+                        let mut delta = vec![(frame - base_frame) as u8];
+                        delta.extend_from_slice(&(stub.gs.state - base.state).to_le_bytes());
+                        cell.save_delta(frame, base_frame, delta, checksum)?;
+                    }
+                }
+                request => stub.handle_requests(vec![request]),
+            }
+        }
+        assert_eq!(stub.gs.frame, i as i32 + 1);
+    }
+
+    // delta states cannot be combined with a state store
+    let result = SessionBuilder::<StubConfig>::new()
+        .with_delta_states(|base, _| *base)
+        .with_state_store(MapStore::default())
+        .start_synctest_session();
+    assert!(matches!(result, Err(GGRSError::InvalidRequest { .. })));
+
+    Ok(())
+}
+
 #[test]
 fn test_advance_frames_with_delayed_input() -> Result<(), GGRSError> {
     let check_distance = 7;
@@ -100,3 +282,275 @@ fn test_advance_frames_with_random_checksums() {
         assert_eq!(stub.gs.frame, i as i32 + 1);
     }
 }
+
+#[test]
+fn test_validate_recorded_checksums() -> Result<(), GGRSError> {
+    // record the checksums of a known-good run
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::new().start_synctest_session()?;
+    for i in 0..50 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        stub.handle_requests(sess.advance_frame()?);
+    }
+    let log = sess.checksum_log().to_vec();
+    assert_eq!(log.len(), 49);
+    assert!(log
+        .iter()
+        .enumerate()
+        .all(|(i, &(frame, _))| frame == i as i32));
+
+    // the same simulation matches the recording
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::new()
+        .with_recorded_checksums(log.clone())
+        .start_synctest_session()?;
+    for i in 0..50 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        let requests = sess.advance_frame()?;
+        assert_eq!(requests.len(), 2); // save, advance
+        stub.handle_requests(requests);
+    }
+    assert_eq!(sess.checksum_log(), &log[..]);
+    sess.finish_validation()?;
+
+    // a different simulation does not
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::new()
+        .with_recorded_checksums(log)
+        .start_synctest_session()?;
+    for i in 0..50 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i + 1 })?;
+        match sess.advance_frame() {
+            Ok(requests) => stub.handle_requests(requests),
+            Err(GGRSError::MismatchedChecksum {
+                frame,
+                expected,
+                got,
+            }) => {
+                assert_ne!(expected, got);
+                assert_eq!(frame, 1);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    panic!("the mismatch should have been detected");
+}
+
+#[test]
+fn test_finish_validation() -> Result<(), GGRSError> {
+    // runs the recorded simulation for the given number of frames, optionally changing the input that leads to the last saved state
+    let run = |log: Vec<(i32, Option<u128>)>, frames: u32, change_last: bool| {
+        let mut stub = stubs::GameStub::new();
+        let mut sess = SessionBuilder::new()
+            .with_recorded_checksums(log)
+            .start_synctest_session()?;
+        for i in 0..frames {
+            let inp = if change_last && i + 2 == frames {
+                i + 1
+            } else {
+                i
+            };
+            sess.add_local_input(0, StubInput { inp: i })?;
+            sess.add_local_input(1, StubInput { inp })?;
+            stub.handle_requests(sess.advance_frame()?);
+        }
+        sess.finish_validation()
+    };
+
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::new().start_synctest_session()?;
+    for i in 0..20 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        stub.handle_requests(sess.advance_frame()?);
+    }
+    let log = sess.checksum_log().to_vec();
+    assert!(sess.finish_validation().is_err());
+
+    // the recorded run is reproduced up to its last frame
+    run(log.clone(), 19, false)?;
+    // the last frame is validated as well
+    assert!(matches!(
+        run(log.clone(), 19, true),
+        Err(GGRSError::MismatchedChecksum { frame: 18, .. })
+    ));
+    // runs ending early are reported
+    assert!(matches!(
+        run(log.clone(), 10, false),
+        Err(GGRSError::InvalidRequest { .. })
+    ));
+    // so are gaps in the recording
+    let mut gap = log;
+    gap.remove(5);
+    assert!(matches!(
+        run(gap, 19, false),
+        Err(GGRSError::InvalidRequest { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_empty_save_state() -> Result<(), GGRSError> {
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_check_distance(2)
+        .start_synctest_session()?;
+    for i in 0..10 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        match sess.advance_frame() {
+            Ok(requests) => {
+                // the game never saves its state
+                stub.handle_requests(
+                    requests
+                        .into_iter()
+                        .filter(|r| !matches!(r, GGRSRequest::SaveGameState { .. })),
+                );
+            }
+            Err(GGRSError::EmptySaveState { frame }) => {
+                assert_eq!(frame, 1);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    panic!("the empty save state should have been detected");
+}
+
+#[test]
+fn test_advance_frame_into() -> Result<(), GGRSError> {
+    let check_distance = 2;
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::new()
+        .with_check_distance(check_distance)
+        .start_synctest_session()?;
+    let mut requests = Vec::new();
+    for i in 0..200 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        sess.advance_frame_into(&mut requests)?;
+        // the buffer is cleared first, so it only holds the requests of this frame
+        if i <= check_distance as u32 {
+            assert_eq!(requests.len(), 2); // save, advance
+        } else {
+            assert_eq!(requests.len(), check_distance * 2 + 2); // load, advance, (save, advance)*(check_distance-1), save, advance
+        }
+        stub.handle_requests(requests.drain(..));
+        assert_eq!(stub.gs.frame, i as i32 + 1);
+    }
+    Ok(())
+}
+
+/// Runs a game whose checksums suddenly change after a number of saves, like a simulation that becomes non-deterministic,
+/// and returns the frame of the reported mismatch.
+fn first_reported_divergence(determinism_checks: bool) -> Result<Frame, GGRSError> {
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_check_distance(4)
+        .with_determinism_checks(determinism_checks)
+        .start_synctest_session()?;
+    let mut saves = 0;
+    for i in 0..50 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        let requests = match sess.advance_frame() {
+            Ok(requests) => requests,
+            Err(GGRSError::MismatchedChecksum { frame, .. }) => return Ok(frame),
+            Err(e) => return Err(e),
+        };
+        for request in requests {
+            match request {
+                GGRSRequest::SaveGameState { cell, frame } => {
+                    saves += 1;
+                    let offset = if saves > 40 { 1000 } else { 0 };
+                    cell.save(frame, Some(stub.gs), Some(frame as u128 + offset));
+                }
+                request => stub.handle_requests(vec![request]),
+            }
+        }
+    }
+    panic!("the mismatch should have been detected");
+}
+
+#[test]
+fn test_determinism_checks() -> Result<(), GGRSError> {
+    // several resimulated frames diverge at once, but only the determinism checks name the oldest one
+    let newest = first_reported_divergence(false)?;
+    let oldest = first_reported_divergence(true)?;
+    assert!(oldest < newest);
+
+    // states without checksums cannot be checked
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_determinism_checks(true)
+        .start_synctest_session()?;
+    for i in 0..10 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        match sess.advance_frame() {
+            Ok(requests) => {
+                for request in requests {
+                    match request {
+                        GGRSRequest::SaveGameState { cell, frame } => {
+                            cell.save(frame, Some(stub.gs), None)
+                        }
+                        request => stub.handle_requests(vec![request]),
+                    }
+                }
+            }
+            Err(GGRSError::InvalidRequest { .. }) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+    panic!("the missing checksums should have been detected");
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_byte_diff() -> Result<(), GGRSError> {
+    let mut stub = stubs::GameStub::new();
+    let mut sess = SessionBuilder::<StubConfig>::new()
+        .with_check_distance(2)
+        .with_byte_diff(true)
+        .start_synctest_session()?;
+    for i in 0..50 {
+        sess.add_local_input(0, StubInput { inp: i })?;
+        sess.add_local_input(1, StubInput { inp: i })?;
+        let requests = match sess.advance_frame() {
+            Ok(requests) => requests,
+            Err(GGRSError::MismatchedState {
+                frame,
+                expected,
+                got,
+                differing_bytes,
+            }) => {
+                // the checksums match, but the second field of the state does not
+                assert!(frame > 0);
+                assert_eq!(expected, got);
+                assert!(!differing_bytes.is_empty());
+                assert!(differing_bytes.iter().all(|r| r.start >= 4 && r.end <= 8));
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        for request in requests {
+            match request {
+                // resimulated states are saved with a corrupted state, but the original checksum
+                GGRSRequest::SaveGameState { cell, frame } => {
+                    let mut state = stub.gs;
+                    if i > 5 {
+                        state.state += 1000;
+                    }
+                    cell.save(frame, Some(state), Some(frame as u128));
+                }
+                request => stub.handle_requests(vec![request]),
+            }
+        }
+    }
+    panic!("the state mismatch should have been detected");
+}